@@ -1,14 +1,12 @@
 #[macro_use]
 extern crate tracing;
 
-use cpal::traits::StreamTrait;
 use eyre::Result;
-use gnome_voice_input::audio_utils::{init_simple_audio_capture, process_simple_audio};
+use gnome_voice_input::audio::start_capture;
 use gnome_voice_input::{
     process_transcription_with_handler, AppState, Config, ConsoleTranscriptionHandler,
 };
 use std::env;
-use std::sync::atomic::Ordering;
 use tokio_util::sync::CancellationToken;
 
 fn main() -> Result<()> {
@@ -36,7 +34,7 @@ fn main() -> Result<()> {
         });
 
         // Start recording using shared state and custom output handler
-        app_state.recording.store(true, Ordering::Relaxed);
+        app_state.begin_recording();
 
         if let Err(e) = start_transcription_only(app_state.clone()).await {
             error!("Transcription failed: {}", e);
@@ -57,10 +55,8 @@ fn create_simple_config() -> Result<Config> {
         "placeholder".to_string()
     });
 
-    let mut config = Config {
-        deepgram_api_key: api_key,
-        ..Default::default()
-    };
+    let mut config = Config::default();
+    config.deepgram_api_key = api_key;
 
     // Enable interim results for more interactive experience
     config.transcription.use_interim_results = true;
@@ -71,32 +67,22 @@ fn create_simple_config() -> Result<Config> {
 /// Start transcription using shared state but with stdout output instead of keyboard typing
 async fn start_transcription_only(app_state: AppState) -> Result<()> {
     debug!("Starting transcription-only process");
-    let (audio_tx, audio_rx) = tokio::sync::mpsc::channel(100);
-
-    // Initialize audio capture - this will fail if device doesn't support 16kHz
-    let audio_capture = init_simple_audio_capture()?;
-
-    // Start audio capture in blocking task
-    let recording = app_state.recording.clone();
+    // Capture from the configured microphone, mixed down to mono
+    let (audio_rx, format) = start_capture(&app_state).await?;
     let shutdown_token = app_state.shutdown_token.clone();
-    tokio::task::spawn_blocking(move || {
-        if let Err(e) = process_simple_audio(audio_capture.sample_rx, audio_tx, recording) {
-            error!("Audio capture error: {}", e);
-        }
-    });
-
-    // Start the audio stream
-    audio_capture.stream.play()?;
 
-    debug!("Creating transcription stream with 16000 Hz sample rate");
+    debug!(
+        "Creating transcription stream with {} Hz sample rate",
+        format.sample_rate
+    );
     let transcriber = app_state.transcriber.read().unwrap().clone();
-    let transcription_rx = transcriber.transcribe_stream(audio_rx, 16000).await?;
+    let transcription_rx = transcriber.transcribe_stream(audio_rx, format).await?;
     debug!("Transcription stream created, waiting for transcriptions");
 
     let handler = ConsoleTranscriptionHandler::new();
 
     tokio::select! {
-        result = process_transcription_with_handler(transcription_rx, handler) => {
+        result = process_transcription_with_handler(transcription_rx, handler, CancellationToken::new()) => {
             if let Err(e) = result {
                 error!("Transcription processing error: {}", e);
             }
@@ -105,7 +91,7 @@ async fn start_transcription_only(app_state: AppState) -> Result<()> {
             debug!("Shutdown requested, breaking transcription loop");
         }
         _ = async {
-            while app_state.recording.load(Ordering::Relaxed) {
+            while app_state.is_recording() {
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
             }
         } => {
@@ -0,0 +1,133 @@
+import Gio from 'gi://Gio';
+import GObject from 'gi://GObject';
+import St from 'gi://St';
+
+import {Extension} from 'resource:///org/gnome/shell/extensions/extension.js';
+import * as Main from 'resource:///org/gnome/shell/ui/main.js';
+import * as PanelMenu from 'resource:///org/gnome/shell/ui/panelMenu.js';
+import * as PopupMenu from 'resource:///org/gnome/shell/ui/popupMenu.js';
+
+const BUS_NAME = 'org.gnome.VoiceInput';
+const OBJECT_PATH = '/org/gnome/VoiceInput';
+
+const VoiceInputInterface = `
+<node>
+  <interface name="org.gnome.VoiceInput">
+    <method name="ToggleRecording"/>
+    <property name="Recording" type="b" access="read"/>
+    <property name="Enabled" type="b" access="read"/>
+    <property name="LastTranscript" type="s" access="read"/>
+    <property name="Level" type="d" access="read"/>
+  </interface>
+</node>`;
+
+const VoiceInputProxy = Gio.DBusProxy.makeProxyWrapper(VoiceInputInterface);
+
+const ICON_IDLE = 'audio-input-microphone-symbolic';
+const ICON_RECORDING = 'media-record-symbolic';
+const ICON_PAUSED = 'microphone-disabled-symbolic';
+const ICON_OFFLINE = 'microphone-sensitivity-muted-symbolic';
+
+const VoiceInputIndicator = GObject.registerClass(
+class VoiceInputIndicator extends PanelMenu.Button {
+    _init() {
+        super._init(0.0, 'Voice Input');
+
+        this._icon = new St.Icon({
+            icon_name: ICON_OFFLINE,
+            style_class: 'system-status-icon',
+        });
+        this.add_child(this._icon);
+
+        this._statusItem = new PopupMenu.PopupMenuItem('Voice input not running', {reactive: false});
+        this.menu.addMenuItem(this._statusItem);
+
+        this._levelItem = new PopupMenu.PopupMenuItem('', {reactive: false});
+        this.menu.addMenuItem(this._levelItem);
+
+        this._transcriptItem = new PopupMenu.PopupMenuItem('', {reactive: false});
+        this._transcriptItem.label.clutter_text.set_line_wrap(true);
+        this.menu.addMenuItem(this._transcriptItem);
+
+        this.menu.addMenuItem(new PopupMenu.PopupSeparatorMenuItem());
+
+        this._toggleItem = new PopupMenu.PopupMenuItem('Toggle Recording');
+        this._toggleItem.connect('activate', () => this._proxy?.ToggleRecordingRemote());
+        this.menu.addMenuItem(this._toggleItem);
+
+        this._proxy = new VoiceInputProxy(
+            Gio.DBus.session,
+            BUS_NAME,
+            OBJECT_PATH,
+            (proxy, error) => {
+                if (error) {
+                    console.error(`voice-input: failed to create proxy: ${error.message}`);
+                    return;
+                }
+                this._propertiesChangedId = proxy.connect('g-properties-changed', () => this._sync());
+                this._ownerChangedId = proxy.connect('notify::g-name-owner', () => this._sync());
+                this._sync();
+            },
+            null,
+            // Show "not running" instead of starting the app when the shell starts
+            Gio.DBusProxyFlags.DO_NOT_AUTO_START_AT_CONSTRUCTION);
+    }
+
+    _sync() {
+        const proxy = this._proxy;
+        const running = proxy && proxy.g_name_owner !== null;
+
+        this._toggleItem.setSensitive(running && proxy.Enabled);
+        this._levelItem.visible = running && proxy.Recording;
+        this._transcriptItem.visible = running && !!proxy.LastTranscript;
+
+        if (!running) {
+            this._icon.icon_name = ICON_OFFLINE;
+            this._statusItem.label.text = 'Voice input not running';
+            return;
+        }
+
+        if (!proxy.Enabled) {
+            this._icon.icon_name = ICON_PAUSED;
+            this._statusItem.label.text = 'Voice input paused';
+        } else if (proxy.Recording) {
+            this._icon.icon_name = ICON_RECORDING;
+            this._statusItem.label.text = 'Recording';
+        } else {
+            this._icon.icon_name = ICON_IDLE;
+            this._statusItem.label.text = 'Idle';
+        }
+
+        const bars = Math.round(Math.min(proxy.Level ?? 0, 1) * 20);
+        this._levelItem.label.text = `Level ${'▮'.repeat(bars)}${'▯'.repeat(20 - bars)}`;
+        this._transcriptItem.label.text = `Last: ${proxy.LastTranscript ?? ''}`;
+
+        if (proxy.Recording)
+            this.add_style_class_name('voice-input-recording');
+        else
+            this.remove_style_class_name('voice-input-recording');
+    }
+
+    destroy() {
+        if (this._proxy) {
+            if (this._propertiesChangedId)
+                this._proxy.disconnect(this._propertiesChangedId);
+            if (this._ownerChangedId)
+                this._proxy.disconnect(this._ownerChangedId);
+            this._proxy = null;
+        }
+        super.destroy();
+    }
+});
+
+export default class VoiceInputExtension extends Extension {
+    enable() {
+        this._indicator = new VoiceInputIndicator();
+        Main.panel.addToStatusArea(this.uuid, this._indicator);
+    }
+
+    disable() {
+        this._indicator?.destroy();
+        this._indicator = null;
+    }
+}
@@ -0,0 +1,68 @@
+//! Deepgram API keys in use: the configured key and the further
+//! `deepgram_api_keys`, rotated when Deepgram rejects the current one (auth
+//! and quota errors), e.g. for teams sharing project keys.
+
+use crate::redact;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The keys tried in order, shared by the transcribers so that a rotation
+/// carries over to later sessions
+pub struct ApiKeys {
+    keys: Vec<String>,
+    current: AtomicUsize,
+}
+
+impl std::fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKeys")
+            .field(
+                "keys",
+                &self
+                    .keys
+                    .iter()
+                    .map(|key| redact::mask(key))
+                    .collect::<Vec<_>>(),
+            )
+            .field("current", &self.current.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<String>) -> Self {
+        assert!(!keys.is_empty(), "at least one API key is needed");
+        Self {
+            keys,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// Index and value of the key to use
+    pub fn current(&self) -> (usize, &str) {
+        let index = self.current.load(Ordering::Relaxed);
+        (index, &self.keys[index])
+    }
+
+    /// Masked form of the key at `index`, to tell which key was used
+    pub fn label(&self, index: usize) -> String {
+        redact::mask(&self.keys[index])
+    }
+
+    /// Move on from the rejected key at `index` to the next one, wrapping
+    /// around. Another session may have rotated already, then its choice is
+    /// kept. Returns the key to use now.
+    pub fn rotate(&self, index: usize) -> usize {
+        let next = (index + 1) % self.keys.len();
+        match self
+            .current
+            .compare_exchange(index, next, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => next,
+            Err(current) => current,
+        }
+    }
+}
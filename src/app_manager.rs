@@ -1,153 +1,429 @@
-use crate::{config::Config, hotkey, state::AppState, tray};
+use crate::{
+    config::Config,
+    dbus_service, hotkey, media, metrics, notifications, overlay, session_lock,
+    state::{AppEvent, AppState, TranscriberSettings},
+    tray,
+};
 use eyre::Result;
-use global_hotkey::{hotkey::HotKey, GlobalHotKeyManager};
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 
+/// The running components, shared by the config reload handler and the
+/// supervisor. `None` once they were torn down.
+pub type SharedComponents = Arc<Mutex<Option<AppComponents>>>;
+
+/// Components the supervisor restarts when they die
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SupervisedComponent {
+    Hotkeys,
+    Tray,
+}
+
+impl std::fmt::Display for SupervisedComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SupervisedComponent::Hotkeys => "Hotkey handler",
+            SupervisedComponent::Tray => "Tray",
+        })
+    }
+}
+
+/// Threads and tasks of one component, stopped through the component's own
+/// token so it can be restarted without touching the others
+struct Component {
+    name: &'static str,
+    shutdown_token: CancellationToken,
+    tasks: Vec<JoinHandle<()>>,
+    threads: Vec<std::thread::JoinHandle<()>>,
+    /// Its threads and tasks run until it is stopped, so one that ended
+    /// means the component died
+    supervised: bool,
+}
+
+impl Component {
+    fn new(name: &'static str, parent_shutdown_token: &CancellationToken) -> Self {
+        Self {
+            name,
+            shutdown_token: parent_shutdown_token.child_token(),
+            tasks: Vec::new(),
+            threads: Vec::new(),
+            supervised: false,
+        }
+    }
+
+    /// A thread or task of a supervised component ended (e.g. panicked)
+    /// although the component was not stopped
+    fn has_died(&self) -> bool {
+        self.supervised
+            && !self.shutdown_token.is_cancelled()
+            && (self.tasks.iter().any(JoinHandle::is_finished)
+                || self
+                    .threads
+                    .iter()
+                    .any(std::thread::JoinHandle::is_finished))
+    }
+
+    /// Stop the component, leaving an empty one in its place
+    async fn stop_in_place(slot: &mut Self, parent_shutdown_token: &CancellationToken) {
+        let stopped = std::mem::replace(slot, Self::new(slot.name, parent_shutdown_token));
+        stopped.stop().await;
+    }
+
+    /// Cancel the component and wait for its threads and tasks to finish
+    async fn stop(self) {
+        self.shutdown_token.cancel();
+
+        let name = self.name;
+        let joined = tokio::time::timeout(tokio::time::Duration::from_secs(3), async move {
+            for task in self.tasks {
+                let _ = task.await;
+            }
+            for thread in self.threads {
+                match tokio::task::spawn_blocking(move || thread.join()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(_)) => warn!("{} thread panicked during teardown", name),
+                    Err(e) => warn!("Failed to join {} thread: {}", name, e),
+                }
+            }
+        })
+        .await;
+
+        match joined {
+            Ok(()) => info!("{} stopped", name),
+            Err(_) => warn!("{} did not shut down within timeout", name),
+        }
+    }
+}
+
 /// Represents all the running components of the application that need to be
 /// managed during lifecycle events (startup, reload, shutdown)
 pub struct AppComponents {
-    pub hotkey_manager: Arc<tokio::sync::Mutex<GlobalHotKeyManager>>,
-    pub registered_hotkey: Arc<tokio::sync::Mutex<HotKey>>,
-    pub hotkey_handle: JoinHandle<()>,
-    pub hotkey_rx_handle: JoinHandle<()>,
-    pub tray_handle: Option<std::thread::JoinHandle<()>>,
-    /// The shutdown token used for these components (child of main token)
-    pub components_shutdown_token: CancellationToken,
+    /// Without tray and hotkeys (`--headless`)
+    headless: bool,
+    hotkeys: Component,
+    tray: Component,
+    overlay: Component,
+    dbus: Component,
+    notification: Component,
+    media: Component,
+    session_lock: Component,
+    metrics: Component,
 }
 
 impl AppComponents {
     /// Tears down all components gracefully
     /// Only tears down components, does NOT cancel the main app shutdown token
-    pub async fn teardown_for_reload(self) -> Result<()> {
-        info!("Tearing down application components for reload");
+    pub async fn teardown(self) {
+        info!("Tearing down application components");
 
-        // Cancel only the components' shutdown token, not the main app token
-        self.components_shutdown_token.cancel();
+        self.hotkeys.stop().await;
+        self.tray.stop().await;
+        self.dbus.stop().await;
+        self.notification.stop().await;
+        self.media.stop().await;
+        self.session_lock.stop().await;
+        self.overlay.stop().await;
+        self.metrics.stop().await;
 
-        // Wait for async tasks to complete
-        let shutdown_timeout = tokio::time::timeout(tokio::time::Duration::from_secs(3), async {
-            let _ = self.hotkey_handle.await;
-            let _ = self.hotkey_rx_handle.await;
-
-            // Wait for the tray thread
-            if let Some(handle) = self.tray_handle {
-                let tray_result = tokio::task::spawn_blocking(move || handle.join()).await;
+        info!("All components torn down");
+    }
 
-                match tray_result {
-                    Ok(Ok(())) => info!("Tray thread joined successfully"),
-                    Ok(Err(_)) => warn!("Tray thread panicked during teardown"),
-                    Err(e) => warn!("Failed to join tray thread: {}", e),
-                }
-            }
-        })
-        .await;
+    /// Supervised components whose threads or tasks died
+    pub fn dead_components(&self) -> Vec<SupervisedComponent> {
+        let mut dead = Vec::new();
+        if self.hotkeys.has_died() {
+            dead.push(SupervisedComponent::Hotkeys);
+        }
+        if self.tray.has_died() {
+            dead.push(SupervisedComponent::Tray);
+        }
+        dead
+    }
 
-        match shutdown_timeout {
-            Ok(_) => {
-                info!("All components torn down gracefully");
+    /// Stop what is left of a component and start it again with the current
+    /// configuration
+    pub async fn restart(
+        &mut self,
+        component: SupervisedComponent,
+        app_state: &AppState,
+        parent_shutdown_token: &CancellationToken,
+    ) -> Result<()> {
+        let config = app_state.config();
+        match component {
+            SupervisedComponent::Hotkeys => {
+                Component::stop_in_place(&mut self.hotkeys, parent_shutdown_token).await;
+                self.hotkeys = start_hotkeys(&config, app_state, parent_shutdown_token).await?;
             }
-            Err(_) => {
-                warn!("Some components did not shut down within timeout");
+            SupervisedComponent::Tray => {
+                Component::stop_in_place(&mut self.tray, parent_shutdown_token).await;
+                self.tray = start_tray(&config, app_state, parent_shutdown_token);
             }
         }
+        Ok(())
+    }
+}
 
-        // Unregister hotkey
-        let manager = self.hotkey_manager.lock().await;
-        let hotkey = self.registered_hotkey.lock().await;
-        if let Err(e) = manager.unregister(*hotkey) {
-            warn!("Failed to unregister hotkey during teardown: {}", e);
-        } else {
-            info!("Hotkey unregistered successfully");
+async fn start_hotkeys(
+    config: &Config,
+    app_state: &AppState,
+    parent_shutdown_token: &CancellationToken,
+) -> Result<Component> {
+    let mut component = Component::new("Hotkey handler", parent_shutdown_token);
+    let registered_hotkeys = hotkey::setup_hotkeys(config).await?;
+    // With a GNOME shortcut there is nothing to listen to
+    component.supervised = !matches!(registered_hotkeys, hotkey::RegisteredHotkeys::GnomeShortcut);
+    let (hotkey_handle, hotkey_rx_handle) = hotkey::setup_hotkey_handlers(
+        app_state.clone(),
+        registered_hotkeys,
+        &component.shutdown_token,
+    );
+    component.tasks = vec![hotkey_handle, hotkey_rx_handle];
+    Ok(component)
+}
+
+fn start_tray(
+    config: &Config,
+    app_state: &AppState,
+    parent_shutdown_token: &CancellationToken,
+) -> Component {
+    let mut component = Component::new("Tray", parent_shutdown_token);
+    component.threads.extend(tray::setup_tray(
+        config,
+        app_state.clone(),
+        &component.shutdown_token,
+    ));
+    component.supervised = !component.threads.is_empty();
+    component
+}
+
+fn start_overlay(
+    config: &Config,
+    app_state: &AppState,
+    parent_shutdown_token: &CancellationToken,
+) -> Component {
+    let mut component = Component::new("Overlay", parent_shutdown_token);
+    match overlay::setup_overlay(
+        config,
+        app_state.input_level.clone(),
+        &component.shutdown_token,
+    ) {
+        Some((overlay, handle)) => {
+            *app_state.overlay.write().unwrap() = Some(overlay);
+            component.threads.push(handle);
         }
+        None => *app_state.overlay.write().unwrap() = None,
+    }
+    component
+}
 
-        Ok(())
+fn start_notification(
+    config: &Config,
+    app_state: &AppState,
+    parent_shutdown_token: &CancellationToken,
+) -> Component {
+    let mut component = Component::new("Recording notification", parent_shutdown_token);
+    component
+        .threads
+        .extend(notifications::setup_recording_notification(
+            config,
+            app_state.clone(),
+            &component.shutdown_token,
+        ));
+    component
+}
+
+fn start_media_pause(
+    config: &Config,
+    app_state: &AppState,
+    parent_shutdown_token: &CancellationToken,
+) -> Component {
+    let mut component = Component::new("Media pause", parent_shutdown_token);
+    component.threads.extend(media::setup_media_pause(
+        config,
+        app_state.clone(),
+        &component.shutdown_token,
+    ));
+    component
+}
+
+fn start_session_lock(
+    config: &Config,
+    app_state: &AppState,
+    parent_shutdown_token: &CancellationToken,
+) -> Component {
+    let mut component = Component::new("Session lock", parent_shutdown_token);
+    component.threads.extend(session_lock::setup_session_lock(
+        config,
+        app_state.clone(),
+        &component.shutdown_token,
+    ));
+    component
+}
+
+fn start_metrics(
+    config: &Config,
+    app_state: &AppState,
+    parent_shutdown_token: &CancellationToken,
+) -> Component {
+    let mut component = Component::new("Metrics endpoint", parent_shutdown_token);
+    if let Some(address) = config.metrics.address {
+        let events = app_state.events.subscribe();
+        let shutdown_token = component.shutdown_token.clone();
+        component.tasks.push(tokio::spawn(async move {
+            if let Err(e) = metrics::serve(address, events, shutdown_token).await {
+                error!("Metrics endpoint failed: {:#}", e);
+            }
+        }));
     }
+    component
 }
 
 /// Initialize all application components with the given configuration
-/// Uses a child token of the main shutdown token so components can be torn down independently
+/// Each component uses a child token of the main shutdown token so it can be torn down independently.
+/// Headless, the tray and hotkeys are left out and the app is controlled over D-Bus or stdin.
 pub async fn initialize_app_components(
     config: Config,
     app_state: AppState,
     parent_shutdown_token: &CancellationToken,
+    headless: bool,
 ) -> Result<AppComponents> {
     info!("Initializing application components");
 
-    // Create a child token for these components
-    let components_shutdown_token = parent_shutdown_token.child_token();
+    let (hotkeys, tray) = if headless {
+        info!("Running headless, without tray and hotkeys");
+        (
+            Component::new("Hotkey handler", parent_shutdown_token),
+            Component::new("Tray", parent_shutdown_token),
+        )
+    } else {
+        (
+            start_hotkeys(&config, &app_state, parent_shutdown_token).await?,
+            start_tray(&config, &app_state, parent_shutdown_token),
+        )
+    };
+    let overlay = start_overlay(&config, &app_state, parent_shutdown_token);
 
-    // Setup hotkeys
-    let (hotkey_manager, registered_hotkey) = hotkey::setup_hotkeys(&config)?;
-    info!("Hotkey registered: {:?}", registered_hotkey);
+    // Expose state and controls on the session bus for the GNOME Shell extension
+    let mut dbus = Component::new("D-Bus service", parent_shutdown_token);
+    dbus.threads.push(dbus_service::setup_dbus_service(
+        app_state.clone(),
+        &dbus.shutdown_token,
+    ));
+
+    // Notification with a "Stop" action while recording
+    let notification = start_notification(&config, &app_state, parent_shutdown_token);
 
-    // Setup tray with the child token
-    let tray_handle = tray::setup_tray(&config, app_state.clone(), &components_shutdown_token);
+    // Media players paused while recording
+    let media = start_media_pause(&config, &app_state, parent_shutdown_token);
 
-    // Convert to Arc for sharing
-    let hotkey_manager_arc = Arc::new(tokio::sync::Mutex::new(hotkey_manager));
-    let registered_hotkey_arc = Arc::new(tokio::sync::Mutex::new(registered_hotkey));
+    // Recording stopped when the screen locks
+    let session_lock = start_session_lock(&config, &app_state, parent_shutdown_token);
 
-    // Setup hotkey handlers with the child token
-    let (hotkey_handle, hotkey_rx_handle) =
-        hotkey::setup_hotkey_handlers(app_state.clone(), &components_shutdown_token);
+    let metrics = start_metrics(&config, &app_state, parent_shutdown_token);
 
     Ok(AppComponents {
-        hotkey_manager: hotkey_manager_arc,
-        registered_hotkey: registered_hotkey_arc,
-        hotkey_handle,
-        hotkey_rx_handle,
-        tray_handle,
-        components_shutdown_token,
+        headless,
+        hotkeys,
+        tray,
+        overlay,
+        dbus,
+        notification,
+        media,
+        session_lock,
+        metrics,
     })
 }
 
-/// Reload the application with a new configuration
-/// This tears down all components except the config watcher and rebuilds them
+/// Apply a new configuration, restarting only the components whose settings
+/// changed. Settings read per recording session (audio) need no restart.
 pub async fn reload_application(
     new_config: Config,
     app_state: &AppState,
-    current_components: AppComponents,
+    components: &mut AppComponents,
     parent_shutdown_token: &CancellationToken,
-) -> Result<AppComponents> {
-    info!("Starting application reload");
+) -> Result<()> {
+    let old_config = app_state.replace_config(new_config.clone());
 
-    // Stop recording if active before teardown
-    app_state
-        .recording
-        .store(false, std::sync::atomic::Ordering::Relaxed);
-
-    // First, teardown existing components (but don't cancel main shutdown token)
-    current_components.teardown_for_reload().await?;
+    if TranscriberSettings::new(&old_config) != TranscriberSettings::new(&new_config) {
+        info!("Transcription settings changed, replacing transcriber");
+        app_state.replace_transcriber(&new_config);
+    }
 
-    // Update the app state with new config
-    {
-        let mut config = app_state.config.write().unwrap();
-        *config = new_config.clone();
+    if old_config.paused != new_config.paused {
+        app_state.set_enabled(!new_config.paused);
     }
 
-    // Update transcriber with new config
-    let new_transcriber = Arc::new(crate::transcription::Transcriber::new(
-        new_config.deepgram_api_key.clone(),
-        new_config.transcription.clone(),
-        app_state.debug,
-    ));
-    {
-        let mut transcriber = app_state.transcriber.write().unwrap();
-        *transcriber = new_transcriber;
+    if old_config.privacy != new_config.privacy {
+        app_state.set_privacy(new_config.privacy);
     }
 
-    // Re-initialize all components with the new configuration
-    // Use the main shutdown token as parent so they respond to app shutdown
-    match initialize_app_components(new_config, app_state.clone(), parent_shutdown_token).await {
-        Ok(components) => {
-            info!("Application reload completed successfully");
-            Ok(components)
+    if old_config.history != new_config.history {
+        info!("History settings changed");
+        if let Err(e) = app_state.history.configure(&new_config.history) {
+            warn!("Failed to prune the transcript history: {:#}", e);
         }
-        Err(e) => {
-            error!("Failed to reload application components: {}", e);
-            Err(e)
+    }
+
+    let hotkey_changed = !components.headless && old_config.hotkey != new_config.hotkey;
+    if hotkey_changed {
+        info!("Hotkey settings changed, re-registering hotkeys");
+        // The old keys must be released before they can be grabbed again
+        Component::stop_in_place(&mut components.hotkeys, parent_shutdown_token).await;
+
+        match start_hotkeys(&new_config, app_state, parent_shutdown_token).await {
+            Ok(hotkeys) => components.hotkeys = hotkeys,
+            Err(e) => {
+                error!(
+                    "Failed to register new hotkeys, restoring previous ones: {:#}",
+                    e
+                );
+                components.hotkeys =
+                    start_hotkeys(&old_config, app_state, parent_shutdown_token).await?;
+                return Err(e.wrap_err("Hotkey settings were not applied"));
+            }
         }
     }
+
+    // The tray shows the hotkey in its menu
+    if !components.headless && (hotkey_changed || old_config.ui != new_config.ui) {
+        info!("Restarting tray");
+        Component::stop_in_place(&mut components.tray, parent_shutdown_token).await;
+        components.tray = start_tray(&new_config, app_state, parent_shutdown_token);
+    }
+
+    if old_config.ui.show_overlay != new_config.ui.show_overlay {
+        info!("Restarting overlay");
+        Component::stop_in_place(&mut components.overlay, parent_shutdown_token).await;
+        components.overlay = start_overlay(&new_config, app_state, parent_shutdown_token);
+    }
+
+    if old_config.ui.recording_notification != new_config.ui.recording_notification {
+        info!("Restarting recording notification");
+        Component::stop_in_place(&mut components.notification, parent_shutdown_token).await;
+        components.notification = start_notification(&new_config, app_state, parent_shutdown_token);
+    }
+
+    if old_config.ui.pause_media != new_config.ui.pause_media {
+        info!("Restarting media pause");
+        Component::stop_in_place(&mut components.media, parent_shutdown_token).await;
+        components.media = start_media_pause(&new_config, app_state, parent_shutdown_token);
+    }
+
+    if old_config.ui.stop_on_lock != new_config.ui.stop_on_lock {
+        info!("Restarting session lock");
+        Component::stop_in_place(&mut components.session_lock, parent_shutdown_token).await;
+        components.session_lock = start_session_lock(&new_config, app_state, parent_shutdown_token);
+    }
+
+    if old_config.metrics != new_config.metrics {
+        info!("Restarting metrics endpoint");
+        Component::stop_in_place(&mut components.metrics, parent_shutdown_token).await;
+        components.metrics = start_metrics(&new_config, app_state, parent_shutdown_token);
+    }
+
+    app_state.request_ui_refresh();
+    app_state.publish(AppEvent::ConfigReloaded);
+    Ok(())
 }
@@ -0,0 +1,183 @@
+//! Text insertion through the AT-SPI2 accessibility bus: finds the focused
+//! editable widget and edits its text directly, without synthetic keystrokes.
+
+use crate::latency::{Stage, LATENCY};
+use dbus::arg::Variant;
+use dbus::blocking::{LocalConnection, Proxy};
+use dbus::channel::Channel;
+use eyre::{Result, WrapErr};
+use std::time::Duration;
+
+const A11Y_BUS_NAME: &str = "org.a11y.Bus";
+const A11Y_BUS_PATH: &str = "/org/a11y/bus";
+const REGISTRY_BUS_NAME: &str = "org.a11y.atspi.Registry";
+const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+const ACCESSIBLE_INTERFACE: &str = "org.a11y.atspi.Accessible";
+const TEXT_INTERFACE: &str = "org.a11y.atspi.Text";
+const EDITABLE_TEXT_INTERFACE: &str = "org.a11y.atspi.EditableText";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Objects looked at when searching a window for the focused widget, so that
+/// huge trees (e.g. browsers) don't stall typing
+const MAX_VISITED: usize = 2000;
+
+/// Bits of `AtspiStateType` in the state set returned by `GetState`
+const STATE_ACTIVE: u32 = 1;
+const STATE_EDITABLE: u32 = 7;
+const STATE_FOCUSED: u32 = 12;
+const STATE_SHOWING: u32 = 25;
+
+/// An accessible object: the unique bus name of its application and its path
+type ObjectRef = (String, dbus::Path<'static>);
+
+/// An editable text widget
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextTarget {
+    bus_name: String,
+    path: dbus::Path<'static>,
+}
+
+/// Connection to the accessibility bus
+pub struct Accessibility {
+    conn: LocalConnection,
+}
+
+fn has_state(states: &[u32], state: u32) -> bool {
+    states
+        .get((state / 32) as usize)
+        .is_some_and(|bits| bits & (1 << (state % 32)) != 0)
+}
+
+impl Accessibility {
+    pub fn connect() -> Result<Self> {
+        let session =
+            LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+        let (address,): (String,) = session
+            .with_proxy(A11Y_BUS_NAME, A11Y_BUS_PATH, TIMEOUT)
+            .method_call(A11Y_BUS_NAME, "GetAddress", ())
+            .wrap_err("Accessibility bus unavailable")?;
+        let mut channel =
+            Channel::open_private(&address).wrap_err("Failed to connect to accessibility bus")?;
+        channel
+            .register()
+            .wrap_err("Failed to register on accessibility bus")?;
+        Ok(Self {
+            conn: LocalConnection::from(channel),
+        })
+    }
+
+    fn proxy<'a>(&'a self, bus_name: &'a str, path: &'a str) -> Proxy<'a, &'a LocalConnection> {
+        self.conn.with_proxy(bus_name, path, TIMEOUT)
+    }
+
+    fn children(&self, (bus_name, path): &ObjectRef) -> Vec<ObjectRef> {
+        self.proxy(bus_name, path)
+            .method_call(ACCESSIBLE_INTERFACE, "GetChildren", ())
+            .map(|(children,): (Vec<ObjectRef>,)| children)
+            .unwrap_or_default()
+    }
+
+    fn states(&self, (bus_name, path): &ObjectRef) -> Vec<u32> {
+        self.proxy(bus_name, path)
+            .method_call(ACCESSIBLE_INTERFACE, "GetState", ())
+            .map(|(states,): (Vec<u32>,)| states)
+            .unwrap_or_default()
+    }
+
+    fn is_editable_text(&self, (bus_name, path): &ObjectRef) -> bool {
+        self.proxy(bus_name, path)
+            .method_call(ACCESSIBLE_INTERFACE, "GetInterfaces", ())
+            .is_ok_and(|(interfaces,): (Vec<String>,)| {
+                interfaces
+                    .iter()
+                    .any(|name| name == EDITABLE_TEXT_INTERFACE)
+            })
+    }
+
+    /// Depth-first search of the showing objects below `object` for the
+    /// focused editable one
+    fn find_focused(&self, object: &ObjectRef, visited: &mut usize) -> Option<ObjectRef> {
+        for child in self.children(object) {
+            *visited += 1;
+            if *visited > MAX_VISITED {
+                return None;
+            }
+            let states = self.states(&child);
+            if !has_state(&states, STATE_SHOWING) {
+                continue;
+            }
+            if has_state(&states, STATE_FOCUSED)
+                && has_state(&states, STATE_EDITABLE)
+                && self.is_editable_text(&child)
+            {
+                return Some(child);
+            }
+            if let Some(focused) = self.find_focused(&child, visited) {
+                return Some(focused);
+            }
+        }
+        None
+    }
+
+    /// The focused editable widget of the active window, if its application
+    /// is accessible
+    pub fn focused_text(&self) -> Option<TextTarget> {
+        let root = (REGISTRY_BUS_NAME.to_string(), dbus::Path::from(ROOT_PATH));
+        for application in self.children(&root) {
+            for window in self.children(&application) {
+                if !has_state(&self.states(&window), STATE_ACTIVE) {
+                    continue;
+                }
+                let mut visited = 0;
+                if let Some((bus_name, path)) = self.find_focused(&window, &mut visited) {
+                    return Some(TextTarget { bus_name, path });
+                }
+            }
+        }
+        None
+    }
+
+    pub fn caret_offset(&self, target: &TextTarget) -> Result<i32> {
+        let (Variant(offset),): (Variant<i32>,) = self
+            .proxy(&target.bus_name, &target.path)
+            .method_call(PROPERTIES_INTERFACE, "Get", (TEXT_INTERFACE, "CaretOffset"))
+            .wrap_err("Failed to read the caret position")?;
+        Ok(offset)
+    }
+
+    /// Insert `text` at the character offset `position` and move the caret
+    /// behind it
+    pub fn insert(&self, target: &TextTarget, position: i32, text: &str) -> Result<()> {
+        let proxy = self.proxy(&target.bus_name, &target.path);
+        // The length is in bytes, offsets are in characters
+        let (inserted,): (bool,) = proxy
+            .method_call(
+                EDITABLE_TEXT_INTERFACE,
+                "InsertText",
+                (position, text, text.len() as i32),
+            )
+            .wrap_err("Failed to insert text")?;
+        if !inserted {
+            bail!("The focused widget refused the text");
+        }
+        LATENCY.mark(Stage::FirstKeystroke);
+        let caret = position + text.chars().count() as i32;
+        let _: (bool,) = proxy
+            .method_call(TEXT_INTERFACE, "SetCaretOffset", (caret,))
+            .wrap_err("Failed to move the caret")?;
+        Ok(())
+    }
+
+    /// Delete the characters from `start` up to `end`
+    pub fn delete(&self, target: &TextTarget, start: i32, end: i32) -> Result<()> {
+        let (deleted,): (bool,) = self
+            .proxy(&target.bus_name, &target.path)
+            .method_call(EDITABLE_TEXT_INTERFACE, "DeleteText", (start, end))
+            .wrap_err("Failed to delete text")?;
+        if !deleted {
+            bail!("The focused widget refused to delete text");
+        }
+        Ok(())
+    }
+}
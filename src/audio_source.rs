@@ -0,0 +1,577 @@
+//! Where the audio of a recording comes from: the microphone (cpal), a WAV
+//! file played in its place, or a synthetic signal, optionally paired with
+//! the system audio (`[audio] system_audio`) as a second channel. The capture
+//! loop in `audio` chunks, mutes and meters the samples of any `AudioSource`.
+
+use crate::config::AudioConfig;
+use crate::metrics::METRICS;
+use crate::microphone::MicrophoneGuard;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use eyre::{OptionExt, Result, WrapErr};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// A source of samples in -1.0..=1.0, read from the capture thread.
+/// Sources need not be `Send`; they are opened on the thread reading them.
+pub trait AudioSource {
+    /// Rate of the samples returned by `read`
+    fn sample_rate(&self) -> u32;
+
+    /// Number of channels; the samples of a frame are interleaved and `read`
+    /// returns whole frames only
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    /// Wait up to `timeout` for the next samples. An empty buffer means none
+    /// arrived in time, `None` that the source has ended.
+    fn read(&mut self, timeout: Duration) -> Result<Option<Vec<f32>>>;
+}
+
+/// Select the configured input device, falling back to the system default
+pub fn select_input_device(audio_config: &AudioConfig) -> Result<cpal::Device> {
+    let host = cpal::default_host();
+
+    if let Some(name) = &audio_config.device {
+        let device = host
+            .input_devices()
+            .wrap_err("Failed to enumerate input devices")?
+            .find(|device| device.name().is_ok_and(|device_name| &device_name == name));
+
+        match device {
+            Some(device) => return Ok(device),
+            None => warn!(
+                "Configured input device '{}' not found, using default device",
+                name
+            ),
+        }
+    }
+
+    host.default_input_device()
+        .ok_or_eyre("No input device available")
+}
+
+/// The configured input device, captured through cpal
+pub struct CpalSource {
+    /// Capture runs while the stream is alive
+    _stream: cpal::Stream,
+    samples: std::sync::mpsc::Receiver<f32>,
+    sample_rate: u32,
+    /// Mutes the microphone again when capture ends
+    _microphone: Option<MicrophoneGuard>,
+}
+
+impl CpalSource {
+    pub fn open(audio_config: &AudioConfig) -> Result<Self> {
+        let microphone = MicrophoneGuard::unmute(audio_config).unwrap_or_else(|e| {
+            warn!("Could not unmute the microphone: {:#}", e);
+            None
+        });
+
+        let device = select_input_device(audio_config)?;
+        info!("Using input device: {}", device.name()?);
+
+        let supported_configs_range = device
+            .supported_input_configs()
+            .wrap_err("Failed to get supported configs")?;
+
+        // Find the best matching config with priority for 16kHz, fallback to any available rate
+        let supported_config =
+            find_best_config_with_priority(supported_configs_range, audio_config.channels)?;
+
+        let config = supported_config.config();
+        let sample_format = supported_config.sample_format();
+        info!(
+            "Audio config: {} channels, {} Hz, {:?}",
+            config.channels, config.sample_rate.0, sample_format
+        );
+
+        let (sample_tx, samples) = std::sync::mpsc::channel();
+        let stream = match sample_format {
+            SampleFormat::F32 => build_input_stream::<f32>(&device, &config, sample_tx)?,
+            SampleFormat::I16 => build_input_stream::<i16>(&device, &config, sample_tx)?,
+            SampleFormat::U16 => build_input_stream::<u16>(&device, &config, sample_tx)?,
+            SampleFormat::U8 => build_input_stream::<u8>(&device, &config, sample_tx)?,
+            SampleFormat::I32 => build_input_stream::<i32>(&device, &config, sample_tx)?,
+            _ => bail!("Unsupported sample format: {:?}", sample_format),
+        };
+        stream.play()?;
+
+        Ok(Self {
+            _stream: stream,
+            samples,
+            sample_rate: config.sample_rate.0,
+            _microphone: microphone,
+        })
+    }
+}
+
+impl AudioSource for CpalSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<Option<Vec<f32>>> {
+        let first = match self.samples.recv_timeout(timeout) {
+            Ok(sample) => sample,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => return Ok(Some(Vec::new())),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                info!("Audio sample channel disconnected");
+                return Ok(None);
+            }
+        };
+        let mut samples = vec![first];
+        samples.extend(self.samples.try_iter());
+        Ok(Some(samples))
+    }
+}
+
+/// Stream mono f32 samples of the device, mixing down multi-channel frames
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sender: std::sync::mpsc::Sender<f32>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample + Send + 'static,
+    f32: FromSample<T>,
+{
+    let channels = usize::from(config.channels.max(1));
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            for frame in data.chunks(channels) {
+                let sum: f32 = frame.iter().map(|&sample| f32::from_sample_(sample)).sum();
+                if sender.send(sum / frame.len() as f32).is_err() {
+                    break;
+                }
+            }
+        },
+        |err| {
+            error!("Audio stream error: {}", err);
+            METRICS.audio_errors.inc();
+        },
+        None,
+    )?;
+
+    Ok(stream)
+}
+
+fn find_best_config_with_priority(
+    configs: impl Iterator<Item = cpal::SupportedStreamConfigRange>,
+    target_channels: u16,
+) -> Result<cpal::SupportedStreamConfig> {
+    let mut best_config = None;
+    let mut best_score = f32::MAX;
+    let preferred_sample_rate = 16000u32; // Priority for 16kHz
+
+    for config_range in configs {
+        // Check if this config supports our channel count
+        if config_range.channels() != target_channels {
+            continue;
+        }
+
+        let min_rate = config_range.min_sample_rate().0;
+        let max_rate = config_range.max_sample_rate().0;
+
+        // Try preferred rate first (16kHz)
+        let sample_rate = if preferred_sample_rate >= min_rate && preferred_sample_rate <= max_rate
+        {
+            cpal::SampleRate(preferred_sample_rate)
+        } else {
+            // Fallback: use the rate closest to 16kHz within the available range
+            if preferred_sample_rate < min_rate {
+                config_range.min_sample_rate()
+            } else {
+                config_range.max_sample_rate()
+            }
+        };
+
+        // Calculate score (lower is better)
+        // Heavily prioritize 16kHz, but allow fallbacks
+        let rate_diff = (sample_rate.0 as f32 - preferred_sample_rate as f32).abs();
+        let rate_score = if sample_rate.0 == preferred_sample_rate {
+            0.0 // Perfect match gets best score
+        } else {
+            rate_diff / 1000.0 // Fallback rates get penalized based on distance from 16kHz
+        };
+
+        let format_score = match config_range.sample_format() {
+            SampleFormat::F32 => 0.0,  // Preferred
+            SampleFormat::I16 => 10.0, // Good
+            SampleFormat::I32 => 15.0, // Good but more processing
+            SampleFormat::U16 => 20.0, // Acceptable
+            SampleFormat::U8 => 30.0,  // Less preferred but supported
+            _ => 1000.0,               // Not supported
+        };
+
+        let score = rate_score + format_score;
+
+        if score < best_score {
+            best_score = score;
+            best_config = Some(config_range.with_sample_rate(sample_rate));
+        }
+    }
+
+    let config = best_config.ok_or_eyre("No compatible audio configuration found")?;
+    info!(
+        "Selected audio configuration: {} Hz (preferred: {} Hz)",
+        config.config().sample_rate.0,
+        preferred_sample_rate
+    );
+    Ok(config)
+}
+
+/// Releases samples no faster than they would be played at `speed` times
+/// realtime, so file and synthetic sources behave like a microphone
+struct Pacer {
+    speed: Option<f64>,
+    started: Option<Instant>,
+}
+
+impl Pacer {
+    /// Wait until the sample at `position` has been played, at most
+    /// `timeout`. Returns whether it has.
+    fn wait_until(&mut self, position: usize, sample_rate: u32, timeout: Duration) -> bool {
+        let Some(speed) = self.speed else {
+            return true;
+        };
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let due = started + Duration::from_secs_f64(position as f64 / sample_rate as f64 / speed);
+        let wait = due.saturating_duration_since(Instant::now());
+        std::thread::sleep(wait.min(timeout));
+        wait <= timeout
+    }
+}
+
+/// Samples released per read, 10 ms worth
+fn block_size(sample_rate: u32) -> usize {
+    (sample_rate / 100).max(1) as usize
+}
+
+/// A WAV file played in place of the microphone
+pub struct FileSource {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    position: usize,
+    pacer: Pacer,
+}
+
+impl FileSource {
+    /// Play the file at `speed` times realtime, or as fast as it is read
+    /// without a speed
+    pub fn open(path: &Path, speed: Option<f64>) -> Result<Self> {
+        let (samples, sample_rate) = read_wav(path)?;
+        Ok(Self {
+            samples,
+            sample_rate,
+            position: 0,
+            pacer: Pacer {
+                speed,
+                started: None,
+            },
+        })
+    }
+}
+
+impl AudioSource for FileSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<Option<Vec<f32>>> {
+        if self.position >= self.samples.len() {
+            return Ok(None);
+        }
+        let end = (self.position + block_size(self.sample_rate)).min(self.samples.len());
+        if !self.pacer.wait_until(end, self.sample_rate, timeout) {
+            return Ok(Some(Vec::new()));
+        }
+        let block = self.samples[self.position..end].to_vec();
+        self.position = end;
+        Ok(Some(block))
+    }
+}
+
+/// Samples of a WAV file mixed down to mono, and its sample rate
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let mut reader = hound::WavReader::open(path)
+        .wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    let samples: Vec<f32> = interleaved
+        .chunks(channels)
+        .map(|frame| (frame.iter().sum::<f32>() / frame.len() as f32).clamp(-1.0, 1.0))
+        .collect();
+    if samples.is_empty() {
+        bail!("{} contains no audio", path.display());
+    }
+    Ok((samples, spec.sample_rate))
+}
+
+/// Signal produced by a `SyntheticSource`, parsed from "silence", "sine"
+/// (440 Hz) or "sine:<Hz>"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Silence,
+    /// Sine wave of the frequency in Hz with the peak amplitude
+    Sine {
+        frequency: f32,
+        amplitude: f32,
+    },
+}
+
+impl std::str::FromStr for Signal {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let sine = |frequency| Signal::Sine {
+            frequency,
+            amplitude: 0.5,
+        };
+        match value.split_once(':') {
+            None if value == "silence" => Ok(Signal::Silence),
+            None if value == "sine" => Ok(sine(440.0)),
+            Some(("sine", frequency)) => match frequency.parse::<f32>() {
+                Ok(frequency) if frequency > 0.0 && frequency.is_finite() => Ok(sine(frequency)),
+                _ => Err(format!("invalid frequency: {frequency}")),
+            },
+            _ => Err(format!(
+                "unknown signal '{value}', expected silence, sine or sine:<Hz>"
+            )),
+        }
+    }
+}
+
+/// A generated signal, e.g. to test the pipeline without a microphone.
+/// Endless and produced as fast as it is read unless `realtime`.
+pub struct SyntheticSource {
+    signal: Signal,
+    sample_rate: u32,
+    position: usize,
+    pacer: Pacer,
+}
+
+impl SyntheticSource {
+    pub fn new(signal: Signal, sample_rate: u32) -> Self {
+        Self {
+            signal,
+            sample_rate,
+            position: 0,
+            pacer: Pacer {
+                speed: None,
+                started: None,
+            },
+        }
+    }
+
+    /// Produce samples in realtime, like a microphone
+    pub fn realtime(mut self) -> Self {
+        self.pacer.speed = Some(1.0);
+        self
+    }
+
+    fn sample(&self, index: usize) -> f32 {
+        match self.signal {
+            Signal::Silence => 0.0,
+            Signal::Sine {
+                frequency,
+                amplitude,
+            } => {
+                let time = index as f64 / self.sample_rate as f64;
+                amplitude * (std::f64::consts::TAU * f64::from(frequency) * time).sin() as f32
+            }
+        }
+    }
+}
+
+impl AudioSource for SyntheticSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<Option<Vec<f32>>> {
+        let end = self.position + block_size(self.sample_rate);
+        if !self.pacer.wait_until(end, self.sample_rate, timeout) {
+            return Ok(Some(Vec::new()));
+        }
+        let block = (self.position..end)
+            .map(|index| self.sample(index))
+            .collect();
+        self.position = end;
+        Ok(Some(block))
+    }
+}
+
+/// System audio lagging behind the microphone by more than this is dropped,
+/// so that both channels stay in sync when the sources drift apart
+const MAX_SYSTEM_AUDIO_LAG: Duration = Duration::from_millis(200);
+
+/// A PulseAudio/PipeWire source recorded with `parec`, usually the monitor
+/// of the speakers to capture what the other side of a call says
+pub struct MonitorSource {
+    child: Child,
+    samples: std::sync::mpsc::Receiver<Vec<f32>>,
+    sample_rate: u32,
+}
+
+impl MonitorSource {
+    /// Record `source` ("default" for the monitor of the default output) at
+    /// `sample_rate`, resampled by the sound server
+    pub fn open(source: &str, sample_rate: u32) -> Result<Self> {
+        let device = match source {
+            "default" => "@DEFAULT_MONITOR@",
+            source => source,
+        };
+        let mut child = Command::new("parec")
+            .arg(format!("--device={device}"))
+            .args(["--format=float32le", "--channels=1", "--raw"])
+            .arg(format!("--rate={sample_rate}"))
+            .arg("--latency-msec=20")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .wrap_err("Failed to run parec, is it installed?")?;
+        info!("Capturing system audio from {}", device);
+
+        let mut stdout = child.stdout.take().ok_or_eyre("parec has no stdout")?;
+        let (sample_tx, samples) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            let mut pending = Vec::new();
+            loop {
+                let read = match stdout.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+                pending.extend_from_slice(&buffer[..read]);
+                let block: Vec<f32> = pending
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    .collect();
+                pending.drain(..block.len() * 4);
+                if sample_tx.send(block).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            samples,
+            sample_rate,
+        })
+    }
+}
+
+impl AudioSource for MonitorSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<Option<Vec<f32>>> {
+        let mut samples = match self.samples.recv_timeout(timeout) {
+            Ok(block) => block,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => return Ok(Some(Vec::new())),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return Ok(None),
+        };
+        for block in self.samples.try_iter() {
+            samples.extend(block);
+        }
+        Ok(Some(samples))
+    }
+}
+
+impl Drop for MonitorSource {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The microphone and the system audio as the two channels of one source.
+/// The microphone sets the pace; system audio that hasn't arrived yet is
+/// silence, and the microphone alone continues if the system audio ends.
+pub struct DualSource {
+    microphone: Box<dyn AudioSource>,
+    system: Option<Box<dyn AudioSource>>,
+    /// System audio samples not yet paired with microphone samples
+    pending: VecDeque<f32>,
+}
+
+impl DualSource {
+    /// Pair two mono sources of the same sample rate
+    pub fn new(microphone: Box<dyn AudioSource>, system: Box<dyn AudioSource>) -> Result<Self> {
+        if microphone.channels() != 1 || system.channels() != 1 {
+            bail!("Only mono sources can be paired");
+        }
+        if microphone.sample_rate() != system.sample_rate() {
+            bail!(
+                "The microphone ({} Hz) and the system audio ({} Hz) differ in sample rate",
+                microphone.sample_rate(),
+                system.sample_rate()
+            );
+        }
+        Ok(Self {
+            microphone,
+            system: Some(system),
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl AudioSource for DualSource {
+    fn sample_rate(&self) -> u32 {
+        self.microphone.sample_rate()
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<Option<Vec<f32>>> {
+        let Some(microphone) = self.microphone.read(timeout)? else {
+            return Ok(None);
+        };
+        if let Some(system) = &mut self.system {
+            match system.read(Duration::ZERO)? {
+                Some(samples) => self.pending.extend(samples),
+                None => {
+                    warn!("System audio ended, continuing with the microphone only");
+                    self.system = None;
+                }
+            }
+        }
+
+        let max_lag = (MAX_SYSTEM_AUDIO_LAG.as_millis() * u128::from(self.sample_rate()) / 1000)
+            as usize
+            + microphone.len();
+        if self.pending.len() > max_lag {
+            let excess = self.pending.len() - max_lag;
+            self.pending.drain(..excess);
+        }
+
+        let mut frames = Vec::with_capacity(microphone.len() * 2);
+        for sample in microphone {
+            frames.push(sample);
+            frames.push(self.pending.pop_front().unwrap_or(0.0));
+        }
+        Ok(Some(frames))
+    }
+}
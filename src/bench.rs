@@ -0,0 +1,202 @@
+//! `gnome-voice-input bench`: stream a recorded WAV file through the
+//! transcription pipeline at real-time pace instead of the microphone and
+//! report how long the first interim and the final transcript took, plus the
+//! word error rate when the expected text is known.
+
+use crate::{
+    audio::AudioFormat,
+    audio_source,
+    config::Config,
+    stats::UsageStats,
+    transcription::Transcriber,
+    transcription_utils::{ConnectionStatus, TranscriptionResult},
+};
+use eyre::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+
+/// Timings of one run, measured from the first audio chunk sent
+struct Measurement {
+    first_interim: Option<Duration>,
+    first_final: Option<Duration>,
+    /// Last final result after the end of the audio
+    final_after_audio: Option<Duration>,
+    transcript: String,
+}
+
+pub async fn run_bench(
+    config_path: Option<PathBuf>,
+    wav_path: &Path,
+    expected: Option<String>,
+    runs: u32,
+) -> Result<()> {
+    let config = Config::load(config_path)?;
+    let (samples, sample_rate) = audio_source::read_wav(wav_path)?;
+    let samples: Vec<i16> = samples
+        .iter()
+        .map(|sample| (sample * i16::MAX as f32) as i16)
+        .collect();
+    let audio_duration = Duration::from_secs_f64(samples.len() as f64 / sample_rate as f64);
+    println!(
+        "{}: {:.1}s at {} Hz, model {}, language {}",
+        wav_path.display(),
+        audio_duration.as_secs_f64(),
+        sample_rate,
+        config.transcription.model,
+        config.transcription.language
+    );
+
+    let chunk_samples = (sample_rate as usize * config.audio.audio_chunk_ms as usize / 1000).max(1);
+    let mut measurements = Vec::new();
+    for run in 1..=runs.max(1) {
+        let measurement = bench_run(&config, &samples, sample_rate, chunk_samples).await?;
+        println!(
+            "\nRun {run}: first interim {}, first final {}, final after end of audio {}",
+            format_latency(measurement.first_interim),
+            format_latency(measurement.first_final),
+            format_latency(measurement.final_after_audio)
+        );
+        println!("  Transcript: {}", measurement.transcript);
+        if let Some(expected) = &expected {
+            println!(
+                "  Word error rate: {:.1}%",
+                word_error_rate(expected, &measurement.transcript) * 100.0
+            );
+        }
+        measurements.push(measurement);
+    }
+
+    if measurements.len() > 1 {
+        println!("\nMedian over {} runs:", measurements.len());
+        println!(
+            "  first interim {}, first final {}, final after end of audio {}",
+            format_latency(median(measurements.iter().map(|m| m.first_interim))),
+            format_latency(median(measurements.iter().map(|m| m.first_final))),
+            format_latency(median(measurements.iter().map(|m| m.final_after_audio)))
+        );
+    }
+    Ok(())
+}
+
+async fn bench_run(
+    config: &Config,
+    samples: &[i16],
+    sample_rate: u32,
+    chunk_samples: usize,
+) -> Result<Measurement> {
+    let transcriber = Transcriber::new(
+        config.deepgram_api_key.clone(),
+        config.transcription.clone(),
+        Arc::new(watch::channel(ConnectionStatus::Disconnected).0),
+        // Not saved, so benchmarks don't count as dictation
+        Arc::new(UsageStats::load()),
+        false,
+    );
+    let (audio_tx, audio_rx) = mpsc::channel(100);
+    let mut transcription_rx = Arc::new(transcriber)
+        .transcribe_stream(audio_rx, AudioFormat::mono(sample_rate))
+        .await?;
+
+    // Feed the file at the pace of a live microphone
+    let chunks: Vec<Vec<u8>> = samples
+        .chunks(chunk_samples)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .flat_map(|sample| sample.to_le_bytes())
+                .collect()
+        })
+        .collect();
+    let chunk_duration = Duration::from_secs_f64(chunk_samples as f64 / sample_rate as f64);
+    let started = Instant::now();
+    let (audio_done_tx, audio_done_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(chunk_duration);
+        for chunk in chunks {
+            ticks.tick().await;
+            if audio_tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+        let _ = audio_done_tx.send(Instant::now());
+        // Dropping the sender closes the stream, which flushes the final results
+    });
+
+    let mut measurement = Measurement {
+        first_interim: None,
+        first_final: None,
+        final_after_audio: None,
+        transcript: String::new(),
+    };
+    let mut last_final = None;
+    let mut finals = Vec::new();
+    while let Some(result) = transcription_rx.recv().await {
+        match result {
+            TranscriptionResult::Interim(_) => {
+                measurement.first_interim.get_or_insert(started.elapsed());
+            }
+            TranscriptionResult::Final(transcript) => {
+                measurement.first_final.get_or_insert(started.elapsed());
+                last_final = Some(Instant::now());
+                finals.push(transcript.text);
+            }
+            TranscriptionResult::UtteranceEnd => {}
+        }
+    }
+
+    if let (Ok(audio_done), Some(last_final)) = (audio_done_rx.await, last_final) {
+        measurement.final_after_audio = Some(last_final.saturating_duration_since(audio_done));
+    }
+    if finals.is_empty() {
+        bail!("No transcript received, check the API key and the log with RUST_LOG=gnome_voice_input=debug");
+    }
+    measurement.transcript = finals.join(" ");
+    Ok(measurement)
+}
+
+/// Word-level edit distance between the transcript and the expected text,
+/// relative to the number of expected words, ignoring case and punctuation
+fn word_error_rate(expected: &str, actual: &str) -> f64 {
+    let words = |text: &str| -> Vec<String> {
+        text.split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>()
+            })
+            .filter(|word| !word.is_empty())
+            .collect()
+    };
+    let expected = words(expected);
+    let actual = words(actual);
+    if expected.is_empty() {
+        return if actual.is_empty() { 0.0 } else { 1.0 };
+    }
+
+    let mut previous: Vec<usize> = (0..=actual.len()).collect();
+    for (i, expected_word) in expected.iter().enumerate() {
+        let mut current = vec![i + 1; actual.len() + 1];
+        for (j, actual_word) in actual.iter().enumerate() {
+            let substitution = previous[j] + usize::from(expected_word != actual_word);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[actual.len()] as f64 / expected.len() as f64
+}
+
+fn median(values: impl Iterator<Item = Option<Duration>>) -> Option<Duration> {
+    let mut values: Vec<Duration> = values.flatten().collect();
+    values.sort();
+    values.get(values.len() / 2).copied()
+}
+
+fn format_latency(latency: Option<Duration>) -> String {
+    match latency {
+        Some(latency) => format!("{} ms", latency.as_millis()),
+        None => "-".to_string(),
+    }
+}
@@ -0,0 +1,85 @@
+//! Sentence casing for transcripts without (or with too little) smart
+//! formatting: capital letters at sentence starts and for the pronoun "I",
+//! and the usual spacing around punctuation.
+
+/// Punctuation written directly after the preceding word
+const CLOSING_PUNCTUATION: [char; 6] = [',', '.', '!', '?', ';', ':'];
+
+/// Punctuation followed by a space when a word comes right after it. Not `.`
+/// and `:`, which also appear inside domains, URLs and times.
+const SPACED_PUNCTUATION: [char; 4] = [',', '!', '?', ';'];
+
+const SENTENCE_END: [char; 3] = ['.', '!', '?'];
+
+/// Collapse runs of spaces, drop spaces before closing punctuation and add
+/// one after it where it is missing. Line breaks are kept.
+fn normalize_spacing(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut normalized = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let next = chars.get(i + 1).copied();
+        if c == ' ' || c == '\t' {
+            let is_run = next.is_some_and(|next| next == ' ' || next == '\t');
+            let before_punctuation = normalized
+                .chars()
+                .last()
+                .is_some_and(|previous| previous != ' ' && previous != '\n')
+                && next.is_some_and(|next| CLOSING_PUNCTUATION.contains(&next));
+            if !is_run && !before_punctuation {
+                normalized.push(' ');
+            }
+            continue;
+        }
+        normalized.push(c);
+        if SPACED_PUNCTUATION.contains(&c) && next.is_some_and(char::is_alphabetic) {
+            normalized.push(' ');
+        }
+    }
+    normalized
+}
+
+/// Whether the `.` at `index` ends an abbreviation such as "e.g."
+fn is_abbreviation(chars: &[char], index: usize) -> bool {
+    chars[index] == '.' && index >= 2 && chars[index - 2] == '.'
+}
+
+/// Whether the word starting at `index` is the pronoun "i" or a contraction
+/// of it ("i'm", "i've")
+fn is_pronoun_i(chars: &[char], index: usize) -> bool {
+    let starts_word = index == 0 || !chars[index - 1].is_alphanumeric();
+    let ends_word = match chars.get(index + 1) {
+        None => true,
+        Some('\'' | '’') => chars.get(index + 2).is_some_and(|c| c.is_alphabetic()),
+        Some(next) => !next.is_alphanumeric(),
+    };
+    chars[index] == 'i' && starts_word && ends_word
+}
+
+/// Capitalize the first letter of every sentence and the pronoun "I", and
+/// normalize the spacing around punctuation
+pub fn sentence_case(text: &str) -> String {
+    let chars: Vec<char> = normalize_spacing(text).chars().collect();
+    let mut cased = String::with_capacity(chars.len());
+    let mut sentence_start = true;
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_alphabetic() {
+            if sentence_start || is_pronoun_i(&chars, i) {
+                cased.extend(c.to_uppercase());
+            } else {
+                cased.push(c);
+            }
+            sentence_start = false;
+            continue;
+        }
+        cased.push(c);
+        if c == '\n' {
+            sentence_start = true;
+        } else if SENTENCE_END.contains(&c) && !is_abbreviation(&chars, i) {
+            // Only when a space follows, so "example.com" stays as it is
+            sentence_start = chars.get(i + 1).is_some_and(|next| next.is_whitespace());
+        } else if c.is_alphanumeric() {
+            sentence_start = false;
+        }
+    }
+    cased
+}
@@ -1,53 +1,490 @@
+use crate::numbers::{self, NumberFormat};
+use crate::pii::Redactor;
+use crate::{keyring, redact, templates, tls};
 use dirs::config_dir;
 use eyre::{OptionExt, Result, WrapErr};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Commented example config documenting every option, written by `config init`
+pub const CONFIG_TEMPLATE: &str = include_str!("../config/default.toml");
+
+/// Optional file next to the config, merged over it (e.g. machine-specific settings)
+const LOCAL_OVERLAY_FILE: &str = "config.local.toml";
+const NO_KEYRING_API_KEY: &str =
+    "No Deepgram API key in the keyring, store one with `gnome-voice-input set-key`";
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// The API key, or "keyring" to read it from the Secret Service keyring
     pub deepgram_api_key: String,
+    /// Further keys, switched to in order when Deepgram rejects the current
+    /// one (invalid key, out of credits, over its rate limit)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deepgram_api_keys: Vec<String>,
+    /// Further config files merged over this one, relative to its directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Name of the profile applied on top of the `[transcription]` section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+    /// Start with voice input paused, kept in sync with the tray's Pause item
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub paused: bool,
+    /// Keep nothing said: no history, debug recordings or transcripts in the
+    /// logs, kept in sync with the tray's Privacy Mode item
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub privacy: bool,
+    /// Whether hotkeys and typing use X11 and `/dev/uinput` directly or only
+    /// XDG portals, as in a Flatpak sandbox. Read at startup only.
+    #[serde(default)]
+    pub desktop_access: DesktopAccess,
     pub hotkey: HotkeyConfig,
     pub audio: AudioConfig,
+    /// Effective transcription settings, with the active profile applied
     #[serde(default)]
     pub transcription: TranscriptionConfig,
     #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub post_processing: PostProcessingConfig,
+    #[serde(default)]
     pub ui: UiConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub meeting: MeetingConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub usage: UsageConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub debug: DebugConfig,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<Profile>,
+    /// Overrides for specific applications, the first matching rule applies
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub app_rules: Vec<AppRule>,
+    /// The `[transcription]` section as written in the file
+    #[serde(skip)]
+    base_transcription: TranscriptionConfig,
+    /// `active_profile` as written in the file
+    #[serde(skip)]
+    configured_profile: Option<String>,
+    /// `deepgram_api_key` was read from the keyring
+    #[serde(skip)]
+    api_key_from_keyring: bool,
+}
+
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let api_key = if self.deepgram_api_key == keyring::KEYRING_PLACEHOLDER {
+            self.deepgram_api_key.clone()
+        } else {
+            redact::mask(&self.deepgram_api_key)
+        };
+        f.debug_struct("Config")
+            .field("deepgram_api_key", &api_key)
+            .field(
+                "deepgram_api_keys",
+                &self
+                    .deepgram_api_keys
+                    .iter()
+                    .map(|key| redact::mask(key))
+                    .collect::<Vec<_>>(),
+            )
+            .field("include", &self.include)
+            .field("active_profile", &self.active_profile)
+            .field("paused", &self.paused)
+            .field("privacy", &self.privacy)
+            .field("desktop_access", &self.desktop_access)
+            .field("hotkey", &self.hotkey)
+            .field("audio", &self.audio)
+            .field("transcription", &self.transcription)
+            .field("output", &self.output)
+            .field("post_processing", &self.post_processing)
+            .field("ui", &self.ui)
+            .field("history", &self.history)
+            .field("meeting", &self.meeting)
+            .field("metrics", &self.metrics)
+            .field("usage", &self.usage)
+            .field("logging", &self.logging)
+            .field("debug", &self.debug)
+            .field("profiles", &self.profiles)
+            .field("app_rules", &self.app_rules)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Named set of transcription settings that override the `[transcription]`
+/// section while the profile is active
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Output mode: type interim results as they arrive, or only final ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_interim_results: Option<bool>,
+    /// Added to (or overriding) the replacements of the `[transcription]` section
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub replacements: BTreeMap<String, String>,
+}
+
+impl Profile {
+    fn apply_to(&self, transcription: &mut TranscriptionConfig) {
+        if let Some(language) = &self.language {
+            transcription.language = language.clone();
+        }
+        if let Some(model) = &self.model {
+            transcription.model = model.clone();
+        }
+        if let Some(use_interim_results) = self.use_interim_results {
+            transcription.use_interim_results = use_interim_results;
+        }
+        transcription.replacements.extend(
+            self.replacements
+                .iter()
+                .map(|(from, to)| (from.clone(), to.clone())),
+        );
+    }
+}
+
+/// Settings applied when recording starts while a matching window is focused
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppRule {
+    /// Window class (WM_CLASS instance or class name), case-insensitive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    /// Text contained in the window title, case-insensitive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Output mode: type interim results as they arrive, or only final ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_interim_results: Option<bool>,
+    /// Don't record at all while this window is focused
+    #[serde(default)]
+    pub disabled: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AppRule {
+    /// Whether the rule applies to a window. A rule without class and title
+    /// matches nothing.
+    pub fn matches(&self, classes: &[String], title: &str) -> bool {
+        if self.class.is_none() && self.title.is_none() {
+            return false;
+        }
+        let class_matches = self.class.as_ref().is_none_or(|class| {
+            classes
+                .iter()
+                .any(|window_class| window_class.eq_ignore_ascii_case(class))
+        });
+        let title_matches = self
+            .title
+            .as_ref()
+            .is_none_or(|part| title.to_lowercase().contains(&part.to_lowercase()));
+        class_matches && title_matches
+    }
+
+    pub fn apply_to(&self, transcription: &mut TranscriptionConfig) {
+        if let Some(language) = &self.language {
+            transcription.language = language.clone();
+        }
+        if let Some(use_interim_results) = self.use_interim_results {
+            transcription.use_interim_results = use_interim_results;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HotkeyConfig {
     pub modifiers: Vec<String>,
     pub key: String,
+    #[serde(default)]
+    pub mode: HotkeyMode,
+    #[serde(default)]
+    pub backend: HotkeyBackend,
+    /// Tried in order when the main hotkey is grabbed by another application
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<HotkeyFallback>,
+    /// Additional hotkeys, each bound to its own action
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bindings: Vec<HotkeyBinding>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HotkeyFallback {
+    pub modifiers: Vec<String>,
+    pub key: String,
+}
+
+/// Modifiers and key of a configured hotkey
+pub trait KeyCombination {
+    fn modifiers(&self) -> &[String];
+    fn key(&self) -> &str;
+}
+
+impl KeyCombination for HotkeyBinding {
+    fn modifiers(&self) -> &[String] {
+        &self.modifiers
+    }
+
+    fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl KeyCombination for HotkeyFallback {
+    fn modifiers(&self) -> &[String] {
+        &self.modifiers
+    }
+
+    fn key(&self) -> &str {
+        &self.key
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How the app reaches the desktop for hotkeys and typing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DesktopAccess {
+    /// Portals only inside a Flatpak sandbox, the configured backends otherwise
+    #[default]
+    Auto,
+    /// The configured hotkey backend, typing through X11 or `/dev/uinput`
+    Direct,
+    /// GlobalShortcuts portal for hotkeys, RemoteDesktop portal for typing;
+    /// no X11 overlay or focused window detection
+    Portal,
+}
+
+/// How global hotkeys are registered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyBackend {
+    /// GlobalShortcuts portal on Wayland sessions, X11 otherwise
+    #[default]
+    Auto,
+    /// Grab keys through X11 (also works under XWayland on some compositors)
+    X11,
+    /// XDG GlobalShortcuts portal
+    Portal,
+    /// GNOME custom keyboard shortcut running `gnome-voice-input toggle`,
+    /// installed with `gnome-voice-input install-shortcut`. No keys are grabbed
+    /// by the app itself and additional bindings are not available.
+    Gnome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HotkeyBinding {
+    pub modifiers: Vec<String>,
+    pub key: String,
+    pub action: HotkeyAction,
+}
+
+/// Action triggered by a hotkey binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    /// Start or stop recording
+    Toggle,
+    /// Record while the hotkey is held down
+    PushToTalk,
+    /// Stop recording without typing anything that is still pending
+    Cancel,
+    /// Type the most recent transcript again
+    RetypeLast,
+    /// Switch to the next language in `transcription.languages`
+    SwitchLanguage,
+    /// Mute or unmute the microphone while keeping the session open
+    Mute,
+}
+
+/// How the hotkey controls recording
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyMode {
+    /// Each press starts or stops recording
+    #[default]
+    Toggle,
+    /// Record only while the hotkey is held down
+    PushToTalk,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AudioConfig {
+    /// Name of the input device to use; the system default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
     pub sample_rate: u32,
     pub channels: u16,
     pub buffer_size: usize,
     #[serde(default = "default_audio_chunk_ms")]
     pub audio_chunk_ms: u32,
+    /// Stop recording automatically after this many seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration_secs: Option<u64>,
+    /// Stop recording when no final transcript arrived for this many seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactivity_timeout_secs: Option<u64>,
+    /// After stopping, wait this long for the remaining final transcripts
+    #[serde(default = "default_finalize_timeout_secs")]
+    pub finalize_timeout_secs: u64,
+    /// Unmute the default PulseAudio/PipeWire source for a recording if it is
+    /// muted, and mute it again afterwards; also when `device` is set
+    #[serde(default)]
+    pub unmute: bool,
+    /// With `unmute`, raise the source volume to at least this many percent
+    /// while recording
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unmute_volume: Option<u32>,
+    /// PulseAudio/PipeWire source captured with `parec` as a second channel
+    /// next to the microphone, e.g. "default" for the monitor of the default
+    /// output, so both sides of a call are transcribed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_audio: Option<String>,
+    /// Labels of the microphone and the system audio channel, starting their
+    /// final transcripts with `system_audio`
+    #[serde(default = "default_channel_labels")]
+    pub channel_labels: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TranscriptionConfig {
     #[serde(default = "default_use_interim_results")]
     pub use_interim_results: bool,
     #[serde(default = "default_model")]
     pub model: String,
+    #[serde(default = "default_models")]
+    pub models: Vec<String>,
     #[serde(default = "default_language")]
     pub language: String,
+    /// Languages cycled through by the `switch_language` hotkey action
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
     #[serde(default = "default_smart_format")]
     pub smart_format: bool,
     #[serde(default = "default_punctuate")]
     pub punctuate: bool,
+    /// Text replaced in transcripts before typing, e.g. "new line" = "\n"
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub replacements: BTreeMap<String, String>,
+    /// Settings applied while a language is active, keyed by language code
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub per_language: BTreeMap<String, LanguageSettings>,
+    /// Deepgram-compatible server used instead of api.deepgram.com, e.g. a
+    /// self-hosted instance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Names and jargon to recognize, sent as keyterms and corrected by the
+    /// `vocabulary` output filter. Includes the terms of `vocabulary_file`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vocabulary: Vec<String>,
+    /// File with one vocabulary term per line, relative to the config file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vocabulary_file: Option<PathBuf>,
+    #[serde(default)]
+    pub tls: TlsConfig,
+}
+
+/// TLS settings of the transcription connection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM file with CA certificates trusted in addition to the built-in
+    /// ones, e.g. of a TLS-intercepting proxy or a private CA
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_file: Option<PathBuf>,
+    /// Verify the server certificate; turning this off is only meant for
+    /// testing against servers with throwaway certificates
+    #[serde(default = "default_tls_verify")]
+    pub verify: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            ca_file: None,
+            verify: default_tls_verify(),
+        }
+    }
+}
+
+fn default_tls_verify() -> bool {
+    true
+}
+
+impl TranscriptionConfig {
+    /// These settings with the `per_language` section of the current language
+    /// applied. It takes precedence over profiles and app rules, which may
+    /// have chosen the language.
+    pub fn for_current_language(&self) -> TranscriptionConfig {
+        let mut transcription = self.clone();
+        if let Some(settings) = self.per_language.get(&self.language) {
+            settings.apply_to(&mut transcription);
+        }
+        transcription
+    }
+}
+
+/// Overrides from a `[transcription.per_language.<code>]` section
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LanguageSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_interim_results: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smart_format: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub punctuate: Option<bool>,
+    /// Added to (or overriding) the replacements of the `[transcription]` section
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub replacements: BTreeMap<String, String>,
+}
+
+impl LanguageSettings {
+    fn apply_to(&self, transcription: &mut TranscriptionConfig) {
+        if let Some(model) = &self.model {
+            transcription.model = model.clone();
+        }
+        if let Some(use_interim_results) = self.use_interim_results {
+            transcription.use_interim_results = use_interim_results;
+        }
+        if let Some(smart_format) = self.smart_format {
+            transcription.smart_format = smart_format;
+        }
+        if let Some(punctuate) = self.punctuate {
+            transcription.punctuate = punctuate;
+        }
+        transcription.replacements.extend(
+            self.replacements
+                .iter()
+                .map(|(from, to)| (from.clone(), to.clone())),
+        );
+    }
 }
 
 fn default_audio_chunk_ms() -> u32 {
     25 // 25ms chunks
 }
 
+fn default_finalize_timeout_secs() -> u64 {
+    5
+}
+
+fn default_channel_labels() -> Vec<String> {
+    vec!["Me".to_string(), "Others".to_string()]
+}
+
 fn default_use_interim_results() -> bool {
     false
 }
@@ -56,6 +493,13 @@ fn default_model() -> String {
     "nova-3".to_string()
 }
 
+fn default_models() -> Vec<String> {
+    ["nova-3", "nova-2", "nova", "enhanced", "base"]
+        .iter()
+        .map(|model| model.to_string())
+        .collect()
+}
+
 fn default_language() -> String {
     "en".to_string()
 }
@@ -72,28 +516,596 @@ fn default_show_tray_icon() -> bool {
     true
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_show_overlay() -> bool {
+    true
+}
+
+fn default_recording_notification() -> bool {
+    true
+}
+
+fn default_stop_on_lock() -> bool {
+    true
+}
+
+fn default_recent_transcripts() -> usize {
+    10
+}
+
+/// How transcripts are processed and where they go, read when a session starts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputConfig {
+    /// Applied to every transcript in order, before the handlers see it
+    #[serde(default = "default_output_filters")]
+    pub filters: Vec<OutputFilter>,
+    /// All of these receive the filtered transcripts
+    #[serde(default = "default_output_handlers")]
+    pub handlers: Vec<OutputHandler>,
+    /// File the `file` handler appends final transcripts to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Stop typing a transcript when another window gets the focus while it
+    /// is spoken, instead of typing the rest into that window (X11 windows)
+    #[serde(default = "default_abort_on_focus_change")]
+    pub abort_on_focus_change: bool,
+    /// Where interim results are shown when `use_interim_results` is on
+    #[serde(default)]
+    pub interim_display: InterimDisplay,
+    /// Spoken phrases the `emoji` filter replaces, in addition to the
+    /// built-in ones ("thumbs up emoji", "smiley face", ...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub emoji: BTreeMap<String, String>,
+    /// Variables the `templates` filter expands after "insert", with their
+    /// strftime format, in addition to the built-in ones ("date", "time", ...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, String>,
+    /// Locale the `numbers` filter formats for, e.g. "de-DE"; the
+    /// transcription language when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number_locale: Option<String>,
+    #[serde(default)]
+    pub profanity: ProfanityConfig,
+    #[serde(default)]
+    pub pii: PiiConfig,
+    #[serde(default)]
+    pub spell: SpellConfig,
+    #[serde(default)]
+    pub typing: TypingConfig,
+    #[serde(default)]
+    pub review: ReviewConfig,
+    #[serde(default)]
+    pub subtitles: SubtitlesConfig,
+}
+
+/// Confirming each final transcript in a dialog before it is typed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReviewConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Insert the transcript unchanged when the dialog isn't answered within
+    /// this many seconds; it waits for an answer when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_insert_secs: Option<u64>,
+}
+
+/// Subtitle files written by the `subtitles` handler
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SubtitlesConfig {
+    /// Directory of the files, one per session named after its start time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+    #[serde(default)]
+    pub format: SubtitleFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleFormat {
+    /// SubRip (`.srt`)
+    #[default]
+    Srt,
+    /// WebVTT (`.vtt`)
+    Vtt,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterimDisplay {
+    /// Typed into the target and replaced as the transcript changes
+    #[default]
+    Typed,
+    /// Shown in the recording overlay only; the target just gets the final
+    /// transcripts, without backspacing and retyping
+    Overlay,
+}
+
+/// How the `keyboard` and `atspi` handlers keep up with the transcripts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypingConfig {
+    /// Typing speed limit for targets that lose fast keystrokes (remote
+    /// desktops, some Electron apps); as fast as possible when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chars_per_second: Option<u32>,
+    /// Keyboard layout such as "fr" or "de", deciding which characters are
+    /// pasted because typing them would produce others; detected when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// What happens to transcripts arriving faster than they are typed
+    #[serde(default)]
+    pub backlog: TypingBacklog,
+    /// Calls waiting to be typed before transcription has to wait
+    #[serde(default = "default_typing_queue_size")]
+    pub queue_size: usize,
+}
+
+impl Default for TypingConfig {
+    fn default() -> Self {
+        Self {
+            chars_per_second: None,
+            layout: None,
+            backlog: TypingBacklog::default(),
+            queue_size: default_typing_queue_size(),
+        }
+    }
+}
+
+fn default_typing_queue_size() -> usize {
+    64
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypingBacklog {
+    /// Type the finals waiting as one and skip interims already outdated
+    #[default]
+    Coalesce,
+    /// Type every transcript in order
+    Keep,
+}
+
+/// Commands switching the `spell` filter's letter-by-letter mode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpellConfig {
+    #[serde(default = "default_spell_start")]
+    pub start: String,
+    #[serde(default = "default_spell_stop")]
+    pub stop: String,
+}
+
+impl Default for SpellConfig {
+    fn default() -> Self {
+        Self {
+            start: default_spell_start(),
+            stop: default_spell_stop(),
+        }
+    }
+}
+
+fn default_spell_start() -> String {
+    "start spelling".to_string()
+}
+
+fn default_spell_stop() -> String {
+    "stop spelling".to_string()
+}
+
+/// What the `pii` filter redacts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PiiConfig {
+    #[serde(default = "default_pii_kinds")]
+    pub kinds: Vec<PiiKind>,
+    /// More regular expressions by name; matches are replaced by "[name]"
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub patterns: BTreeMap<String, String>,
+}
+
+impl Default for PiiConfig {
+    fn default() -> Self {
+        Self {
+            kinds: default_pii_kinds(),
+            patterns: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    /// Numbers passing the Luhn checksum
+    CreditCard,
+    Phone,
+}
+
+fn default_pii_kinds() -> Vec<PiiKind> {
+    vec![PiiKind::Email, PiiKind::CreditCard, PiiKind::Phone]
+}
+
+/// Words the `profanity` filter masks or removes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfanityConfig {
+    #[serde(default)]
+    pub mode: ProfanityMode,
+    /// Blocked in addition to the built-in list; a trailing `*` blocks all
+    /// words starting with the rest
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfanityMode {
+    /// Keep the first letter and replace the rest with `*`
+    #[default]
+    Mask,
+    /// Drop the word
+    Remove,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            filters: default_output_filters(),
+            handlers: default_output_handlers(),
+            file: None,
+            abort_on_focus_change: default_abort_on_focus_change(),
+            interim_display: InterimDisplay::default(),
+            emoji: BTreeMap::new(),
+            templates: BTreeMap::new(),
+            number_locale: None,
+            profanity: ProfanityConfig::default(),
+            pii: PiiConfig::default(),
+            spell: SpellConfig::default(),
+            typing: TypingConfig::default(),
+            review: ReviewConfig::default(),
+            subtitles: SubtitlesConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFilter {
+    /// The `replacements` of the transcription settings
+    Replacements,
+    /// Upper-case the first letter of each transcript
+    Capitalize,
+    /// Capitalize sentence starts and the pronoun "I", and normalize the
+    /// spacing around punctuation
+    SentenceCase,
+    /// Replace spoken emoji names such as "thumbs up emoji" with the emoji
+    Emoji,
+    /// Expand "insert today's date", "insert time" and the configured
+    /// template variables
+    Templates,
+    /// Write numbers, currencies, percentages and units the way the
+    /// `number_locale` does ("1.234,5", "12,50 €")
+    Numbers,
+    /// Mask or remove the words of the `[output.profanity]` blocklist
+    Profanity,
+    /// Replace email addresses, phone and credit card numbers and the
+    /// `[output.pii]` patterns with a placeholder
+    Pii,
+    /// Write spoken letters, NATO alphabet words and digits as characters
+    /// between the `[output.spell]` commands
+    Spell,
+    /// Correct words that sound like a `vocabulary` term to the term
+    Vocabulary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputHandler {
+    /// Type into the focused window
+    Keyboard,
+    /// Insert into the focused widget through the AT-SPI2 accessibility bus,
+    /// typing where that isn't available
+    Atspi,
+    /// Collect final transcripts in an editable window and paste them at
+    /// once when it is closed with Insert
+    Buffer,
+    /// Append final transcripts to `[output] file`
+    File,
+    /// Write final transcripts as timed subtitles, a file per session in
+    /// `[output.subtitles] directory`
+    Subtitles,
+    /// Put the session's final transcripts in the PRIMARY selection, pasted
+    /// with a middle click
+    Primary,
+    /// Log what would be typed
+    Log,
+    /// Print to stdout
+    Console,
+}
+
+fn default_output_filters() -> Vec<OutputFilter> {
+    vec![OutputFilter::Replacements, OutputFilter::Vocabulary]
+}
+
+fn default_output_handlers() -> Vec<OutputHandler> {
+    vec![OutputHandler::Keyboard]
+}
+
+fn default_abort_on_focus_change() -> bool {
+    true
+}
+
+/// Steps rewriting final transcripts after the `[output]` filters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PostProcessingConfig {
+    /// Clean up final transcripts with a language model; off when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llm: Option<LlmConfig>,
+}
+
+/// An OpenAI-compatible chat completions endpoint rewriting final transcripts
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmConfig {
+    /// Base URL of the API; `/chat/completions` is appended
+    #[serde(default = "default_llm_endpoint")]
+    pub endpoint: String,
+    pub model: String,
+    /// System prompt; the transcript is sent as the user message
+    #[serde(default = "default_llm_prompt")]
+    pub prompt: String,
+    /// Sent as bearer token; local servers usually need none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// The raw transcript is used if no answer arrives within this time
+    #[serde(default = "default_llm_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl std::fmt::Debug for LlmConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlmConfig")
+            .field("endpoint", &self.endpoint)
+            .field("model", &self.model)
+            .field("prompt", &self.prompt)
+            .field("api_key", &self.api_key.as_deref().map(redact::mask))
+            .field("timeout_ms", &self.timeout_ms)
+            .finish()
+    }
+}
+
+fn default_llm_endpoint() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+fn default_llm_prompt() -> String {
+    "Fix grammar, punctuation and obvious recognition errors in this dictated text. \
+     Keep its meaning and language. Reply with the corrected text only."
+        .to_string()
+}
+
+fn default_llm_timeout_ms() -> u64 {
+    3000
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UiConfig {
     #[serde(default = "default_show_tray_icon")]
     pub show_tray_icon: bool,
+    #[serde(default = "default_show_overlay")]
+    pub show_overlay: bool,
+    #[serde(default = "default_recent_transcripts")]
+    pub recent_transcripts: usize,
+    #[serde(default = "default_recording_notification")]
+    pub recording_notification: bool,
+    #[serde(default)]
+    pub animate_tray_icon: bool,
+    /// Pause playing MPRIS media players while recording and resume them
+    /// afterwards
+    #[serde(default)]
+    pub pause_media: bool,
+    /// Stop recording when the screen locks, the screensaver starts or the
+    /// system goes to sleep
+    #[serde(default = "default_stop_on_lock")]
+    pub stop_on_lock: bool,
 }
 
 impl Default for UiConfig {
     fn default() -> Self {
         Self {
-            show_tray_icon: true,
+            show_tray_icon: default_show_tray_icon(),
+            show_overlay: default_show_overlay(),
+            recent_transcripts: default_recent_transcripts(),
+            recording_notification: default_recording_notification(),
+            animate_tray_icon: false,
+            pause_media: false,
+            stop_on_lock: default_stop_on_lock(),
+        }
+    }
+}
+
+/// Persistent transcript history in the state directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// Record final transcripts; when off nothing is written
+    #[serde(default = "default_history_enabled")]
+    pub enabled: bool,
+    /// Delete entries older than this many days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<u64>,
+    /// Keep at most this many entries, deleting the oldest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_history_enabled(),
+            retention_days: None,
+            max_entries: None,
+        }
+    }
+}
+
+fn default_history_enabled() -> bool {
+    true
+}
+
+/// Meeting transcripts, started from the tray: transcribed continuously into
+/// a file instead of typed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MeetingConfig {
+    /// Directory of the transcripts, one per meeting named after its start;
+    /// `Meetings` in the documents directory when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+    #[serde(default)]
+    pub format: MeetingFormat,
+    /// Tell speakers apart and start each of their turns with "Speaker N:"
+    #[serde(default)]
+    pub speaker_labels: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MeetingFormat {
+    /// `.md` with a heading and a list item per transcript
+    #[default]
+    Markdown,
+    /// `.txt` with a line per transcript
+    Text,
+}
+
+/// Log outputs besides stdout, set up once at startup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Write rotating log files to the `logs` directory in the state directory
+    #[serde(default = "default_log_file")]
+    pub file: bool,
+    /// Rotate the log file when it grows beyond this size
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated log files kept besides the current one
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+    /// Send log events with structured fields to the systemd journal
+    #[serde(default)]
+    pub journald: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: default_log_file(),
+            max_size_mb: default_log_max_size_mb(),
+            max_files: default_log_max_files(),
+            journald: false,
+        }
+    }
+}
+
+fn default_log_file() -> bool {
+    true
+}
+
+fn default_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+/// Files written in debug mode (`--debug`): the audio sent to Deepgram and
+/// its responses, per session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebugConfig {
+    /// Directory of the files; `recordings` in the state directory when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+    /// Name of a session's files without extension, in which `{timestamp}`
+    /// and `{session}` (the session ID, 0 outside sessions) are replaced
+    #[serde(default = "default_debug_file_name")]
+    pub file_name: String,
+    /// Delete the oldest files when all of them take more than this
+    #[serde(default = "default_debug_max_total_mb")]
+    pub max_total_mb: u64,
+    /// Delete files older than this many days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            file_name: default_debug_file_name(),
+            max_total_mb: default_debug_max_total_mb(),
+            max_age_days: None,
+        }
+    }
+}
+
+fn default_debug_file_name() -> String {
+    "{timestamp}-session{session}".to_string()
+}
+
+fn default_debug_max_total_mb() -> u64 {
+    200
+}
+
+/// Monthly cap of the Deepgram usage, counting sessions without a `base_url`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageConfig {
+    /// Minutes dictated per calendar month
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_minutes: Option<f64>,
+    /// Dollars spent per calendar month, at `price_per_minute`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_dollars: Option<f64>,
+    #[serde(default = "default_price_per_minute")]
+    pub price_per_minute: f64,
+    /// Deepgram-compatible server (e.g. a local one) transcribing once the
+    /// cap is reached; without it no session starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_base_url: Option<String>,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            monthly_minutes: None,
+            monthly_dollars: None,
+            price_per_minute: default_price_per_minute(),
+            fallback_base_url: None,
         }
     }
 }
 
+/// Deepgram's pay-as-you-go streaming price for Nova-3
+fn default_price_per_minute() -> f64 {
+    0.0077
+}
+
+/// Prometheus metrics endpoint
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// Address serving `/metrics`, e.g. "127.0.0.1:9464"; off when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<std::net::SocketAddr>,
+}
+
 impl Default for TranscriptionConfig {
     fn default() -> Self {
         Self {
             use_interim_results: false,
             model: default_model(),
+            models: default_models(),
             language: default_language(),
+            languages: Vec::new(),
             smart_format: default_smart_format(),
             punctuate: default_punctuate(),
+            replacements: BTreeMap::new(),
+            per_language: BTreeMap::new(),
+            base_url: None,
+            vocabulary: Vec::new(),
+            vocabulary_file: None,
+            tls: TlsConfig::default(),
         }
     }
 }
@@ -102,23 +1114,340 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             deepgram_api_key: String::new(),
+            deepgram_api_keys: Vec::new(),
+            include: Vec::new(),
+            active_profile: None,
+            paused: false,
+            privacy: false,
+            desktop_access: DesktopAccess::default(),
             hotkey: HotkeyConfig {
                 modifiers: vec!["super".to_string()],
                 key: "v".to_string(),
+                mode: HotkeyMode::default(),
+                backend: HotkeyBackend::default(),
+                fallbacks: Vec::new(),
+                bindings: vec![HotkeyBinding {
+                    modifiers: vec!["super".to_string(), "alt".to_string()],
+                    key: "v".to_string(),
+                    action: HotkeyAction::Cancel,
+                }],
             },
             audio: AudioConfig {
+                device: None,
                 sample_rate: 16000,
                 channels: 1,
                 buffer_size: 1024,
                 audio_chunk_ms: 25,
+                max_duration_secs: None,
+                inactivity_timeout_secs: None,
+                finalize_timeout_secs: default_finalize_timeout_secs(),
+                unmute: false,
+                unmute_volume: None,
+                system_audio: None,
+                channel_labels: default_channel_labels(),
             },
             transcription: TranscriptionConfig::default(),
+            output: OutputConfig::default(),
+            post_processing: PostProcessingConfig::default(),
             ui: UiConfig::default(),
+            history: HistoryConfig::default(),
+            meeting: MeetingConfig::default(),
+            metrics: MetricsConfig::default(),
+            usage: UsageConfig::default(),
+            logging: LoggingConfig::default(),
+            debug: DebugConfig::default(),
+            profiles: Vec::new(),
+            app_rules: Vec::new(),
+            base_transcription: TranscriptionConfig::default(),
+            configured_profile: None,
+            api_key_from_keyring: false,
+        }
+    }
+}
+
+fn read_source(path: &Path) -> Result<String> {
+    fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read config from {}", path.display()))
+}
+
+/// A setting saved by `Config::save_runtime_settings`: its key path, its
+/// value and the value it has when unset (`None` for unset)
+struct RuntimeSetting {
+    key: &'static [&'static str],
+    value: Option<toml::Value>,
+    default: Option<toml::Value>,
+}
+
+impl RuntimeSetting {
+    fn flag(key: &'static [&'static str], value: bool) -> Self {
+        Self {
+            key,
+            value: Some(toml::Value::Boolean(value)),
+            default: Some(toml::Value::Boolean(false)),
+        }
+    }
+
+    fn text(key: &'static [&'static str], value: &str, default: &str) -> Self {
+        Self {
+            key,
+            value: Some(toml::Value::String(value.to_string())),
+            default: Some(toml::Value::String(default.to_string())),
+        }
+    }
+
+    fn lookup<'a>(&self, document: &'a toml::Value) -> Option<&'a toml::Value> {
+        self.key
+            .iter()
+            .try_fold(document, |value, part| value.get(part))
+    }
+
+    fn write(&self, document: &mut toml_edit::Document) {
+        let (name, tables) = self.key.split_last().expect("keys are not empty");
+        let mut item = document.as_item_mut();
+        for table in tables {
+            item = &mut item[table];
+        }
+        match &self.value {
+            Some(toml::Value::Boolean(flag)) => item[name] = toml_edit::value(*flag),
+            Some(toml::Value::String(text)) => item[name] = toml_edit::value(text.as_str()),
+            Some(other) => unreachable!("runtime settings are flags or text, not {other}"),
+            None => self.remove(document),
+        }
+    }
+
+    fn remove(&self, document: &mut toml_edit::Document) {
+        let (name, tables) = self.key.split_last().expect("keys are not empty");
+        let table = tables
+            .iter()
+            .try_fold(document.as_item_mut(), |item, table| item.get_mut(table));
+        if let Some(table) = table.and_then(toml_edit::Item::as_table_like_mut) {
+            table.remove(name);
+        }
+    }
+}
+
+/// Merge `overlay` into `base`: tables are merged key by key, everything else
+/// (including arrays such as `[[hotkey.bindings]]`) is replaced
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Tables whose keys are chosen by the user rather than defined by the config
+const FREE_FORM_TABLES: &[&str] = &[
+    "transcription.replacements",
+    "transcription.per_language.replacements",
+    "profiles.replacements",
+];
+
+/// Tables keyed by user-chosen names whose entries are config sections
+const KEYED_TABLES: &[&str] = &["transcription.per_language"];
+
+/// Describe keys in the config file that are not config options (typically
+/// typos, which serde would otherwise silently ignore)
+pub fn unknown_keys(contents: &str) -> Vec<String> {
+    let (Ok(document), Ok(known)) = (
+        contents.parse::<toml::Value>(),
+        toml::Value::try_from(Config::schema_sample()),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut warnings = Vec::new();
+    collect_unknown_keys(&document, &known, "", &mut warnings);
+    warnings
+}
+
+fn collect_unknown_keys(
+    value: &toml::Value,
+    known: &toml::Value,
+    path: &str,
+    warnings: &mut Vec<String>,
+) {
+    match (value, known) {
+        (toml::Value::Table(table), toml::Value::Table(known_table)) => {
+            if FREE_FORM_TABLES.contains(&path) {
+                return;
+            }
+            // Entries are checked against the sample entry, like arrays of tables
+            if KEYED_TABLES.contains(&path) {
+                if let Some(toml::Value::Table(known_entry)) = known_table.values().next() {
+                    for entry in table.values().filter_map(toml::Value::as_table) {
+                        collect_unknown_table_keys(entry, known_entry, path, warnings);
+                    }
+                }
+                return;
+            }
+            collect_unknown_table_keys(table, known_table, path, warnings);
+        }
+        // Arrays of tables are checked against the sample entry
+        (toml::Value::Array(items), toml::Value::Array(known_items)) => {
+            if let Some(known) = known_items.first() {
+                for item in items {
+                    collect_unknown_keys(item, known, path, warnings);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_unknown_table_keys(
+    table: &toml::Table,
+    known_table: &toml::Table,
+    path: &str,
+    warnings: &mut Vec<String>,
+) {
+    for (key, value) in table {
+        let key_path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        match known_table.get(key) {
+            Some(known) => collect_unknown_keys(value, known, &key_path, warnings),
+            None => {
+                let suggestion = closest_key(key, known_table.keys())
+                    .map(|known| format!(", did you mean `{known}`?"))
+                    .unwrap_or_default();
+                warnings.push(format!("unknown config key `{key_path}`{suggestion}"));
+            }
+        }
+    }
+}
+
+/// Known key within a small edit distance of `key`
+fn closest_key<'a>(key: &str, known: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    known
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
         }
+        previous = current;
     }
+    previous[b.len()]
+}
+
+/// `path` with a leading `~/` replaced by the home directory
+pub fn expand_home(path: &Path) -> Result<PathBuf> {
+    match path.strip_prefix("~") {
+        Ok(relative) => Ok(dirs::home_dir()
+            .ok_or_eyre("Failed to get home directory")?
+            .join(relative)),
+        Err(_) => Ok(path.to_path_buf()),
+    }
+}
+
+/// Parse error including toml's line, column and snippet, with the API key masked
+fn parse_error(path: &Path, error: impl std::fmt::Display) -> eyre::Report {
+    eyre!(
+        "Failed to parse {}:\n{}",
+        path.display(),
+        redact::redact(&error.to_string())
+    )
 }
 
 impl Config {
+    /// Config with every optional field set, so that serializing it yields all
+    /// known keys. Optional fields added to the config must be filled in here.
+    fn schema_sample() -> Self {
+        let mut config = Self {
+            deepgram_api_keys: vec![String::new()],
+            include: vec![String::new()],
+            active_profile: Some(String::new()),
+            paused: true,
+            privacy: true,
+            ..Self::default()
+        };
+        config.hotkey.fallbacks = vec![HotkeyFallback {
+            modifiers: Vec::new(),
+            key: String::new(),
+        }];
+        config.audio.device = Some(String::new());
+        config.audio.max_duration_secs = Some(0);
+        config.audio.inactivity_timeout_secs = Some(0);
+        config.audio.unmute_volume = Some(0);
+        config.output.file = Some(PathBuf::new());
+        config.output.emoji = BTreeMap::from([(String::new(), String::new())]);
+        config.output.templates = BTreeMap::from([(String::new(), String::new())]);
+        config.output.number_locale = Some(String::new());
+        config.output.profanity.words = vec![String::new()];
+        config.output.pii.patterns = BTreeMap::from([(String::new(), String::new())]);
+        config.output.typing.chars_per_second = Some(0);
+        config.output.typing.layout = Some(String::new());
+        config.output.review.auto_insert_secs = Some(0);
+        config.output.subtitles.directory = Some(PathBuf::new());
+        config.usage.monthly_minutes = Some(0.0);
+        config.usage.monthly_dollars = Some(0.0);
+        config.usage.fallback_base_url = Some(String::new());
+        config.post_processing.llm = Some(LlmConfig {
+            endpoint: String::new(),
+            model: String::new(),
+            prompt: String::new(),
+            api_key: Some(String::new()),
+            timeout_ms: 0,
+        });
+        config.history.retention_days = Some(0);
+        config.history.max_entries = Some(0);
+        config.meeting.directory = Some(PathBuf::new());
+        config.debug.directory = Some(PathBuf::new());
+        config.debug.max_age_days = Some(0);
+        config.metrics.address = Some(std::net::SocketAddr::from(([127, 0, 0, 1], 0)));
+        config.transcription.languages = vec![String::new()];
+        config.transcription.base_url = Some(String::new());
+        config.transcription.vocabulary = vec![String::new()];
+        config.transcription.vocabulary_file = Some(PathBuf::new());
+        config.transcription.tls.ca_file = Some(PathBuf::new());
+        config.transcription.replacements = BTreeMap::from([(String::new(), String::new())]);
+        config.transcription.per_language = BTreeMap::from([(
+            String::new(),
+            LanguageSettings {
+                model: Some(String::new()),
+                use_interim_results: Some(false),
+                smart_format: Some(false),
+                punctuate: Some(false),
+                replacements: BTreeMap::from([(String::new(), String::new())]),
+            },
+        )]);
+        config.profiles = vec![Profile {
+            name: String::new(),
+            language: Some(String::new()),
+            model: Some(String::new()),
+            use_interim_results: Some(false),
+            replacements: BTreeMap::from([(String::new(), String::new())]),
+        }];
+        config.app_rules = vec![AppRule {
+            class: Some(String::new()),
+            title: Some(String::new()),
+            language: Some(String::new()),
+            use_interim_results: Some(false),
+            disabled: false,
+        }];
+        config
+    }
+
     pub fn get_config_path(custom_path: Option<PathBuf>) -> Result<PathBuf> {
         match custom_path {
             Some(path) => {
@@ -135,7 +1464,38 @@ impl Config {
         }
     }
 
+    /// The `[logging]` section of the main config file, read before logging
+    /// is set up and therefore without reporting problems. Defaults are used
+    /// if the file is missing or invalid; `load` reports those errors later.
+    pub fn load_logging(custom_path: Option<PathBuf>) -> LoggingConfig {
+        #[derive(Deserialize)]
+        struct LoggingSection {
+            #[serde(default)]
+            logging: LoggingConfig,
+        }
+
+        custom_path
+            .map_or_else(Self::config_path, Ok)
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str::<LoggingSection>(&text).ok())
+            .map(|section| section.logging)
+            .unwrap_or_default()
+    }
+
     pub fn load(custom_path: Option<PathBuf>) -> Result<Self> {
+        Self::read(custom_path, None)
+    }
+
+    /// Load the config again after its files changed. With `deepgram_api_key =
+    /// "keyring"` the key `previous` read from the keyring is kept, since asking
+    /// the Secret Service may block on an unlock prompt, and a missing key is
+    /// not asked for.
+    pub fn reload(custom_path: Option<PathBuf>, previous: &Config) -> Result<Self> {
+        Self::read(custom_path, Some(previous))
+    }
+
+    fn read(custom_path: Option<PathBuf>, previous: Option<&Config>) -> Result<Self> {
         let config_path = match custom_path {
             Some(path) => {
                 // Use the provided custom config path
@@ -152,8 +1512,7 @@ impl Config {
                 // Use the default config path
                 let default_path = Self::config_path()?;
                 if !default_path.exists() {
-                    let config = Self::default();
-                    config.save()?;
+                    Self::write_template(&default_path, false)?;
                     bail!(
                         "Created default config at {}. Please add your Deepgram API key.",
                         default_path.display()
@@ -165,18 +1524,255 @@ impl Config {
 
         info!("Loading config from {}", config_path.display());
 
-        let contents = fs::read_to_string(&config_path)
-            .wrap_err_with(|| format!("Failed to read config from {}", config_path.display()))?;
+        let sources: Vec<PathBuf> = Self::source_paths(&config_path)?
+            .into_iter()
+            .filter(|path| path == &config_path || path.exists())
+            .collect();
+        let mut contents = Vec::with_capacity(sources.len());
+        for source in &sources {
+            let text = read_source(source)?;
+            for warning in unknown_keys(&text) {
+                warn!("{}: {}", source.display(), warning);
+            }
+            contents.push(text);
+        }
+
+        // toml errors carry line, column and the offending snippet, which gets
+        // lost when merging, so a single file is deserialized directly
+        let mut config: Config = if sources.len() == 1 {
+            toml::from_str(&contents[0]).map_err(|e| parse_error(&config_path, e))?
+        } else {
+            info!(
+                "Merging config files: {}",
+                sources
+                    .iter()
+                    .map(|source| source.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            let mut document = toml::Value::Table(toml::Table::new());
+            for (source, text) in sources.iter().zip(&contents) {
+                let value = text
+                    .parse::<toml::Value>()
+                    .map_err(|e| parse_error(source, e))?;
+                merge_toml(&mut document, value);
+            }
+            document
+                .try_into()
+                .map_err(|e| eyre!("Invalid configuration: {}", e))?
+        };
+
+        if let Some(path) = &config.transcription.vocabulary_file {
+            let dir = config_path.parent().unwrap_or(Path::new("."));
+            let path = dir.join(expand_home(path)?);
+            config
+                .transcription
+                .vocabulary
+                .extend(crate::vocabulary::read_file(&path)?);
+            // Absolute, for the config watcher
+            config.transcription.vocabulary_file = Some(path);
+        }
+        if let Some(path) = &config.transcription.tls.ca_file {
+            let dir = config_path.parent().unwrap_or(Path::new("."));
+            config.transcription.tls.ca_file = Some(dir.join(expand_home(path)?));
+        }
 
-        let config: Config = toml::from_str(&contents).wrap_err("Failed to parse config file")?;
+        config.base_transcription = config.transcription.clone();
+        config.configured_profile = config.active_profile.clone();
+        if let Some(profile) = config.active_profile.clone() {
+            config.apply_profile(Some(&profile))?;
+        }
+
+        if config.deepgram_api_key == keyring::KEYRING_PLACEHOLDER {
+            config.deepgram_api_key = match previous {
+                Some(previous) if previous.api_key_from_keyring => {
+                    previous.deepgram_api_key.clone()
+                }
+                Some(_) => keyring::load_api_key()?.ok_or_eyre(NO_KEYRING_API_KEY)?,
+                None => Self::keyring_api_key()?,
+            };
+            config.api_key_from_keyring = true;
+        }
 
         if config.deepgram_api_key.is_empty() {
             bail!("Deepgram API key not set in config file");
         }
+        if config
+            .deepgram_api_keys
+            .iter()
+            .any(|key| key.trim().is_empty())
+        {
+            bail!("deepgram_api_keys must not contain empty keys");
+        }
+        if let Some(base_url) = &config.transcription.base_url {
+            deepgram::Deepgram::with_base_url(base_url.as_str())
+                .map_err(|_| eyre!("Invalid transcription base_url: {}", base_url))?;
+        }
+        if let Some(base_url) = &config.usage.fallback_base_url {
+            deepgram::Deepgram::with_base_url(base_url.as_str())
+                .map_err(|_| eyre!("Invalid usage fallback_base_url: {}", base_url))?;
+        }
+        tls::client_config(&config.transcription.tls)?;
+        if config
+            .audio
+            .unmute_volume
+            .is_some_and(|volume| !(1..=150).contains(&volume))
+        {
+            bail!("audio.unmute_volume must be a percentage between 1 and 150");
+        }
+        if config.output.handlers.contains(&OutputHandler::File) && config.output.file.is_none() {
+            bail!("The `file` output handler needs a path in `[output] file`");
+        }
+        if config.output.handlers.contains(&OutputHandler::Subtitles)
+            && config.output.subtitles.directory.is_none()
+        {
+            bail!("The `subtitles` output handler needs `[output.subtitles] directory`");
+        }
+        if let Some(locale) = &config.output.number_locale {
+            if NumberFormat::for_locale(locale).is_none() {
+                bail!(
+                    "Unsupported number_locale {:?}, supported: {}",
+                    locale,
+                    numbers::SUPPORTED_LOCALES.join(", ")
+                );
+            }
+        }
+        Redactor::new(&config.output.pii)?;
+        let typing = &config.output.typing;
+        if typing.chars_per_second == Some(0) || typing.queue_size == 0 {
+            bail!("[output.typing] chars_per_second and queue_size must be positive");
+        }
+        let spell = &config.output.spell;
+        if spell.start.trim().is_empty()
+            || spell.stop.trim().is_empty()
+            || spell.start == spell.stop
+        {
+            bail!("[output.spell] needs two different, non-empty commands for start and stop");
+        }
+        for (name, format) in &config.output.templates {
+            if !templates::is_valid_format(format) {
+                bail!(
+                    "Invalid format for template variable {:?}: {:?}",
+                    name,
+                    format
+                );
+            }
+        }
+        if let Some(llm) = &config.post_processing.llm {
+            reqwest::Url::parse(&llm.endpoint)
+                .map_err(|_| eyre!("Invalid post_processing.llm endpoint: {}", llm.endpoint))?;
+            if let Some(api_key) = &llm.api_key {
+                redact::register_secret(api_key);
+            }
+        }
+        redact::register_secret(&config.deepgram_api_key);
+        for api_key in &config.deepgram_api_keys {
+            redact::register_secret(api_key);
+        }
 
         Ok(config)
     }
 
+    /// The configured key followed by `deepgram_api_keys`, in the order they
+    /// are tried
+    pub fn api_keys(&self) -> Vec<String> {
+        std::iter::once(self.deepgram_api_key.clone())
+            .chain(self.deepgram_api_keys.iter().cloned())
+            .collect()
+    }
+
+    /// First app rule matching the given window
+    pub fn app_rule_for(&self, classes: &[String], title: &str) -> Option<&AppRule> {
+        self.app_rules
+            .iter()
+            .find(|rule| rule.matches(classes, title))
+    }
+
+    /// Activate a profile (or none) on top of the `[transcription]` section.
+    /// Runtime changes to the transcription settings are reset.
+    pub fn apply_profile(&mut self, name: Option<&str>) -> Result<()> {
+        let mut transcription = self.base_transcription.clone();
+        if let Some(name) = name {
+            let profile = self
+                .profiles
+                .iter()
+                .find(|profile| profile.name == name)
+                .ok_or_else(|| eyre!("Unknown profile: {}", name))?;
+            profile.apply_to(&mut transcription);
+        }
+
+        self.transcription = transcription;
+        self.active_profile = name.map(str::to_string);
+        Ok(())
+    }
+
+    /// Keep the profile selected at runtime (tray, `--profile`) across a
+    /// reload, unless `active_profile` was changed in the file
+    pub fn keep_active_profile(&mut self, previous: &Config) -> Result<()> {
+        if self.configured_profile != previous.configured_profile
+            || self.active_profile == previous.active_profile
+        {
+            return Ok(());
+        }
+        match &previous.active_profile {
+            Some(name) if !self.profiles.iter().any(|profile| &profile.name == name) => {
+                warn!("Profile {} no longer exists", name);
+                Ok(())
+            }
+            name => self.apply_profile(name.as_deref()),
+        }
+    }
+
+    /// Read the API key from the keyring. On first run (nothing stored yet)
+    /// ask for it on the terminal and store it.
+    fn keyring_api_key() -> Result<String> {
+        if let Some(api_key) = keyring::load_api_key()? {
+            return Ok(api_key);
+        }
+        if !std::io::stdin().is_terminal() {
+            bail!(NO_KEYRING_API_KEY);
+        }
+
+        print!("No Deepgram API key in the keyring yet. Deepgram API key: ");
+        std::io::stdout().flush()?;
+        let mut api_key = String::new();
+        std::io::stdin().lock().read_line(&mut api_key)?;
+        let api_key = api_key.trim().to_string();
+        if !api_key.is_empty() {
+            keyring::store_api_key(&api_key)?;
+        }
+        Ok(api_key)
+    }
+
+    /// Files making up the configuration in merge order: the main file, its
+    /// `include`s and the local overlay (which may not exist)
+    pub fn source_paths(config_path: &Path) -> Result<Vec<PathBuf>> {
+        let document: toml::Value = read_source(config_path)?
+            .parse()
+            .map_err(|e| parse_error(config_path, e))?;
+        let dir = config_path.parent().unwrap_or(Path::new("."));
+
+        let mut paths = vec![config_path.to_path_buf()];
+        if let Some(includes) = document.get("include").and_then(toml::Value::as_array) {
+            for include in includes {
+                let include = include
+                    .as_str()
+                    .ok_or_eyre("Entries of `include` must be file paths")?;
+                let path = dir.join(expand_home(Path::new(include))?);
+                let path = path.canonicalize().wrap_err_with(|| {
+                    format!("Included config file not found: {}", path.display())
+                })?;
+                paths.push(path);
+            }
+        }
+
+        let local = dir.join(LOCAL_OVERLAY_FILE);
+        if local != config_path {
+            paths.push(local);
+        }
+        Ok(paths)
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_path = Self::config_path()?;
 
@@ -194,6 +1790,145 @@ impl Config {
         Ok(())
     }
 
+    /// Write the settings that can be changed at runtime (tray, hotkeys) back
+    /// to the config files. Only settings that differ from what the files
+    /// produce are written, and only their keys are touched, so comments,
+    /// includes and a keyring placeholder are kept. A setting goes into the
+    /// main file, unless an include or the local overlay sets it, then into
+    /// the overlay so that it takes effect. Language and model are only
+    /// written while no profile is active, otherwise they belong to the
+    /// profile. Returns the written files with their new contents, see
+    /// [`Self::runtime_settings_saved`].
+    pub fn save_runtime_settings(
+        &self,
+        custom_path: Option<PathBuf>,
+    ) -> Result<Vec<(PathBuf, String)>> {
+        // Resolve symlinks so the rename below replaces the actual file
+        let path = Self::get_config_path(custom_path)?.canonicalize()?;
+        let overlay = path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(LOCAL_OVERLAY_FILE);
+        let mut sources = Vec::new();
+        for source in Self::source_paths(&path)? {
+            if source != path && !source.exists() {
+                continue;
+            }
+            let document: toml::Value = read_source(&source)?
+                .parse()
+                .map_err(|e| parse_error(&source, e))?;
+            sources.push((source, document));
+        }
+
+        let defaults = TranscriptionConfig::default();
+        let mut settings = vec![
+            RuntimeSetting {
+                key: &["active_profile"],
+                value: self.active_profile.clone().map(toml::Value::String),
+                default: None,
+            },
+            RuntimeSetting::flag(&["paused"], self.paused),
+            RuntimeSetting::flag(&["privacy"], self.privacy),
+        ];
+        if self.active_profile.is_none() {
+            settings.extend([
+                RuntimeSetting::text(
+                    &["transcription", "language"],
+                    &self.transcription.language,
+                    &defaults.language,
+                ),
+                RuntimeSetting::text(
+                    &["transcription", "model"],
+                    &self.transcription.model,
+                    &defaults.model,
+                ),
+            ]);
+        }
+
+        let mut changes: Vec<(PathBuf, Vec<RuntimeSetting>)> = Vec::new();
+        for setting in settings {
+            // The last file setting a key decides its value
+            let defined_in = sources
+                .iter()
+                .rev()
+                .find_map(|(source, document)| Some((source, setting.lookup(document)?)));
+            let current = defined_in
+                .map(|(_, value)| value.clone())
+                .or_else(|| setting.default.clone());
+            if current == setting.value {
+                continue;
+            }
+            let target = match defined_in {
+                Some((source, _)) if *source != path => overlay.clone(),
+                _ => path.clone(),
+            };
+            match changes.iter_mut().find(|(file, _)| *file == target) {
+                Some((_, file_settings)) => file_settings.push(setting),
+                None => changes.push((target, vec![setting])),
+            }
+        }
+
+        let mut written = Vec::with_capacity(changes.len());
+        for (file, file_settings) in changes {
+            let contents = if file.exists() {
+                fs::read_to_string(&file)
+                    .wrap_err_with(|| format!("Failed to read config file: {}", file.display()))?
+            } else {
+                String::new()
+            };
+            let mut document: toml_edit::Document = contents
+                .parse()
+                .wrap_err_with(|| format!("Failed to parse {}", file.display()))?;
+            for setting in file_settings {
+                // Defaults are left out of the main file, the overlay has to
+                // override what the other files set
+                if file == path && setting.value == setting.default {
+                    setting.remove(&mut document);
+                } else {
+                    setting.write(&mut document);
+                }
+            }
+
+            // Replace the file in one step so the config watcher never sees it half written
+            let contents = document.to_string();
+            let temp_path = file.with_extension("toml.tmp");
+            fs::write(&temp_path, &contents)
+                .wrap_err_with(|| format!("Failed to write {}", temp_path.display()))?;
+            fs::rename(&temp_path, &file)
+                .wrap_err_with(|| format!("Failed to replace {}", file.display()))?;
+            written.push((file.canonicalize().unwrap_or(file), contents));
+        }
+        Ok(written)
+    }
+
+    /// Record that the config files now produce the runtime settings of
+    /// `saved`, once [`Self::save_runtime_settings`] wrote them
+    pub fn runtime_settings_saved(&mut self, saved: &Config) {
+        if saved.active_profile.is_none() {
+            self.base_transcription = saved.transcription.clone();
+        }
+        self.configured_profile = saved.active_profile.clone();
+    }
+
+    /// Write the commented example config to `path`. An existing file is only
+    /// replaced with `force`.
+    pub fn write_template(path: &Path, force: bool) -> Result<()> {
+        if path.exists() && !force {
+            bail!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            );
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).wrap_err_with(|| {
+                format!("Failed to create config directory: {}", parent.display())
+            })?;
+        }
+
+        fs::write(path, CONFIG_TEMPLATE)
+            .wrap_err_with(|| format!("Failed to write config to {}", path.display()))
+    }
+
     fn config_path() -> Result<PathBuf> {
         let config_dir = config_dir().ok_or_eyre("Failed to get config directory")?;
         Ok(config_dir.join("gnome-voice-input").join("config.toml"))
@@ -0,0 +1,169 @@
+//! `gnome-voice-input config validate`: checks the configuration without
+//! starting the app and prints what is wrong and how to fix it.
+
+use crate::{
+    config::{self, Config},
+    hotkey,
+    redact::redact,
+    transcription,
+};
+use cpal::traits::{DeviceTrait, HostTrait};
+use std::path::PathBuf;
+
+/// Outcome of the individual checks, printed as they run
+#[derive(Default)]
+pub struct Report {
+    pub problems: usize,
+}
+
+impl Report {
+    pub fn ok(&self, message: impl std::fmt::Display) {
+        println!("✓ {}", redact(&message.to_string()));
+    }
+
+    /// Something that may be fine, but limits what works
+    pub fn note(&self, message: impl std::fmt::Display, hint: &str) {
+        println!("! {}", redact(&message.to_string()));
+        println!("  → {hint}");
+    }
+
+    pub fn problem(&mut self, message: impl std::fmt::Display, hint: &str) {
+        self.problems += 1;
+        println!("✗ {}", redact(&message.to_string()));
+        println!("  → {hint}");
+    }
+}
+
+/// Run all checks. Returns the number of problems found.
+pub async fn validate_config(custom_path: Option<PathBuf>, check_api_key: bool) -> usize {
+    let mut report = Report::default();
+
+    let path = match Config::get_config_path(custom_path.clone()) {
+        Ok(path) if path.exists() => path,
+        Ok(path) => {
+            report.problem(
+                format!("No config file at {}", path.display()),
+                "Run gnome-voice-input from a terminal to start the setup wizard",
+            );
+            return report.problems;
+        }
+        Err(e) => {
+            report.problem(format!("{e:#}"), "Check the path passed with --config");
+            return report.problems;
+        }
+    };
+
+    let config = match Config::load(Some(path.clone())) {
+        Ok(config) => {
+            report.ok(format!("Config file {} parsed", path.display()));
+            config
+        }
+        Err(e) => {
+            report.problem(
+                format!("{e:#}"),
+                "Fix the config file; config/default.toml documents every option",
+            );
+            return report.problems;
+        }
+    };
+
+    let sources = Config::source_paths(&path).unwrap_or_default();
+    for source in sources.iter().filter(|source| source.exists()) {
+        if source != &path {
+            report.ok(format!("Merged {}", source.display()));
+        }
+        let Ok(contents) = std::fs::read_to_string(source) else {
+            continue;
+        };
+        for warning in config::unknown_keys(&contents) {
+            report.problem(
+                format!("{}: {}", source.display(), warning),
+                "Remove or rename it; config/default.toml lists all options",
+            );
+        }
+    }
+
+    check_hotkeys(&config, &mut report);
+    check_audio_device(&config, &mut report);
+
+    if check_api_key {
+        match transcription::verify_api_key(&config.deepgram_api_key).await {
+            Ok(()) => report.ok("Deepgram accepted the API key"),
+            Err(e) => report.problem(
+                format!("{e:#}"),
+                "Check deepgram_api_key, keys are managed at https://console.deepgram.com/",
+            ),
+        }
+    }
+
+    if report.problems == 0 {
+        println!("\nConfiguration looks good.");
+    } else {
+        println!("\n{} problem(s) found.", report.problems);
+    }
+    report.problems
+}
+
+pub fn check_hotkeys(config: &Config, report: &mut Report) {
+    let combinations = std::iter::once((&config.hotkey.modifiers, &config.hotkey.key, "hotkey"))
+        .chain(
+            config
+                .hotkey
+                .bindings
+                .iter()
+                .map(|binding| (&binding.modifiers, &binding.key, "hotkey.bindings")),
+        )
+        .chain(
+            config
+                .hotkey
+                .fallbacks
+                .iter()
+                .map(|fallback| (&fallback.modifiers, &fallback.key, "hotkey.fallbacks")),
+        );
+
+    for (modifiers, key, section) in combinations {
+        let combination = format!("{} + {}", modifiers.join("+"), key);
+        match hotkey::parse_key_combination(modifiers, key) {
+            Ok(_) => report.ok(format!("Hotkey {combination} ({section})")),
+            Err(e) => report.problem(
+                format!("Hotkey {combination} ({section}): {e}"),
+                "Use modifiers super, ctrl, alt, shift and key names such as \"v\", \"f9\" or \"insert\"",
+            ),
+        }
+    }
+}
+
+pub fn check_audio_device(config: &Config, report: &mut Report) {
+    let host = cpal::default_host();
+    let devices: Vec<String> = match host.input_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(e) => {
+            report.problem(
+                format!("Failed to enumerate input devices: {e}"),
+                "Check that PipeWire or PulseAudio is running",
+            );
+            return;
+        }
+    };
+
+    match &config.audio.device {
+        Some(name) if devices.contains(name) => report.ok(format!("Input device {name}")),
+        Some(name) => report.problem(
+            format!("Input device {name} not found"),
+            &format!(
+                "Set audio.device to one of: {} (or remove it to use the default)",
+                devices.join(", ")
+            ),
+        ),
+        None => match host.default_input_device() {
+            Some(device) => report.ok(format!(
+                "Default input device {}",
+                device.name().unwrap_or_default()
+            )),
+            None => report.problem(
+                "No default input device",
+                "Connect a microphone or set audio.device",
+            ),
+        },
+    }
+}
@@ -1,14 +1,14 @@
 use crate::{
-    app_manager::{reload_application, AppComponents},
+    app_manager::{reload_application, SharedComponents},
     config::Config,
     state::AppState,
 };
-use eyre::Result;
+use eyre::{eyre, Result};
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
 use std::path::PathBuf;
-use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::mpsc;
 use tokio::time::{timeout, Instant};
 use tokio_util::sync::CancellationToken;
 
@@ -20,20 +20,30 @@ pub(crate) struct ConfigWatcher {
 impl ConfigWatcher {
     pub fn new(
         config_path: PathBuf,
-        reload_tx: mpsc::Sender<()>,
+        vocabulary_file: Option<PathBuf>,
+        reload_tx: mpsc::Sender<PathBuf>,
         _shutdown_token: CancellationToken,
     ) -> Result<Self> {
-        let config_path_clone = config_path.clone();
+        // The main file, its includes, the local overlay and the vocabulary
+        // file. Files added later are picked up after a restart.
+        let mut watched_paths = Config::source_paths(&config_path).unwrap_or_else(|e| {
+            warn!("Watching only the main config file: {:#}", e);
+            vec![config_path.clone()]
+        });
+        watched_paths.extend(vocabulary_file);
+        let watched_paths_clone = watched_paths.clone();
 
         let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
             match res {
                 Ok(event) => {
-                    // Only react to modify and create events on the config file
+                    // Only react to modify and create events on the config files
                     match event.kind {
                         EventKind::Modify(_) | EventKind::Create(_) => {
-                            if event.paths.iter().any(|p| p == &config_path_clone) {
+                            if let Some(path) =
+                                event.paths.iter().find(|p| watched_paths_clone.contains(p))
+                            {
                                 info!("Config file changed, triggering reload");
-                                let _ = reload_tx.blocking_send(());
+                                let _ = reload_tx.blocking_send(path.clone());
                             }
                         }
                         _ => {}
@@ -43,16 +53,17 @@ impl ConfigWatcher {
             }
         })?;
 
-        // Watch the parent directory to catch file replacements (common with editors)
-        if let Some(parent) = config_path.parent() {
+        // Watch the parent directories to catch file replacements (common with editors)
+        let parents: BTreeSet<_> = watched_paths.iter().filter_map(|p| p.parent()).collect();
+        for parent in parents {
             watcher.watch(parent, RecursiveMode::NonRecursive)?;
             info!("Watching config directory: {}", parent.display());
         }
 
-        // Also watch the file directly
-        if config_path.exists() {
-            watcher.watch(&config_path, RecursiveMode::NonRecursive)?;
-            info!("Watching config file: {}", config_path.display());
+        // Also watch the files directly
+        for path in watched_paths.iter().filter(|p| p.exists()) {
+            watcher.watch(path, RecursiveMode::NonRecursive)?;
+            info!("Watching config file: {}", path.display());
         }
 
         Ok(Self {
@@ -71,18 +82,20 @@ impl Drop for ConfigWatcher {
 pub fn setup_config_reload_handler(
     config_path: PathBuf,
     app_state: AppState,
-    initial_components: AppComponents,
+    components: SharedComponents,
     shutdown_token: &CancellationToken,
 ) -> Result<(tokio::task::JoinHandle<()>, ConfigWatcher)> {
     let (config_reload_tx, mut config_reload_rx) = tokio::sync::mpsc::channel(10);
-    let config_watcher =
-        ConfigWatcher::new(config_path, config_reload_tx, shutdown_token.child_token())?;
+    let vocabulary_file = app_state.config().transcription.vocabulary_file.clone();
+    let config_watcher = ConfigWatcher::new(
+        config_path,
+        vocabulary_file,
+        config_reload_tx,
+        shutdown_token.child_token(),
+    )?;
 
     let shutdown_token_clone = shutdown_token.child_token();
 
-    // Wrap components in Arc<Mutex> to allow updates during reload
-    let components = Arc::new(Mutex::new(Some(initial_components)));
-
     let handle = tokio::spawn(async move {
         let mut last_reload = Instant::now();
         const DEBOUNCE_DURATION: Duration = Duration::from_millis(500);
@@ -96,19 +109,24 @@ pub fn setup_config_reload_handler(
                     let mut components_guard = components.lock().await;
                     if let Some(app_components) = components_guard.take() {
                         info!("Tearing down components during shutdown");
-                        if let Err(e) = app_components.teardown_for_reload().await {
-                            error!("Error tearing down components during shutdown: {}", e);
-                        }
+                        app_components.teardown().await;
                     }
 
                     break;
                 }
-                Some(()) = config_reload_rx.recv() => {
+                Some(changed_path) = config_reload_rx.recv() => {
+                    // Settings saved from the tray are already applied
+                    if app_state.is_own_config_write(&changed_path) {
+                        debug!("Ignoring config change written by this instance");
+                        continue;
+                    }
+
+
                     // Debounce: ignore events that come too quickly after the last reload
                     let now = Instant::now();
                     if now.duration_since(last_reload) < DEBOUNCE_DURATION {
                         // Drain any additional events that might be queued
-                        while let Ok(Some(())) = timeout(Duration::from_millis(50), config_reload_rx.recv()).await {
+                        while let Ok(Some(_)) = timeout(Duration::from_millis(50), config_reload_rx.recv()).await {
                             // Just consume the events
                         }
                         continue;
@@ -117,26 +135,23 @@ pub fn setup_config_reload_handler(
                     info!("Reloading configuration...");
                     last_reload = now;
 
-                    match Config::load(app_state.custom_config_path.clone()) {
+                    let previous_config = app_state.config();
+                    let custom_path = app_state.custom_config_path.clone();
+                    // A keyring lookup can block on an unlock prompt
+                    let loaded = tokio::task::spawn_blocking(move || {
+                        Config::reload(custom_path, &previous_config)
+                            .and_then(|mut config| config.keep_active_profile(&previous_config).map(|()| config))
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(eyre!("Config reload task failed: {}", e)));
+                    match loaded {
                         Ok(new_config) => {
-                            // Take current components
                             let mut components_guard = components.lock().await;
-                            if let Some(current_components) = components_guard.take() {
-                                // Reload application with new config
-                                // Pass the main shutdown token so reloaded components respond to app shutdown
+                            if let Some(current_components) = components_guard.as_mut() {
+                                // Pass the main shutdown token so restarted components respond to app shutdown
                                 match reload_application(new_config, &app_state, current_components, &shutdown_token_clone).await {
-                                    Ok(new_components) => {
-                                        // Store new components
-                                        *components_guard = Some(new_components);
-                                        info!("Configuration and application reloaded successfully");
-                                    }
-                                    Err(e) => {
-                                        error!("Failed to reload application: {}", e);
-                                        error!("Application components have been torn down. Manual restart required.");
-                                        // At this point the app is in a broken state
-                                        // We could try to recover by loading the old config
-                                        // but for now we'll just log the error
-                                    }
+                                    Ok(()) => info!("Configuration reloaded successfully"),
+                                    Err(e) => error!("Failed to apply reloaded configuration: {:#}", e),
                                 }
                             } else {
                                 error!("No components available for reload");
@@ -0,0 +1,459 @@
+use crate::{
+    latency::LATENCY,
+    redact::redact,
+    state::{AppEvent, AppState, RecordingState},
+};
+use dbus::arg::{prop_cast, PropMap, RefArg, Variant};
+use dbus::blocking::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+use dbus::blocking::LocalConnection;
+use dbus::channel::Sender;
+use dbus::message::SignalArgs;
+use dbus_tree::{Access, EmitsChangedSignal, Factory};
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+pub const BUS_NAME: &str = "org.gnome.VoiceInput";
+pub const OBJECT_PATH: &str = "/org/gnome/VoiceInput";
+pub const INTERFACE: &str = "org.gnome.VoiceInput";
+
+/// Long enough for the bus to start the app on demand
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Snapshot of the state exposed as D-Bus properties, used to detect changes
+#[derive(Clone, PartialEq)]
+struct ExposedState {
+    recording: bool,
+    state: RecordingState,
+    enabled: bool,
+    language: String,
+    last_transcript: String,
+    /// Level quantized to whole percent so small fluctuations don't flood the bus
+    level_percent: u8,
+}
+
+impl ExposedState {
+    fn capture(app_state: &AppState) -> Self {
+        Self {
+            recording: app_state.is_recording(),
+            state: app_state.recording_state(),
+            enabled: app_state.enabled.load(Ordering::Relaxed),
+            language: app_state.config().transcription.language.clone(),
+            last_transcript: last_transcript(app_state),
+            level_percent: (app_state.input_level() * 100.0).round() as u8,
+        }
+    }
+
+    fn changed_properties(&self, previous: &Self) -> PropMap {
+        let mut changed = PropMap::new();
+        let mut insert = |name: &str, value: Box<dyn RefArg>| {
+            changed.insert(name.to_string(), Variant(value));
+        };
+        if self.recording != previous.recording {
+            insert("Recording", Box::new(self.recording));
+        }
+        if self.state != previous.state {
+            insert("State", Box::new(self.state.as_str().to_string()));
+        }
+        if self.enabled != previous.enabled {
+            insert("Enabled", Box::new(self.enabled));
+        }
+        if self.language != previous.language {
+            insert("Language", Box::new(self.language.clone()));
+        }
+        if self.last_transcript != previous.last_transcript {
+            insert("LastTranscript", Box::new(self.last_transcript.clone()));
+        }
+        if self.level_percent != previous.level_percent {
+            insert("Level", Box::new(f64::from(self.level_percent) / 100.0));
+        }
+        changed
+    }
+}
+
+/// Everything a client may want to show, returned by `GetState`
+fn full_state(app_state: &AppState) -> PropMap {
+    let config = app_state.config();
+    let mut state = PropMap::new();
+    let mut insert = |name: &str, value: Box<dyn RefArg>| {
+        state.insert(name.to_string(), Variant(value));
+    };
+    insert("recording", Box::new(app_state.is_recording()));
+    insert(
+        "state",
+        Box::new(app_state.recording_state().as_str().to_string()),
+    );
+    insert(
+        "enabled",
+        Box::new(app_state.enabled.load(Ordering::Relaxed)),
+    );
+    insert("muted", Box::new(app_state.muted.load(Ordering::Relaxed)));
+    let session_id = *app_state.current_session.read().unwrap();
+    insert("session_id", Box::new(session_id.map_or(0, |id| id.get())));
+    insert("language", Box::new(config.transcription.language.clone()));
+    insert("model", Box::new(config.transcription.model.clone()));
+    insert(
+        "profile",
+        Box::new(config.active_profile.clone().unwrap_or_default()),
+    );
+    insert(
+        "connection",
+        Box::new(app_state.connection_status.borrow().to_string()),
+    );
+    insert("last_transcript", Box::new(last_transcript(app_state)));
+    let today = app_state.stats.today();
+    insert("minutes_today", Box::new(today.minutes_dictated()));
+    insert("words_today", Box::new(today.words_typed));
+    insert(
+        "average_latency_ms",
+        Box::new(
+            today
+                .average_latency()
+                .map_or(0, |latency| latency.as_millis() as u64),
+        ),
+    );
+    insert(
+        "last_session_latency",
+        Box::new(LATENCY.last_summary().unwrap_or_default()),
+    );
+    insert(
+        "last_error",
+        Box::new(
+            app_state
+                .last_error
+                .read()
+                .unwrap()
+                .clone()
+                .unwrap_or_default(),
+        ),
+    );
+    state
+}
+
+fn last_transcript(app_state: &AppState) -> String {
+    app_state
+        .recent_transcripts
+        .read()
+        .unwrap()
+        .front()
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Signal announcing an event to D-Bus clients, if it is exposed
+fn event_signal(event: &AppEvent) -> Result<Option<dbus::Message>> {
+    let (session, name, detail) = match event {
+        AppEvent::RecordingStarted(session) => (*session, "started", String::new()),
+        AppEvent::FirstTranscript(session) => (*session, "first-transcript", String::new()),
+        AppEvent::Reconnecting(session) => (*session, "reconnecting", String::new()),
+        AppEvent::Finalizing(session) => (*session, "finalizing", String::new()),
+        AppEvent::InactivityTimeout(session) => (*session, "timed-out", String::new()),
+        AppEvent::RecordingStopped(session) => (*session, "ended", String::new()),
+        AppEvent::BackendError {
+            session: Some(session),
+            message,
+        } => (*session, "error", redact(message).into_owned()),
+        AppEvent::ConfigReloaded => {
+            let signal = dbus::Message::new_signal(OBJECT_PATH, INTERFACE, "ConfigReloaded")
+                .map_err(|e| eyre!("Failed to create ConfigReloaded signal: {}", e))?;
+            return Ok(Some(signal));
+        }
+        // Transcripts are exposed through the LastTranscript property
+        AppEvent::TranscriptFinal { .. }
+        | AppEvent::BackendError { session: None, .. }
+        | AppEvent::CaptureFailed(_) => return Ok(None),
+    };
+    let signal = dbus::Message::new_signal(OBJECT_PATH, INTERFACE, "SessionEvent")
+        .map_err(|e| eyre!("Failed to create SessionEvent signal: {}", e))?
+        .append3(session.get(), name, detail);
+    Ok(Some(signal))
+}
+
+/// Call a method on the running instance, e.g. `ToggleRecording`. The bus
+/// starts the app first if it is installed as an activatable service.
+pub fn call_running_instance(method: &str) -> Result<()> {
+    let conn = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+    let proxy = conn.with_proxy(BUS_NAME, OBJECT_PATH, ACTIVATION_TIMEOUT);
+    let _: () = proxy
+        .method_call(INTERFACE, method, ())
+        .wrap_err("Failed to reach gnome-voice-input, is it running?")?;
+    Ok(())
+}
+
+/// State of the running instance, as reported by `gnome-voice-input status`
+#[derive(Debug, Default, Serialize)]
+pub struct InstanceStatus {
+    pub recording: bool,
+    /// idle, connecting, recording, finalizing or error
+    pub recording_state: String,
+    pub enabled: bool,
+    pub muted: bool,
+    pub language: String,
+    pub model: String,
+    /// Active profile, empty without one
+    pub profile: String,
+    /// Health of the Deepgram connection
+    pub connection: String,
+    pub last_transcript: String,
+    pub last_error: String,
+    pub minutes_today: f64,
+    pub words_today: u64,
+    /// Average latency of final transcripts today, 0 without any
+    pub average_latency_ms: u64,
+    /// When the stages of the last session were reached after the request,
+    /// empty before the first one
+    pub last_session_latency: String,
+}
+
+/// Whether an instance owns the bus name. Unlike calling it, this doesn't
+/// start the service through D-Bus activation.
+pub fn instance_running() -> Result<bool> {
+    let conn = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_secs(5),
+    );
+    let (has_owner,): (bool,) =
+        proxy.method_call("org.freedesktop.DBus", "NameHasOwner", (BUS_NAME,))?;
+    Ok(has_owner)
+}
+
+/// State of the running instance from `GetState`, or `None` if no instance
+/// is running. Doesn't start one through D-Bus activation.
+pub fn running_instance_status() -> Result<Option<InstanceStatus>> {
+    if !instance_running()? {
+        return Ok(None);
+    }
+
+    let conn = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+    let proxy = conn.with_proxy(BUS_NAME, OBJECT_PATH, Duration::from_secs(5));
+    let (state,): (PropMap,) = proxy
+        .method_call(INTERFACE, "GetState", ())
+        .wrap_err("Failed to reach gnome-voice-input")?;
+
+    let string = |name: &str| {
+        prop_cast::<String>(&state, name)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let flag = |name: &str| prop_cast::<bool>(&state, name).copied().unwrap_or_default();
+    Ok(Some(InstanceStatus {
+        recording: flag("recording"),
+        recording_state: string("state"),
+        enabled: flag("enabled"),
+        muted: flag("muted"),
+        language: string("language"),
+        model: string("model"),
+        profile: string("profile"),
+        connection: string("connection"),
+        last_transcript: string("last_transcript"),
+        last_error: string("last_error"),
+        minutes_today: prop_cast::<f64>(&state, "minutes_today")
+            .copied()
+            .unwrap_or_default(),
+        words_today: prop_cast::<u64>(&state, "words_today")
+            .copied()
+            .unwrap_or_default(),
+        average_latency_ms: prop_cast::<u64>(&state, "average_latency_ms")
+            .copied()
+            .unwrap_or_default(),
+        last_session_latency: string("last_session_latency"),
+    }))
+}
+
+/// Start the D-Bus service used by the GNOME Shell extension and other clients
+pub fn setup_dbus_service(
+    app_state: AppState,
+    shutdown_token: &CancellationToken,
+) -> std::thread::JoinHandle<()> {
+    let runtime = Handle::current();
+    let service_shutdown_token = shutdown_token.child_token();
+
+    std::thread::spawn(move || {
+        info!("Starting D-Bus service thread");
+        if let Err(e) = run_service(app_state, runtime, service_shutdown_token) {
+            warn!("D-Bus service unavailable: {:#}", e);
+        }
+        info!("D-Bus service thread exiting");
+    })
+}
+
+fn run_service(
+    app_state: AppState,
+    runtime: Handle,
+    shutdown_token: CancellationToken,
+) -> Result<()> {
+    let conn = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+    conn.request_name(BUS_NAME, false, true, true)
+        .wrap_err_with(|| format!("Failed to acquire bus name {BUS_NAME}"))?;
+    info!("D-Bus service registered as {}", BUS_NAME);
+
+    let f = Factory::new_fn::<()>();
+    let runtime_toggle = runtime.clone();
+
+    let toggle_state = app_state.clone();
+    let start_state = app_state.clone();
+    let stop_state = app_state.clone();
+    let get_state = app_state.clone();
+    let set_language_state = app_state.clone();
+    let language_state = app_state.clone();
+    let recording_state = app_state.clone();
+    let phase_state = app_state.clone();
+    let enabled_state = app_state.clone();
+    let transcript_state = app_state.clone();
+    let level_state = app_state.clone();
+
+    let toggle = move |m: &dbus_tree::MethodInfo<'_, dbus_tree::MTFn<()>, ()>| {
+        info!("Toggle recording requested via D-Bus");
+        runtime_toggle.spawn(crate::toggle_recording(toggle_state.clone()));
+        Ok(vec![m.msg.method_return()])
+    };
+
+    let interface = f
+        .interface(INTERFACE, ())
+        .add_m(f.method("ToggleRecording", (), toggle.clone()))
+        // Name used by earlier versions of the GNOME Shell extension
+        .add_m(f.method("Toggle", (), toggle))
+        .add_m(f.method("StartRecording", (), move |m| {
+            info!("Start recording requested via D-Bus");
+            let app_state = start_state.clone();
+            runtime.spawn(async move { crate::start_recording(app_state) });
+            Ok(vec![m.msg.method_return()])
+        }))
+        .add_m(f.method("StopRecording", (), move |m| {
+            info!("Stop recording requested via D-Bus");
+            crate::stop_recording(&stop_state);
+            Ok(vec![m.msg.method_return()])
+        }))
+        .add_m(
+            f.method("GetState", (), move |m| {
+                Ok(vec![m.msg.method_return().append1(full_state(&get_state))])
+            })
+            .outarg::<PropMap, _>("state"),
+        )
+        .add_m(
+            f.method("SetLanguage", (), move |m| {
+                let language: &str = m.msg.read1()?;
+                info!("Switching language to {} via D-Bus", language);
+                set_language_state.update_transcription_config(|transcription| {
+                    transcription.language = language.to_string()
+                });
+                set_language_state.request_ui_refresh();
+                Ok(vec![m.msg.method_return()])
+            })
+            .inarg::<&str, _>("language"),
+        )
+        .add_s(
+            f.signal("SessionEvent", ())
+                .sarg::<u64, _>("session_id")
+                .sarg::<&str, _>("event")
+                .sarg::<&str, _>("detail"),
+        )
+        .add_s(f.signal("ConfigReloaded", ()))
+        .add_p(
+            f.property::<bool, _>("Recording", ())
+                .access(Access::Read)
+                .emits_changed(EmitsChangedSignal::True)
+                .on_get(move |i, _| {
+                    i.append(recording_state.is_recording());
+                    Ok(())
+                }),
+        )
+        .add_p(
+            f.property::<&str, _>("State", ())
+                .access(Access::Read)
+                .emits_changed(EmitsChangedSignal::True)
+                .on_get(move |i, _| {
+                    i.append(phase_state.recording_state().as_str());
+                    Ok(())
+                }),
+        )
+        .add_p(
+            f.property::<bool, _>("Enabled", ())
+                .access(Access::Read)
+                .emits_changed(EmitsChangedSignal::True)
+                .on_get(move |i, _| {
+                    i.append(enabled_state.enabled.load(Ordering::Relaxed));
+                    Ok(())
+                }),
+        )
+        .add_p(
+            f.property::<&str, _>("Language", ())
+                .access(Access::Read)
+                .emits_changed(EmitsChangedSignal::True)
+                .on_get(move |i, _| {
+                    let config = language_state.config();
+                    i.append(config.transcription.language.clone());
+                    Ok(())
+                }),
+        )
+        .add_p(
+            f.property::<&str, _>("LastTranscript", ())
+                .access(Access::Read)
+                .emits_changed(EmitsChangedSignal::True)
+                .on_get(move |i, _| {
+                    i.append(last_transcript(&transcript_state));
+                    Ok(())
+                }),
+        )
+        .add_p(
+            f.property::<f64, _>("Level", ())
+                .access(Access::Read)
+                .emits_changed(EmitsChangedSignal::True)
+                .on_get(move |i, _| {
+                    i.append(f64::from(level_state.input_level()));
+                    Ok(())
+                }),
+        );
+
+    let tree = f.tree(()).add(
+        f.object_path(OBJECT_PATH, ())
+            .introspectable()
+            .add(interface),
+    );
+    tree.start_receive(&conn);
+
+    let object_path = dbus::Path::from(OBJECT_PATH);
+    let mut previous = ExposedState::capture(&app_state);
+    let mut events = app_state.events.subscribe();
+
+    while !shutdown_token.is_cancelled() {
+        conn.process(Duration::from_millis(100))?;
+
+        loop {
+            let event = match events.try_recv() {
+                Ok(event) => event,
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                    debug!("D-Bus service skipped {} events", skipped);
+                    continue;
+                }
+                Err(_) => break,
+            };
+            if let Some(signal) = event_signal(&event)? {
+                conn.send(signal)
+                    .map_err(|_| eyre!("Failed to emit signal for {:?}", event))?;
+            }
+        }
+
+        let current = ExposedState::capture(&app_state);
+        let changed_properties = current.changed_properties(&previous);
+        if !changed_properties.is_empty() {
+            let signal = PropertiesPropertiesChanged {
+                interface_name: INTERFACE.to_string(),
+                changed_properties,
+                invalidated_properties: Vec::new(),
+            };
+            conn.send(signal.to_emit_message(&object_path))
+                .map_err(|_| eyre!("Failed to emit PropertiesChanged signal"))?;
+            previous = current;
+        }
+    }
+
+    let _ = conn.release_name(BUS_NAME);
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use eyre::{Result, WrapErr};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Open a file or directory with the desktop's default application
+pub fn open_path(path: &Path) -> Result<()> {
+    info!("Opening {}", path.display());
+
+    Command::new("xdg-open")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .wrap_err_with(|| format!("Failed to run xdg-open for {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Text currently on the clipboard, read with the clipboard tool of the
+/// current session; None when it is empty or not text
+pub fn read_clipboard() -> Result<Option<String>> {
+    let (program, args): (&str, &[&str]) = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        ("wl-paste", &["--no-newline", "--type", "text"])
+    } else {
+        ("xclip", &["-selection", "clipboard", "-out"])
+    };
+
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .wrap_err_with(|| format!("Failed to run {program}, is it installed?"))?;
+
+    // Both tools fail when the clipboard holds nothing they can convert to text
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(output.stdout).ok())
+}
+
+/// Copy text to the clipboard using the clipboard tool of the current session
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let (program, args): (&str, &[&str]) = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        ("wl-copy", &[])
+    } else {
+        ("xclip", &["-selection", "clipboard"])
+    };
+
+    copy_with(program, args, text)?;
+    debug!("Copied {} characters to clipboard", text.chars().count());
+    Ok(())
+}
+
+/// Set the PRIMARY selection, which a middle click pastes, using the
+/// clipboard tool of the current session
+pub fn set_primary_selection(text: &str) -> Result<()> {
+    let (program, args): (&str, &[&str]) = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        ("wl-copy", &["--primary"])
+    } else {
+        ("xclip", &["-selection", "primary"])
+    };
+
+    copy_with(program, args, text)?;
+    debug!(
+        "Set the primary selection to {} characters",
+        text.chars().count()
+    );
+    Ok(())
+}
+
+/// Send `text` to a clipboard tool, which keeps serving it after exiting
+fn copy_with(program: &str, args: &[&str], text: &str) -> Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .wrap_err_with(|| format!("Failed to run {program}, is it installed?"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .wrap_err_with(|| format!("Failed to send text to {program}"))?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Dictation buffer: final transcripts are collected in an editable zenity
+//! text window during the session instead of being typed one by one. Its
+//! Insert button pastes the whole (edited) text into the window that had the
+//! focus before, through the clipboard (the `buffer` output handler).
+
+use crate::keyboard;
+use eyre::{Result, WrapErr};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::task::JoinHandle;
+
+const TITLE: &str = "Dictation buffer";
+/// Time for the window manager to focus the target again after the window
+const REFOCUS_DELAY: Duration = Duration::from_millis(200);
+
+/// An open buffer window, fed through zenity's stdin
+pub struct BufferWindow {
+    stdin: ChildStdin,
+    closed: JoinHandle<()>,
+    empty: bool,
+}
+
+impl BufferWindow {
+    /// Show an empty buffer window. Closing it with Insert pastes its text,
+    /// also after the session ended.
+    pub fn open() -> Result<Self> {
+        let mut child = Command::new("zenity")
+            .args([
+                "--text-info",
+                "--editable",
+                "--auto-scroll",
+                "--title",
+                TITLE,
+                "--width=500",
+                "--height=250",
+                "--ok-label=_Insert",
+                "--cancel-label=Discard",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err("Failed to run zenity, is it installed?")?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+
+        let closed = tokio::spawn(async move {
+            if let Err(e) = insert_when_closed(child).await {
+                error!("Failed to insert the dictation buffer: {:#}", e);
+            }
+        });
+
+        Ok(Self {
+            stdin,
+            closed,
+            empty: true,
+        })
+    }
+
+    /// False once the window was closed (inserted or discarded)
+    pub fn is_open(&self) -> bool {
+        !self.closed.is_finished()
+    }
+
+    /// Add a transcript at the end of the buffer
+    pub async fn append(&mut self, text: &str) -> Result<()> {
+        let text = if self.empty {
+            text.to_string()
+        } else {
+            format!(" {text}")
+        };
+        self.stdin
+            .write_all(text.as_bytes())
+            .await
+            .wrap_err("Failed to add the transcript to the dictation buffer")?;
+        self.stdin.flush().await?;
+        self.empty = false;
+        Ok(())
+    }
+
+    /// Close the window without inserting anything
+    pub fn discard(self) {
+        // Dropping zenity's child handle kills it
+        self.closed.abort();
+    }
+}
+
+/// Wait for the window to be closed and paste its text if that was with Insert
+async fn insert_when_closed(child: Child) -> Result<()> {
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        info!("Dictation buffer discarded");
+        return Ok(());
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if text.is_empty() {
+        return Ok(());
+    }
+    tokio::time::sleep(REFOCUS_DELAY).await;
+    tokio::task::spawn_blocking(move || keyboard::paste_text(&text)).await?
+}
@@ -0,0 +1,298 @@
+//! `gnome-voice-input doctor`: checks the environment the app depends on,
+//! from the desktop session to Deepgram, and prints how to fix what fails.
+
+use crate::{
+    atspi, audio_source,
+    config::{Config, HotkeyBackend, OutputHandler},
+    config_check::{self, Report},
+    dbus_service, hotkey, portal, transcription, tray,
+};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use global_hotkey::GlobalHotKeyManager;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long the microphone is opened to see that audio arrives
+const MIC_TEST_DURATION: Duration = Duration::from_millis(500);
+
+/// Run all checks. Returns the number of problems found.
+pub async fn run_doctor(custom_path: Option<PathBuf>) -> usize {
+    let mut report = Report::default();
+
+    let wayland = check_session(&mut report);
+    check_tray(&mut report);
+
+    let config = match Config::load(custom_path) {
+        Ok(config) => {
+            report.ok("Config loaded");
+            portal::select_backends(config.desktop_access);
+            Some(config)
+        }
+        Err(e) => {
+            report.problem(
+                format!("{e:#}"),
+                "Run `gnome-voice-input config validate` for details",
+            );
+            None
+        }
+    };
+
+    let running = match dbus_service::instance_running() {
+        Ok(running) => running,
+        Err(e) => {
+            report.problem(
+                format!("{e:#}"),
+                "The session bus is needed for the tray, the CLI and the GNOME Shell extension",
+            );
+            false
+        }
+    };
+    if running {
+        report.ok("gnome-voice-input is running");
+    }
+
+    if portal::is_portal_only() {
+        report.ok(if portal::is_sandboxed() {
+            "Portal-only mode, running in a Flatpak sandbox"
+        } else {
+            "Portal-only mode (desktop_access = \"portal\")"
+        });
+        check_portal(&mut report);
+    } else {
+        check_typing(wayland, &mut report);
+    }
+
+    if let Some(config) = &config {
+        check_hotkeys(config, wayland, running, &mut report);
+        if config.output.handlers.contains(&OutputHandler::Atspi) {
+            check_accessibility(&mut report);
+        }
+        config_check::check_audio_device(config, &mut report);
+        check_microphone(config, &mut report);
+        check_deepgram(config, &mut report).await;
+    }
+
+    if report.problems == 0 {
+        println!("\nEverything looks good.");
+    } else {
+        println!("\n{} problem(s) found.", report.problems);
+    }
+    report.problems
+}
+
+/// Report the session type. Returns whether it is Wayland.
+fn check_session(report: &mut Report) -> bool {
+    let session = std::env::var("XDG_SESSION_TYPE").unwrap_or_default();
+    let desktop = tray::detect_desktop_environment();
+    match session.as_str() {
+        "x11" => report.ok(format!("X11 session ({desktop})")),
+        "wayland" => report.ok(format!("Wayland session ({desktop})")),
+        "" => report.note(
+            "Session type unknown, XDG_SESSION_TYPE is not set",
+            "Run doctor from a terminal inside the desktop session",
+        ),
+        other => report.note(
+            format!("Unusual session type {other} ({desktop})"),
+            "Hotkeys and typing are only supported on X11 and Wayland sessions",
+        ),
+    }
+    hotkey::is_wayland_session()
+}
+
+fn check_tray(report: &mut Report) {
+    match tray::status_notifier_available() {
+        Ok(true) => report.ok("StatusNotifierWatcher available, the tray icon is shown"),
+        Ok(false) if tray::detect_desktop_environment() == "GNOME" => report.problem(
+            "No StatusNotifierWatcher, GNOME won't show the tray icon",
+            "Install the AppIndicator extension (https://extensions.gnome.org/extension/615/appindicator-support/ \
+             or the gnome-shell-extension-appindicator package) and log in again, \
+             or use the bundled top bar extension (`just install-extension`) with ui.show_tray_icon = false",
+        ),
+        Ok(false) => report.problem(
+            "No StatusNotifierWatcher, the tray icon won't be shown",
+            "Enable a system tray or app indicator applet in your panel",
+        ),
+        Err(e) => report.problem(
+            format!("Failed to query the session bus: {e}"),
+            "Check that DBUS_SESSION_BUS_ADDRESS is set",
+        ),
+    }
+}
+
+fn check_typing(wayland: bool, report: &mut Report) {
+    match enigo::Enigo::new(&enigo::Settings::default()) {
+        Ok(_) => report.ok("Keyboard simulation available"),
+        Err(e) => report.problem(
+            format!("Keyboard simulation unavailable: {e}"),
+            "Typing needs an X server; check that DISPLAY is set",
+        ),
+    }
+    if !wayland {
+        return;
+    }
+
+    // Typing goes through XWayland, which native Wayland windows don't receive
+    let uinput = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/uinput")
+        .is_ok();
+    let ydotool = which("ydotool");
+    match (uinput, ydotool) {
+        (true, true) => report.ok("/dev/uinput writable and ydotool installed"),
+        (true, false) => report.note(
+            "/dev/uinput is writable, but ydotool is not installed",
+            "Text is typed through XWayland; install ydotool to type into native Wayland windows",
+        ),
+        (false, _) => report.note(
+            "Text is typed through XWayland and may not reach native Wayland windows",
+            "Add yourself to the input group (or add a udev rule for /dev/uinput) and install ydotool",
+        ),
+    }
+}
+
+fn check_accessibility(report: &mut Report) {
+    match atspi::Accessibility::connect() {
+        Ok(_) => report.ok("Accessibility bus available for inserting text"),
+        Err(e) => report.problem(
+            format!("{e:#}"),
+            "The atspi output handler falls back to typing; install at-spi2-core and enable it",
+        ),
+    }
+}
+
+fn check_hotkeys(config: &Config, wayland: bool, running: bool, report: &mut Report) {
+    config_check::check_hotkeys(config, report);
+    if portal::is_portal_only() {
+        // Checked with the portal-only mode above
+        return;
+    }
+
+    let use_portal = match config.hotkey.backend {
+        HotkeyBackend::Gnome => {
+            report.ok("Hotkey handled by the GNOME custom shortcut (install-shortcut)");
+            return;
+        }
+        HotkeyBackend::Auto => wayland,
+        HotkeyBackend::X11 => false,
+        HotkeyBackend::Portal => true,
+    };
+    if use_portal {
+        check_portal(report);
+        return;
+    }
+    if running {
+        report.ok("Hotkeys are grabbed by the running instance, not testing them");
+        return;
+    }
+    if std::env::var_os("DISPLAY").is_none() {
+        report.problem(
+            "No X display to grab hotkeys on, DISPLAY is not set",
+            "Run doctor inside the desktop session; on Wayland set hotkey.backend = \"portal\" or \"gnome\"",
+        );
+        return;
+    }
+
+    let manager = match GlobalHotKeyManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            report.problem(
+                format!("Failed to create the hotkey manager: {e}"),
+                "X11 hotkeys need an X server; on Wayland set hotkey.backend = \"portal\" or \"gnome\"",
+            );
+            return;
+        }
+    };
+    let Ok(hotkey) = hotkey::parse_hotkey(config) else {
+        return;
+    };
+    match manager.register(hotkey) {
+        Ok(()) => {
+            report.ok("Hotkey can be registered");
+            let _ = manager.unregister(hotkey);
+        }
+        Err(e) => report.problem(
+            format!("Hotkey can't be registered: {e}"),
+            "Another application uses it; choose a different key or add hotkey.fallbacks",
+        ),
+    }
+}
+
+fn check_portal(report: &mut Report) {
+    let (purpose, alternative) = if portal::is_portal_only() {
+        (
+            "global shortcuts and typing",
+            "Install xdg-desktop-portal-gnome or xdg-desktop-portal-kde on the host",
+        )
+    } else {
+        (
+            "global shortcuts",
+            "Install xdg-desktop-portal-gnome, or set hotkey.backend = \"gnome\" and run install-shortcut",
+        )
+    };
+    match portal::is_available() {
+        Ok(true) => report.ok(format!("XDG desktop portal available for {purpose}")),
+        Ok(false) => report.problem("XDG desktop portal not running", alternative),
+        Err(e) => report.problem(
+            format!("Failed to query the session bus: {e}"),
+            "Check that DBUS_SESSION_BUS_ADDRESS is set",
+        ),
+    }
+}
+
+/// Open the input device briefly and check that audio arrives
+fn check_microphone(config: &Config, report: &mut Report) {
+    let result = (|| -> eyre::Result<usize> {
+        let device = audio_source::select_input_device(&config.audio)?;
+        let supported = device.default_input_config()?;
+        let samples = Arc::new(AtomicUsize::new(0));
+        let counter = samples.clone();
+        let stream = device.build_input_stream_raw(
+            &supported.config(),
+            supported.sample_format(),
+            move |data: &cpal::Data, _: &cpal::InputCallbackInfo| {
+                counter.fetch_add(data.len(), Ordering::Relaxed);
+            },
+            |e| warn!("Audio stream error: {}", e),
+            None,
+        )?;
+        stream.play()?;
+        std::thread::sleep(MIC_TEST_DURATION);
+        drop(stream);
+        Ok(samples.load(Ordering::Relaxed))
+    })();
+
+    match result {
+        Ok(0) => report.problem(
+            "The microphone delivered no audio",
+            "Check that it isn't muted or blocked in the privacy settings",
+        ),
+        Ok(_) => report.ok("Microphone delivers audio"),
+        Err(e) => report.problem(
+            format!("Failed to open the microphone: {e:#}"),
+            "Check audio.device (see list-devices) and that no other application holds it exclusively",
+        ),
+    }
+}
+
+async fn check_deepgram(config: &Config, report: &mut Report) {
+    match transcription::verify_api_key(&config.deepgram_api_key).await {
+        Ok(()) => report.ok("Deepgram reachable and the API key accepted"),
+        Err(e) => {
+            let message = format!("{e:#}");
+            let hint = if message.contains("Failed to reach") {
+                "Check the network connection and proxy settings"
+            } else {
+                "Check deepgram_api_key, keys are managed at https://console.deepgram.com/"
+            };
+            report.problem(message, hint);
+        }
+    }
+}
+
+/// Whether `program` is an executable on the PATH
+fn which(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
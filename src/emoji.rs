@@ -0,0 +1,54 @@
+//! Spoken emoji: phrases like "thumbs up emoji" or "smiley face" replaced by
+//! the emoji they name.
+
+use std::collections::BTreeMap;
+
+/// Phrases understood without configuration; `[output.emoji]` adds to and
+/// overrides these. Emoji are single characters so that interim text typed
+/// with them can be deleted character by character.
+const BUILTIN: [(&str, &str); 24] = [
+    ("smiley face", "🙂"),
+    ("smiley emoji", "🙂"),
+    ("smile emoji", "😄"),
+    ("sad face", "🙁"),
+    ("wink emoji", "😉"),
+    ("laughing emoji", "😂"),
+    ("crying emoji", "😢"),
+    ("thinking emoji", "🤔"),
+    ("heart eyes emoji", "😍"),
+    ("shrug emoji", "🤷"),
+    ("facepalm emoji", "🤦"),
+    ("thumbs up emoji", "👍"),
+    ("thumbs down emoji", "👎"),
+    ("clapping emoji", "👏"),
+    ("waving emoji", "👋"),
+    ("folded hands emoji", "🙏"),
+    ("ok hand emoji", "👌"),
+    ("eyes emoji", "👀"),
+    ("heart emoji", "❤"),
+    ("fire emoji", "🔥"),
+    ("party emoji", "🎉"),
+    ("rocket emoji", "🚀"),
+    ("check mark emoji", "✅"),
+    ("hundred emoji", "💯"),
+];
+
+/// The built-in phrases with `configured` added, keyed by lowercase phrase
+pub fn table(configured: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut table: BTreeMap<String, String> = BUILTIN
+        .iter()
+        .map(|(phrase, emoji)| (phrase.to_string(), emoji.to_string()))
+        .collect();
+    table.extend(
+        configured
+            .iter()
+            .map(|(phrase, emoji)| (phrase.to_lowercase(), emoji.clone())),
+    );
+    table
+}
+
+/// Whether `c` is an emoji that keyboard simulation is unlikely to type
+/// (pictographs, symbols and dingbats outside the keyboard layout)
+pub fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF)
+}
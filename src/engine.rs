@@ -0,0 +1,157 @@
+//! The capture and transcription pipeline for embedding in other
+//! applications, without the tray, hotkeys, overlay or keyboard typing.
+
+use crate::audio::AudioInput;
+use crate::config::Config;
+use crate::handlers::TranscriptionHandler;
+use crate::session::RecordingSession;
+use crate::state::{AppEvent, AppState, RecordingState};
+use crate::stats::UsageStats;
+use crate::transcription_utils::{Transcript, TranscriptionResult};
+use async_trait::async_trait;
+use eyre::Result;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Transcripts buffered per subscriber before the slowest one misses some
+const TRANSCRIPT_CAPACITY: usize = 64;
+
+/// Records from the configured microphone (or another `AudioInput`) and
+/// transcribes it with Deepgram, one session at a time. Transcripts are
+/// delivered to subscribers instead of being typed.
+///
+/// The `[history]` settings of the config apply as in the application;
+/// disable them to keep transcripts out of the shared history. Usage
+/// statistics are kept in memory only.
+///
+/// ```no_run
+/// # async fn example() -> eyre::Result<()> {
+/// use gnome_voice_input::{Config, TranscriptionResult, VoiceInputEngine};
+///
+/// let engine = VoiceInputEngine::new(Config::load(None)?);
+/// let mut transcripts = engine.subscribe_transcripts();
+/// engine.start()?;
+/// tokio::spawn(async move {
+///     while let Ok(result) = transcripts.recv().await {
+///         if let TranscriptionResult::Final(transcript) = result {
+///             println!("{} ({:.0}%)", transcript.text, transcript.confidence * 100.0);
+///         }
+///     }
+/// });
+/// tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+/// engine.stop().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct VoiceInputEngine {
+    app_state: AppState,
+    transcripts: broadcast::Sender<TranscriptionResult>,
+    session: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl VoiceInputEngine {
+    pub fn new(config: Config) -> Self {
+        let mut app_state = AppState::new(config, false, None, CancellationToken::new());
+        app_state.stats = Arc::new(UsageStats::in_memory());
+        app_state.replace_transcriber(&app_state.config());
+        Self {
+            app_state,
+            transcripts: broadcast::channel(TRANSCRIPT_CAPACITY).0,
+            session: Mutex::new(None),
+        }
+    }
+
+    /// Record from `input` instead of the configured microphone
+    pub fn with_input(mut self, input: AudioInput) -> Self {
+        self.app_state.input = Some(input);
+        self
+    }
+
+    /// Start a recording session. Fails if one is still running; connection
+    /// and capture errors are reported as `AppEvent::BackendError` and by
+    /// `stop`. Must be called within a Tokio runtime.
+    pub fn start(&self) -> Result<()> {
+        if !self.app_state.begin_recording() {
+            bail!(
+                "A recording session is still {}",
+                self.app_state.recording_state()
+            );
+        }
+        let session =
+            RecordingSession::new(self.app_state.clone()).with_output(Box::new(BroadcastHandler {
+                transcripts: self.transcripts.clone(),
+            }));
+        *self.session.lock().unwrap() = Some(tokio::spawn(session.run()));
+        Ok(())
+    }
+
+    /// Stop recording and wait until the last transcripts were delivered.
+    /// Returns the session's error, if it failed.
+    pub async fn stop(&self) -> Result<()> {
+        self.app_state.stop_recording();
+        let session = self.session.lock().unwrap().take();
+        if let Some(session) = session {
+            session.await?;
+        }
+        if self.app_state.recording_state() == RecordingState::Error {
+            let error = self.app_state.last_error.read().unwrap().clone();
+            bail!(error.unwrap_or_else(|| "Recording failed".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Interim (if enabled in the config) and final transcripts of all
+    /// sessions from now on
+    pub fn subscribe_transcripts(&self) -> broadcast::Receiver<TranscriptionResult> {
+        self.transcripts.subscribe()
+    }
+
+    /// Session lifecycle and errors
+    pub fn subscribe_events(&self) -> broadcast::Receiver<AppEvent> {
+        self.app_state.events.subscribe()
+    }
+
+    pub fn recording_state(&self) -> RecordingState {
+        self.app_state.recording_state()
+    }
+
+    /// Use a new configuration from the next session on
+    pub fn set_config(&self, config: Config) -> Result<()> {
+        self.app_state.history.configure(&config.history)?;
+        self.app_state.replace_transcriber(&config);
+        self.app_state.replace_config(config);
+        Ok(())
+    }
+}
+
+impl Drop for VoiceInputEngine {
+    fn drop(&mut self) {
+        // Ends a capture that is still running
+        self.app_state.shutdown_token.cancel();
+    }
+}
+
+/// Passes the transcripts to the engine's subscribers
+struct BroadcastHandler {
+    transcripts: broadcast::Sender<TranscriptionResult>,
+}
+
+#[async_trait]
+impl TranscriptionHandler for BroadcastHandler {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        // Nobody subscribed is fine
+        let _ = self
+            .transcripts
+            .send(TranscriptionResult::Interim(transcript));
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        let _ = self
+            .transcripts
+            .send(TranscriptionResult::Final(transcript));
+        Ok(())
+    }
+}
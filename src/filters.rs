@@ -0,0 +1,112 @@
+//! Transcript filters: the text transforms run in order between the
+//! transcriber's results and the handlers (`[output] filters`).
+
+use crate::numbers::{self, NumberFormat};
+use crate::pii::Redactor;
+use crate::profanity::Blocklist;
+use crate::spelling::SpellMode;
+use crate::{casing, phrases, templates, vocabulary};
+use std::collections::BTreeMap;
+
+/// Rewrites the text of interim and final transcripts
+pub trait TranscriptFilter: Send + Sync {
+    /// The rewritten text, or None to drop the transcript so that no
+    /// handler sees it (e.g. a spoken command that was carried out)
+    fn apply(&self, text: String, is_final: bool) -> Option<String>;
+}
+
+/// Runs the filters in order; a filter dropping the transcript ends the chain
+impl TranscriptFilter for Vec<Box<dyn TranscriptFilter>> {
+    fn apply(&self, text: String, is_final: bool) -> Option<String> {
+        self.iter()
+            .try_fold(text, |text, filter| filter.apply(text, is_final))
+    }
+}
+
+/// The `replacements` of the transcription settings, in order of their keys
+pub struct Replacements(pub BTreeMap<String, String>);
+
+impl TranscriptFilter for Replacements {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        Some(
+            self.0
+                .iter()
+                .fold(text, |text, (from, to)| text.replace(from.as_str(), to)),
+        )
+    }
+}
+
+/// Upper-cases the first letter of each transcript
+pub struct Capitalize;
+
+impl TranscriptFilter for Capitalize {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        let mut chars = text.chars();
+        Some(match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => text,
+        })
+    }
+}
+
+/// Capitalizes sentences and "I", see [`casing::sentence_case`]
+pub struct SentenceCase;
+
+impl TranscriptFilter for SentenceCase {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        Some(casing::sentence_case(&text))
+    }
+}
+
+/// Replaces spoken emoji with the emoji of a table built by
+/// [`crate::emoji::table`]
+pub struct Emoji(pub BTreeMap<String, String>);
+
+impl TranscriptFilter for Emoji {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        Some(phrases::replace(&text, &self.0))
+    }
+}
+
+/// Expands template variables by name and format, built by
+/// [`templates::table`]
+pub struct Templates(pub BTreeMap<String, String>);
+
+impl TranscriptFilter for Templates {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        Some(templates::expand(&text, &self.0, &chrono::Local::now()))
+    }
+}
+
+/// Corrects words that sound like one of the terms
+pub struct Vocabulary(pub Vec<String>);
+
+impl TranscriptFilter for Vocabulary {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        Some(vocabulary::correct(&text, &self.0))
+    }
+}
+
+impl TranscriptFilter for NumberFormat {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        Some(numbers::format(&text, self))
+    }
+}
+
+impl TranscriptFilter for Blocklist {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        Some(self.filter(&text))
+    }
+}
+
+impl TranscriptFilter for Redactor {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        Some(self.redact(&text))
+    }
+}
+
+impl TranscriptFilter for SpellMode {
+    fn apply(&self, text: String, is_final: bool) -> Option<String> {
+        self.process(&text, is_final)
+    }
+}
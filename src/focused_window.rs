@@ -0,0 +1,81 @@
+//! Class and title of the focused window, read through X11 (`_NET_ACTIVE_WINDOW`).
+//! Native Wayland windows are not visible to X11 clients, so they are
+//! reported as no window.
+
+use crate::portal;
+use eyre::{Result, WrapErr};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, Window};
+use x11rb::rust_connection::RustConnection;
+
+#[derive(Debug, Clone)]
+pub struct FocusedWindow {
+    /// Instance and class name from WM_CLASS, e.g. "gnome-terminal-server"
+    pub classes: Vec<String>,
+    pub title: String,
+}
+
+/// The currently focused window, if it can be determined. Never known in
+/// portal-only mode, where X11 is off limits.
+pub fn focused_window() -> Result<Option<FocusedWindow>> {
+    if portal::is_portal_only() {
+        return Ok(None);
+    }
+    let (conn, screen_num) =
+        RustConnection::connect(None).wrap_err("Failed to connect to X server")?;
+    let Some(window) = active(&conn, screen_num)? else {
+        return Ok(None);
+    };
+
+    let classes = string_property(&conn, window, AtomEnum::WM_CLASS.into())?
+        .split('\0')
+        .filter(|class| !class.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let mut title = string_property(&conn, window, intern(&conn, "_NET_WM_NAME")?)?;
+    if title.is_empty() {
+        title = string_property(&conn, window, AtomEnum::WM_NAME.into())?;
+    }
+
+    Ok(Some(FocusedWindow { classes, title }))
+}
+
+/// ID of the currently focused window, to notice when the focus moves
+pub fn active_window() -> Result<Option<Window>> {
+    if portal::is_portal_only() {
+        return Ok(None);
+    }
+    let (conn, screen_num) =
+        RustConnection::connect(None).wrap_err("Failed to connect to X server")?;
+    active(&conn, screen_num)
+}
+
+fn active(conn: &RustConnection, screen_num: usize) -> Result<Option<Window>> {
+    let root = conn.setup().roots[screen_num].root;
+    let active = conn
+        .get_property(
+            false,
+            root,
+            intern(conn, "_NET_ACTIVE_WINDOW")?,
+            AtomEnum::WINDOW,
+            0,
+            1,
+        )?
+        .reply()?;
+    Ok(active
+        .value32()
+        .and_then(|mut value| value.next())
+        .filter(|window| *window != x11rb::NONE))
+}
+
+fn intern(conn: &RustConnection, name: &str) -> Result<u32> {
+    Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+}
+
+fn string_property(conn: &RustConnection, window: Window, property: u32) -> Result<String> {
+    let reply = conn
+        .get_property(false, window, property, AtomEnum::ANY, 0, 1024)?
+        .reply()?;
+    Ok(String::from_utf8_lossy(&reply.value).into_owned())
+}
@@ -0,0 +1,133 @@
+//! Registers the recording toggle as a GNOME custom keyboard shortcut through
+//! gsettings. GNOME Shell then runs `gnome-voice-input toggle`, which works on
+//! Wayland without grabbing keys in this process.
+
+use crate::config::Config;
+use eyre::{Result, WrapErr};
+use std::process::Command;
+
+const MEDIA_KEYS_SCHEMA: &str = "org.gnome.settings-daemon.plugins.media-keys";
+const CUSTOM_KEYBINDING_SCHEMA: &str =
+    "org.gnome.settings-daemon.plugins.media-keys.custom-keybinding";
+const CUSTOM_KEYBINDINGS_KEY: &str = "custom-keybindings";
+const SHORTCUT_PATH: &str =
+    "/org/gnome/settings-daemon/plugins/media-keys/custom-keybindings/gnome-voice-input/";
+const SHORTCUT_NAME: &str = "Voice Input: start or stop recording";
+
+/// Install (or update) the custom shortcut. Uses `accelerator` if given, e.g.
+/// `<Super>v`, otherwise the main hotkey from the config. Returns the
+/// accelerator that was bound.
+pub fn install_shortcut(config: &Config, accelerator: Option<String>) -> Result<String> {
+    let accelerator = match accelerator {
+        Some(accelerator) => accelerator,
+        None => config_accelerator(config)?,
+    };
+    let executable = std::env::current_exe().wrap_err("Failed to locate the executable")?;
+    let command = format!("'{}' toggle", executable.display());
+
+    let relocatable = format!("{CUSTOM_KEYBINDING_SCHEMA}:{SHORTCUT_PATH}");
+    gsettings_set(&relocatable, "name", &gvariant_string(SHORTCUT_NAME))?;
+    gsettings_set(&relocatable, "command", &gvariant_string(&command))?;
+    gsettings_set(&relocatable, "binding", &gvariant_string(&accelerator))?;
+
+    let mut paths = custom_keybinding_paths()?;
+    if !paths.iter().any(|path| path == SHORTCUT_PATH) {
+        paths.push(SHORTCUT_PATH.to_string());
+        set_custom_keybinding_paths(&paths)?;
+    }
+
+    info!(
+        "Installed GNOME shortcut {} running {}",
+        accelerator, command
+    );
+    Ok(accelerator)
+}
+
+/// Remove the custom shortcut. Returns false if it was not installed.
+pub fn uninstall_shortcut() -> Result<bool> {
+    let mut paths = custom_keybinding_paths()?;
+    let installed = paths.iter().any(|path| path == SHORTCUT_PATH);
+    if installed {
+        paths.retain(|path| path != SHORTCUT_PATH);
+        set_custom_keybinding_paths(&paths)?;
+    }
+
+    gsettings(&[
+        "reset-recursively",
+        &format!("{CUSTOM_KEYBINDING_SCHEMA}:{SHORTCUT_PATH}"),
+    ])?;
+    Ok(installed)
+}
+
+/// GTK accelerator for the main hotkey, e.g. `<Super><Shift>v`
+fn config_accelerator(config: &Config) -> Result<String> {
+    let mut accelerator = String::new();
+    for modifier in &config.hotkey.modifiers {
+        let name = match modifier.to_lowercase().as_str() {
+            "super" | "meta" | "cmd" => "<Super>",
+            "ctrl" | "control" => "<Control>",
+            "alt" => "<Alt>",
+            "shift" => "<Shift>",
+            _ => bail!("Unknown modifier: {}", modifier),
+        };
+        accelerator.push_str(name);
+    }
+    let code = crate::hotkey::parse_key(&config.hotkey.key)?;
+    accelerator.push_str(&crate::hotkey_portal::keysym_name(code));
+    Ok(accelerator)
+}
+
+fn custom_keybinding_paths() -> Result<Vec<String>> {
+    let output = gsettings(&["get", MEDIA_KEYS_SCHEMA, CUSTOM_KEYBINDINGS_KEY])?;
+    Ok(parse_string_array(&output))
+}
+
+fn set_custom_keybinding_paths(paths: &[String]) -> Result<()> {
+    let value = format!(
+        "[{}]",
+        paths
+            .iter()
+            .map(|path| gvariant_string(path))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    gsettings_set(MEDIA_KEYS_SCHEMA, CUSTOM_KEYBINDINGS_KEY, &value)
+}
+
+/// Parse a GVariant string array as printed by gsettings, e.g.
+/// `['/a/', '/b/']` or `@as []`
+fn parse_string_array(value: &str) -> Vec<String> {
+    value
+        .trim()
+        .trim_start_matches("@as")
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|item| item.trim().trim_matches('\'').to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn gvariant_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn gsettings_set(schema: &str, key: &str, value: &str) -> Result<()> {
+    gsettings(&["set", schema, key, value]).map(|_| ())
+}
+
+fn gsettings(args: &[&str]) -> Result<String> {
+    let output = Command::new("gsettings")
+        .args(args)
+        .output()
+        .wrap_err("Failed to run gsettings, is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "gsettings {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,170 @@
+use crate::atspi::{Accessibility, TextTarget};
+use crate::privacy;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::keyboard_handler::KeyboardTranscriptionHandler;
+use super::transcription_handler::TranscriptionHandler;
+
+/// Where the text of the current utterance goes
+enum Route {
+    /// Nothing inserted yet
+    Undecided,
+    /// Interim text of `length` characters inserted at `start` through AT-SPI
+    Atspi {
+        target: TextTarget,
+        start: i32,
+        length: i32,
+    },
+    /// The focused widget isn't accessible, typed with keystrokes
+    Keyboard,
+}
+
+/// Handler that inserts transcripts into the focused widget through AT-SPI2,
+/// replacing interim text exactly. Falls back to keyboard simulation for
+/// windows without accessible editable text.
+pub struct AtspiTranscriptionHandler {
+    use_interim_results: bool,
+    route: Route,
+    keyboard: KeyboardTranscriptionHandler,
+}
+
+impl AtspiTranscriptionHandler {
+    pub fn new(use_interim_results: bool) -> Self {
+        Self {
+            use_interim_results,
+            route: Route::Undecided,
+            keyboard: KeyboardTranscriptionHandler::new(use_interim_results),
+        }
+    }
+
+    /// Type no faster than this where the widget isn't accessible
+    pub fn with_chars_per_second(mut self, chars_per_second: Option<u32>) -> Self {
+        self.keyboard = self.keyboard.with_chars_per_second(chars_per_second);
+        self
+    }
+
+    /// Keyboard layout used where the widget isn't accessible, see
+    /// [`KeyboardTranscriptionHandler::with_layout`]
+    pub fn with_layout(mut self, layout: Option<String>) -> Self {
+        self.keyboard = self.keyboard.with_layout(layout);
+        self
+    }
+}
+
+/// Insert `text` at the caret of the focused editable widget; None if there
+/// is none or it can't be edited
+fn insert_at_caret(text: &str) -> Option<(TextTarget, i32)> {
+    let insert = || -> Result<Option<(TextTarget, i32)>> {
+        let accessibility = Accessibility::connect()?;
+        let Some(target) = accessibility.focused_text() else {
+            return Ok(None);
+        };
+        let start = accessibility.caret_offset(&target)?;
+        accessibility.insert(&target, start, text)?;
+        Ok(Some((target, start)))
+    };
+    insert().unwrap_or_else(|e| {
+        debug!("Not inserting through AT-SPI: {:#}", e);
+        None
+    })
+}
+
+/// Replace the `length` characters at `start` with `text`
+fn replace(target: &TextTarget, start: i32, length: i32, text: &str) -> Result<()> {
+    let accessibility = Accessibility::connect()?;
+    if length > 0 {
+        accessibility.delete(target, start, start + length)?;
+    }
+    if !text.is_empty() {
+        accessibility.insert(target, start, text)?;
+    }
+    Ok(())
+}
+
+fn char_count(text: &str) -> i32 {
+    text.chars().count() as i32
+}
+
+#[async_trait]
+impl TranscriptionHandler for AtspiTranscriptionHandler {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        if !self.use_interim_results || transcript.text.trim().is_empty() {
+            return Ok(());
+        }
+        let text = &transcript.text;
+
+        match &mut self.route {
+            Route::Atspi {
+                target,
+                start,
+                length,
+            } => {
+                replace(target, *start, *length, text)?;
+                *length = char_count(text);
+            }
+            Route::Keyboard => self.keyboard.on_interim_result(transcript).await?,
+            Route::Undecided => match insert_at_caret(text) {
+                Some((target, start)) => {
+                    self.route = Route::Atspi {
+                        target,
+                        start,
+                        length: char_count(text),
+                    };
+                }
+                None => {
+                    self.route = Route::Keyboard;
+                    self.keyboard.on_interim_result(transcript).await?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        let text = &transcript.text;
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+
+        // A space after each final result, as when typing
+        let inserted = format!("{text} ");
+        match std::mem::replace(&mut self.route, Route::Undecided) {
+            Route::Atspi {
+                target,
+                start,
+                length,
+            } => {
+                info!("Final transcribed: {}", privacy::loggable(text));
+                replace(&target, start, length, &inserted)?;
+            }
+            Route::Keyboard => self.keyboard.on_final_result(transcript).await?,
+            Route::Undecided => match insert_at_caret(&inserted) {
+                Some(_) => info!("Final transcribed: {}", privacy::loggable(text)),
+                None => self.keyboard.on_final_result(transcript).await?,
+            },
+        }
+        Ok(())
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        match std::mem::replace(&mut self.route, Route::Undecided) {
+            Route::Atspi {
+                target,
+                start,
+                length,
+            } => {
+                info!("Removing {} characters of interim text", length);
+                replace(&target, start, length, "")
+            }
+            Route::Keyboard => self.keyboard.on_transcription_discarded().await,
+            Route::Undecided => Ok(()),
+        }
+    }
+
+    async fn on_focus_lost(&mut self) -> Result<()> {
+        self.route = Route::Undecided;
+        self.keyboard.on_focus_lost().await
+    }
+}
@@ -0,0 +1,52 @@
+use crate::dictation_buffer::BufferWindow;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler collecting final transcripts in the dictation buffer window
+/// instead of typing them. The window opens with the first transcript; when
+/// it was closed during the session, the next transcript opens a new one.
+#[derive(Default)]
+pub struct BufferTranscriptionHandler {
+    window: Option<BufferWindow>,
+}
+
+impl BufferTranscriptionHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TranscriptionHandler for BufferTranscriptionHandler {
+    async fn on_interim_result(&mut self, _transcript: Transcript) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        let text = transcript.text.trim();
+        if text.is_empty() {
+            return Ok(());
+        }
+        let window = match self.window.take() {
+            Some(window) if window.is_open() => window,
+            _ => BufferWindow::open()?,
+        };
+        self.window.insert(window).append(text).await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        // The window stays open for editing until it is inserted or discarded
+        self.window = None;
+        Ok(())
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        if let Some(window) = self.window.take() {
+            window.discard();
+        }
+        Ok(())
+    }
+}
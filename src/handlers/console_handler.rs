@@ -1,3 +1,4 @@
+use crate::transcription_utils::Transcript;
 use async_trait::async_trait;
 use eyre::Result;
 use std::io::Write;
@@ -16,14 +17,14 @@ impl ConsoleTranscriptionHandler {
 
 #[async_trait]
 impl TranscriptionHandler for ConsoleTranscriptionHandler {
-    async fn on_interim_result(&mut self, text: String) -> Result<()> {
-        print!("\rInterim: {}", text);
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        print!("\rInterim: {}", transcript.text);
         std::io::stdout().flush()?;
         Ok(())
     }
 
-    async fn on_final_result(&mut self, text: String) -> Result<()> {
-        println!("\nFinal: {}", text);
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        println!("\nFinal: {}", transcript.text);
         Ok(())
     }
 
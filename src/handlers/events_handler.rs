@@ -0,0 +1,72 @@
+use crate::session::SessionId;
+use crate::state::{AppEvent, AppState};
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that publishes the first transcript of a recording session and
+/// every final transcript on the event bus before passing results on to the
+/// wrapped handler
+pub struct EventsHandler<H> {
+    inner: H,
+    session: SessionId,
+    app_state: AppState,
+    seen_transcript: bool,
+}
+
+impl<H: TranscriptionHandler> EventsHandler<H> {
+    pub fn new(inner: H, session: SessionId, app_state: AppState) -> Self {
+        Self {
+            inner,
+            session,
+            app_state,
+            seen_transcript: false,
+        }
+    }
+
+    fn transcript_arrived(&mut self, text: &str) {
+        if self.seen_transcript || text.trim().is_empty() {
+            return;
+        }
+        self.seen_transcript = true;
+        self.app_state
+            .publish(AppEvent::FirstTranscript(self.session));
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for EventsHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.transcript_arrived(&transcript.text);
+        self.inner.on_interim_result(transcript).await
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.transcript_arrived(&transcript.text);
+        if !transcript.text.trim().is_empty() {
+            self.app_state.publish(AppEvent::TranscriptFinal {
+                session: self.session,
+                text: transcript.text.clone(),
+            });
+        }
+        self.inner.on_final_result(transcript).await
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.inner.on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+}
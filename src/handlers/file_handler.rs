@@ -0,0 +1,38 @@
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that appends final transcripts to a file, one line each
+pub struct FileTranscriptionHandler {
+    file: File,
+}
+
+impl FileTranscriptionHandler {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .wrap_err_with(|| format!("Failed to open output file {}", path.display()))?;
+        Ok(Self { file })
+    }
+}
+
+#[async_trait]
+impl TranscriptionHandler for FileTranscriptionHandler {
+    async fn on_interim_result(&mut self, _transcript: Transcript) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        if !transcript.text.trim().is_empty() {
+            writeln!(self.file, "{}", transcript.text)?;
+        }
+        Ok(())
+    }
+}
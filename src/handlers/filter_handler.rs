@@ -0,0 +1,60 @@
+use crate::filters::TranscriptFilter;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that runs interim and final transcripts through the `[output]`
+/// filters before passing them on to the wrapped handler. Transcripts a
+/// filter drops don't reach it.
+pub struct FilterHandler<H> {
+    inner: H,
+    filters: Vec<Box<dyn TranscriptFilter>>,
+}
+
+impl<H: TranscriptionHandler> FilterHandler<H> {
+    pub fn new(inner: H, filters: Vec<Box<dyn TranscriptFilter>>) -> Self {
+        Self { inner, filters }
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for FilterHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        match self.filters.apply(transcript.text.clone(), false) {
+            Some(text) => {
+                self.inner
+                    .on_interim_result(transcript.with_text(text))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        match self.filters.apply(transcript.text.clone(), true) {
+            Some(text) => self.inner.on_final_result(transcript.with_text(text)).await,
+            None => {
+                debug!("Final transcript dropped by a filter");
+                Ok(())
+            }
+        }
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.inner.on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+}
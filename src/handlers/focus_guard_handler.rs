@@ -0,0 +1,155 @@
+use crate::focused_window;
+use crate::keyboard::{self, FocusLost};
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that stops passing an utterance on to the wrapped (typing)
+/// handler once the window focus moved away from the window the recording
+/// started in, so the rest of a sentence doesn't end up in whatever window
+/// was clicked. Typing is stopped between words as well. The next utterance
+/// is typed into the newly focused window.
+pub struct FocusGuardHandler<H> {
+    inner: H,
+    /// Focused window when the recording started, or after a focus change
+    /// when the utterance it happened in ended; None before the first result
+    /// without a start. Windows X11 can't see (native Wayland) are
+    /// `Some(None)`.
+    window: Option<Option<u32>>,
+    /// The focus moved during the current utterance
+    lost: bool,
+    active_window: fn() -> Option<u32>,
+}
+
+impl<H: TranscriptionHandler> FocusGuardHandler<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            window: None,
+            lost: false,
+            active_window,
+        }
+    }
+
+    /// Look up the focused window with `active_window` instead of asking X11
+    pub fn with_active_window(mut self, active_window: fn() -> Option<u32>) -> Self {
+        self.active_window = active_window;
+        self
+    }
+
+    /// Whether the focus is still where the utterance started; tells the
+    /// wrapped handler when it moved
+    async fn still_focused(&mut self) -> Result<bool> {
+        if self.lost {
+            return Ok(false);
+        }
+        let current = (self.active_window)();
+        match self.window {
+            None => {
+                self.window = Some(current);
+                Ok(true)
+            }
+            Some(window) if window == current => Ok(true),
+            Some(_) => {
+                warn!("Window focus changed, not typing the rest of the transcript");
+                self.focus_lost().await?;
+                Ok(false)
+            }
+        }
+    }
+
+    async fn focus_lost(&mut self) -> Result<()> {
+        self.lost = true;
+        self.inner.on_focus_lost().await
+    }
+
+    /// Pass a result on, stopping its typing when the focus moves away in
+    /// the middle of it
+    async fn pass_guarded(&mut self, transcript: Transcript, is_final: bool) -> Result<()> {
+        let result = {
+            let _guard = keyboard::guard_focus(self.window.flatten());
+            if is_final {
+                self.inner.on_final_result(transcript).await
+            } else {
+                self.inner.on_interim_result(transcript).await
+            }
+        };
+        match result {
+            Err(e) if e.is::<FocusLost>() => {
+                warn!("Window focus changed while typing, not typing the rest of the transcript");
+                self.focus_lost().await
+            }
+            result => result,
+        }
+    }
+
+    /// After an utterance; if the focus moved, the next one is typed into
+    /// the window focused now
+    fn end_utterance(&mut self) {
+        if self.lost {
+            self.window = Some((self.active_window)());
+            self.lost = false;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window = None;
+        self.lost = false;
+    }
+}
+
+fn active_window() -> Option<u32> {
+    focused_window::active_window().unwrap_or_else(|e| {
+        debug!("Could not determine the focused window: {:#}", e);
+        None
+    })
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for FocusGuardHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        if self.still_focused().await? {
+            self.pass_guarded(transcript, false).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        if self.still_focused().await? {
+            self.pass_guarded(transcript, true).await?;
+        }
+        self.end_utterance();
+        Ok(())
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.reset();
+        self.window = Some((self.active_window)());
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.reset();
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        // Deleting the interim text would hit the newly focused window
+        let focused = self.window.is_none() || self.still_focused().await?;
+        self.reset();
+        if focused {
+            self.inner.on_transcription_discarded().await?;
+        }
+        Ok(())
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+
+    async fn on_focus_lost(&mut self) -> Result<()> {
+        self.inner.on_focus_lost().await
+    }
+}
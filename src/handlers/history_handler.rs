@@ -0,0 +1,57 @@
+use crate::history::{History, HistoryContext};
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+use std::sync::Arc;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that appends final transcripts to the persistent history before
+/// passing them on to the wrapped handler
+pub struct HistoryHandler<H> {
+    inner: H,
+    history: Arc<History>,
+    context: HistoryContext,
+}
+
+impl<H: TranscriptionHandler> HistoryHandler<H> {
+    pub fn new(inner: H, history: Arc<History>, context: HistoryContext) -> Self {
+        Self {
+            inner,
+            history,
+            context,
+        }
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for HistoryHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.inner.on_interim_result(transcript).await
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        if !transcript.text.trim().is_empty() {
+            if let Err(e) = self.history.add(&transcript, &self.context) {
+                warn!("Failed to save transcript to history: {:#}", e);
+            }
+        }
+        self.inner.on_final_result(transcript).await
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.inner.on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+}
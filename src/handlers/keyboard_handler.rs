@@ -1,4 +1,6 @@
 use crate::keyboard;
+use crate::privacy;
+use crate::transcription_utils::Transcript;
 use async_trait::async_trait;
 use eyre::Result;
 
@@ -8,6 +10,8 @@ use super::transcription_handler::TranscriptionHandler;
 pub struct KeyboardTranscriptionHandler {
     use_interim_results: bool,
     last_interim_length: usize,
+    chars_per_second: Option<u32>,
+    layout: Option<String>,
 }
 
 impl KeyboardTranscriptionHandler {
@@ -15,14 +19,33 @@ impl KeyboardTranscriptionHandler {
         Self {
             use_interim_results,
             last_interim_length: 0,
+            chars_per_second: None,
+            layout: None,
         }
     }
+
+    /// Type no faster than this, see `[output.typing]`
+    pub fn with_chars_per_second(mut self, chars_per_second: Option<u32>) -> Self {
+        self.chars_per_second = chars_per_second;
+        self
+    }
+
+    /// Keyboard layout deciding what is pasted instead of typed, the active
+    /// one when unset; see `[output.typing]`
+    pub fn with_layout(mut self, layout: Option<String>) -> Self {
+        self.layout = layout;
+        self
+    }
 }
 
 #[async_trait]
 impl TranscriptionHandler for KeyboardTranscriptionHandler {
-    async fn on_interim_result(&mut self, text: String) -> Result<()> {
-        debug!("Received interim transcription: '{}'", text);
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        let text = transcript.text;
+        debug!(
+            "Received interim transcription: '{}'",
+            privacy::loggable(&text)
+        );
 
         if self.use_interim_results && !text.trim().is_empty() {
             // Delete previous interim text by sending backspaces
@@ -33,15 +56,19 @@ impl TranscriptionHandler for KeyboardTranscriptionHandler {
             }
 
             // Type new interim text
-            keyboard::type_text(&text)?;
+            keyboard::type_text_paced(&text, self.chars_per_second, self.layout.as_deref())?;
             self.last_interim_length = text.chars().count();
         }
 
         Ok(())
     }
 
-    async fn on_final_result(&mut self, text: String) -> Result<()> {
-        debug!("Received final transcription: '{}'", text);
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        let text = transcript.text;
+        debug!(
+            "Received final transcription: '{}'",
+            privacy::loggable(&text)
+        );
 
         if !text.trim().is_empty() {
             // Delete previous interim text if any
@@ -52,13 +79,34 @@ impl TranscriptionHandler for KeyboardTranscriptionHandler {
                 self.last_interim_length = 0;
             }
 
-            info!("Final transcribed: {}", text);
-            keyboard::type_text(&text)?;
+            info!("Final transcribed: {}", privacy::loggable(&text));
+            keyboard::type_text_paced(&text, self.chars_per_second, self.layout.as_deref())?;
 
             // Add a space after final transcription for better flow
-            keyboard::type_text(" ")?;
+            keyboard::type_text_paced(" ", self.chars_per_second, self.layout.as_deref())?;
         }
 
         Ok(())
     }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        // Remove interim text that was already typed for the discarded session
+        if self.last_interim_length > 0 {
+            info!(
+                "Removing {} characters of interim text",
+                self.last_interim_length
+            );
+            for _ in 0..self.last_interim_length {
+                keyboard::press_key(enigo::Key::Backspace)?;
+            }
+            self.last_interim_length = 0;
+        }
+
+        Ok(())
+    }
+
+    async fn on_focus_lost(&mut self) -> Result<()> {
+        self.last_interim_length = 0;
+        Ok(())
+    }
 }
@@ -0,0 +1,65 @@
+use crate::llm::LlmClient;
+use crate::privacy;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that has final transcripts cleaned up by a language model before
+/// passing them on to the wrapped handler. The raw transcript is passed on
+/// if the model fails or is too slow.
+pub struct LlmCleanupHandler<H> {
+    inner: H,
+    client: LlmClient,
+}
+
+impl<H: TranscriptionHandler> LlmCleanupHandler<H> {
+    pub fn new(inner: H, client: LlmClient) -> Self {
+        Self { inner, client }
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for LlmCleanupHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.inner.on_interim_result(transcript).await
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        if transcript.text.trim().is_empty() {
+            return self.inner.on_final_result(transcript).await;
+        }
+        let transcript = match self.client.rewrite(&transcript.text).await {
+            Ok(cleaned) => {
+                debug!(
+                    "LLM cleanup: '{}' -> '{}'",
+                    privacy::loggable(&transcript.text),
+                    privacy::loggable(&cleaned)
+                );
+                transcript.with_text(cleaned)
+            }
+            Err(e) => {
+                warn!("LLM cleanup failed, using the raw transcript: {:#}", e);
+                transcript
+            }
+        };
+        self.inner.on_final_result(transcript).await
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.inner.on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+}
@@ -0,0 +1,39 @@
+use crate::privacy;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that logs transcription results instead of typing them, used by
+/// `--dry-run`
+#[derive(Default)]
+pub struct LogTranscriptionHandler;
+
+impl LogTranscriptionHandler {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl TranscriptionHandler for LogTranscriptionHandler {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        debug!(
+            "Dry run, interim: '{}'",
+            privacy::loggable(&transcript.text)
+        );
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        if !transcript.text.trim().is_empty() {
+            info!(
+                "Dry run, would type: '{}' (confidence: {:.2})",
+                privacy::loggable(&transcript.text),
+                transcript.confidence
+            );
+        }
+        Ok(())
+    }
+}
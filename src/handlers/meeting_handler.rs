@@ -0,0 +1,73 @@
+use crate::config::MeetingFormat;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler writing a meeting transcript: each final transcript with the time
+/// it arrived, a line per speaker turn when they are labeled
+pub struct MeetingTranscriptionHandler {
+    file: File,
+    path: PathBuf,
+    format: MeetingFormat,
+}
+
+impl MeetingTranscriptionHandler {
+    /// Create the transcript of a meeting starting now in `directory`
+    pub fn create(directory: &Path, format: MeetingFormat) -> Result<Self> {
+        std::fs::create_dir_all(directory)
+            .wrap_err_with(|| format!("Failed to create {}", directory.display()))?;
+        let now = chrono::Local::now();
+        let extension = match format {
+            MeetingFormat::Markdown => "md",
+            MeetingFormat::Text => "txt",
+        };
+        let path = directory.join(format!(
+            "{} Meeting.{}",
+            now.format("%Y-%m-%d %H-%M"),
+            extension
+        ));
+        let mut file =
+            File::create(&path).wrap_err_with(|| format!("Failed to create {}", path.display()))?;
+        let title = format!("Meeting {}", now.format("%Y-%m-%d %H:%M"));
+        match format {
+            MeetingFormat::Markdown => write!(file, "# {title}\n\n")?,
+            MeetingFormat::Text => write!(file, "{title}\n\n")?,
+        }
+        info!("Writing meeting transcript to {}", path.display());
+        Ok(Self { file, path, format })
+    }
+}
+
+#[async_trait]
+impl TranscriptionHandler for MeetingTranscriptionHandler {
+    async fn on_interim_result(&mut self, _transcript: Transcript) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        let time = chrono::Local::now().format("%H:%M:%S");
+        for line in transcript
+            .text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            match self.format {
+                MeetingFormat::Markdown => writeln!(self.file, "- **{time}** {line}")?,
+                MeetingFormat::Text => writeln!(self.file, "[{time}] {line}")?,
+            }
+        }
+        self.file.flush()?;
+        Ok(())
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        info!("Meeting transcript saved to {}", self.path.display());
+        Ok(())
+    }
+}
@@ -1,9 +1,44 @@
+pub mod atspi_handler;
+pub mod buffer_handler;
 pub mod console_handler;
+pub mod events_handler;
+pub mod file_handler;
+pub mod filter_handler;
+pub mod focus_guard_handler;
+pub mod history_handler;
 pub mod keyboard_handler;
+pub mod llm_handler;
+pub mod log_handler;
+pub mod meeting_handler;
+pub mod overlay_handler;
+pub mod primary_handler;
+pub mod recent_handler;
+pub mod review_handler;
+pub mod stats_handler;
+pub mod subtitle_handler;
 mod transcription_handler;
+pub mod typing_queue_handler;
 
-#[allow(unused_imports)]
+pub use atspi_handler::AtspiTranscriptionHandler;
+pub use buffer_handler::BufferTranscriptionHandler;
 pub use console_handler::ConsoleTranscriptionHandler;
+pub use events_handler::EventsHandler;
+pub use file_handler::FileTranscriptionHandler;
+pub use filter_handler::FilterHandler;
+pub use focus_guard_handler::FocusGuardHandler;
+pub use history_handler::HistoryHandler;
 pub use keyboard_handler::KeyboardTranscriptionHandler;
+pub use llm_handler::LlmCleanupHandler;
+pub use log_handler::LogTranscriptionHandler;
+pub use meeting_handler::MeetingTranscriptionHandler;
+pub use overlay_handler::OverlayTranscriptionHandler;
+pub use primary_handler::PrimarySelectionHandler;
+pub use recent_handler::RecentTranscriptsHandler;
+pub use review_handler::ReviewHandler;
+pub use stats_handler::UsageStatsHandler;
+pub use subtitle_handler::SubtitleTranscriptionHandler;
+pub use typing_queue_handler::TypingQueueHandler;
 
-pub use transcription_handler::{process_transcription_with_handler, TranscriptionHandler};
+pub use transcription_handler::{
+    process_transcription_until, process_transcription_with_handler, TranscriptionHandler,
+};
@@ -0,0 +1,52 @@
+use crate::overlay::Overlay;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that mirrors transcription results into the recording overlay
+/// before passing them on to the wrapped handler
+pub struct OverlayTranscriptionHandler<H> {
+    inner: H,
+    overlay: Option<Overlay>,
+}
+
+impl<H: TranscriptionHandler> OverlayTranscriptionHandler<H> {
+    pub fn new(inner: H, overlay: Option<Overlay>) -> Self {
+        Self { inner, overlay }
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for OverlayTranscriptionHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        if let Some(overlay) = &self.overlay {
+            overlay.set_text(transcript.text.clone());
+        }
+        self.inner.on_interim_result(transcript).await
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        if let Some(overlay) = &self.overlay {
+            overlay.set_text(transcript.text.clone());
+        }
+        self.inner.on_final_result(transcript).await
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.inner.on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+}
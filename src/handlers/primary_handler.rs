@@ -0,0 +1,43 @@
+use crate::desktop;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler putting the session's final transcripts in the PRIMARY selection,
+/// so a middle click pastes what was said so far
+#[derive(Default)]
+pub struct PrimarySelectionHandler {
+    text: String,
+}
+
+impl PrimarySelectionHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TranscriptionHandler for PrimarySelectionHandler {
+    async fn on_interim_result(&mut self, _transcript: Transcript) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        let text = transcript.text.trim();
+        if text.is_empty() {
+            return Ok(());
+        }
+        if !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        self.text.push_str(text);
+        desktop::set_primary_selection(&self.text)
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.text.clear();
+        Ok(())
+    }
+}
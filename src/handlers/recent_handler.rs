@@ -0,0 +1,50 @@
+use crate::state::AppState;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that remembers final transcripts in the app state before passing
+/// them on to the wrapped handler
+pub struct RecentTranscriptsHandler<H> {
+    inner: H,
+    app_state: AppState,
+}
+
+impl<H: TranscriptionHandler> RecentTranscriptsHandler<H> {
+    pub fn new(inner: H, app_state: AppState) -> Self {
+        Self { inner, app_state }
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for RecentTranscriptsHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.inner.on_interim_result(transcript).await
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        if !transcript.text.trim().is_empty() {
+            self.app_state
+                .push_recent_transcript(transcript.text.clone());
+        }
+        self.inner.on_final_result(transcript).await
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.inner.on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+}
@@ -0,0 +1,76 @@
+use crate::privacy;
+use crate::review::{self, Review};
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+use std::time::Duration;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Time for the window manager to focus the target again after the dialog
+const REFOCUS_DELAY: Duration = Duration::from_millis(200);
+
+/// Handler that asks before passing a final transcript on to the wrapped
+/// (typing) handler, see [`review::review`]. Interim results are not typed,
+/// the overlay still shows them.
+pub struct ReviewHandler<H> {
+    inner: H,
+    auto_insert: Option<Duration>,
+}
+
+impl<H: TranscriptionHandler> ReviewHandler<H> {
+    pub fn new(inner: H, auto_insert: Option<Duration>) -> Self {
+        Self { inner, auto_insert }
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for ReviewHandler<H> {
+    async fn on_interim_result(&mut self, _transcript: Transcript) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        if transcript.text.trim().is_empty() {
+            return Ok(());
+        }
+        let auto_insert = self.auto_insert;
+        let reviewed = {
+            let text = transcript.text.clone();
+            tokio::task::spawn_blocking(move || review::review(&text, auto_insert)).await??
+        };
+        match reviewed {
+            Review::Insert(text) => {
+                tokio::time::sleep(REFOCUS_DELAY).await;
+                self.inner.on_final_result(transcript.with_text(text)).await
+            }
+            Review::Discard => {
+                info!(
+                    "Discarded transcript: {}",
+                    privacy::loggable(&transcript.text)
+                );
+                Ok(())
+            }
+        }
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.inner.on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+
+    async fn on_focus_lost(&mut self) -> Result<()> {
+        self.inner.on_focus_lost().await
+    }
+}
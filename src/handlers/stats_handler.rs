@@ -0,0 +1,49 @@
+use crate::stats::UsageStats;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+use std::sync::Arc;
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Handler that counts the words of final transcripts for the usage
+/// statistics before passing them on to the wrapped handler
+pub struct UsageStatsHandler<H> {
+    inner: H,
+    stats: Arc<UsageStats>,
+}
+
+impl<H: TranscriptionHandler> UsageStatsHandler<H> {
+    pub fn new(inner: H, stats: Arc<UsageStats>) -> Self {
+        Self { inner, stats }
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler> TranscriptionHandler for UsageStatsHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.inner.on_interim_result(transcript).await
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.stats
+            .record_words(transcript.text.split_whitespace().count());
+        self.inner.on_final_result(transcript).await
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.inner.on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.inner.on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.inner.on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.inner.on_transcription_error(error).await
+    }
+}
@@ -0,0 +1,107 @@
+use crate::config::SubtitleFormat;
+use crate::subtitles;
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// Assumed speaking time per word for cues without an interim result
+/// marking when the speech began (about 150 words per minute)
+const TIME_PER_WORD: Duration = Duration::from_millis(400);
+
+/// Handler writing the final transcripts of a session as subtitle cues to a
+/// file in `directory` named after the session's start. A cue spans the audio
+/// Deepgram transcribed; for transcripts without timing it starts with the
+/// first interim result and ends with the final one.
+pub struct SubtitleTranscriptionHandler {
+    directory: PathBuf,
+    format: SubtitleFormat,
+    file: Option<File>,
+    started: Instant,
+    started_at: chrono::DateTime<chrono::Local>,
+    cues: usize,
+    cue_start: Option<Duration>,
+    last_end: Duration,
+}
+
+impl SubtitleTranscriptionHandler {
+    pub fn new(directory: PathBuf, format: SubtitleFormat) -> Self {
+        Self {
+            directory,
+            format,
+            file: None,
+            started: Instant::now(),
+            started_at: chrono::Local::now(),
+            cues: 0,
+            cue_start: None,
+            last_end: Duration::ZERO,
+        }
+    }
+
+    /// The session's file, created with the first cue
+    fn file(&mut self) -> Result<&mut File> {
+        if self.file.is_none() {
+            std::fs::create_dir_all(&self.directory)
+                .wrap_err_with(|| format!("Failed to create {}", self.directory.display()))?;
+            let path = self.directory.join(format!(
+                "{}.{}",
+                self.started_at.format("%Y-%m-%d_%H-%M-%S"),
+                subtitles::extension(self.format)
+            ));
+            let mut file = File::create(&path)
+                .wrap_err_with(|| format!("Failed to create {}", path.display()))?;
+            file.write_all(subtitles::header(self.format).as_bytes())?;
+            info!("Writing subtitles to {}", path.display());
+            self.file = Some(file);
+        }
+        Ok(self.file.as_mut().expect("the file was just created"))
+    }
+}
+
+#[async_trait]
+impl TranscriptionHandler for SubtitleTranscriptionHandler {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        if !transcript.text.trim().is_empty() && self.cue_start.is_none() {
+            self.cue_start = Some(self.started.elapsed());
+        }
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        let text = transcript.text.trim();
+        if text.is_empty() {
+            return Ok(());
+        }
+        let cue_start = self.cue_start.take();
+        // Deepgram's offsets start over when the stream reconnects
+        let (start, end) =
+            if transcript.duration > Duration::ZERO && transcript.end() > self.last_end {
+                (transcript.start, transcript.end())
+            } else {
+                let end = self.started.elapsed();
+                let words = text.split_whitespace().count() as u32;
+                let start = cue_start.unwrap_or_else(|| end.saturating_sub(TIME_PER_WORD * words));
+                (start, end)
+            };
+        let start = start.max(self.last_end);
+        self.last_end = end;
+        self.cues += 1;
+
+        let cue = subtitles::cue(self.format, self.cues, start, end, text);
+        let file = self.file()?;
+        file.write_all(cue.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.started = Instant::now();
+        self.started_at = chrono::Local::now();
+        Ok(())
+    }
+}
@@ -1,17 +1,18 @@
-use crate::transcription_utils::TranscriptionResult;
+use crate::transcription_utils::{Transcript, TranscriptionResult};
 use async_trait::async_trait;
 use eyre::Result;
+use tokio_util::sync::CancellationToken;
 
 /// Trait for handling transcription results from the speech-to-text system
 #[async_trait]
 pub trait TranscriptionHandler: Send + Sync {
     /// Called when an interim (temporary) transcription result is received
     /// These results may change as more audio is processed
-    async fn on_interim_result(&mut self, text: String) -> Result<()>;
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()>;
 
     /// Called when a final transcription result is received
     /// These results are stable and will not change
-    async fn on_final_result(&mut self, text: String) -> Result<()>;
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()>;
 
     /// Called when transcription starts (optional hook)
     async fn on_transcription_start(&mut self) -> Result<()> {
@@ -23,37 +24,179 @@ pub trait TranscriptionHandler: Send + Sync {
         Ok(())
     }
 
+    /// Called instead of `on_transcription_end` when the session was cancelled
+    /// and its results should be discarded (optional hook)
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     /// Called when transcription encounters an error (optional hook)
     async fn on_transcription_error(&mut self, error: String) -> Result<()> {
         error!("Transcription error: {}", error);
         Ok(())
     }
+
+    /// Called when the window focus moved away while text of the current
+    /// utterance was shown: forget it without deleting anything, as that
+    /// would now hit the newly focused window (optional hook)
+    async fn on_focus_lost(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Lets the innermost handler be chosen at runtime, e.g. keyboard or log
+#[async_trait]
+impl TranscriptionHandler for Box<dyn TranscriptionHandler> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        (**self).on_interim_result(transcript).await
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        (**self).on_final_result(transcript).await
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        (**self).on_transcription_start().await
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        (**self).on_transcription_end().await
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        (**self).on_transcription_discarded().await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        (**self).on_transcription_error(error).await
+    }
+
+    async fn on_focus_lost(&mut self) -> Result<()> {
+        (**self).on_focus_lost().await
+    }
+}
+
+/// Passes every call to all handlers in order, e.g. the `[output] handlers`.
+/// All of them are called even if one fails; the first error is returned.
+#[async_trait]
+impl TranscriptionHandler for Vec<Box<dyn TranscriptionHandler>> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        let mut result = Ok(());
+        for handler in self.iter_mut() {
+            result = result.and(handler.on_interim_result(transcript.clone()).await);
+        }
+        result
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        let mut result = Ok(());
+        for handler in self.iter_mut() {
+            result = result.and(handler.on_final_result(transcript.clone()).await);
+        }
+        result
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for handler in self.iter_mut() {
+            result = result.and(handler.on_transcription_start().await);
+        }
+        result
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for handler in self.iter_mut() {
+            result = result.and(handler.on_transcription_end().await);
+        }
+        result
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for handler in self.iter_mut() {
+            result = result.and(handler.on_transcription_discarded().await);
+        }
+        result
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        let mut result = Ok(());
+        for handler in self.iter_mut() {
+            result = result.and(handler.on_transcription_error(error.clone()).await);
+        }
+        result
+    }
+
+    async fn on_focus_lost(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for handler in self.iter_mut() {
+            result = result.and(handler.on_focus_lost().await);
+        }
+        result
+    }
 }
 
-/// Process transcription results using a handler
+/// Process transcription results using a handler until the results end or
+/// `discard` is cancelled. On discard, remaining results are dropped and the
+/// handler's `on_transcription_discarded` hook is called.
 pub async fn process_transcription_with_handler<H>(
+    transcription_rx: tokio::sync::mpsc::Receiver<TranscriptionResult>,
+    handler: H,
+    discard: CancellationToken,
+) -> Result<()>
+where
+    H: TranscriptionHandler,
+{
+    process_transcription_until(transcription_rx, handler, discard, CancellationToken::new()).await
+}
+
+/// Like [`process_transcription_with_handler`], but once `finish` is
+/// cancelled the remaining results are dropped and the session ends as if
+/// they had ended, with the handler's `on_transcription_end` hook
+pub async fn process_transcription_until<H>(
     mut transcription_rx: tokio::sync::mpsc::Receiver<TranscriptionResult>,
     mut handler: H,
+    discard: CancellationToken,
+    finish: CancellationToken,
 ) -> Result<()>
 where
     H: TranscriptionHandler,
 {
     handler.on_transcription_start().await?;
 
-    while let Some(result) = transcription_rx.recv().await {
+    loop {
+        let result = tokio::select! {
+            biased;
+            _ = discard.cancelled() => {
+                debug!("Discarding transcription session");
+                return handler.on_transcription_discarded().await;
+            }
+            _ = finish.cancelled() => {
+                debug!("Finishing transcription session, dropping the remaining results");
+                break;
+            }
+            result = transcription_rx.recv() => result,
+        };
+        let Some(result) = result else {
+            break;
+        };
+
         match result {
-            TranscriptionResult::Interim(text) => {
-                if let Err(e) = handler.on_interim_result(text).await {
+            TranscriptionResult::Interim(transcript) => {
+                if let Err(e) = handler.on_interim_result(transcript).await {
                     let error_msg = format!("Error handling interim result: {e}");
                     handler.on_transcription_error(error_msg).await?;
                 }
             }
-            TranscriptionResult::Final(text) => {
-                if let Err(e) = handler.on_final_result(text).await {
+            TranscriptionResult::Final(transcript) => {
+                if let Err(e) = handler.on_final_result(transcript).await {
                     let error_msg = format!("Error handling final result: {e}");
                     handler.on_transcription_error(error_msg).await?;
                 }
             }
+            // Ends single-utterance sessions, which stop recording on their own
+            TranscriptionResult::UtteranceEnd => {}
         }
     }
 
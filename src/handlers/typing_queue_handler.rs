@@ -0,0 +1,188 @@
+use crate::config::{TypingBacklog, TypingConfig};
+use crate::transcription_utils::Transcript;
+use async_trait::async_trait;
+use eyre::Result;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+use super::transcription_handler::TranscriptionHandler;
+
+/// A call queued for the wrapped handler
+enum Item {
+    Interim(Transcript),
+    Final(Transcript),
+    Start,
+    Error(String),
+    FocusLost,
+    /// Reported back once everything queued before it was handled
+    End(oneshot::Sender<Result<()>>),
+    Discarded(oneshot::Sender<Result<()>>),
+}
+
+/// Handler that queues the transcripts for the wrapped (typing) handler,
+/// which works through them on its own task, so that a slow target doesn't
+/// hold up the transcripts behind it. With [`TypingBacklog::Coalesce`]
+/// finals that piled up are typed as one and stale interims are skipped;
+/// with `Keep` everything is typed, and a full queue makes callers wait.
+pub struct TypingQueueHandler<H> {
+    inner: Option<H>,
+    backlog: TypingBacklog,
+    queue_size: usize,
+    items: Option<mpsc::Sender<Item>>,
+    /// Set from a discard until the worker reaches it, to skip the text
+    /// queued before
+    discarding: Arc<AtomicBool>,
+}
+
+impl<H: TranscriptionHandler + 'static> TypingQueueHandler<H> {
+    pub fn new(inner: H, config: &TypingConfig) -> Self {
+        Self {
+            inner: Some(inner),
+            backlog: config.backlog,
+            queue_size: config.queue_size.max(1),
+            items: None,
+            discarding: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Queue `item`, starting the worker on first use
+    async fn push(&mut self, item: Item) -> Result<()> {
+        if self.items.is_none() {
+            let (items_tx, items_rx) = mpsc::channel(self.queue_size);
+            let inner = self.inner.take().expect("the worker starts once");
+            tokio::spawn(work(inner, items_rx, self.backlog, self.discarding.clone()));
+            self.items = Some(items_tx);
+        }
+        let items = self.items.as_ref().expect("the worker was started");
+        items
+            .send(item)
+            .await
+            .map_err(|_| eyre!("The typing queue stopped"))
+    }
+
+    /// Queue a call reporting back, and wait until it was handled
+    async fn push_and_wait(
+        &mut self,
+        item: impl FnOnce(oneshot::Sender<Result<()>>) -> Item,
+    ) -> Result<()> {
+        let (done_tx, done_rx) = oneshot::channel();
+        self.push(item(done_tx)).await?;
+        done_rx
+            .await
+            .map_err(|_| eyre!("The typing queue stopped"))?
+    }
+}
+
+/// Merge the newly received `item` into the waiting ones: interims are
+/// replaced by what follows them, consecutive finals joined
+fn coalesce(waiting: &mut VecDeque<Item>, item: Item) {
+    if matches!(item, Item::Interim(_) | Item::Final(_)) {
+        while matches!(waiting.back(), Some(Item::Interim(_))) {
+            waiting.pop_back();
+        }
+    }
+    match (waiting.back_mut(), item) {
+        (Some(Item::Final(queued)), Item::Final(transcript)) => {
+            debug!("Typing behind, joining final transcripts");
+            queued.append(transcript, " ");
+        }
+        (_, item) => waiting.push_back(item),
+    }
+}
+
+async fn work<H: TranscriptionHandler>(
+    mut inner: H,
+    mut items: mpsc::Receiver<Item>,
+    backlog: TypingBacklog,
+    discarding: Arc<AtomicBool>,
+) {
+    let mut waiting = VecDeque::new();
+    loop {
+        if waiting.is_empty() {
+            match items.recv().await {
+                Some(item) => waiting.push_back(item),
+                None => return,
+            }
+        }
+        if backlog == TypingBacklog::Coalesce {
+            while let Ok(item) = items.try_recv() {
+                coalesce(&mut waiting, item);
+            }
+        }
+        let Some(item) = waiting.pop_front() else {
+            continue;
+        };
+
+        match item {
+            Item::Interim(_) | Item::Final(_) if discarding.load(Ordering::Relaxed) => {}
+            Item::Interim(transcript) => {
+                if let Err(e) = inner.on_interim_result(transcript).await {
+                    let error = format!("Error handling interim result: {e}");
+                    let _ = inner.on_transcription_error(error).await;
+                }
+            }
+            Item::Final(transcript) => {
+                if let Err(e) = inner.on_final_result(transcript).await {
+                    let error = format!("Error handling final result: {e}");
+                    let _ = inner.on_transcription_error(error).await;
+                }
+            }
+            Item::Start => {
+                if let Err(e) = inner.on_transcription_start().await {
+                    warn!("Failed to start typing: {:#}", e);
+                }
+            }
+            Item::Error(error) => {
+                let _ = inner.on_transcription_error(error).await;
+            }
+            Item::FocusLost => {
+                if let Err(e) = inner.on_focus_lost().await {
+                    warn!("Failed to handle the focus change: {:#}", e);
+                }
+            }
+            Item::End(done) => {
+                let _ = done.send(inner.on_transcription_end().await);
+            }
+            Item::Discarded(done) => {
+                discarding.store(false, Ordering::Relaxed);
+                let _ = done.send(inner.on_transcription_discarded().await);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<H: TranscriptionHandler + 'static> TranscriptionHandler for TypingQueueHandler<H> {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.push(Item::Interim(transcript)).await
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.push(Item::Final(transcript)).await
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.push(Item::Start).await
+    }
+
+    /// Waits until the queued transcripts are typed
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.push_and_wait(Item::End).await
+    }
+
+    /// Drops the transcripts not typed yet
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.discarding.store(true, Ordering::Relaxed);
+        self.push_and_wait(Item::Discarded).await
+    }
+
+    async fn on_transcription_error(&mut self, error: String) -> Result<()> {
+        self.push(Item::Error(error)).await
+    }
+
+    async fn on_focus_lost(&mut self) -> Result<()> {
+        self.push(Item::FocusLost).await
+    }
+}
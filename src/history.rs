@@ -0,0 +1,362 @@
+//! Persistent history of final transcripts with their context (language,
+//! target application, duration, confidence), stored in an SQLite database in
+//! the state directory.
+
+use crate::api_keys::ApiKeys;
+use crate::config::HistoryConfig;
+use crate::privacy;
+use crate::transcription_utils::Transcript;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta, TimeZone};
+use eyre::{Result, WrapErr};
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const HISTORY_DATABASE: &str = "history.sqlite3";
+/// The history of earlier versions, imported into the database once
+const LEGACY_HISTORY_FILE: &str = "history.jsonl";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        -- Unix time in milliseconds
+        timestamp INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        language TEXT,
+        app TEXT,
+        duration_secs REAL,
+        confidence REAL,
+        api_key TEXT
+    );
+    CREATE INDEX IF NOT EXISTS entries_timestamp ON entries (timestamp);
+";
+const COLUMNS: &str = "id, timestamp, text, language, app, duration_secs, confidence, api_key";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub timestamp: DateTime<Local>,
+    pub text: String,
+    /// Transcription language of the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// WM_CLASS of the window focused when recording started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// Length of the transcribed speech, when the backend reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    /// Recognition confidence (0-1), when the backend reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    /// Masked Deepgram API key the transcript was received with, when several
+    /// are configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Whether the text contains `query`, ignoring case. Everything matches an
+    /// empty query.
+    pub fn matches(&self, query: &str) -> bool {
+        query.is_empty() || self.text.to_lowercase().contains(&query.to_lowercase())
+    }
+
+    /// Whether the entry was recorded on a day from `since` to `until`, each
+    /// inclusive and unbounded when `None`
+    pub fn recorded_between(&self, since: Option<NaiveDate>, until: Option<NaiveDate>) -> bool {
+        let day = self.timestamp.date_naive();
+        since.is_none_or(|since| day >= since) && until.is_none_or(|until| day <= until)
+    }
+}
+
+/// Context of a recording session stored with its transcripts
+#[derive(Debug, Clone, Default)]
+pub struct HistoryContext {
+    pub language: Option<String>,
+    pub app: Option<String>,
+    /// The keys of the session's transcriber, when it rotates between several.
+    /// Entries record the one their transcript was received with.
+    pub api_keys: Option<Arc<ApiKeys>>,
+}
+
+/// Transcript history shared between the recording pipeline and the UI
+pub struct History {
+    database: Mutex<Connection>,
+    settings: Mutex<HistoryConfig>,
+}
+
+impl History {
+    /// Open the history database in the state directory, applying the
+    /// retention settings. Without a usable database the history is kept in
+    /// memory until the app exits.
+    pub fn load(settings: &HistoryConfig) -> Self {
+        crate::storage::state_dir()
+            .and_then(|dir| Self::open(&dir, settings))
+            .unwrap_or_else(|e| {
+                warn!("Transcript history will not be persisted: {:#}", e);
+                let database =
+                    Connection::open_in_memory().expect("SQLite can open an in-memory database");
+                database
+                    .execute_batch(SCHEMA)
+                    .expect("the schema is valid SQL");
+                Self::new(database, settings)
+            })
+    }
+
+    /// Open the history database in `dir` instead of the state directory,
+    /// creating it if needed and importing the JSON lines history of earlier
+    /// versions
+    pub fn open(dir: &Path, settings: &HistoryConfig) -> Result<Self> {
+        std::fs::create_dir_all(dir).wrap_err("Failed to create state directory")?;
+        let path = dir.join(HISTORY_DATABASE);
+        let mut database = Connection::open(&path)
+            .wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+        database
+            .execute_batch(SCHEMA)
+            .wrap_err_with(|| format!("Failed to set up {}", path.display()))?;
+
+        let legacy = dir.join(LEGACY_HISTORY_FILE);
+        if legacy.exists() {
+            if let Err(e) = import_legacy(&mut database, &legacy) {
+                warn!("Failed to import {}: {:#}", legacy.display(), e);
+            }
+        }
+        Ok(Self::new(database, settings))
+    }
+
+    fn new(database: Connection, settings: &HistoryConfig) -> Self {
+        let history = Self {
+            database: Mutex::new(database),
+            settings: Mutex::new(settings.clone()),
+        };
+        if let Err(e) = history.apply_retention() {
+            warn!("Failed to prune the transcript history: {:#}", e);
+        }
+        history
+    }
+
+    /// Apply changed settings, pruning entries beyond the new limits
+    pub fn configure(&self, settings: &HistoryConfig) -> Result<()> {
+        *self.settings.lock().unwrap() = settings.clone();
+        self.apply_retention()
+    }
+
+    /// Whether transcripts are recorded: the history is enabled and privacy
+    /// mode is off
+    pub fn is_enabled(&self) -> bool {
+        self.settings.lock().unwrap().enabled && !privacy::is_enabled()
+    }
+
+    /// Record a final transcript in the history, unless it is disabled. The
+    /// language Deepgram detected takes precedence over the session's.
+    pub fn add(&self, transcript: &Transcript, context: &HistoryContext) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let language = transcript
+            .language
+            .clone()
+            .or_else(|| context.language.clone());
+        let duration_secs =
+            Some(transcript.duration.as_secs_f64()).filter(|&duration| duration > 0.0);
+        let api_key = context
+            .api_keys
+            .as_ref()
+            .zip(transcript.api_key)
+            .filter(|(api_keys, index)| *index < api_keys.keys().len())
+            .map(|(api_keys, index)| api_keys.label(index));
+        self.database
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO entries (timestamp, text, language, app, duration_secs, confidence, api_key)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    Local::now().timestamp_millis(),
+                    transcript.text,
+                    language,
+                    context.app,
+                    duration_secs,
+                    transcript.confidence,
+                    api_key,
+                ],
+            )
+            .wrap_err("Failed to add the transcript to the history")?;
+
+        if self.settings.lock().unwrap().max_entries.is_some() {
+            return self.apply_retention();
+        }
+        Ok(())
+    }
+
+    /// All entries, newest first
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.search("", None, None)
+    }
+
+    /// Entries matching `query` recorded from `since` to `until`, newest
+    /// first, see [`HistoryEntry::matches`] and [`HistoryEntry::recorded_between`]
+    pub fn search(
+        &self,
+        query: &str,
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+    ) -> Vec<HistoryEntry> {
+        let from = since.map_or(i64::MIN, day_start);
+        let to = until
+            .and_then(|until| until.succ_opt())
+            .map_or(i64::MAX, day_start);
+        let entries = self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM entries WHERE timestamp >= ?1 AND timestamp < ?2 ORDER BY id DESC"
+            ),
+            params![from, to],
+        );
+        // SQLite only ignores the case of ASCII letters, so the text is matched here
+        entries
+            .into_iter()
+            .filter(|entry| entry.matches(query))
+            .collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<HistoryEntry> {
+        self.query(
+            &format!("SELECT {COLUMNS} FROM entries WHERE id = ?1"),
+            params![sql_id(id)],
+        )
+        .pop()
+    }
+
+    /// Remove an entry
+    pub fn delete(&self, id: u64) -> Result<()> {
+        self.database
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM entries WHERE id = ?1", params![sql_id(id)])
+            .wrap_err("Failed to delete the history entry")?;
+        Ok(())
+    }
+
+    /// Entries of a query selecting [`COLUMNS`]. Failures are logged and
+    /// yield no entries.
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> Vec<HistoryEntry> {
+        let database = self.database.lock().unwrap();
+        let entries = database.prepare_cached(sql).and_then(|mut statement| {
+            statement
+                .query_map(params, entry_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        });
+        entries.unwrap_or_else(|e| {
+            warn!("Failed to read the transcript history: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// Drop entries older than `retention_days` and beyond `max_entries`
+    fn apply_retention(&self) -> Result<()> {
+        let settings = self.settings.lock().unwrap().clone();
+        let database = self.database.lock().unwrap();
+        let mut removed = 0;
+        // A retention too long to compute a cutoff for keeps everything
+        if let Some(cutoff) = settings.retention_days.and_then(retention_cutoff) {
+            removed += database
+                .execute(
+                    "DELETE FROM entries WHERE timestamp < ?1",
+                    params![cutoff.timestamp_millis()],
+                )
+                .wrap_err("Failed to remove old history entries")?;
+        }
+        if let Some(max_entries) = settings.max_entries {
+            removed += database
+                .execute(
+                    "DELETE FROM entries WHERE id NOT IN
+                     (SELECT id FROM entries ORDER BY id DESC LIMIT ?1)",
+                    params![i64::try_from(max_entries).unwrap_or(i64::MAX)],
+                )
+                .wrap_err("Failed to remove excess history entries")?;
+        }
+        if removed > 0 {
+            debug!("Removed {} old history entries", removed);
+        }
+        Ok(())
+    }
+}
+
+fn entry_from_row(row: &Row<'_>) -> rusqlite::Result<HistoryEntry> {
+    let timestamp: i64 = row.get("timestamp")?;
+    Ok(HistoryEntry {
+        id: u64::try_from(row.get::<_, i64>("id")?).unwrap_or_default(),
+        timestamp: Local
+            .timestamp_millis_opt(timestamp)
+            .single()
+            .unwrap_or_default(),
+        text: row.get("text")?,
+        language: row.get("language")?,
+        app: row.get("app")?,
+        duration_secs: row.get("duration_secs")?,
+        confidence: row.get("confidence")?,
+        api_key: row.get("api_key")?,
+    })
+}
+
+/// Copy the entries of a `history.jsonl` into the database, renaming the
+/// file once done. Unreadable lines are skipped.
+fn import_legacy(database: &mut Connection, path: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(path)?;
+    let transaction = database.transaction()?;
+    let mut imported = 0;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let entry: HistoryEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Skipping invalid history entry: {}", e);
+                continue;
+            }
+        };
+        imported += transaction.execute(
+            "INSERT OR IGNORE INTO entries (id, timestamp, text, language, app, duration_secs, confidence, api_key)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                sql_id(entry.id),
+                entry.timestamp.timestamp_millis(),
+                entry.text,
+                entry.language,
+                entry.app,
+                entry.duration_secs,
+                entry.confidence,
+                entry.api_key,
+            ],
+        )?;
+    }
+    transaction.commit()?;
+    std::fs::rename(path, path.with_extension("jsonl.imported"))?;
+    info!(
+        "Imported {} entries of {} into the history database",
+        imported,
+        path.display()
+    );
+    Ok(())
+}
+
+/// SQLite integers are signed, IDs beyond them match no entry
+fn sql_id(id: u64) -> i64 {
+    i64::try_from(id).unwrap_or(-1)
+}
+
+/// Unix time in milliseconds of the local midnight starting `day`
+fn day_start(day: NaiveDate) -> i64 {
+    let midnight = day.and_time(NaiveTime::MIN);
+    midnight.and_local_timezone(Local).earliest().map_or_else(
+        || midnight.and_utc().timestamp_millis(),
+        |start| start.timestamp_millis(),
+    )
+}
+
+/// The time from which on entries are kept, `None` if `days` reaches
+/// beyond the representable dates
+fn retention_cutoff(days: u64) -> Option<DateTime<Local>> {
+    let retention = TimeDelta::try_days(i64::try_from(days).ok()?)?;
+    Local::now().checked_sub_signed(retention)
+}
@@ -0,0 +1,108 @@
+//! `history export`: the transcript history as JSON, CSV or Markdown, to
+//! archive it or analyze it elsewhere.
+
+use crate::history::HistoryEntry;
+use chrono::NaiveDate;
+use eyre::Result;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// An array of the entries as stored
+    Json,
+    /// A row per entry with a header row
+    Csv,
+    /// A section per day with a list item per entry
+    Markdown,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            _ => Err(format!(
+                "unknown format '{value}', expected json, csv or md"
+            )),
+        }
+    }
+}
+
+/// The entries from `since` on (all without), oldest first
+pub fn select(entries: Vec<HistoryEntry>, since: Option<NaiveDate>) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = entries
+        .into_iter()
+        .filter(|entry| entry.recorded_between(since, None))
+        .collect();
+    entries.sort_by_key(|entry| (entry.timestamp, entry.id));
+    entries
+}
+
+pub fn export(entries: &[HistoryEntry], format: ExportFormat) -> Result<String> {
+    Ok(match format {
+        ExportFormat::Json => serde_json::to_string_pretty(entries)? + "\n",
+        ExportFormat::Csv => csv(entries),
+        ExportFormat::Markdown => markdown(entries),
+    })
+}
+
+fn csv(entries: &[HistoryEntry]) -> String {
+    let mut csv = String::from("id,timestamp,text,language,app,duration_secs,confidence\n");
+    for entry in entries {
+        let fields = [
+            entry.id.to_string(),
+            entry.timestamp.to_rfc3339(),
+            entry.text.clone(),
+            entry.language.clone().unwrap_or_default(),
+            entry.app.clone().unwrap_or_default(),
+            entry
+                .duration_secs
+                .map(|secs| secs.to_string())
+                .unwrap_or_default(),
+            entry
+                .confidence
+                .map(|confidence| confidence.to_string())
+                .unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quoted if it contains a separator, quote or line break (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn markdown(entries: &[HistoryEntry]) -> String {
+    let mut markdown = String::from("# Transcription history\n");
+    let mut day = None;
+    for entry in entries {
+        let date = entry.timestamp.date_naive();
+        if day != Some(date) {
+            day = Some(date);
+            let _ = write!(markdown, "\n## {}\n\n", date.format("%Y-%m-%d"));
+        }
+        let text = entry.text.replace('\n', " ");
+        let _ = write!(
+            markdown,
+            "- **{}** {}",
+            entry.timestamp.format("%H:%M"),
+            text
+        );
+        if let Some(app) = &entry.app {
+            let _ = write!(markdown, " _({app})_");
+        }
+        markdown.push('\n');
+    }
+    markdown
+}
@@ -0,0 +1,136 @@
+//! Transcript history window, shown with zenity so no GUI toolkit is needed.
+
+use crate::{
+    desktop,
+    history::{History, HistoryEntry},
+};
+use eyre::{Result, WrapErr};
+use std::process::{Command, Output};
+
+const TITLE: &str = "Voice Input History";
+const SEARCH_BUTTON: &str = "Search";
+const DELETE_BUTTON: &str = "Delete";
+
+enum EntryAction {
+    Copy,
+    Delete,
+    Back,
+}
+
+/// Show the history list until the user closes it. Blocks the calling thread.
+pub fn show_history_window(history: &History) -> Result<()> {
+    let mut query = String::new();
+
+    loop {
+        let entries = history.search(&query, None, None);
+
+        let output = run_zenity(&list_args(&entries, &query))?;
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        if !output.status.success() {
+            // Extra buttons report their label, closing reports nothing
+            if stdout == SEARCH_BUTTON {
+                query = ask_search_query(&query)?;
+                continue;
+            }
+            return Ok(());
+        }
+
+        let Some(entry) = stdout.parse().ok().and_then(|id| history.get(id)) else {
+            continue;
+        };
+
+        match ask_entry_action(&entry)? {
+            EntryAction::Copy => {
+                desktop::copy_to_clipboard(&entry.text)?;
+                return Ok(());
+            }
+            EntryAction::Delete => {
+                info!("Deleting history entry {}", entry.id);
+                history.delete(entry.id)?;
+            }
+            EntryAction::Back => {}
+        }
+    }
+}
+
+fn list_args(entries: &[HistoryEntry], query: &str) -> Vec<String> {
+    let text = if query.is_empty() {
+        format!("{} transcripts", entries.len())
+    } else {
+        format!("{} transcripts matching \"{}\"", entries.len(), query)
+    };
+
+    let mut args: Vec<String> = [
+        "--list",
+        "--title",
+        TITLE,
+        "--width=900",
+        "--height=500",
+        "--no-markup",
+        "--column=ID",
+        "--column=Time",
+        "--column=Transcript",
+        "--hide-column=1",
+        "--print-column=1",
+        "--ok-label=Open",
+        "--cancel-label=Close",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+    args.push(format!("--text={text}"));
+    args.push(format!("--extra-button={SEARCH_BUTTON}"));
+
+    for entry in entries {
+        args.push(entry.id.to_string());
+        args.push(entry.timestamp.format("%Y-%m-%d %H:%M").to_string());
+        args.push(entry.text.replace('\n', " "));
+    }
+    args
+}
+
+fn ask_search_query(current: &str) -> Result<String> {
+    let output = run_zenity(&[
+        "--entry".to_string(),
+        "--title".to_string(),
+        TITLE.to_string(),
+        "--text=Show transcripts containing:".to_string(),
+        format!("--entry-text={current}"),
+    ])?;
+
+    // Cancelling the search keeps the current filter
+    if !output.status.success() {
+        return Ok(current.to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn ask_entry_action(entry: &HistoryEntry) -> Result<EntryAction> {
+    let output = run_zenity(&[
+        "--question".to_string(),
+        "--title".to_string(),
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+        "--no-markup".to_string(),
+        "--width=600".to_string(),
+        format!("--text={}", entry.text),
+        "--ok-label=Copy".to_string(),
+        "--cancel-label=Back".to_string(),
+        format!("--extra-button={DELETE_BUTTON}"),
+    ])?;
+
+    if output.status.success() {
+        return Ok(EntryAction::Copy);
+    }
+    if String::from_utf8_lossy(&output.stdout).trim() == DELETE_BUTTON {
+        return Ok(EntryAction::Delete);
+    }
+    Ok(EntryAction::Back)
+}
+
+fn run_zenity(args: &[String]) -> Result<Output> {
+    Command::new("zenity")
+        .args(args)
+        .output()
+        .wrap_err("Failed to run zenity, is it installed?")
+}
@@ -1,16 +1,28 @@
-use crate::{config::Config, state::AppState};
+use crate::{
+    config::{Config, HotkeyAction, HotkeyBackend, HotkeyBinding, HotkeyMode, KeyCombination},
+    hotkey_portal::PortalShortcuts,
+    keyboard, notifications, portal, privacy,
+    state::AppState,
+};
 use eyre::{Result, WrapErr};
 use global_hotkey::{
     hotkey::{Code, HotKey, Modifiers},
     GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState,
 };
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
 use tokio_util::sync::CancellationToken;
 
 /// Parse hotkey configuration into a HotKey without registering it
 pub fn parse_hotkey(config: &Config) -> Result<HotKey> {
+    parse_key_combination(&config.hotkey.modifiers, &config.hotkey.key)
+}
+
+/// Parse a modifier list and key name (as used in the config) into a HotKey
+pub fn parse_key_combination(modifier_names: &[String], key: &str) -> Result<HotKey> {
     let mut modifiers = Modifiers::empty();
 
-    for modifier in &config.hotkey.modifiers {
+    for modifier in modifier_names {
         match modifier.to_lowercase().as_str() {
             "super" | "meta" | "cmd" => modifiers |= Modifiers::SUPER,
             "ctrl" | "control" => modifiers |= Modifiers::CONTROL,
@@ -20,72 +32,220 @@ pub fn parse_hotkey(config: &Config) -> Result<HotKey> {
         }
     }
 
-    let code = match config.hotkey.key.to_lowercase().as_str() {
-        "a" => Code::KeyA,
-        "b" => Code::KeyB,
-        "c" => Code::KeyC,
-        "d" => Code::KeyD,
-        "e" => Code::KeyE,
-        "f" => Code::KeyF,
-        "g" => Code::KeyG,
-        "h" => Code::KeyH,
-        "i" => Code::KeyI,
-        "j" => Code::KeyJ,
-        "k" => Code::KeyK,
-        "l" => Code::KeyL,
-        "m" => Code::KeyM,
-        "n" => Code::KeyN,
-        "o" => Code::KeyO,
-        "p" => Code::KeyP,
-        "q" => Code::KeyQ,
-        "r" => Code::KeyR,
-        "s" => Code::KeyS,
-        "t" => Code::KeyT,
-        "u" => Code::KeyU,
-        "v" => Code::KeyV,
-        "w" => Code::KeyW,
-        "x" => Code::KeyX,
-        "y" => Code::KeyY,
-        "z" => Code::KeyZ,
-        "space" => Code::Space,
-        "f1" => Code::F1,
-        "f2" => Code::F2,
-        "f3" => Code::F3,
-        "f4" => Code::F4,
-        "f5" => Code::F5,
-        "f6" => Code::F6,
-        "f7" => Code::F7,
-        "f8" => Code::F8,
-        "f9" => Code::F9,
-        "f10" => Code::F10,
-        "f11" => Code::F11,
-        "f12" => Code::F12,
-        _ => bail!("Unknown key: {}", config.hotkey.key),
-    };
+    let code = parse_key(key)?;
 
     let hotkey = HotKey::new(Some(modifiers), code);
     Ok(hotkey)
 }
 
-pub fn setup_hotkeys(config: &Config) -> Result<(GlobalHotKeyManager, HotKey)> {
+/// Parse a key name into a key code. Accepts the names of the global-hotkey
+/// crate (case-insensitive): letters, digits, punctuation ("comma", "."),
+/// "f1".."f24", "insert", "home", "end", "pageup", arrows ("up", "arrowleft"),
+/// numpad keys ("numpad1", "numadd"), "escape" and media keys ("mediaplaypause").
+pub fn parse_key(key: &str) -> Result<Code> {
+    // A single token is parsed as a plain key, '+' would introduce modifiers
+    let trimmed = key.trim();
+    if trimmed.is_empty() || trimmed.contains('+') {
+        bail!("Unknown key: {}", key);
+    }
+
+    trimmed
+        .parse::<HotKey>()
+        .map(|hotkey| hotkey.key)
+        .map_err(|_| eyre!("Unknown key: {}", key))
+}
+
+/// Registered X11 hotkeys and the action each of them triggers, keyed by hotkey id
+pub type HotkeyBindings = HashMap<u32, (HotKey, HotkeyAction)>;
+
+/// Hotkeys registered with one of the supported backends
+pub enum RegisteredHotkeys {
+    /// Keys grabbed through X11 by the global-hotkey crate
+    GlobalHotkey {
+        manager: GlobalHotKeyManager,
+        bindings: HotkeyBindings,
+    },
+    /// Shortcuts bound through the XDG GlobalShortcuts portal
+    Portal(PortalShortcuts),
+    /// Keys are handled by a GNOME custom shortcut calling the D-Bus service
+    GnomeShortcut,
+}
+
+/// The main hotkey followed by all additional bindings from the config
+/// Bind through the portal on a blocking thread: the request waits until the
+/// user confirms the shortcuts
+async fn bind_portal(bindings: Vec<HotkeyBinding>) -> Result<PortalShortcuts> {
+    tokio::task::spawn_blocking(move || PortalShortcuts::bind(&bindings)).await?
+}
+
+fn configured_bindings(config: &Config) -> Vec<HotkeyBinding> {
+    let main_action = match config.hotkey.mode {
+        HotkeyMode::Toggle => HotkeyAction::Toggle,
+        HotkeyMode::PushToTalk => HotkeyAction::PushToTalk,
+    };
+    let main_binding = HotkeyBinding {
+        modifiers: config.hotkey.modifiers.clone(),
+        key: config.hotkey.key.clone(),
+        action: main_action,
+    };
+
+    std::iter::once(main_binding)
+        .chain(config.hotkey.bindings.iter().cloned())
+        .collect()
+}
+
+pub fn is_wayland_session() -> bool {
+    std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland")
+}
+
+/// Register the main hotkey and all additional bindings from the config with
+/// the configured (or, in auto mode, detected) backend
+pub async fn setup_hotkeys(config: &Config) -> Result<RegisteredHotkeys> {
+    let bindings = configured_bindings(config);
+
+    // Validate all bindings up front, whichever backend ends up being used
+    let mut hotkeys = Vec::with_capacity(bindings.len());
+    for binding in &bindings {
+        let combination = describe(binding);
+        let hotkey = parse_key_combination(&binding.modifiers, &binding.key)
+            .wrap_err_with(|| format!("Invalid hotkey {combination}"))?;
+        if hotkeys
+            .iter()
+            .any(|(existing, _): &(HotKey, _)| existing.id() == hotkey.id())
+        {
+            bail!("Hotkey {combination} is bound more than once");
+        }
+        hotkeys.push((hotkey, binding));
+    }
+
+    if portal::is_portal_only() {
+        if !matches!(
+            config.hotkey.backend,
+            HotkeyBackend::Auto | HotkeyBackend::Portal
+        ) {
+            warn!(
+                "hotkey.backend = {:?} is not available in portal-only mode, using the portal",
+                config.hotkey.backend
+            );
+        }
+        return bind_portal(bindings)
+            .await
+            .map(RegisteredHotkeys::Portal)
+            .wrap_err("Failed to bind shortcuts through the portal");
+    }
+
+    let use_portal = match config.hotkey.backend {
+        HotkeyBackend::Auto => is_wayland_session(),
+        HotkeyBackend::X11 => false,
+        HotkeyBackend::Portal => true,
+        HotkeyBackend::Gnome => {
+            info!("Hotkey handled by the GNOME custom shortcut, not grabbing keys");
+            if !config.hotkey.bindings.is_empty() {
+                warn!("Additional hotkey bindings are ignored with the gnome backend");
+            }
+            return Ok(RegisteredHotkeys::GnomeShortcut);
+        }
+    };
+    if use_portal {
+        match bind_portal(bindings.clone()).await {
+            Ok(shortcuts) => return Ok(RegisteredHotkeys::Portal(shortcuts)),
+            Err(e) if config.hotkey.backend == HotkeyBackend::Auto => {
+                warn!(
+                    "GlobalShortcuts portal unavailable, falling back to X11 hotkeys: {:#}",
+                    e
+                );
+            }
+            Err(e) => return Err(e.wrap_err("Failed to bind shortcuts through the portal")),
+        }
+    }
+
+    // Alternatives for the main hotkey in case another application owns it
+    let mut fallbacks = Vec::with_capacity(config.hotkey.fallbacks.len());
+    for fallback in &config.hotkey.fallbacks {
+        let hotkey = parse_key_combination(&fallback.modifiers, &fallback.key)
+            .wrap_err_with(|| format!("Invalid fallback hotkey {}", describe(fallback)))?;
+        fallbacks.push((hotkey, describe(fallback)));
+    }
+
     let manager = GlobalHotKeyManager::new().wrap_err("Failed to create hotkey manager")?;
-    let hotkey = parse_hotkey(config)?;
+    let mut registered = HotkeyBindings::new();
+    for (index, (hotkey, binding)) in hotkeys.into_iter().enumerate() {
+        let combination = describe(binding);
+        let mut candidates = vec![(hotkey, combination.clone())];
+        if index == 0 {
+            candidates.extend(fallbacks.iter().cloned());
+        }
+
+        match register_first_available(&manager, &candidates) {
+            Some((hotkey, used)) => {
+                if used != combination {
+                    warn!("Hotkey {} is taken, using {} instead", combination, used);
+                    notify_hotkey_problem(format!(
+                        "{combination} is used by another application, using {used} instead."
+                    ));
+                }
+                info!("Registered hotkey: {} ({:?})", used, binding.action);
+                registered.insert(hotkey.id(), (hotkey, binding.action));
+            }
+            None => {
+                error!(
+                    "Could not register hotkey {} ({:?}), it is probably used by another application",
+                    combination, binding.action
+                );
+                notify_hotkey_problem(format!(
+                    "{combination} is used by another application. Choose a different key \
+                     or add hotkey.fallbacks to the config; the tray menu still works."
+                ));
+            }
+        }
+    }
 
-    manager
-        .register(hotkey)
-        .wrap_err("Failed to register hotkey")?;
+    Ok(RegisteredHotkeys::GlobalHotkey {
+        manager,
+        bindings: registered,
+    })
+}
 
-    info!(
-        "Registered hotkey: {} + {}",
-        config.hotkey.modifiers.join("+"),
-        config.hotkey.key
-    );
+/// Register the first candidate that isn't grabbed by another application
+fn register_first_available(
+    manager: &GlobalHotKeyManager,
+    candidates: &[(HotKey, String)],
+) -> Option<(HotKey, String)> {
+    for (hotkey, combination) in candidates {
+        match manager.register(*hotkey) {
+            Ok(()) => return Some((*hotkey, combination.clone())),
+            Err(global_hotkey::Error::AlreadyRegistered(_)) => {
+                warn!("Hotkey {} is already grabbed", combination);
+            }
+            Err(e) => warn!("Failed to register hotkey {}: {}", combination, e),
+        }
+    }
+    None
+}
 
-    Ok((manager, hotkey))
+/// Human readable form of a key combination, e.g. "super+shift + v"
+fn describe(combination: &impl KeyCombination) -> String {
+    format!(
+        "{} + {}",
+        combination.modifiers().join("+"),
+        combination.key()
+    )
 }
 
+/// Tell the user about hotkey problems without blocking startup
+fn notify_hotkey_problem(body: String) {
+    std::thread::spawn(move || {
+        if let Err(e) = notifications::notify("Voice Input hotkey", &body) {
+            warn!("Failed to show hotkey notification: {:#}", e);
+        }
+    });
+}
+
+/// Listen for hotkey events and dispatch their actions. The listener owns the
+/// registered hotkeys and releases them when the shutdown token is cancelled.
 pub fn setup_hotkey_handlers(
     app_state: AppState,
+    hotkeys: RegisteredHotkeys,
     shutdown_token: &CancellationToken,
 ) -> (tokio::task::JoinHandle<()>, tokio::task::JoinHandle<()>) {
     let (hotkey_tx, mut hotkey_rx) = tokio::sync::mpsc::channel(10);
@@ -93,35 +253,46 @@ pub fn setup_hotkey_handlers(
 
     let hotkey_handle = tokio::task::spawn_blocking(move || {
         let runtime = tokio::runtime::Handle::current();
+        let send = move |action: HotkeyAction, state: HotKeyState| {
+            debug!("Hotkey {:?} {:?}", action, state);
+            let tx = hotkey_tx.clone();
+            runtime.spawn(async move {
+                let _ = tx.send((action, state)).await;
+            });
+        };
 
-        loop {
-            if hotkey_shutdown_token.is_cancelled() {
-                info!("Hotkey handler shutting down");
-                break;
-            }
+        match hotkeys {
+            RegisteredHotkeys::GlobalHotkey { manager, bindings } => {
+                listen_global_hotkeys(&bindings, send, &hotkey_shutdown_token);
 
-            match GlobalHotKeyEvent::receiver().recv_timeout(std::time::Duration::from_millis(100))
-            {
-                Ok(event) => {
-                    if event.state == HotKeyState::Pressed {
-                        info!("Hotkey pressed");
-                        let tx = hotkey_tx.clone();
-                        runtime.spawn(async move {
-                            let _ = tx.send(()).await;
-                        });
+                for (hotkey, action) in bindings.values() {
+                    if let Err(e) = manager.unregister(*hotkey) {
+                        warn!("Failed to unregister {:?} hotkey: {}", action, e);
+                    } else {
+                        info!("{:?} hotkey unregistered successfully", action);
                     }
                 }
-                Err(_) => continue,
             }
+            RegisteredHotkeys::Portal(shortcuts) => {
+                if let Err(e) = shortcuts.listen(send, hotkey_shutdown_token) {
+                    error!("GlobalShortcuts portal listener failed: {:#}", e);
+                }
+            }
+            RegisteredHotkeys::GnomeShortcut => {}
         }
+        info!("Hotkey handler shutting down");
     });
 
     let hotkey_rx_shutdown_token = shutdown_token.child_token();
     let hotkey_rx_handle = tokio::spawn(async move {
         loop {
             tokio::select! {
-                Some(()) = hotkey_rx.recv() => {
-                    crate::toggle_recording(app_state.clone()).await;
+                Some((action, state)) = hotkey_rx.recv() => {
+                    if !app_state.enabled.load(Ordering::Relaxed) {
+                        info!("Voice input is paused, ignoring hotkey");
+                        continue;
+                    }
+                    handle_hotkey_action(&app_state, action, state).await;
                 }
                 _ = hotkey_rx_shutdown_token.cancelled() => {
                     info!("Hotkey receiver shutting down");
@@ -133,3 +304,87 @@ pub fn setup_hotkey_handlers(
 
     (hotkey_handle, hotkey_rx_handle)
 }
+
+fn listen_global_hotkeys(
+    bindings: &HotkeyBindings,
+    mut send: impl FnMut(HotkeyAction, HotKeyState),
+    shutdown_token: &CancellationToken,
+) {
+    while !shutdown_token.is_cancelled() {
+        match GlobalHotKeyEvent::receiver().recv_timeout(std::time::Duration::from_millis(100)) {
+            Ok(event) => match bindings.get(&event.id) {
+                Some(&(_, action)) => send(action, event.state),
+                None => debug!("Ignoring event for unknown hotkey {}", event.id),
+            },
+            Err(_) => continue,
+        }
+    }
+}
+
+async fn handle_hotkey_action(app_state: &AppState, action: HotkeyAction, state: HotKeyState) {
+    match (action, state) {
+        (HotkeyAction::PushToTalk, HotKeyState::Pressed) => {
+            info!("Push-to-talk hotkey pressed");
+            crate::start_recording(app_state.clone());
+        }
+        (HotkeyAction::PushToTalk, HotKeyState::Released) => {
+            info!("Push-to-talk hotkey released");
+            crate::stop_recording(app_state);
+        }
+        // All other actions trigger on key press only
+        (_, HotKeyState::Released) => {}
+        (HotkeyAction::Toggle, HotKeyState::Pressed) => {
+            info!("Hotkey pressed");
+            crate::toggle_recording(app_state.clone()).await;
+        }
+        (HotkeyAction::Cancel, HotKeyState::Pressed) => {
+            info!("Cancel hotkey pressed");
+            crate::cancel_recording(app_state);
+        }
+        (HotkeyAction::Mute, HotKeyState::Pressed) => match app_state.toggle_muted() {
+            Some(true) => info!("Microphone muted, session kept open"),
+            Some(false) => info!("Microphone unmuted"),
+            None => info!("Not recording, nothing to mute"),
+        },
+        (HotkeyAction::RetypeLast, HotKeyState::Pressed) => {
+            let Some(text) = app_state
+                .recent_transcripts
+                .read()
+                .unwrap()
+                .front()
+                .cloned()
+            else {
+                info!("No transcript to retype yet");
+                return;
+            };
+            if app_state.dry_run {
+                info!("Dry run, would retype: '{}'", privacy::loggable(&text));
+                return;
+            }
+            info!("Retyping last transcript");
+            let result = tokio::task::spawn_blocking(move || keyboard::type_text(&text)).await;
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!("Failed to retype last transcript: {}", e),
+                Err(e) => error!("Retype task failed: {}", e),
+            }
+        }
+        (HotkeyAction::SwitchLanguage, HotKeyState::Pressed) => {
+            let body = match app_state.switch_to_next_language() {
+                Some(language) => {
+                    info!("Switched transcription language to {}", language);
+                    format!("Transcription language: {language}")
+                }
+                None => {
+                    warn!("No languages configured in transcription.languages");
+                    "No languages configured in transcription.languages".to_string()
+                }
+            };
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = notifications::notify("Voice Input", &body) {
+                    warn!("Failed to show language notification: {:#}", e);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Hotkey backend using the XDG GlobalShortcuts portal, which works on
+//! Wayland sessions where grabbing keys through X11 is not possible.
+
+use crate::config::{HotkeyAction, HotkeyBinding};
+use crate::portal;
+use dbus::arg::{PropMap, Variant};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use eyre::{Result, WrapErr};
+use global_hotkey::{hotkey::Code, HotKeyState};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const SHORTCUTS_INTERFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+/// Shortcuts bound through the portal, waiting to be listened to
+pub struct PortalShortcuts {
+    conn: Connection,
+    session: dbus::Path<'static>,
+    actions: HashMap<String, HotkeyAction>,
+}
+
+impl PortalShortcuts {
+    /// Create a portal session and bind all given hotkeys to it
+    pub fn bind(bindings: &[HotkeyBinding]) -> Result<Self> {
+        let conn = Connection::new_session().wrap_err("Failed to connect to session bus")?;
+
+        let session = portal::create_session(&conn, SHORTCUTS_INTERFACE)?;
+
+        let mut actions = HashMap::new();
+        let shortcuts: Vec<(String, PropMap)> = bindings
+            .iter()
+            .enumerate()
+            .map(|(index, binding)| {
+                let id = format!("voice-input-{index}");
+                actions.insert(id.clone(), binding.action);
+
+                let mut properties = PropMap::new();
+                properties.insert(
+                    "description".to_string(),
+                    Variant(Box::new(describe_action(binding.action).to_string())),
+                );
+                properties.insert(
+                    "preferred_trigger".to_string(),
+                    Variant(Box::new(preferred_trigger(binding))),
+                );
+                (id, properties)
+            })
+            .collect();
+
+        let bind_session = session.clone();
+        portal::request(&conn, "bind", move |proxy, options| {
+            let (request,): (dbus::Path<'static>,) = proxy
+                .method_call(
+                    SHORTCUTS_INTERFACE,
+                    "BindShortcuts",
+                    (bind_session, shortcuts, "", options),
+                )
+                .wrap_err("Failed to bind shortcuts")?;
+            Ok(request)
+        })?;
+
+        info!(
+            "Bound {} shortcut(s) through the GlobalShortcuts portal",
+            actions.len()
+        );
+        Ok(Self {
+            conn,
+            session,
+            actions,
+        })
+    }
+
+    /// Forward shortcut activations until shutdown, then close the session
+    pub fn listen(
+        self,
+        mut on_event: impl FnMut(HotkeyAction, HotKeyState) + Send + 'static,
+        shutdown_token: CancellationToken,
+    ) -> Result<()> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        for (member, state) in [
+            ("Activated", HotKeyState::Pressed),
+            ("Deactivated", HotKeyState::Released),
+        ] {
+            let events = events.clone();
+            let session = self.session.clone();
+            self.conn.add_match(
+                MatchRule::new_signal(SHORTCUTS_INTERFACE, member),
+                move |(signal_session, id, _timestamp, _options): (
+                    dbus::Path<'static>,
+                    String,
+                    u64,
+                    PropMap,
+                ),
+                      _,
+                      _| {
+                    if signal_session == session {
+                        events.lock().unwrap().push((id, state));
+                    }
+                    true
+                },
+            )?;
+        }
+
+        while !shutdown_token.is_cancelled() {
+            self.conn.process(Duration::from_millis(100))?;
+
+            let pending: Vec<_> = std::mem::take(&mut *events.lock().unwrap());
+            for (id, state) in pending {
+                match self.actions.get(&id) {
+                    Some(&action) => on_event(action, state),
+                    None => debug!("Ignoring unknown portal shortcut {}", id),
+                }
+            }
+        }
+
+        portal::close_session(&self.conn, &self.session);
+        Ok(())
+    }
+}
+
+/// Trigger in the format of the XDG shortcuts specification, e.g. `LOGO+v`
+fn preferred_trigger(binding: &HotkeyBinding) -> String {
+    let modifiers =
+        binding
+            .modifiers
+            .iter()
+            .map(|modifier| match modifier.to_lowercase().as_str() {
+                "super" | "meta" | "cmd" => "LOGO".to_string(),
+                "ctrl" | "control" => "CTRL".to_string(),
+                other => other.to_uppercase(),
+            });
+    let key = crate::hotkey::parse_key(&binding.key)
+        .map(keysym_name)
+        .unwrap_or_else(|_| binding.key.clone());
+    modifiers
+        .chain(std::iter::once(key))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// XKB keysym name of a key code, as used in portal triggers and GTK accelerators
+pub fn keysym_name(code: Code) -> String {
+    let name = format!("{code:?}");
+    if let Some(letter) = name.strip_prefix("Key") {
+        return letter.to_lowercase();
+    }
+    if let Some(digit) = name.strip_prefix("Digit") {
+        return digit.to_string();
+    }
+    if let Some(numpad) = name.strip_prefix("Numpad") {
+        return format!("KP_{numpad}");
+    }
+
+    match code {
+        Code::Space => "space",
+        Code::Enter => "Return",
+        Code::Backspace => "BackSpace",
+        Code::PageUp => "Page_Up",
+        Code::PageDown => "Page_Down",
+        Code::ArrowUp => "Up",
+        Code::ArrowDown => "Down",
+        Code::ArrowLeft => "Left",
+        Code::ArrowRight => "Right",
+        Code::Minus => "minus",
+        Code::Equal => "equal",
+        Code::Comma => "comma",
+        Code::Period => "period",
+        Code::Slash => "slash",
+        Code::Backslash => "backslash",
+        Code::Semicolon => "semicolon",
+        Code::Quote => "apostrophe",
+        Code::Backquote => "grave",
+        Code::BracketLeft => "bracketleft",
+        Code::BracketRight => "bracketright",
+        Code::PrintScreen => "Print",
+        Code::ScrollLock => "Scroll_Lock",
+        Code::CapsLock => "Caps_Lock",
+        Code::NumLock => "Num_Lock",
+        Code::AudioVolumeUp => "XF86AudioRaiseVolume",
+        Code::AudioVolumeDown => "XF86AudioLowerVolume",
+        Code::AudioVolumeMute => "XF86AudioMute",
+        Code::MediaPlayPause | Code::MediaPlay => "XF86AudioPlay",
+        Code::MediaPause => "XF86AudioPause",
+        Code::MediaStop => "XF86AudioStop",
+        Code::MediaTrackNext => "XF86AudioNext",
+        Code::MediaTrackPrevious => "XF86AudioPrev",
+        // Insert, Home, End, Delete, Escape, Tab, Pause and F-keys match already
+        _ => return name,
+    }
+    .to_string()
+}
+
+fn describe_action(action: HotkeyAction) -> &'static str {
+    match action {
+        HotkeyAction::Toggle => "Start or stop voice input",
+        HotkeyAction::PushToTalk => "Record voice input while held",
+        HotkeyAction::Cancel => "Cancel voice input",
+        HotkeyAction::RetypeLast => "Type the last transcript again",
+        HotkeyAction::SwitchLanguage => "Switch voice input language",
+        HotkeyAction::Mute => "Mute or unmute voice input",
+    }
+}
@@ -0,0 +1,104 @@
+//! Bundled tray icons, rendered as ARGB32 pixmaps so they don't depend on the
+//! installed icon theme.
+
+use ksni::Icon;
+
+const ICON_SIZES: [i32; 5] = [16, 22, 24, 32, 48];
+
+/// Visual state shown by the tray icon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconState {
+    Idle,
+    Recording,
+    /// Alternate recording frame for the animated icon
+    RecordingPulse,
+    Paused,
+    Error,
+}
+
+impl IconState {
+    fn background(self) -> [u8; 3] {
+        match self {
+            IconState::Idle => [0x3d, 0x38, 0x46],
+            IconState::Recording => [0xe0, 0x1b, 0x24],
+            IconState::RecordingPulse => [0xf6, 0x61, 0x51],
+            IconState::Paused => [0x77, 0x76, 0x7b],
+            IconState::Error => [0xe5, 0xa5, 0x0a],
+        }
+    }
+}
+
+/// Render the microphone icon for the given state in all common tray sizes
+pub fn tray_icon(state: IconState) -> Vec<Icon> {
+    ICON_SIZES
+        .iter()
+        .map(|&size| render_icon(state, size))
+        .collect()
+}
+
+fn render_icon(state: IconState, size: i32) -> Icon {
+    let scale = size as f32;
+    let [bg_r, bg_g, bg_b] = state.background();
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+
+    for y in 0..size {
+        for x in 0..size {
+            // Sample at the pixel center in normalized coordinates
+            let p = ((x as f32 + 0.5) / scale, (y as f32 + 0.5) / scale);
+
+            let background = coverage(circle(p, (0.5, 0.5), 0.48), scale);
+            let foreground = coverage(microphone(p), scale).min(background);
+
+            let blend = |bg: u8| (bg as f32 + (255.0 - bg as f32) * foreground).round() as u8;
+            let alpha = (background * 255.0).round() as u8;
+            data.extend_from_slice(&[alpha, blend(bg_r), blend(bg_g), blend(bg_b)]);
+        }
+    }
+
+    Icon {
+        width: size,
+        height: size,
+        data,
+    }
+}
+
+/// Signed distance to the microphone glyph (capsule, stand, stem and base)
+fn microphone(p: (f32, f32)) -> f32 {
+    let capsule = segment(p, (0.5, 0.29), (0.5, 0.45)) - 0.11;
+    let stand = stand_arc(p, (0.5, 0.45), 0.19) - 0.04;
+    let stem = segment(p, (0.5, 0.64), (0.5, 0.76)) - 0.035;
+    let base = segment(p, (0.38, 0.78), (0.62, 0.78)) - 0.035;
+    capsule.min(stand).min(stem).min(base)
+}
+
+/// Distance to the lower half of a circle outline
+fn stand_arc(p: (f32, f32), center: (f32, f32), radius: f32) -> f32 {
+    if p.1 >= center.1 {
+        (length((p.0 - center.0, p.1 - center.1)) - radius).abs()
+    } else {
+        let left = length((p.0 - (center.0 - radius), p.1 - center.1));
+        let right = length((p.0 - (center.0 + radius), p.1 - center.1));
+        left.min(right)
+    }
+}
+
+fn circle(p: (f32, f32), center: (f32, f32), radius: f32) -> f32 {
+    length((p.0 - center.0, p.1 - center.1)) - radius
+}
+
+/// Distance from `p` to the line segment between `a` and `b`
+fn segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let pa = (p.0 - a.0, p.1 - a.1);
+    let ba = (b.0 - a.0, b.1 - a.1);
+    let h = ((pa.0 * ba.0 + pa.1 * ba.1) / (ba.0 * ba.0 + ba.1 * ba.1)).clamp(0.0, 1.0);
+    length((pa.0 - ba.0 * h, pa.1 - ba.1 * h))
+}
+
+fn length(v: (f32, f32)) -> f32 {
+    (v.0 * v.0 + v.1 * v.1).sqrt()
+}
+
+/// Anti-aliased coverage of a pixel for a signed distance in normalized units
+fn coverage(distance: f32, scale: f32) -> f32 {
+    (0.5 - distance * scale).clamp(0.0, 1.0)
+}
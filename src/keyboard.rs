@@ -1,9 +1,144 @@
+use crate::latency::{Stage, LATENCY};
+use crate::metrics::METRICS;
+use crate::remote_desktop::RemoteDesktop;
+use crate::{desktop, emoji, focused_window, keyboard_layout, portal, privacy};
 use enigo::{Direction, Enigo, Key, Keyboard, Settings};
 use eyre::{Result, WrapErr};
-use std::time::Duration;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Pause between characters, so that none are lost
+const CHARACTER_DELAY: Duration = Duration::from_millis(2);
+
+/// Least time between checks of the window focus while typing, see
+/// [`guard_focus`]
+const FOCUS_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Session typing in portal-only mode, started with the first keystroke
+static REMOTE_DESKTOP: Mutex<Option<RemoteDesktop>> = Mutex::new(None);
+
+/// Window typing has to stay in while a [`FocusGuard`] is held
+static GUARDED_WINDOW: Mutex<Option<Option<u32>>> = Mutex::new(None);
+
+/// Typing stopped because the focus left the window of a [`FocusGuard`]
+#[derive(Debug)]
+pub struct FocusLost;
+
+impl std::fmt::Display for FocusLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the window focus changed while typing")
+    }
+}
+
+impl std::error::Error for FocusLost {}
+
+/// Keeps typing in a window until dropped, see [`guard_focus`]
+pub struct FocusGuard(());
+
+impl Drop for FocusGuard {
+    fn drop(&mut self) {
+        *GUARDED_WINDOW.lock().unwrap() = None;
+    }
+}
+
+/// Have typing stop with [`FocusLost`] between words once the focus leaves
+/// `window` (`None` for a window X11 can't see), as long as the returned
+/// guard is held
+pub fn guard_focus(window: Option<u32>) -> FocusGuard {
+    *GUARDED_WINDOW.lock().unwrap() = Some(window);
+    FocusGuard(())
+}
+
+/// Checks the focus at word boundaries, at most every
+/// [`FOCUS_CHECK_INTERVAL`]
+struct FocusCheck {
+    last: Instant,
+}
+
+impl FocusCheck {
+    fn new() -> Self {
+        Self {
+            last: Instant::now(),
+        }
+    }
+
+    /// Fails with [`FocusLost`] before `ch` if it starts a word in a guarded
+    /// window that lost the focus
+    fn before(&mut self, ch: char) -> Result<()> {
+        if !ch.is_whitespace() || self.last.elapsed() < FOCUS_CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.last = Instant::now();
+        let Some(window) = *GUARDED_WINDOW.lock().unwrap() else {
+            return Ok(());
+        };
+        match focused_window::active_window() {
+            Ok(current) if current != window => Err(FocusLost.into()),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                debug!("Could not determine the focused window: {:#}", e);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Run `input` with the RemoteDesktop session, starting one if needed. A
+/// session that failed is closed, so the next keystroke starts a new one.
+fn with_remote_desktop(input: impl FnOnce(&RemoteDesktop) -> Result<()>) -> Result<()> {
+    let mut session = REMOTE_DESKTOP.lock().unwrap();
+    if session.is_none() {
+        *session = Some(RemoteDesktop::start()?);
+    }
+    let result = input(session.as_ref().expect("the session was just started"));
+    if result.is_err() {
+        *session = None;
+    }
+    result
+}
 
 pub fn type_text(text: &str) -> Result<()> {
-    debug!("Typing text: {}", text);
+    type_text_paced(text, None, None)
+}
+
+/// Type `text` at no more than `chars_per_second` characters per second.
+/// Characters `layout` (the active one when `None`) would mangle are pasted,
+/// see [`keyboard_layout`].
+pub fn type_text_paced(
+    text: &str,
+    chars_per_second: Option<u32>,
+    layout: Option<&str>,
+) -> Result<()> {
+    debug!("Typing text: {}", privacy::loggable(text));
+
+    let delay = chars_per_second
+        .filter(|&rate| rate > 0)
+        .map_or(CHARACTER_DELAY, |rate| {
+            (Duration::from_secs(1) / rate).max(CHARACTER_DELAY)
+        });
+    let started = Instant::now();
+    let result = type_characters(text, delay, layout);
+    match &result {
+        Ok(()) => METRICS.typing_latency.observe(started.elapsed()),
+        Err(e) if e.is::<FocusLost>() => {}
+        Err(_) => METRICS.keyboard_errors.inc(),
+    }
+    result
+}
+
+fn type_characters(text: &str, delay: Duration, layout: Option<&str>) -> Result<()> {
+    if portal::is_portal_only() {
+        let mut focus = FocusCheck::new();
+        return with_remote_desktop(|session| {
+            for ch in text.chars() {
+                focus.before(ch)?;
+                session.type_char(ch)?;
+                LATENCY.mark(Stage::FirstKeystroke);
+                std::thread::sleep(delay);
+            }
+            Ok(())
+        });
+    }
 
     // Add a small delay before creating Enigo to ensure the system is ready
     std::thread::sleep(Duration::from_millis(20));
@@ -13,18 +148,103 @@ pub fn type_text(text: &str) -> Result<()> {
     // Add another small delay after initialization
     std::thread::sleep(Duration::from_millis(30));
 
-    // Type the text character by character with small delays to prevent loss
-    for ch in text.chars() {
+    // Type the text character by character with small delays to prevent loss.
+    // Emoji are pasted instead: typing them remaps a keysym, which many
+    // applications ignore without an error. So are the characters the
+    // keyboard layout puts on levels that come out differently, a run at a time.
+    let layout = layout
+        .map(str::to_string)
+        .or_else(keyboard_layout::active_layout);
+    let needs_paste =
+        |ch: char| emoji::is_emoji(ch) || !keyboard_layout::types_reliably(layout.as_deref(), ch);
+    let mut focus = FocusCheck::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        focus.before(ch)?;
         let ch_str = ch.to_string();
-        enigo.text(&ch_str).wrap_err("Failed to type character")?;
-        // Tiny delay between characters to ensure they're all captured
-        std::thread::sleep(Duration::from_millis(2));
+        if needs_paste(ch) {
+            let mut run = ch_str;
+            while let Some(next) = chars.next_if(|&next| needs_paste(next)) {
+                run.push(next);
+            }
+            paste(&mut enigo, &run)?;
+        } else if let Err(e) = enigo.text(&ch_str) {
+            debug!("Typing {:?} failed ({}), pasting it", ch, e);
+            paste(&mut enigo, &ch_str).wrap_err("Failed to type character")?;
+        }
+        LATENCY.mark(Stage::FirstKeystroke);
+        std::thread::sleep(delay);
+    }
+
+    Ok(())
+}
+
+/// Insert `text` at once by pasting it, see [`paste`]
+pub fn paste_text(text: &str) -> Result<()> {
+    debug!("Pasting text: {}", privacy::loggable(text));
+
+    if portal::is_portal_only() {
+        return paste_through_portal(text).inspect_err(|_| METRICS.keyboard_errors.inc());
     }
+    std::thread::sleep(Duration::from_millis(20));
+    let mut enigo = Enigo::new(&Settings::default()).wrap_err("Failed to initialize Enigo")?;
+    std::thread::sleep(Duration::from_millis(30));
 
+    paste(&mut enigo, text).inspect_err(|_| METRICS.keyboard_errors.inc())?;
+    LATENCY.mark(Stage::FirstKeystroke);
+    Ok(())
+}
+
+/// Paste `text` with Ctrl+V through the clipboard, restoring the text that
+/// was on the clipboard before
+fn paste(enigo: &mut Enigo, text: &str) -> Result<()> {
+    let previous = desktop::read_clipboard().unwrap_or_else(|e| {
+        warn!("Failed to read the clipboard before pasting: {:#}", e);
+        None
+    });
+    desktop::copy_to_clipboard(text)?;
+
+    enigo
+        .key(Key::Control, Direction::Press)
+        .wrap_err("Failed to press Ctrl")?;
+    let pasted = enigo
+        .key(Key::Unicode('v'), Direction::Click)
+        .wrap_err("Failed to press V");
+    enigo
+        .key(Key::Control, Direction::Release)
+        .wrap_err("Failed to release Ctrl")?;
+    pasted?;
+
+    // Give the application time to fetch the clipboard before restoring it
+    std::thread::sleep(Duration::from_millis(150));
+    if let Some(previous) = previous {
+        if let Err(e) = desktop::copy_to_clipboard(&previous) {
+            warn!("Failed to restore the clipboard after pasting: {:#}", e);
+        }
+    }
+    Ok(())
+}
+
+/// [`paste_text`] in portal-only mode: the clipboard, then Ctrl+V through
+/// the RemoteDesktop portal
+fn paste_through_portal(text: &str) -> Result<()> {
+    desktop::copy_to_clipboard(text)?;
+    with_remote_desktop(|session| {
+        session.key(Key::Control, true)?;
+        let pasted = session.click_key(Key::Unicode('v'));
+        session.key(Key::Control, false)?;
+        pasted
+    })?;
+    LATENCY.mark(Stage::FirstKeystroke);
     Ok(())
 }
 
 pub fn press_key(key: Key) -> Result<()> {
+    if portal::is_portal_only() {
+        return with_remote_desktop(|session| session.click_key(key))
+            .inspect_err(|_| METRICS.keyboard_errors.inc());
+    }
+
     // Add a small delay before creating Enigo
     std::thread::sleep(Duration::from_millis(10));
 
@@ -35,7 +255,8 @@ pub fn press_key(key: Key) -> Result<()> {
 
     enigo
         .key(key, Direction::Click)
-        .wrap_err("Failed to press key")?;
+        .wrap_err("Failed to press key")
+        .inspect_err(|_| METRICS.keyboard_errors.inc())?;
 
     Ok(())
 }
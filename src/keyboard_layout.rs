@@ -0,0 +1,104 @@
+//! The active keyboard layout, and which characters typing can be trusted
+//! with on it. Simulated keystrokes are looked up in the layout, and on
+//! layouts other than US (AZERTY, QWERTZ, ...) digits and punctuation sit on
+//! shifted or AltGr levels that come out as different characters, so those
+//! are pasted instead.
+
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a detected layout is used before looking again, since the user
+/// may switch input sources at any time
+const DETECTION_TTL: Duration = Duration::from_secs(5);
+
+/// Layouts on which every ASCII character is on the level enigo expects
+const US_COMPATIBLE_LAYOUTS: &[&str] = &["us", "gb", "uk", "ie", "au", "nz", "za", "in"];
+
+static DETECTED: Mutex<Option<(Instant, Option<String>)>> = Mutex::new(None);
+
+/// The active layout, e.g. "fr" or "de" without variant, from the GNOME
+/// input sources, `setxkbmap` or `XKB_DEFAULT_LAYOUT`. Cached for a few
+/// seconds.
+pub fn active_layout() -> Option<String> {
+    let mut detected = DETECTED.lock().unwrap();
+    if let Some((at, layout)) = detected.as_ref() {
+        if at.elapsed() < DETECTION_TTL {
+            return layout.clone();
+        }
+    }
+    let layout = detect();
+    debug!("Active keyboard layout: {:?}", layout);
+    *detected = Some((Instant::now(), layout.clone()));
+    layout
+}
+
+fn detect() -> Option<String> {
+    // The most recently used GNOME input source is the active one
+    ["mru-sources", "sources"]
+        .iter()
+        .find_map(|key| {
+            command_output(
+                "gsettings",
+                &["get", "org.gnome.desktop.input-sources", key],
+            )
+            .and_then(|output| parse_gnome_sources(&output))
+        })
+        .or_else(|| {
+            command_output("setxkbmap", &["-query"]).and_then(|output| parse_setxkbmap(&output))
+        })
+        .or_else(|| {
+            std::env::var("XKB_DEFAULT_LAYOUT")
+                .ok()
+                .and_then(|layouts| first_layout(&layouts))
+        })
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The first XKB layout of a GNOME input sources list such as
+/// `[('xkb', 'fr+azerty'), ('ibus', 'anthy')]`
+pub fn parse_gnome_sources(sources: &str) -> Option<String> {
+    let start = sources.find("('xkb', '")? + "('xkb', '".len();
+    let end = sources[start..].find('\'')?;
+    first_layout(&sources[start..start + end])
+}
+
+/// The first layout in `setxkbmap -query` output (`layout:     fr,us`)
+pub fn parse_setxkbmap(query: &str) -> Option<String> {
+    query
+        .lines()
+        .find_map(|line| line.strip_prefix("layout:"))
+        .and_then(first_layout)
+}
+
+/// "fr" of "fr+azerty", "de(nodeadkeys)" or "fr,us"
+fn first_layout(layouts: &str) -> Option<String> {
+    let layout = layouts
+        .split(',')
+        .next()?
+        .split(['+', '('])
+        .next()?
+        .trim()
+        .to_lowercase();
+    (!layout.is_empty()).then_some(layout)
+}
+
+/// Whether typing `ch` produces that character on `layout`. Letters and
+/// whitespace are typed on every layout, everything else only on US
+/// compatible ones; unknown layouts are assumed to be US compatible.
+pub fn types_reliably(layout: Option<&str>, ch: char) -> bool {
+    let us_compatible = layout.is_none_or(|layout| US_COMPATIBLE_LAYOUTS.contains(&layout));
+    us_compatible || ch.is_alphabetic() || ch.is_whitespace()
+}
@@ -0,0 +1,187 @@
+//! Deepgram API key storage in the Secret Service keyring (GNOME Keyring),
+//! talking to `org.freedesktop.secrets` directly over D-Bus.
+
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::{LocalConnection, Proxy};
+use dbus::message::MatchRule;
+use eyre::{Result, WrapErr};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const SECRETS_BUS_NAME: &str = "org.freedesktop.secrets";
+const SECRETS_PATH: &str = "/org/freedesktop/secrets";
+const DEFAULT_COLLECTION_PATH: &str = "/org/freedesktop/secrets/aliases/default";
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+const PROMPT_INTERFACE: &str = "org.freedesktop.Secret.Prompt";
+/// Unlocking may ask for the keyring password, so give the user time to answer
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+const ITEM_LABEL: &str = "GNOME Voice Input Deepgram API key";
+
+/// Secret as transferred over D-Bus: session, parameters, value, content type
+type Secret = (dbus::Path<'static>, Vec<u8>, Vec<u8>, String);
+
+/// Value of `deepgram_api_key` that makes the app read the key from the keyring
+pub const KEYRING_PLACEHOLDER: &str = "keyring";
+
+/// Look up the stored API key. Returns `None` if no key has been stored yet.
+pub fn load_api_key() -> Result<Option<String>> {
+    let keyring = Keyring::open()?;
+    let service = keyring.proxy(SECRETS_PATH);
+
+    let (unlocked, locked): (Vec<dbus::Path<'static>>, Vec<dbus::Path<'static>>) = service
+        .method_call(SERVICE_INTERFACE, "SearchItems", (attributes(),))
+        .wrap_err("Failed to search the keyring")?;
+
+    let mut items = unlocked;
+    if !locked.is_empty() {
+        items.extend(keyring.unlock(locked)?);
+    }
+    if items.is_empty() {
+        return Ok(None);
+    }
+
+    let (secrets,): (HashMap<dbus::Path<'static>, Secret>,) = service
+        .method_call(
+            SERVICE_INTERFACE,
+            "GetSecrets",
+            (items.clone(), keyring.session.clone()),
+        )
+        .wrap_err("Failed to read the API key from the keyring")?;
+
+    let secret = items.iter().find_map(|item| secrets.get(item));
+    match secret {
+        Some((_, _, value, _)) => {
+            let api_key = String::from_utf8(value.clone())
+                .wrap_err("API key in the keyring is not valid UTF-8")?;
+            Ok(Some(api_key.trim().to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Store the API key in the default keyring collection, replacing any
+/// previously stored key
+pub fn store_api_key(api_key: &str) -> Result<()> {
+    let keyring = Keyring::open()?;
+    keyring.unlock(vec![dbus::Path::from(DEFAULT_COLLECTION_PATH)])?;
+
+    let mut properties = PropMap::new();
+    properties.insert(
+        "org.freedesktop.Secret.Item.Label".to_string(),
+        Variant(Box::new(ITEM_LABEL.to_string()) as Box<dyn RefArg>),
+    );
+    properties.insert(
+        "org.freedesktop.Secret.Item.Attributes".to_string(),
+        Variant(Box::new(attributes()) as Box<dyn RefArg>),
+    );
+    let secret: Secret = (
+        keyring.session.clone(),
+        Vec::new(),
+        api_key.as_bytes().to_vec(),
+        "text/plain".to_string(),
+    );
+
+    let (_item, prompt): (dbus::Path<'static>, dbus::Path<'static>) = keyring
+        .proxy(DEFAULT_COLLECTION_PATH)
+        .method_call(
+            COLLECTION_INTERFACE,
+            "CreateItem",
+            (properties, secret, true),
+        )
+        .wrap_err("Failed to store the API key in the keyring")?;
+    keyring.complete_prompt(prompt)?;
+
+    info!("Stored Deepgram API key in the keyring");
+    Ok(())
+}
+
+fn attributes() -> HashMap<String, String> {
+    HashMap::from([
+        ("application".to_string(), "gnome-voice-input".to_string()),
+        ("key".to_string(), "deepgram_api_key".to_string()),
+    ])
+}
+
+/// Connection to the Secret Service with an open (unencrypted) session. The
+/// secret only travels over the local session bus.
+struct Keyring {
+    conn: LocalConnection,
+    session: dbus::Path<'static>,
+}
+
+impl Keyring {
+    fn open() -> Result<Self> {
+        let conn = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+        let (_output, session): (Variant<Box<dyn RefArg>>, dbus::Path<'static>) = conn
+            .with_proxy(SECRETS_BUS_NAME, SECRETS_PATH, Duration::from_secs(5))
+            .method_call(
+                SERVICE_INTERFACE,
+                "OpenSession",
+                ("plain", Variant(Box::new(String::new()) as Box<dyn RefArg>)),
+            )
+            .wrap_err("Secret Service unavailable, is GNOME Keyring running?")?;
+        Ok(Self { conn, session })
+    }
+
+    fn proxy<'a>(&'a self, path: &'a str) -> Proxy<'a, &'a LocalConnection> {
+        self.conn
+            .with_proxy(SECRETS_BUS_NAME, path, Duration::from_secs(5))
+    }
+
+    /// Unlock the given objects, asking the user if necessary. Returns the
+    /// objects that are unlocked now.
+    fn unlock(&self, objects: Vec<dbus::Path<'static>>) -> Result<Vec<dbus::Path<'static>>> {
+        let (unlocked, prompt): (Vec<dbus::Path<'static>>, dbus::Path<'static>) = self
+            .proxy(SECRETS_PATH)
+            .method_call(SERVICE_INTERFACE, "Unlock", (objects.clone(),))
+            .wrap_err("Failed to unlock the keyring")?;
+        if self.complete_prompt(prompt)? {
+            return Ok(objects);
+        }
+        Ok(unlocked)
+    }
+
+    /// Show a prompt returned by the Secret Service and wait until the user
+    /// answers. Returns false if there was nothing to prompt for.
+    fn complete_prompt(&self, prompt: dbus::Path<'static>) -> Result<bool> {
+        if &*prompt == "/" {
+            return Ok(false);
+        }
+
+        let dismissed = Rc::new(Cell::new(None::<bool>));
+        let result = dismissed.clone();
+        let match_token = self.conn.add_match(
+            MatchRule::new_signal(PROMPT_INTERFACE, "Completed").with_path(prompt.clone()),
+            move |(was_dismissed, _result): (bool, Variant<Box<dyn RefArg>>), _, _| {
+                result.set(Some(was_dismissed));
+                false
+            },
+        )?;
+
+        let _: () = self
+            .conn
+            .with_proxy(SECRETS_BUS_NAME, &prompt, Duration::from_secs(5))
+            .method_call(PROMPT_INTERFACE, "Prompt", ("",))
+            .wrap_err("Failed to show the keyring prompt")?;
+
+        let started = Instant::now();
+        let dismissed = loop {
+            if let Some(dismissed) = dismissed.get() {
+                break dismissed;
+            }
+            if started.elapsed() > PROMPT_TIMEOUT {
+                let _ = self.conn.remove_match(match_token);
+                bail!("Timed out waiting for the keyring prompt");
+            }
+            self.conn.process(Duration::from_millis(100))?;
+        };
+
+        if dismissed {
+            bail!("Keyring prompt was dismissed");
+        }
+        Ok(true)
+    }
+}
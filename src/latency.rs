@@ -0,0 +1,121 @@
+//! Latency trace of the current recording session: when it first reached
+//! each stage after recording was requested (hotkey, tray, D-Bus). The
+//! summary is logged at debug level when the session ends and reported by
+//! `status`, so slow setups and regressions show where the time goes.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Trace of this process, updated by the instrumented modules
+pub static LATENCY: LatencyTrace = LatencyTrace::new();
+
+/// Stages of a session in the order they are normally reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Audio capture is running
+    CaptureStarted,
+    /// The first audio chunk went out to the transcription service
+    FirstAudioSent,
+    FirstInterim,
+    FirstFinal,
+    /// The first character was typed, pasted or inserted into the target
+    FirstKeystroke,
+}
+
+impl Stage {
+    const ALL: [Stage; 5] = [
+        Stage::CaptureStarted,
+        Stage::FirstAudioSent,
+        Stage::FirstInterim,
+        Stage::FirstFinal,
+        Stage::FirstKeystroke,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Stage::CaptureStarted => "capture started",
+            Stage::FirstAudioSent => "first audio sent",
+            Stage::FirstInterim => "first interim",
+            Stage::FirstFinal => "first final",
+            Stage::FirstKeystroke => "first keystroke",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Trace {
+    /// When the running session was requested, `None` between sessions
+    requested: Option<Instant>,
+    /// Time from the request until each of `Stage::ALL` was reached
+    reached: [Option<Duration>; Stage::ALL.len()],
+    /// Summary of the last finished session
+    last_summary: Option<String>,
+}
+
+pub struct LatencyTrace {
+    trace: Mutex<Option<Trace>>,
+}
+
+impl LatencyTrace {
+    const fn new() -> Self {
+        Self {
+            trace: Mutex::new(None),
+        }
+    }
+
+    /// Start tracing a session requested just now
+    pub fn begin(&self) {
+        self.with_trace(|trace| {
+            trace.requested = Some(Instant::now());
+            trace.reached = Default::default();
+        });
+    }
+
+    /// Note that the running session reached `stage`; later calls for the
+    /// same stage and calls between sessions are ignored
+    pub fn mark(&self, stage: Stage) {
+        self.with_trace(|trace| {
+            let Some(requested) = trace.requested else {
+                return;
+            };
+            trace.reached[stage as usize].get_or_insert_with(|| requested.elapsed());
+        });
+    }
+
+    /// End tracing the running session, returning its summary
+    pub fn finish(&self) -> Option<String> {
+        self.with_trace(|trace| {
+            trace.requested.take()?;
+            let summary = summary(&trace.reached);
+            trace.last_summary = Some(summary.clone());
+            Some(summary)
+        })
+    }
+
+    /// Summary of the last finished session, if any
+    pub fn last_summary(&self) -> Option<String> {
+        self.with_trace(|trace| trace.last_summary.clone())
+    }
+
+    fn with_trace<T>(&self, f: impl FnOnce(&mut Trace) -> T) -> T {
+        let mut trace = self.trace.lock().unwrap();
+        f(trace.get_or_insert_with(Trace::default))
+    }
+}
+
+/// The reached stages with their time since the request, e.g. "capture
+/// started +35 ms, first audio sent +410 ms, first final +1450 ms"
+fn summary(reached: &[Option<Duration>]) -> String {
+    let stages: Vec<String> = Stage::ALL
+        .iter()
+        .zip(reached)
+        .filter_map(|(stage, time)| {
+            time.map(|time| format!("{} +{} ms", stage.label(), time.as_millis()))
+        })
+        .collect();
+    if stages.is_empty() {
+        "no stage reached".to_string()
+    } else {
+        stages.join(", ")
+    }
+}
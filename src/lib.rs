@@ -1,24 +1,68 @@
+//! Voice input for GNOME with Deepgram's streaming speech-to-text.
+//!
+//! [`VoiceInputEngine`] embeds the capture and transcription pipeline in
+//! other applications. The remaining modules are the building blocks of the
+//! `gnome-voice-input` application.
+
 #[macro_use]
 extern crate tracing;
 
 #[macro_use]
 extern crate eyre;
 
+pub mod api_keys;
+pub mod atspi;
 pub mod audio;
-pub mod audio_utils;
+pub mod audio_source;
+pub mod casing;
 pub mod config;
+pub mod desktop;
+pub mod dictation_buffer;
+pub mod emoji;
+pub mod engine;
+pub mod filters;
+pub mod focused_window;
 pub mod handlers;
+pub mod history;
+pub mod history_export;
 pub mod keyboard;
+pub mod keyboard_layout;
+pub mod keyring;
+pub mod latency;
+pub mod listen;
+pub mod llm;
+pub mod metrics;
+pub mod microphone;
+pub mod numbers;
+pub mod overlay;
+pub mod phrases;
+pub mod pii;
+pub mod pipeline;
+pub mod portal;
+pub mod privacy;
+pub mod profanity;
+pub mod redact;
+pub mod remote_desktop;
+pub mod review;
+pub mod session;
+pub mod spelling;
 pub mod state;
+pub mod stats;
+pub mod storage;
+pub mod subtitles;
+pub mod templates;
+pub mod tls;
 pub mod transcription;
 pub mod transcription_utils;
+pub mod vocabulary;
 
 // Re-export commonly used items
 pub use config::Config;
+pub use engine::VoiceInputEngine;
 pub use handlers::{
-    process_transcription_with_handler, ConsoleTranscriptionHandler, KeyboardTranscriptionHandler,
-    TranscriptionHandler,
+    process_transcription_until, process_transcription_with_handler, ConsoleTranscriptionHandler,
+    KeyboardTranscriptionHandler, TranscriptionHandler,
 };
 pub use state::AppState;
 pub use transcription::Transcriber;
-pub use transcription_utils::TranscriptionResult;
+pub use transcription_utils::{Transcript, TranscriptionResult};
@@ -0,0 +1,82 @@
+//! `gnome-voice-input list-devices`: input devices with their supported
+//! formats, to pick a value for `audio.device`.
+
+use crate::config::Config;
+use cpal::traits::{DeviceTrait, HostTrait};
+use eyre::{Result, WrapErr};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+pub fn list_devices(custom_path: Option<PathBuf>) -> Result<()> {
+    let host = cpal::default_host();
+    let default_name = host
+        .default_input_device()
+        .and_then(|device| device.name().ok());
+    let configured_name = configured_device(custom_path);
+
+    let devices = host
+        .input_devices()
+        .wrap_err("Failed to enumerate input devices")?;
+    let mut found_configured = false;
+    for device in devices {
+        let Ok(name) = device.name() else {
+            continue;
+        };
+
+        let mut markers = Vec::new();
+        if default_name.as_ref() == Some(&name) {
+            markers.push("default");
+        }
+        if configured_name.as_ref() == Some(&name) {
+            markers.push("configured");
+            found_configured = true;
+        }
+        if markers.is_empty() {
+            println!("{name}");
+        } else {
+            println!("{name} ({})", markers.join(", "));
+        }
+
+        let configs = match device.supported_input_configs() {
+            Ok(configs) => configs,
+            Err(e) => {
+                println!("  supported formats unavailable: {e}");
+                continue;
+            }
+        };
+        // Backends often report the same range more than once
+        let mut formats = BTreeSet::new();
+        for config in configs {
+            let (min_rate, max_rate) = (config.min_sample_rate().0, config.max_sample_rate().0);
+            let rates = if min_rate == max_rate {
+                format!("{min_rate} Hz")
+            } else {
+                format!("{min_rate}-{max_rate} Hz")
+            };
+            formats.insert((rates, config.channels(), config.sample_format().to_string()));
+        }
+        for (rates, channels, sample_format) in formats {
+            println!("  {rates}, {channels} channel(s), {sample_format}");
+        }
+    }
+
+    if let (Some(name), false) = (configured_name, found_configured) {
+        println!("\nConfigured device {name} was not found, the default device is used instead.");
+    }
+    Ok(())
+}
+
+/// `audio.device` from the config, if there is a config to read
+fn configured_device(custom_path: Option<PathBuf>) -> Option<String> {
+    let path = Config::get_config_path(custom_path).ok()?;
+    if !path.exists() {
+        return None;
+    }
+    match Config::load(Some(path)) {
+        Ok(config) => config.audio.device,
+        Err(e) => {
+            eprintln!("Could not read the configured device: {e:#}");
+            None
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Client of Deepgram's streaming endpoint (`/v1/listen`): the audio is sent
+//! as binary messages and `StreamResponse`s come back as JSON. Used instead
+//! of the deepgram crate's WebSocket client, whose TLS setup is fixed, so
+//! that `[transcription.tls]` applies.
+
+use deepgram::common::stream_response::StreamResponse;
+use eyre::{Result, WrapErr};
+use futures::stream::{Stream, StreamExt};
+use futures::SinkExt;
+use reqwest::Url;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::Connector;
+
+const DEFAULT_BASE_URL: &str = "https://api.deepgram.com";
+const LISTEN_PATH: &str = "v1/listen";
+
+/// Deepgram closes connections without data for 10 seconds
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// WebSocket URL of the streaming endpoint of `base_url` (api.deepgram.com
+/// when unset) with the request options in `query`
+pub fn listen_url(base_url: Option<&str>, query: &str) -> Result<Url> {
+    let base_url = base_url.unwrap_or(DEFAULT_BASE_URL);
+    let mut url = Url::parse(base_url)
+        .and_then(|url| url.join(LISTEN_PATH))
+        .wrap_err_with(|| format!("Invalid transcription base_url: {base_url}"))?;
+    let scheme = match url.scheme() {
+        "http" | "ws" => "ws",
+        "https" | "wss" => "wss",
+        other => bail!("Unsupported scheme {other:?} in transcription base_url"),
+    };
+    url.set_scheme(scheme)
+        .map_err(|()| eyre!("Invalid transcription base_url: {}", base_url))?;
+    url.set_query(Some(query));
+    Ok(url)
+}
+
+/// Responses of a streaming request
+pub struct ListenStream {
+    request_id: String,
+    responses: mpsc::Receiver<Result<StreamResponse>>,
+}
+
+impl ListenStream {
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// The next response; None once the connection is closed
+    pub async fn next(&mut self) -> Option<Result<StreamResponse>> {
+        self.responses.recv().await
+    }
+}
+
+/// Whether a failed connection was refused for the API key
+pub fn is_auth_error(error: &eyre::Report) -> bool {
+    use tokio_tungstenite::tungstenite::Error;
+    matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::Http(response)) if matches!(response.status().as_u16(), 401 | 403)
+    )
+}
+
+/// Whether a failed connection was refused for the key's quota: out of
+/// credits (402) or over its rate or concurrency limit (429)
+pub fn is_quota_error(error: &eyre::Report) -> bool {
+    use tokio_tungstenite::tungstenite::Error;
+    matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::Http(response)) if matches!(response.status().as_u16(), 402 | 429)
+    )
+}
+
+/// Connect to `url` and stream `audio` to it. When the audio ends the
+/// remaining results are requested and the stream is closed.
+pub async fn connect(
+    url: Url,
+    api_key: &str,
+    tls: Option<Arc<rustls::ClientConfig>>,
+    audio: impl Stream<Item = Vec<u8>> + Send + Unpin + 'static,
+) -> Result<ListenStream> {
+    let mut request = url.as_str().into_client_request()?;
+    request.headers_mut().insert(
+        "authorization",
+        format!("Token {api_key}")
+            .parse()
+            .wrap_err("Invalid API key")?,
+    );
+    let (socket, response) = tokio_tungstenite::connect_async_tls_with_config(
+        request,
+        None,
+        false,
+        tls.map(Connector::Rustls),
+    )
+    .await?;
+    let request_id = response
+        .headers()
+        .get("dg-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+
+    let (responses_tx, responses) = mpsc::channel(256);
+    tokio::spawn(async move {
+        let (mut sink, mut source) = socket.split();
+        let mut audio = audio.fuse();
+        let mut audio_ended = false;
+        let mut last_sent = Instant::now();
+        loop {
+            let sent = tokio::select! {
+                message = source.next() => {
+                    let response = match message {
+                        Some(Ok(Message::Text(text))) => serde_json::from_str(&text)
+                            .wrap_err_with(|| format!("Unexpected message from Deepgram: {text}")),
+                        Some(Ok(Message::Close(Some(frame)))) if frame.code != CloseCode::Normal => {
+                            Err(eyre!("Deepgram closed the connection: {} {}", frame.code, frame.reason))
+                        }
+                        Some(Ok(Message::Close(_))) | None => break,
+                        // Pings are answered by tungstenite
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => Err(e.into()),
+                    };
+                    let failed = response.is_err();
+                    if responses_tx.send(response).await.is_err() || failed {
+                        break;
+                    }
+                    continue;
+                }
+                chunk = audio.next(), if !audio_ended => match chunk {
+                    Some(chunk) => sink.send(Message::binary(chunk)).await,
+                    None => {
+                        audio_ended = true;
+                        debug!("Audio ended, closing the transcription stream");
+                        let finalize = sink.send(Message::text(r#"{"type":"Finalize"}"#)).await;
+                        match finalize {
+                            Ok(()) => sink.send(Message::text(r#"{"type":"CloseStream"}"#)).await,
+                            Err(e) => Err(e),
+                        }
+                    }
+                },
+                _ = tokio::time::sleep_until(last_sent + KEEP_ALIVE_INTERVAL), if !audio_ended => {
+                    sink.send(Message::text(r#"{"type":"KeepAlive"}"#)).await
+                }
+            };
+            if let Err(e) = sent {
+                let _ = responses_tx.send(Err(e.into())).await;
+                break;
+            }
+            last_sent = Instant::now();
+        }
+    });
+
+    Ok(ListenStream {
+        request_id,
+        responses,
+    })
+}
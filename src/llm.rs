@@ -0,0 +1,66 @@
+//! Client for the OpenAI-compatible chat completions API used by the
+//! `[post_processing.llm]` cleanup of final transcripts.
+
+use crate::config::LlmConfig;
+use eyre::{OptionExt, Result, WrapErr};
+use serde_json::json;
+use std::time::Duration;
+
+pub struct LlmClient {
+    config: LlmConfig,
+    client: reqwest::Client,
+}
+
+impl LlmClient {
+    pub fn new(config: LlmConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The transcript as rewritten by the model according to the prompt.
+    /// Fails on errors and when the configured timeout passes.
+    pub async fn rewrite(&self, text: &str) -> Result<String> {
+        let url = format!(
+            "{}/chat/completions",
+            self.config.endpoint.trim_end_matches('/')
+        );
+        let mut request = self
+            .client
+            .post(&url)
+            .timeout(Duration::from_millis(self.config.timeout_ms))
+            .json(&json!({
+                "model": self.config.model,
+                "temperature": 0,
+                "messages": [
+                    { "role": "system", "content": self.config.prompt },
+                    { "role": "user", "content": text },
+                ],
+            }));
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .wrap_err_with(|| format!("Failed to reach {url}"))?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("{} answered with HTTP {}", url, status);
+        }
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .wrap_err("Invalid chat completion response")?;
+        let content = body["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_eyre("No message in the chat completion response")?
+            .trim();
+        if content.is_empty() {
+            bail!("The model returned an empty text");
+        }
+        Ok(content.to_string())
+    }
+}
@@ -0,0 +1,207 @@
+//! Log outputs besides stdout configured in `[logging]`: rotating log files
+//! in the state directory and native journald logging with structured fields.
+
+use crate::{config::LoggingConfig, redact::redact, storage};
+use eyre::{Result, WrapErr};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+const LOG_FILE: &str = "gnome-voice-input.log";
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+const SYSLOG_IDENTIFIER: &str = "gnome-voice-input";
+
+/// Handle to the current log file, rotated by size. Clones share the file.
+#[derive(Clone)]
+pub struct LogFile(Arc<Mutex<RotatingFile>>);
+
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl LogFile {
+    /// Open the log file in the state directory for appending
+    pub fn open(config: &LoggingConfig) -> Result<Self> {
+        let dir = storage::log_dir()?;
+        std::fs::create_dir_all(&dir)
+            .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(LOG_FILE);
+        let file = open_append(&path)?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        Ok(Self(Arc::new(Mutex::new(RotatingFile {
+            path,
+            file,
+            size,
+            max_size: config.max_size_mb.max(1) * 1024 * 1024,
+            max_files: config.max_files,
+        }))))
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.0.lock().unwrap().path.clone()
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut file = self.0.lock().unwrap();
+        if file.size > 0 && file.size + buf.len() as u64 > file.max_size {
+            file.rotate()?;
+        }
+        file.file.write_all(buf)?;
+        file.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().file.flush()
+    }
+}
+
+impl RotatingFile {
+    /// Shift `x.log.1` to `x.log.2` and so on, dropping the oldest, and start
+    /// a new `x.log`
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |index: usize| PathBuf::from(format!("{}.{index}", self.path.display()));
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(rotated(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let _ = std::fs::rename(rotated(index), rotated(index + 1));
+            }
+            std::fs::rename(&self.path, rotated(1))?;
+        }
+        self.file = open_append(&self.path).map_err(io::Error::other)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .wrap_err_with(|| format!("Failed to open {}", path.display()))
+}
+
+/// Sends events to the journal over its native protocol, with the event's
+/// fields as journal fields (e.g. `SESSION=3`) besides `MESSAGE`
+pub struct JournaldLayer {
+    socket: UnixDatagram,
+}
+
+impl JournaldLayer {
+    pub fn connect() -> Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket
+            .connect(JOURNALD_SOCKET)
+            .wrap_err_with(|| format!("Failed to connect to {JOURNALD_SOCKET}"))?;
+        Ok(Self { socket })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for JournaldLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+
+        let mut payload = Vec::new();
+        put_field(&mut payload, "PRIORITY", priority(metadata.level()));
+        put_field(&mut payload, "SYSLOG_IDENTIFIER", SYSLOG_IDENTIFIER);
+        put_field(&mut payload, "MESSAGE", &redact(&fields.message));
+        put_field(&mut payload, "TARGET", metadata.target());
+        if let Some(file) = metadata.file() {
+            put_field(&mut payload, "CODE_FILE", file);
+        }
+        if let Some(line) = metadata.line() {
+            put_field(&mut payload, "CODE_LINE", &line.to_string());
+        }
+        for (name, value) in &fields.fields {
+            put_field(&mut payload, &journal_field_name(name), &redact(value));
+        }
+        // Logging must not fail the caller; a full or missing journal drops the event
+        let _ = self.socket.send(&payload);
+    }
+}
+
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.fields
+                .push((field.name().to_string(), format!("{value:?}")));
+        }
+    }
+}
+
+fn priority(level: &Level) -> &'static str {
+    match *level {
+        Level::ERROR => "3",
+        Level::WARN => "4",
+        Level::INFO => "6",
+        _ => "7",
+    }
+}
+
+/// Journal field names are upper case letters, digits and underscores and
+/// must not start with an underscore (reserved for trusted fields) or a digit
+fn journal_field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_start_matches('_');
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("F_{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Append a field in the journal's native format; values with line breaks
+/// are length-prefixed
+fn put_field(payload: &mut Vec<u8>, name: &str, value: &str) {
+    payload.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        payload.push(b'\n');
+        payload.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        payload.push(b'=');
+    }
+    payload.extend_from_slice(value.as_bytes());
+    payload.push(b'\n');
+}
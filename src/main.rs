@@ -4,43 +4,463 @@ extern crate tracing;
 #[macro_use]
 extern crate eyre;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use eyre::Result;
+use std::io::IsTerminal;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::signal::unix::SignalKind;
 use tokio_util::sync::CancellationToken;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod app_manager;
-mod audio;
-mod audio_utils;
-mod config;
+mod bench;
+mod config_check;
 mod config_watcher;
-mod handlers;
+mod dbus_service;
+mod doctor;
+mod gnome_shortcut;
+mod history_window;
 mod hotkey;
-mod keyboard;
-mod state;
-mod transcription;
-mod transcription_utils;
+mod hotkey_portal;
+mod icons;
+mod list_devices;
+mod logging;
+mod media;
+mod notifications;
+mod once;
+mod session_lock;
+mod setup_wizard;
+mod stdin_control;
+mod supervisor;
+mod systemd;
 mod tray;
 
+use gnome_voice_input::{
+    atspi, audio, audio_source, config, desktop, focused_window, handlers, history, history_export,
+    keyboard, keyring, latency, metrics, overlay, pipeline, portal, privacy, redact, session,
+    state, stats, storage, transcription, transcription_utils,
+};
+
 use app_manager::initialize_app_components;
 use config::Config;
+use handlers::LogTranscriptionHandler;
+use session::RecordingSession;
 use state::AppState;
 
 #[derive(Parser, Debug)]
 #[command(name = "gnome-voice-input")]
 #[command(about = "Voice input utility for GNOME desktop using Deepgram", long_about = None)]
 struct Args {
-    /// Enable debug mode to save WAV files of audio sent to Deepgram
+    /// Enable debug mode to save WAV files of audio sent to Deepgram to the state directory
     #[arg(long, default_value_t = false)]
     debug: bool,
 
     /// Path to custom configuration file
     #[arg(short, long, value_name = "FILE")]
     config: Option<std::path::PathBuf>,
+
+    /// Activate the named profile from the config
+    #[arg(short, long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Record a single utterance right away, type it and exit
+    #[arg(long)]
+    once: bool,
+
+    /// Log transcripts instead of typing them, to try out the configuration
+    /// without keystrokes landing in the focused window
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Run without tray and hotkeys, controlled over D-Bus or by commands on
+    /// stdin (toggle, start, stop, cancel, pause, resume, language, status, quit)
+    #[arg(long, conflicts_with = "once")]
+    headless: bool,
+
+    /// Save recordings as WAV files without transcribing them, e.g. when
+    /// offline or to save quota
+    #[arg(long)]
+    record_only: bool,
+
+    /// Play this WAV file in place of the microphone, through the same
+    /// streaming and typing code path
+    #[arg(long, value_name = "WAV")]
+    input_file: Option<std::path::PathBuf>,
+
+    /// Playback speed of --input-file relative to realtime
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        default_value_t = 1.0,
+        requires = "input_file"
+    )]
+    input_speed: f64,
+
+    /// Record a generated signal in place of the microphone, e.g. to check
+    /// the connection without one: silence, sine or sine:<Hz>
+    #[arg(long, value_name = "SIGNAL", conflicts_with = "input_file")]
+    input_signal: Option<audio_source::Signal>,
+
+    /// Stop each recording automatically after this many seconds
+    #[arg(long, value_name = "SECS")]
+    duration: Option<u64>,
+
+    /// With --once, print the transcript to stdout instead of typing it
+    #[arg(long, requires = "once")]
+    print: bool,
+
+    /// Run as a systemd user service (see `install-service`): notify readiness,
+    /// ping the watchdog and exit with status 78 on configuration errors
+    #[arg(long, conflicts_with = "once")]
+    service: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn init_logging(debug: bool) {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Start or stop recording in the running instance
+    Toggle,
+    /// Start recording in the running instance
+    Start,
+    /// Stop recording in the running instance, typing what was said
+    Stop,
+    /// Show the state of the running instance: recording, profile, language,
+    /// connection and today's usage
+    Status {
+        /// Print JSON for status bars such as Waybar and scripts
+        #[arg(long)]
+        json: bool,
+    },
+    /// Register a GNOME custom keyboard shortcut that runs `toggle`
+    InstallShortcut {
+        /// GTK accelerator such as "<Super>v"; defaults to the configured hotkey
+        #[arg(long)]
+        binding: Option<String>,
+    },
+    /// Remove the GNOME custom keyboard shortcut
+    UninstallShortcut,
+    /// List audio input devices and their supported formats
+    ListDevices,
+    /// Check the desktop session, tray, typing, hotkeys, microphone and
+    /// Deepgram, with hints for what fails
+    Doctor,
+    /// Install a systemd user unit running gnome-voice-input as a service
+    InstallService {
+        /// Also enable and start the service
+        #[arg(long)]
+        enable: bool,
+    },
+    /// Store the Deepgram API key in the Secret Service keyring (GNOME Keyring)
+    SetKey,
+    /// Stream a WAV file through transcription in place of the microphone and
+    /// report latency and accuracy
+    Bench {
+        /// Recording to transcribe
+        #[arg(value_name = "WAV")]
+        file: std::path::PathBuf,
+        /// What is said in the recording, to report the word error rate
+        #[arg(long, value_name = "TEXT")]
+        expected: Option<String>,
+        /// Number of times to run the benchmark
+        #[arg(long, default_value_t = 1)]
+        runs: u32,
+    },
+    /// Create or inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Work with the transcription history
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Write a commented example config documenting every option
+    Init {
+        /// Print the example to stdout instead of writing the config file
+        #[arg(long)]
+        stdout: bool,
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the config file, hotkeys and audio device without starting the app
+    Validate {
+        /// Also check that Deepgram accepts the API key
+        #[arg(long)]
+        check_key: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryCommand {
+    /// Print the history, oldest first, to archive or analyze it elsewhere
+    Export {
+        /// json, csv or md
+        #[arg(long, default_value = "json")]
+        format: history_export::ExportFormat,
+        /// Only entries from this day (YYYY-MM-DD) on
+        #[arg(long, value_name = "DATE")]
+        since: Option<chrono::NaiveDate>,
+    },
+    /// List the transcripts containing a text (ignoring case), newest first
+    Search {
+        query: String,
+        /// Only transcripts from this day (YYYY-MM-DD) on
+        #[arg(long, value_name = "DATE")]
+        since: Option<chrono::NaiveDate>,
+        /// Only transcripts up to this day (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE")]
+        until: Option<chrono::NaiveDate>,
+        /// Copy the newest matching transcript to the clipboard
+        #[arg(long)]
+        copy: bool,
+    },
+}
+
+async fn run_command(command: Command, config_path: Option<std::path::PathBuf>) -> Result<()> {
+    match command {
+        Command::Toggle => dbus_service::call_running_instance("ToggleRecording"),
+        Command::InstallService { enable } => {
+            let unit_path = systemd::install_service(config_path, enable)?;
+            println!("Installed {}", unit_path.display());
+            if !enable {
+                println!("Start it with: systemctl --user enable --now gnome-voice-input");
+            }
+            Ok(())
+        }
+        Command::ListDevices => list_devices::list_devices(config_path),
+        Command::Doctor => {
+            if doctor::run_doctor(config_path).await > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Bench {
+            file,
+            expected,
+            runs,
+        } => bench::run_bench(config_path, &file, expected, runs).await,
+        Command::Start => dbus_service::call_running_instance("StartRecording"),
+        Command::Stop => dbus_service::call_running_instance("StopRecording"),
+        Command::Status { json } => print_status(json),
+        Command::InstallShortcut { binding } => {
+            let config = Config::load(config_path)?;
+            let accelerator = gnome_shortcut::install_shortcut(&config, binding)?;
+            println!("Installed GNOME shortcut {accelerator} for gnome-voice-input toggle.");
+            if config.hotkey.backend != config::HotkeyBackend::Gnome {
+                println!(
+                    "Set backend = \"gnome\" in the [hotkey] section so the app stops grabbing the key itself."
+                );
+            }
+            Ok(())
+        }
+        Command::SetKey => {
+            let api_key = setup_wizard::prompt("Deepgram API key: ")?;
+            if api_key.is_empty() {
+                bail!("No API key entered");
+            }
+            redact::register_secret(&api_key);
+            keyring::store_api_key(&api_key)?;
+            println!("API key stored in the keyring.");
+            println!(
+                "Set deepgram_api_key = \"{}\" in the config to use it.",
+                keyring::KEYRING_PLACEHOLDER
+            );
+            Ok(())
+        }
+        Command::Config {
+            command: ConfigCommand::Init { stdout, force },
+        } => {
+            if stdout {
+                print!("{}", config::CONFIG_TEMPLATE);
+                return Ok(());
+            }
+            let path = match config_path {
+                Some(path) => path,
+                None => Config::get_config_path(None)?,
+            };
+            Config::write_template(&path, force)?;
+            println!("Wrote example config to {}", path.display());
+            println!(
+                "Set deepgram_api_key (or run `gnome-voice-input set-key`) and adjust the options to your liking."
+            );
+            Ok(())
+        }
+        Command::Config {
+            command: ConfigCommand::Validate { check_key },
+        } => {
+            let problems = config_check::validate_config(config_path, check_key).await;
+            if problems > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::History {
+            command: HistoryCommand::Export { format, since },
+        } => {
+            let config = Config::load(config_path)?;
+            let history = history::History::load(&config.history);
+            let entries = history_export::select(history.entries(), since);
+            print!("{}", history_export::export(&entries, format)?);
+            Ok(())
+        }
+        Command::History {
+            command:
+                HistoryCommand::Search {
+                    query,
+                    since,
+                    until,
+                    copy,
+                },
+        } => {
+            let config = Config::load(config_path)?;
+            let history = history::History::load(&config.history);
+            let entries = history.search(&query, since, until);
+            if entries.is_empty() {
+                eprintln!("No transcripts match \"{query}\".");
+                std::process::exit(1);
+            }
+            for entry in &entries {
+                let text = entry.text.replace('\n', " ");
+                println!("{}  {}", entry.timestamp.format("%Y-%m-%d %H:%M"), text);
+            }
+            if copy {
+                desktop::copy_to_clipboard(&entries[0].text)?;
+                eprintln!(
+                    "Copied the transcript from {}.",
+                    entries[0].timestamp.format("%Y-%m-%d %H:%M")
+                );
+            }
+            Ok(())
+        }
+        Command::UninstallShortcut => {
+            if gnome_shortcut::uninstall_shortcut()? {
+                println!("Removed the GNOME shortcut.");
+            } else {
+                println!("No GNOME shortcut was installed.");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Print the state of the running instance for `status`, as text or as JSON
+/// that status bars such as Waybar read directly (`text`, `alt`, `class`,
+/// `tooltip`) with the full state alongside
+fn print_status(json: bool) -> Result<()> {
+    let status = dbus_service::running_instance_status()?;
+    let state = match &status {
+        None => "stopped".to_string(),
+        Some(status) if !status.enabled => "paused".to_string(),
+        Some(status) if !status.recording_state.is_empty() => status.recording_state.clone(),
+        Some(status) if status.recording => "recording".to_string(),
+        Some(_) => "idle".to_string(),
+    };
+    let status = status.unwrap_or_default();
+
+    if json {
+        let tooltip = if status.last_error.is_empty() {
+            &status.last_transcript
+        } else {
+            &status.last_error
+        };
+        let mut output = serde_json::json!({
+            "text": state,
+            "alt": state,
+            "class": state,
+            "tooltip": tooltip,
+            "state": state,
+        });
+        if let (Some(output), serde_json::Value::Object(details)) =
+            (output.as_object_mut(), serde_json::to_value(&status)?)
+        {
+            output.extend(details);
+        }
+        println!("{output}");
+        return Ok(());
+    }
+
+    println!("{state}");
+    if state == "stopped" {
+        return Ok(());
+    }
+    let profile = if status.profile.is_empty() {
+        String::new()
+    } else {
+        format!(", profile {}", status.profile)
+    };
+    println!(
+        "Language {}, model {}{}{}",
+        status.language,
+        status.model,
+        profile,
+        if status.muted { ", muted" } else { "" }
+    );
+    println!("Connection: {}", status.connection);
+    let latency = if status.average_latency_ms > 0 {
+        format!(", average latency {} ms", status.average_latency_ms)
+    } else {
+        String::new()
+    };
+    println!(
+        "Today: {:.1} min dictated, {} words{}",
+        status.minutes_today, status.words_today, latency
+    );
+    if !status.last_session_latency.is_empty() {
+        println!("Last session: {}", status.last_session_latency);
+    }
+    if !status.last_transcript.is_empty() {
+        println!("Last transcript: {}", status.last_transcript);
+    }
+    if !status.last_error.is_empty() {
+        println!("Last error: {}", status.last_error);
+    }
+    Ok(())
+}
+
+/// Log to stdout, or to stderr when stdout carries the transcript (`--once`)
+/// or command replies (`--headless`), plus the outputs in `[logging]`.
+/// The journal doesn't render colors, so they are off for `--service`, and
+/// stdout is left out when the service logs to the journal natively.
+fn init_logging(args: &Args) {
+    let debug = args.debug;
+    let logging_config = Config::load_logging(args.config.clone());
+    let (log_file, log_file_error) = if logging_config.file {
+        match logging::LogFile::open(&logging_config) {
+            Ok(file) => (Some(file), None),
+            Err(e) => (None, Some(e)),
+        }
+    } else {
+        (None, None)
+    };
+    let (journald, journald_error) = if logging_config.journald {
+        match logging::JournaldLayer::connect() {
+            Ok(layer) => (Some(layer), None),
+            Err(e) => (None, Some(e)),
+        }
+    } else {
+        (None, None)
+    };
+    let log_stdout = !(args.service && journald.is_some());
+    let log_file_path = log_file.as_ref().map(logging::LogFile::path);
+
+    let to_stderr = args.once || args.headless;
+    let writer = move || -> redact::Redacted<Box<dyn std::io::Write>> {
+        if to_stderr {
+            redact::Redacted(Box::new(std::io::stderr()))
+        } else {
+            redact::Redacted(Box::new(std::io::stdout()))
+        }
+    };
+
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
@@ -51,49 +471,137 @@ fn init_logging(debug: bool) {
                 }
             }),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(log_stdout.then(|| {
+            tracing_subscriber::fmt::layer()
+                .with_ansi(!args.service)
+                .with_writer(writer)
+        }))
+        .with(log_file.map(|file| {
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || redact::Redacted(file.clone()))
+        }))
+        .with(journald)
         .init();
 
     info!("Starting GNOME Voice Input");
+    if let Some(path) = log_file_path {
+        debug!("Logging to {}", path.display());
+    }
+    if let Some(e) = log_file_error {
+        warn!("Not logging to a file: {:#}", e);
+    }
+    if let Some(e) = journald_error {
+        warn!("Not logging to the journal: {:#}", e);
+    }
     if debug {
-        info!("Debug mode enabled - will save WAV files to current directory");
+        info!("Debug mode enabled - will save the audio sent to Deepgram and its responses as set in [debug]");
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    if let Some(command) = args.command.take() {
+        return run_command(command, args.config).await;
+    }
 
-    init_logging(args.debug);
+    init_logging(&args);
 
-    let config = Config::load(args.config.clone())?;
+    let config = match load_config(&args).await {
+        Ok(config) => config,
+        // Restarting the service won't fix the config, so exit with a status the unit doesn't restart on
+        Err(e) if args.service => {
+            error!("{:#}", e);
+            let summary = e.to_string();
+            let summary = summary.lines().next().unwrap_or_default();
+            systemd::notify(&format!("STATUS=Configuration error: {summary}"));
+            std::process::exit(systemd::EXIT_CONFIG);
+        }
+        Err(e) => return Err(e),
+    };
     let config_path = Config::get_config_path(args.config.clone())?;
     let shutdown_token = CancellationToken::new();
 
-    let app_state = AppState::new(
+    let mut app_state = AppState::new(
         config.clone(),
         args.debug,
         args.config.clone(),
         shutdown_token.clone(),
     );
+    app_state.duration_override = args.duration.map(std::time::Duration::from_secs);
+    app_state.dry_run = args.dry_run;
+    app_state.record_only = args.record_only;
+    if let Some(path) = &args.input_file {
+        if !args.input_speed.is_finite() || args.input_speed <= 0.0 {
+            bail!("--input-speed must be greater than 0");
+        }
+        app_state.input = Some(audio::AudioInput::File {
+            path: path.clone(),
+            speed: args.input_speed,
+        });
+    }
+    if let Some(signal) = args.input_signal {
+        app_state.input = Some(audio::AudioInput::Signal(signal));
+    }
+    if args.record_only {
+        let dir = storage::recordings_dir()?;
+        info!(
+            "Record-only mode: recordings are saved to {}",
+            dir.display()
+        );
+    }
+    if args.dry_run {
+        info!("Dry run: transcripts are logged, not typed");
+    }
+
+    if args.once {
+        return once::run_once(app_state, args.print).await;
+    }
 
     // Initialize all application components
-    let components =
-        initialize_app_components(config.clone(), app_state.clone(), &shutdown_token).await?;
+    let components = initialize_app_components(
+        config.clone(),
+        app_state.clone(),
+        &shutdown_token,
+        args.headless,
+    )
+    .await?;
+    if args.headless {
+        stdin_control::spawn_stdin_control(app_state.clone(), &shutdown_token);
+    }
+
+    let components = Arc::new(tokio::sync::Mutex::new(Some(components)));
 
     // Setup config watcher with access to components for reload
     let (config_reload_handle, _config_watcher) = config_watcher::setup_config_reload_handler(
         config_path,
         app_state.clone(),
-        components,
+        components.clone(),
         &shutdown_token,
     )?;
+    // Restart components that die and report persistent failures
+    supervisor::spawn_supervisor(app_state.clone(), components, &shutdown_token);
 
-    // Wait for shutdown signal
-    tokio::signal::ctrl_c().await?;
+    if args.service {
+        systemd::notify("READY=1");
+        systemd::spawn_watchdog(&shutdown_token);
+    }
+
+    // Wait for shutdown signal (Ctrl+C, SIGTERM from systemd, or `quit` on stdin)
+    let mut terminate = tokio::signal::unix::signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+        _ = shutdown_token.cancelled() => {}
+    }
 
     info!("Shutting down GNOME Voice Input");
+    if args.service {
+        systemd::notify("STOPPING=1");
+    }
     shutdown_token.cancel();
 
     // Wait for config reload handler to finish
@@ -102,19 +610,88 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Load the config, running the interactive setup on first launch instead of
+/// failing on a missing config
+async fn load_config(args: &Args) -> Result<Config> {
+    let needs_setup = args.config.is_none() && !Config::get_config_path(None)?.exists();
+    let mut config = if needs_setup && std::io::stdin().is_terminal() {
+        setup_wizard::run_setup_wizard().await?
+    } else {
+        Config::load(args.config.clone())?
+    };
+    if let Some(profile) = &args.profile {
+        config.apply_profile(Some(profile))?;
+    }
+    Ok(config)
+}
+
 pub async fn toggle_recording(app_state: AppState) {
-    let was_recording = app_state.recording.fetch_xor(true, Ordering::Relaxed);
-    let is_recording = !was_recording;
-
-    if is_recording {
-        info!("Starting recording");
-        let app_state_clone = app_state.clone();
-        tokio::spawn(async move {
-            if let Err(e) = audio::start_recording(app_state_clone).await {
-                error!("Recording error: {}", e);
-            }
-        });
+    if app_state.is_recording() {
+        stop_recording(&app_state);
     } else {
+        start_recording(app_state);
+    }
+}
+
+/// Start a recording session unless one is already running or voice input is paused
+pub fn start_recording(app_state: AppState) {
+    if !app_state.enabled.load(Ordering::Relaxed) {
+        info!("Voice input is paused, ignoring recording request");
+        return;
+    }
+
+    if !app_state.begin_recording() {
+        info!(
+            "Session still {}, ignoring recording request",
+            app_state.recording_state()
+        );
+        return;
+    }
+
+    info!("Starting recording");
+    let mut session = RecordingSession::new(app_state.clone());
+    if app_state.dry_run {
+        session = session.with_output(Box::new(LogTranscriptionHandler::new()));
+    }
+    tokio::spawn(session.run());
+}
+
+/// Start transcribing a meeting into a file (`[meeting]`) unless a session is
+/// already running or voice input is paused
+pub fn start_meeting(app_state: AppState) {
+    if !app_state.enabled.load(Ordering::Relaxed) {
+        info!("Voice input is paused, ignoring meeting request");
+        return;
+    }
+
+    if !app_state.begin_recording() {
+        info!(
+            "Session still {}, ignoring meeting request",
+            app_state.recording_state()
+        );
+        return;
+    }
+
+    info!("Starting meeting transcript");
+    let mut session = RecordingSession::new(app_state.clone()).meeting();
+    if app_state.dry_run {
+        session = session.with_output(Box::new(LogTranscriptionHandler::new()));
+    }
+    tokio::spawn(session.run());
+}
+
+/// Stop the current recording session, if any
+pub fn stop_recording(app_state: &AppState) {
+    if app_state.stop_recording() {
         info!("Stopping recording");
     }
 }
+
+/// Abort the current recording session, discarding its pending results and
+/// removing interim text that was already typed
+pub fn cancel_recording(app_state: &AppState) {
+    app_state.session_discard.read().unwrap().cancel();
+    if app_state.stop_recording() {
+        info!("Cancelling recording");
+    }
+}
@@ -0,0 +1,122 @@
+//! Pauses MPRIS media players (Spotify, browsers, ...) while recording so that
+//! their sound doesn't reach the microphone, and resumes them afterwards.
+
+use crate::{
+    config::Config,
+    state::{AppEvent, AppState},
+};
+use dbus::arg::Variant;
+use dbus::blocking::LocalConnection;
+use eyre::{Result, WrapErr};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Pause playing media players while recording, if enabled in the
+/// configuration. Returns the thread watching the recording state.
+pub fn setup_media_pause(
+    config: &Config,
+    app_state: AppState,
+    shutdown_token: &CancellationToken,
+) -> Option<std::thread::JoinHandle<()>> {
+    if !config.ui.pause_media {
+        return None;
+    }
+
+    let media_shutdown_token = shutdown_token.child_token();
+    Some(std::thread::spawn(move || {
+        info!("Starting media pause thread");
+        if let Err(e) = run_media_pause(app_state, media_shutdown_token) {
+            warn!("Media players can't be paused: {:#}", e);
+        }
+        info!("Media pause thread exiting");
+    }))
+}
+
+fn playback_status(conn: &LocalConnection, player: &str) -> Option<String> {
+    conn.with_proxy(player, MPRIS_PATH, TIMEOUT)
+        .method_call(
+            PROPERTIES_INTERFACE,
+            "Get",
+            (PLAYER_INTERFACE, "PlaybackStatus"),
+        )
+        .map(|(Variant(status),): (Variant<String>,)| status)
+        .ok()
+}
+
+fn call_player(conn: &LocalConnection, player: &str, method: &str) {
+    let result: Result<(), dbus::Error> =
+        conn.with_proxy(player, MPRIS_PATH, TIMEOUT)
+            .method_call(PLAYER_INTERFACE, method, ());
+    if let Err(e) = result {
+        warn!("Failed to {} {}: {}", method.to_lowercase(), player, e);
+    }
+}
+
+/// Pause every playing player; returns the paused ones
+fn pause_players(conn: &LocalConnection) -> Result<Vec<String>> {
+    let (names,): (Vec<String>,) = conn
+        .with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", TIMEOUT)
+        .method_call("org.freedesktop.DBus", "ListNames", ())
+        .wrap_err("Failed to list bus names")?;
+    let mut paused = Vec::new();
+    for player in names
+        .into_iter()
+        .filter(|name| name.starts_with(MPRIS_PREFIX))
+    {
+        if playback_status(conn, &player).as_deref() == Some("Playing") {
+            info!("Pausing {} while recording", player);
+            call_player(conn, &player, "Pause");
+            paused.push(player);
+        }
+    }
+    Ok(paused)
+}
+
+/// Resume the players paused for recording, unless they were stopped or
+/// started again meanwhile
+fn resume_players(conn: &LocalConnection, paused: &mut Vec<String>) {
+    for player in paused.drain(..) {
+        if playback_status(conn, &player).as_deref() == Some("Paused") {
+            info!("Resuming {}", player);
+            call_player(conn, &player, "Play");
+        }
+    }
+}
+
+fn run_media_pause(app_state: AppState, shutdown_token: CancellationToken) -> Result<()> {
+    let conn = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+    let mut paused: Vec<String> = Vec::new();
+
+    let mut events = app_state.events.subscribe();
+    while !shutdown_token.is_cancelled() {
+        let event = match events.try_recv() {
+            Ok(event) => event,
+            Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                debug!("Media pause skipped {} events", skipped);
+                continue;
+            }
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+        };
+        match event {
+            AppEvent::RecordingStarted(_) => match pause_players(&conn) {
+                Ok(players) => paused.extend(players),
+                Err(e) => warn!("Failed to pause media players: {:#}", e),
+            },
+            AppEvent::RecordingStopped(_) => resume_players(&conn, &mut paused),
+            _ => {}
+        }
+    }
+
+    resume_players(&conn, &mut paused);
+    Ok(())
+}
@@ -0,0 +1,288 @@
+//! Process-wide counters and histograms of the dictation pipeline (sessions,
+//! audio streamed, transcripts, typing latency, connections, reconnects,
+//! errors), served
+//! in the Prometheus text format when `[metrics] address` is set.
+
+use crate::state::AppEvent;
+use eyre::{Result, WrapErr};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+/// Metrics of this process, updated by the instrumented modules
+pub static METRICS: Metrics = Metrics::new();
+
+/// Upper bounds of the histogram buckets, in seconds
+const BUCKETS: [f64; 10] = [0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Longest request read before answering, headers included
+const MAX_REQUEST_BYTES: usize = 8192;
+
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Distribution of durations over the fixed `BUCKETS`
+pub struct Histogram {
+    /// Observations per bucket, not cumulative
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(
+            duration.as_micros().try_into().unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+pub struct Metrics {
+    pub sessions: Counter,
+    /// Audio sent to the transcription backend, in microseconds
+    pub audio_streamed_micros: Counter,
+    pub interim_transcripts: Counter,
+    pub final_transcripts: Counter,
+    /// From the end of the spoken audio to its final transcript
+    pub transcription_latency: Histogram,
+    /// Time taken to type one transcript
+    pub typing_latency: Histogram,
+    pub connections: Counter,
+    pub connection_failures: Counter,
+    /// Connections re-established after dropping during a recording
+    pub reconnects: Counter,
+    pub audio_errors: Counter,
+    pub transcription_errors: Counter,
+    pub keyboard_errors: Counter,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            sessions: Counter::new(),
+            audio_streamed_micros: Counter::new(),
+            interim_transcripts: Counter::new(),
+            final_transcripts: Counter::new(),
+            transcription_latency: Histogram::new(),
+            typing_latency: Histogram::new(),
+            connections: Counter::new(),
+            connection_failures: Counter::new(),
+            reconnects: Counter::new(),
+            audio_errors: Counter::new(),
+            transcription_errors: Counter::new(),
+            keyboard_errors: Counter::new(),
+        }
+    }
+
+    /// Count `bytes` of 16 bit mono audio at `sample_rate` as streamed
+    pub fn record_audio_streamed(&self, bytes: usize, sample_rate: u32) {
+        if sample_rate > 0 {
+            self.audio_streamed_micros
+                .add(bytes as u64 / 2 * 1_000_000 / u64::from(sample_rate));
+        }
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        counter(
+            &mut out,
+            "sessions_total",
+            "Recording sessions started",
+            &[("", self.sessions.get() as f64)],
+        );
+        counter(
+            &mut out,
+            "audio_streamed_seconds_total",
+            "Seconds of audio sent to the transcription backend",
+            &[("", self.audio_streamed_micros.get() as f64 / 1e6)],
+        );
+        counter(
+            &mut out,
+            "transcripts_total",
+            "Transcripts received from the backend",
+            &[
+                ("kind=\"interim\"", self.interim_transcripts.get() as f64),
+                ("kind=\"final\"", self.final_transcripts.get() as f64),
+            ],
+        );
+        histogram(
+            &mut out,
+            "transcription_latency_seconds",
+            "Time from the end of the spoken audio to its final transcript",
+            &self.transcription_latency,
+        );
+        histogram(
+            &mut out,
+            "typing_latency_seconds",
+            "Time taken to type a transcript",
+            &self.typing_latency,
+        );
+        counter(
+            &mut out,
+            "connections_total",
+            "Connections opened to the transcription backend",
+            &[("", self.connections.get() as f64)],
+        );
+        counter(
+            &mut out,
+            "connection_failures_total",
+            "Failed attempts to connect to the transcription backend",
+            &[("", self.connection_failures.get() as f64)],
+        );
+        counter(
+            &mut out,
+            "reconnects_total",
+            "Reconnects after the connection dropped during a recording",
+            &[("", self.reconnects.get() as f64)],
+        );
+        counter(
+            &mut out,
+            "errors_total",
+            "Errors by component",
+            &[
+                ("component=\"audio\"", self.audio_errors.get() as f64),
+                (
+                    "component=\"transcription\"",
+                    self.transcription_errors.get() as f64,
+                ),
+                ("component=\"keyboard\"", self.keyboard_errors.get() as f64),
+            ],
+        );
+        out
+    }
+}
+
+const PREFIX: &str = "gnome_voice_input_";
+
+fn counter(out: &mut String, name: &str, help: &str, samples: &[(&str, f64)]) {
+    let _ = writeln!(out, "# HELP {PREFIX}{name} {help}");
+    let _ = writeln!(out, "# TYPE {PREFIX}{name} counter");
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{PREFIX}{name} {value}");
+        } else {
+            let _ = writeln!(out, "{PREFIX}{name}{{{labels}}} {value}");
+        }
+    }
+}
+
+fn histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    let _ = writeln!(out, "# HELP {PREFIX}{name} {help}");
+    let _ = writeln!(out, "# TYPE {PREFIX}{name} histogram");
+    let mut cumulative = 0;
+    for (bound, bucket) in BUCKETS.iter().zip(&histogram.buckets) {
+        cumulative += bucket.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{PREFIX}{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+    }
+    let count = histogram.count.load(Ordering::Relaxed);
+    let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+    let _ = writeln!(out, "{PREFIX}{name}_bucket{{le=\"+Inf\"}} {count}");
+    let _ = writeln!(out, "{PREFIX}{name}_sum {sum}");
+    let _ = writeln!(out, "{PREFIX}{name}_count {count}");
+}
+
+/// Serve `GET /metrics` on `address` until the token is cancelled, counting
+/// the sessions started on the event bus meanwhile
+pub async fn serve(
+    address: SocketAddr,
+    mut events: broadcast::Receiver<AppEvent>,
+    shutdown_token: CancellationToken,
+) -> Result<()> {
+    let listener = TcpListener::bind(address)
+        .await
+        .wrap_err_with(|| format!("Failed to listen on {address}"))?;
+    info!("Serving metrics at http://{}/metrics", address);
+
+    loop {
+        tokio::select! {
+            _ = shutdown_token.cancelled() => return Ok(()),
+            event = events.recv() => match event {
+                Ok(AppEvent::RecordingStarted(_)) => METRICS.sessions.inc(),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("Metrics skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream).await {
+                            debug!("Metrics request from {} failed: {:#}", peer, e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept metrics connection: {}", e),
+            },
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream) -> Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+            .await
+            .wrap_err("Timed out reading the request")??;
+        if read == 0 || request.len() + read > MAX_REQUEST_BYTES {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request_line = String::from_utf8_lossy(&request);
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", METRICS.render()),
+        (Some("GET"), _) => (
+            "404 Not Found",
+            "Metrics are served at /metrics\n".to_string(),
+        ),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
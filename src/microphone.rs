@@ -0,0 +1,110 @@
+//! Mute state and volume of the default PulseAudio/PipeWire source, read and
+//! set with `pactl`: a muted microphone is unmuted for a recording
+//! (`[audio] unmute`) and muted again afterwards.
+
+use crate::config::AudioConfig;
+use eyre::{OptionExt, Result, WrapErr};
+use std::process::{Command, Stdio};
+
+const SOURCE: &str = "@DEFAULT_SOURCE@";
+
+fn pactl(args: &[&str]) -> Result<String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .wrap_err("Failed to run pactl, is it installed?")?;
+    if !output.status.success() {
+        bail!("pactl {} failed with {}", args.join(" "), output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Whether `pactl get-source-mute` reported "Mute: yes"
+pub fn parse_mute(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("Mute:")?.trim() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Volume in percent of the first channel in `pactl get-source-volume`
+/// output, e.g. 50 for "Volume: front-left: 32768 /  50% / -18.06 dB, ..."
+pub fn parse_volume(output: &str) -> Option<u32> {
+    let percent = output.split('%').next()?;
+    let digits = percent.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+    digits.parse().ok()
+}
+
+/// What was changed on the source for a recording; undone when dropped
+#[derive(Debug)]
+pub struct MicrophoneGuard {
+    was_muted: bool,
+    /// The volume before it was raised
+    volume: Option<u32>,
+}
+
+impl MicrophoneGuard {
+    /// Unmute the default source and raise its volume to `unmute_volume` if
+    /// configured; None when nothing had to change. This is always the
+    /// default source, also when `device` selects another input: cpal's
+    /// device names don't identify a PulseAudio source.
+    pub fn unmute(audio_config: &AudioConfig) -> Result<Option<Self>> {
+        if !audio_config.unmute {
+            return Ok(None);
+        }
+        let was_muted = parse_mute(&pactl(&["get-source-mute", SOURCE])?)
+            .ok_or_eyre("Unexpected pactl get-source-mute output")?;
+        if was_muted {
+            info!("Unmuting the microphone for recording");
+            pactl(&["set-source-mute", SOURCE, "0"])?;
+        }
+
+        // Mutes the source again if raising the volume fails
+        let mut guard = Self {
+            was_muted,
+            volume: None,
+        };
+        if let Some(level) = audio_config.unmute_volume {
+            if let Err(e) = guard.raise_volume(level) {
+                warn!("Failed to raise the microphone volume: {:#}", e);
+            }
+        }
+        Ok((guard.was_muted || guard.volume.is_some()).then_some(guard))
+    }
+
+    /// Raise the volume to `level` percent if it is lower, remembering the
+    /// volume to restore
+    fn raise_volume(&mut self, level: u32) -> Result<()> {
+        let current = parse_volume(&pactl(&["get-source-volume", SOURCE])?)
+            .ok_or_eyre("Unexpected pactl get-source-volume output")?;
+        if current < level {
+            info!(
+                "Raising the microphone volume from {}% to {}%",
+                current, level
+            );
+            pactl(&["set-source-volume", SOURCE, &format!("{level}%")])?;
+            self.volume = Some(current);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MicrophoneGuard {
+    fn drop(&mut self) {
+        if let Some(volume) = self.volume {
+            debug!("Restoring the microphone volume to {}%", volume);
+            if let Err(e) = pactl(&["set-source-volume", SOURCE, &format!("{volume}%")]) {
+                warn!("Failed to restore the microphone volume: {:#}", e);
+            }
+        }
+        if self.was_muted {
+            info!("Muting the microphone again");
+            if let Err(e) = pactl(&["set-source-mute", SOURCE, "1"]) {
+                warn!("Failed to mute the microphone again: {:#}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{
+    config::Config,
+    redact::redact,
+    state::{AppEvent, AppState},
+};
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::LocalConnection;
+use dbus::message::MatchRule;
+use eyre::{Result, WrapErr};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+const NOTIFICATIONS_BUS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
+const APP_NAME: &str = "GNOME Voice Input";
+const STOP_ACTION: &str = "stop";
+
+/// Show a transient notification
+pub fn notify(summary: &str, body: &str) -> Result<()> {
+    let conn = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+    let proxy = conn.with_proxy(
+        NOTIFICATIONS_BUS_NAME,
+        NOTIFICATIONS_PATH,
+        Duration::from_secs(5),
+    );
+
+    let mut hints = PropMap::new();
+    hints.insert(
+        "transient".to_string(),
+        Variant(Box::new(true) as Box<dyn RefArg>),
+    );
+    let _: (u32,) = proxy
+        .method_call(
+            NOTIFICATIONS_INTERFACE,
+            "Notify",
+            (
+                APP_NAME,
+                0u32,
+                "audio-input-microphone",
+                summary,
+                body,
+                Vec::<&str>::new(),
+                hints,
+                -1i32,
+            ),
+        )
+        .wrap_err("Failed to show notification")?;
+    Ok(())
+}
+
+/// Show a notification with a "Stop" action while recording, if enabled in the
+/// configuration. Returns the thread driving the notification.
+pub fn setup_recording_notification(
+    config: &Config,
+    app_state: AppState,
+    shutdown_token: &CancellationToken,
+) -> Option<std::thread::JoinHandle<()>> {
+    if !config.ui.recording_notification {
+        info!("Recording notification disabled in configuration");
+        return None;
+    }
+
+    let notification_shutdown_token = shutdown_token.child_token();
+    Some(std::thread::spawn(move || {
+        info!("Starting recording notification thread");
+        if let Err(e) = run_notifications(app_state, notification_shutdown_token) {
+            warn!("Recording notifications unavailable: {:#}", e);
+        }
+        info!("Recording notification thread exiting");
+    }))
+}
+
+fn run_notifications(app_state: AppState, shutdown_token: CancellationToken) -> Result<()> {
+    let conn = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+    let proxy = conn.with_proxy(
+        NOTIFICATIONS_BUS_NAME,
+        NOTIFICATIONS_PATH,
+        Duration::from_secs(5),
+    );
+
+    // Id of the notification currently shown, if any
+    let notification_id = Rc::new(Cell::new(None::<u32>));
+
+    let action_id = notification_id.clone();
+    let action_state = app_state.clone();
+    conn.add_match(
+        MatchRule::new_signal(NOTIFICATIONS_INTERFACE, "ActionInvoked"),
+        move |(id, action): (u32, String), _, _| {
+            if action_id.get() == Some(id) && action == STOP_ACTION {
+                info!("Stop recording requested from notification");
+                crate::stop_recording(&action_state);
+            }
+            true
+        },
+    )
+    .wrap_err("Failed to subscribe to notification actions")?;
+
+    let closed_id = notification_id.clone();
+    conn.add_match(
+        MatchRule::new_signal(NOTIFICATIONS_INTERFACE, "NotificationClosed"),
+        move |(id, _reason): (u32, u32), _, _| {
+            if closed_id.get() == Some(id) {
+                closed_id.set(None);
+            }
+            true
+        },
+    )
+    .wrap_err("Failed to subscribe to closed notifications")?;
+
+    let mut events = app_state.events.subscribe();
+    while !shutdown_token.is_cancelled() {
+        conn.process(Duration::from_millis(100))?;
+
+        let event = match events.try_recv() {
+            Ok(event) => event,
+            Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                debug!("Recording notification skipped {} events", skipped);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        let recording = match event {
+            AppEvent::RecordingStarted(_) => true,
+            AppEvent::RecordingStopped(_) => false,
+            AppEvent::BackendError {
+                session: Some(_),
+                message,
+            } => {
+                let body = redact(&message).into_owned();
+                if let Err(e) = notify("Recording failed", &body) {
+                    warn!("Failed to show error notification: {:#}", e);
+                }
+                continue;
+            }
+            AppEvent::InactivityTimeout(_) => {
+                if let Err(e) = notify(
+                    "Dictation timed out",
+                    "Nothing was transcribed for a while, recording stopped",
+                ) {
+                    warn!("Failed to show timeout notification: {:#}", e);
+                }
+                continue;
+            }
+            AppEvent::Reconnecting(_) => {
+                if let Err(e) = notify(
+                    "Connection lost",
+                    "Reconnecting to Deepgram, recording continues",
+                ) {
+                    warn!("Failed to show reconnect notification: {:#}", e);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        if recording && notification_id.get().is_none() {
+            let mut hints = PropMap::new();
+            hints.insert(
+                "resident".to_string(),
+                Variant(Box::new(true) as Box<dyn RefArg>),
+            );
+            hints.insert(
+                "urgency".to_string(),
+                Variant(Box::new(1u8) as Box<dyn RefArg>),
+            );
+
+            let shown: Result<(u32,), _> = proxy.method_call(
+                NOTIFICATIONS_INTERFACE,
+                "Notify",
+                (
+                    APP_NAME,
+                    0u32,
+                    "audio-input-microphone",
+                    "Recording",
+                    "Voice input is listening",
+                    vec![STOP_ACTION, "Stop"],
+                    hints,
+                    0i32,
+                ),
+            );
+            match shown {
+                Ok((id,)) => notification_id.set(Some(id)),
+                Err(e) => warn!("Failed to show recording notification: {}", e),
+            }
+        } else if let Some(id) = notification_id.take() {
+            let closed: Result<(), _> =
+                proxy.method_call(NOTIFICATIONS_INTERFACE, "CloseNotification", (id,));
+            if let Err(e) = closed {
+                warn!("Failed to close recording notification: {}", e);
+            }
+        }
+    }
+
+    if let Some(id) = notification_id.take() {
+        let _: Result<(), _> =
+            proxy.method_call(NOTIFICATIONS_INTERFACE, "CloseNotification", (id,));
+    }
+    Ok(())
+}
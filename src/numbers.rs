@@ -0,0 +1,221 @@
+//! Locale-aware numbers: rewrites the US-style numerals of Deepgram's
+//! formatting ("1,234.5", "$12.50", "50%", "3.5km") with the separators,
+//! currency placement and spacing of another locale.
+
+/// Currency symbols moved according to the locale
+const CURRENCY_SYMBOLS: [char; 3] = ['$', '€', '£'];
+
+/// Units written after a number; separated from it by a space. Not "s",
+/// which also makes decades ("1990s").
+const UNITS: [&str; 20] = [
+    "mm", "cm", "m", "km", "mg", "g", "kg", "ml", "l", "ms", "min", "h", "kb", "mb", "gb", "tb",
+    "kw", "kwh", "°c", "°f",
+];
+
+/// Locales `NumberFormat::for_locale` knows
+pub const SUPPORTED_LOCALES: [&str; 9] = ["en", "de", "de-CH", "fr", "es", "it", "nl", "pt", "pl"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurrencyPosition {
+    /// "$12.50"
+    Before,
+    /// "€ 12,50"
+    BeforeSpaced,
+    /// "12,50 €"
+    After,
+}
+
+/// How a locale writes numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    pub group_separator: char,
+    pub currency: CurrencyPosition,
+    /// "50 %" rather than "50%"
+    pub space_before_percent: bool,
+}
+
+impl NumberFormat {
+    /// The conventions of a locale such as "de", "de-DE" or "fr_CH"; None
+    /// for locales not in `SUPPORTED_LOCALES`
+    pub fn for_locale(locale: &str) -> Option<Self> {
+        let locale = locale.to_lowercase().replace('_', "-");
+        let (language, region) = locale.split_once('-').unwrap_or((&locale, ""));
+        let (decimal_separator, group_separator, currency, space_before_percent) =
+            match (language, region) {
+                ("en", _) => ('.', ',', CurrencyPosition::Before, false),
+                ("de", "ch") => ('.', '\'', CurrencyPosition::BeforeSpaced, true),
+                ("de", _) => (',', '.', CurrencyPosition::After, true),
+                ("fr", _) => (',', ' ', CurrencyPosition::After, true),
+                ("es" | "it" | "pt", _) => (',', '.', CurrencyPosition::After, true),
+                ("nl", _) => (',', '.', CurrencyPosition::BeforeSpaced, false),
+                ("pl", _) => (',', ' ', CurrencyPosition::After, true),
+                _ => return None,
+            };
+        Some(Self {
+            decimal_separator,
+            group_separator,
+            currency,
+            space_before_percent,
+        })
+    }
+}
+
+/// A numeral as written by Deepgram
+#[derive(Debug, Default)]
+struct Numeral {
+    currency: Option<char>,
+    integer: String,
+    /// Whether the integer part had group separators; none are added to
+    /// numbers written without, such as years
+    grouped: bool,
+    fraction: Option<String>,
+    suffix: Suffix,
+}
+
+#[derive(Debug, Default)]
+enum Suffix {
+    #[default]
+    None,
+    Percent,
+    Unit(String),
+}
+
+impl Numeral {
+    fn format(&self, format: &NumberFormat) -> String {
+        let mut number = String::new();
+        let digits = self.integer.len();
+        for (i, digit) in self.integer.chars().enumerate() {
+            if self.grouped && i > 0 && (digits - i).is_multiple_of(3) {
+                number.push(format.group_separator);
+            }
+            number.push(digit);
+        }
+        if let Some(fraction) = &self.fraction {
+            number.push(format.decimal_separator);
+            number.push_str(fraction);
+        }
+
+        let number = match self.currency {
+            Some(symbol) => match format.currency {
+                CurrencyPosition::Before => format!("{symbol}{number}"),
+                CurrencyPosition::BeforeSpaced => format!("{symbol} {number}"),
+                CurrencyPosition::After => format!("{number} {symbol}"),
+            },
+            None => number,
+        };
+        match &self.suffix {
+            Suffix::None => number,
+            Suffix::Percent if format.space_before_percent => format!("{number} %"),
+            Suffix::Percent => format!("{number}%"),
+            Suffix::Unit(unit) => format!("{number} {unit}"),
+        }
+    }
+}
+
+fn digits_at(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .count()
+}
+
+/// Parse the numeral starting at `start`; None when the text there is not a
+/// plain number (a version, time, identifier, ...). Returns the numeral and
+/// the index after it.
+fn parse(chars: &[char], start: usize) -> Option<(Numeral, usize)> {
+    let mut numeral = Numeral::default();
+    let mut i = start;
+    if CURRENCY_SYMBOLS.contains(&chars[i]) {
+        numeral.currency = Some(chars[i]);
+        i += 1;
+    }
+
+    let leading = digits_at(chars, i);
+    if leading == 0 {
+        return None;
+    }
+    numeral.integer.extend(&chars[i..i + leading]);
+    i += leading;
+    // US grouping: one to three digits, then groups of exactly three
+    while leading <= 3 && chars.get(i) == Some(&',') && digits_at(chars, i + 1) == 3 {
+        numeral.grouped = true;
+        numeral.integer.extend(&chars[i + 1..i + 4]);
+        i += 4;
+    }
+    if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(char::is_ascii_digit) {
+        let fraction = digits_at(chars, i + 1);
+        numeral.fraction = Some(chars[i + 1..i + 1 + fraction].iter().collect());
+        i += 1 + fraction;
+    }
+
+    match chars.get(i) {
+        Some('%') => {
+            numeral.suffix = Suffix::Percent;
+            i += 1;
+        }
+        Some(symbol) if numeral.currency.is_none() && CURRENCY_SYMBOLS.contains(symbol) => {
+            numeral.currency = Some(*symbol);
+            i += 1;
+        }
+        Some(c) if c.is_alphabetic() || *c == '°' => {
+            let length = 1 + chars[i + 1..]
+                .iter()
+                .take_while(|c| c.is_alphabetic())
+                .count();
+            let unit: String = chars[i..i + length].iter().collect();
+            if !UNITS.contains(&unit.to_lowercase().as_str()) {
+                return None;
+            }
+            numeral.suffix = Suffix::Unit(unit);
+            i += length;
+        }
+        _ => {}
+    }
+
+    // Must be followed by the end, a space or closing punctuation; a dot,
+    // colon or comma followed by a digit belongs to a version, time or list
+    match chars.get(i) {
+        None => Some((numeral, i)),
+        Some(c) if c.is_whitespace() || matches!(c, ')' | '"' | '-' | '!' | '?' | ';') => {
+            Some((numeral, i))
+        }
+        Some('.' | ',' | ':') if !chars.get(i + 1).is_some_and(char::is_ascii_digit) => {
+            Some((numeral, i))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a numeral may start at `index`: at the start of a word
+fn starts_word(chars: &[char], index: usize) -> bool {
+    index == 0 || matches!(chars[index - 1], ' ' | '\t' | '\n' | '(' | '"' | '-' | '+')
+}
+
+/// Rewrite the US-style numbers, currencies, percentages and units in `text`
+/// with the conventions of `format`
+pub fn format(text: &str, format: &NumberFormat) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut formatted = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let candidate = c.is_ascii_digit() || CURRENCY_SYMBOLS.contains(&c);
+        if candidate && starts_word(&chars, i) {
+            if let Some((numeral, end)) = parse(&chars, i) {
+                formatted.push_str(&numeral.format(format));
+                i = end;
+                continue;
+            }
+            // Not a plain number: keep the rest of the word as it is
+            while i < chars.len() && !chars[i].is_whitespace() {
+                formatted.push(chars[i]);
+                i += 1;
+            }
+            continue;
+        }
+        formatted.push(c);
+        i += 1;
+    }
+    formatted
+}
@@ -0,0 +1,176 @@
+//! `--once`: record a single utterance right away, type or print it and exit,
+//! without tray, hotkeys or the D-Bus service.
+
+use crate::{
+    audio, focused_window,
+    handlers::{
+        process_transcription_with_handler, FilterHandler, HistoryHandler, TranscriptionHandler,
+        UsageStatsHandler,
+    },
+    history::HistoryContext,
+    keyboard, pipeline, privacy, session,
+    state::AppState,
+    transcription_utils::{Transcript, TranscriptionResult},
+};
+use async_trait::async_trait;
+use eyre::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Pause after speech that ends the utterance
+const UTTERANCE_SILENCE: Duration = Duration::from_millis(1000);
+
+/// Collects the final results and outputs them once the session ends
+struct OutputHandler {
+    print: bool,
+    dry_run: bool,
+    text: Vec<String>,
+}
+
+#[async_trait]
+impl TranscriptionHandler for OutputHandler {
+    async fn on_interim_result(&mut self, _transcript: Transcript) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.text.push(transcript.text);
+        Ok(())
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        if self.text.is_empty() {
+            bail!("No speech recognized");
+        }
+        let text = self.text.join(" ");
+        if self.print {
+            println!("{text}");
+        } else if self.dry_run {
+            info!("Dry run, would type: '{}'", privacy::loggable(&text));
+        } else {
+            keyboard::type_text(&text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Record until the speaker pauses (or Ctrl+C), then type the transcript, or
+/// print it to stdout with `print`
+pub async fn run_once(app_state: AppState, print: bool) -> Result<()> {
+    app_state.begin_recording();
+
+    let recording = app_state.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("Interrupted, finishing the utterance");
+            recording.stop_recording();
+        }
+    });
+
+    if let Some(max_duration) = app_state.max_recording_duration() {
+        let recording = app_state.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(max_duration).await;
+            if recording.stop_recording() {
+                info!("Maximum recording duration reached, stopping");
+            }
+        });
+    }
+
+    let (audio_rx, format) = audio::start_capture(&app_state).await?;
+
+    // Without transcription there is no pause detection, recording ends on
+    // Ctrl+C or after --duration
+    if app_state.record_only {
+        info!("Recording, press Ctrl+C to finish");
+        if let Some(path) = audio::save_recording(&app_state, audio_rx, format).await? {
+            println!("{}", path.display());
+        }
+        return Ok(());
+    }
+
+    let mut transcription = app_state.config().transcription.clone();
+    session::apply_usage_cap(&app_state, &mut transcription)?;
+    let cloud = transcription.base_url.is_none();
+
+    let (transcriber, history_context, filters, post_processing) = {
+        let config = app_state.config();
+        let history_context = HistoryContext {
+            language: Some(transcription.language.clone()),
+            app: focused_window::focused_window()
+                .ok()
+                .flatten()
+                .and_then(|window| window.classes.last().cloned()),
+            api_keys: Some(app_state.api_keys())
+                .filter(|api_keys| cloud && api_keys.keys().len() > 1),
+        };
+        let filters = pipeline::filters(&config.output, &transcription);
+        let transcriber = app_state
+            .session_transcriber(transcription, false)
+            .with_utterance_end(UTTERANCE_SILENCE);
+        (
+            transcriber,
+            history_context,
+            filters,
+            config.post_processing.clone(),
+        )
+    };
+    let transcription_rx = Arc::new(transcriber)
+        .transcribe_stream(audio_rx, format)
+        .await?;
+    info!("Listening, pause to finish");
+
+    // Stop capturing at the first pause after some speech; the remaining
+    // results arrive before the transcription stream closes
+    let (results_tx, results_rx) = mpsc::channel(10);
+    let recording = app_state.clone();
+    tokio::spawn(async move {
+        let mut transcription_rx = transcription_rx;
+        let mut heard_speech = false;
+        while let Some(result) = transcription_rx.recv().await {
+            match result {
+                TranscriptionResult::Final(_) => heard_speech = true,
+                TranscriptionResult::UtteranceEnd if heard_speech => {
+                    debug!("Utterance ended, stopping capture");
+                    recording.stop_recording();
+                }
+                _ => {}
+            }
+            if results_tx.send(result).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let dictation_started = std::time::Instant::now();
+    let handler = FilterHandler::new(
+        pipeline::post_processed(
+            UsageStatsHandler::new(
+                HistoryHandler::new(
+                    OutputHandler {
+                        print,
+                        dry_run: app_state.dry_run,
+                        text: Vec::new(),
+                    },
+                    app_state.history.clone(),
+                    history_context,
+                ),
+                app_state.stats.clone(),
+            ),
+            &post_processing,
+        ),
+        filters,
+    );
+    let result =
+        process_transcription_with_handler(results_rx, handler, CancellationToken::new()).await;
+
+    app_state
+        .stats
+        .record_dictation(dictation_started.elapsed(), cloud);
+    if let Err(e) = app_state.stats.save() {
+        warn!("Failed to save usage statistics: {:#}", e);
+    }
+    result
+}
@@ -0,0 +1,341 @@
+use crate::config::Config;
+use crate::portal;
+use eyre::{Result, WrapErr};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Arc as XArc, Char2b, ConnectionExt, CreateGCAux, CreateWindowAux, EventMask, Gcontext,
+    Rectangle, Window, WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::COPY_DEPTH_FROM_PARENT;
+
+const OVERLAY_WIDTH: u16 = 640;
+const OVERLAY_HEIGHT: u16 = 36;
+const OVERLAY_TOP_MARGIN: i16 = 48;
+const INDICATOR_SIZE: u16 = 14;
+const TEXT_X: i16 = 36;
+/// Only the tail of long interim transcripts fits into the overlay
+const MAX_VISIBLE_CHARS: usize = 60;
+/// Level meter at the right edge, one bar per refresh tick
+const METER_BARS: usize = 24;
+const METER_BAR_WIDTH: u16 = 3;
+const METER_BAR_GAP: u16 = 1;
+const METER_MARGIN: u16 = 12;
+/// Input levels below this (in dBFS) are drawn as silence
+const METER_FLOOR_DB: f32 = -60.0;
+const OVERLAY_FONT: &str = "-misc-fixed-medium-r-normal--18-*-*-*-*-*-iso10646-1";
+const FALLBACK_FONT: &str = "fixed";
+
+enum OverlayCommand {
+    Show,
+    Hide,
+    SetText(String),
+}
+
+/// Handle to the on-screen recording overlay running in its own thread
+#[derive(Clone)]
+pub struct Overlay {
+    tx: mpsc::Sender<OverlayCommand>,
+}
+
+impl Overlay {
+    pub fn show(&self) {
+        let _ = self.tx.send(OverlayCommand::Show);
+    }
+
+    pub fn hide(&self) {
+        let _ = self.tx.send(OverlayCommand::Hide);
+    }
+
+    pub fn set_text(&self, text: impl Into<String>) {
+        let _ = self.tx.send(OverlayCommand::SetText(text.into()));
+    }
+}
+
+/// Create the recording overlay if enabled in the configuration.
+/// Returns the overlay handle and the thread driving the X11 window.
+pub fn setup_overlay(
+    config: &Config,
+    input_level: Arc<AtomicU32>,
+    shutdown_token: &CancellationToken,
+) -> Option<(Overlay, std::thread::JoinHandle<()>)> {
+    if !config.ui.show_overlay {
+        info!("Recording overlay disabled in configuration");
+        return None;
+    }
+    if portal::is_portal_only() {
+        info!("Recording overlay needs X11, not shown in portal-only mode");
+        return None;
+    }
+
+    match OverlayWindow::create(input_level) {
+        Ok(window) => {
+            let (tx, rx) = mpsc::channel();
+            let overlay_shutdown_token = shutdown_token.child_token();
+            let handle = std::thread::spawn(move || {
+                info!("Starting overlay thread");
+                window.run(rx, overlay_shutdown_token);
+                info!("Overlay thread exiting");
+            });
+            Some((Overlay { tx }, handle))
+        }
+        Err(e) => {
+            warn!("Failed to create recording overlay: {:#}", e);
+            None
+        }
+    }
+}
+
+struct OverlayWindow {
+    conn: RustConnection,
+    window: Window,
+    text_gc: Gcontext,
+    indicator_gc: Gcontext,
+    input_level: Arc<AtomicU32>,
+    /// Recent meter heights (0.0..=1.0), oldest first
+    levels: VecDeque<f32>,
+    text: String,
+    visible: bool,
+}
+
+impl OverlayWindow {
+    fn create(input_level: Arc<AtomicU32>) -> Result<Self> {
+        let (conn, screen_num) =
+            RustConnection::connect(None).wrap_err("Failed to connect to X server")?;
+        let screen = conn.setup().roots[screen_num].clone();
+
+        let window = conn.generate_id()?;
+        let x = (screen.width_in_pixels.saturating_sub(OVERLAY_WIDTH) / 2) as i16;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            screen.root,
+            x,
+            OVERLAY_TOP_MARGIN,
+            OVERLAY_WIDTH,
+            OVERLAY_HEIGHT,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            // Override-redirect keeps the window manager from decorating or focusing it
+            &CreateWindowAux::new()
+                .background_pixel(screen.black_pixel)
+                .override_redirect(1)
+                .event_mask(EventMask::EXPOSURE),
+        )?;
+
+        let font = conn.generate_id()?;
+        if conn
+            .open_font(font, OVERLAY_FONT.as_bytes())?
+            .check()
+            .is_err()
+        {
+            debug!("Overlay font not available, falling back to '{FALLBACK_FONT}'");
+            conn.open_font(font, FALLBACK_FONT.as_bytes())?
+                .check()
+                .wrap_err("Failed to open overlay font")?;
+        }
+
+        let text_gc = conn.generate_id()?;
+        conn.create_gc(
+            text_gc,
+            window,
+            &CreateGCAux::new()
+                .foreground(screen.white_pixel)
+                .background(screen.black_pixel)
+                .font(font),
+        )?;
+
+        let red = conn
+            .alloc_color(screen.default_colormap, 0xffff, 0x3000, 0x3000)?
+            .reply()
+            .wrap_err("Failed to allocate indicator color")?
+            .pixel;
+        let indicator_gc = conn.generate_id()?;
+        conn.create_gc(indicator_gc, window, &CreateGCAux::new().foreground(red))?;
+
+        conn.close_font(font)?;
+        conn.flush()?;
+
+        Ok(Self {
+            conn,
+            window,
+            text_gc,
+            indicator_gc,
+            input_level,
+            levels: VecDeque::from(vec![0.0; METER_BARS]),
+            text: String::new(),
+            visible: false,
+        })
+    }
+
+    fn run(mut self, rx: mpsc::Receiver<OverlayCommand>, shutdown_token: CancellationToken) {
+        while !shutdown_token.is_cancelled() {
+            let result = match rx.recv_timeout(Duration::from_millis(50)) {
+                Ok(command) => self.apply(command),
+                Err(mpsc::RecvTimeoutError::Timeout) => Ok(()),
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            .and_then(|_| self.process_events())
+            .and_then(|_| self.update_meter());
+
+            if let Err(e) = result {
+                warn!("Overlay error, closing overlay: {:#}", e);
+                break;
+            }
+        }
+
+        let _ = self.conn.destroy_window(self.window);
+        let _ = self.conn.flush();
+    }
+
+    fn apply(&mut self, command: OverlayCommand) -> Result<()> {
+        match command {
+            OverlayCommand::Show => {
+                self.text.clear();
+                self.levels.iter_mut().for_each(|level| *level = 0.0);
+                self.visible = true;
+                self.conn.map_window(self.window)?;
+            }
+            OverlayCommand::Hide => {
+                self.visible = false;
+                self.conn.unmap_window(self.window)?;
+            }
+            OverlayCommand::SetText(text) => self.text = text,
+        }
+
+        if self.visible {
+            self.draw()?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn process_events(&mut self) -> Result<()> {
+        while let Some(event) = self.conn.poll_for_event()? {
+            if let Event::Expose(_) = event {
+                self.draw()?;
+                self.conn.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sample the current input level and redraw the meter
+    fn update_meter(&mut self) -> Result<()> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let rms = f32::from_bits(self.input_level.load(Ordering::Relaxed));
+        self.levels.pop_front();
+        self.levels.push_back(meter_height(rms));
+
+        self.draw_meter()?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn draw_meter(&self) -> Result<()> {
+        let meter_width = METER_BARS as u16 * (METER_BAR_WIDTH + METER_BAR_GAP);
+        let meter_x = (OVERLAY_WIDTH - METER_MARGIN - meter_width) as i16;
+        let max_bar_height = OVERLAY_HEIGHT - 2 * 8;
+        let center_y = (OVERLAY_HEIGHT / 2) as i16;
+
+        self.conn
+            .clear_area(false, self.window, meter_x, 0, meter_width, OVERLAY_HEIGHT)?;
+
+        // Bars grow symmetrically around the vertical center, at least 2px tall
+        let bars: Vec<Rectangle> = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(i, level)| {
+                let height = ((level * max_bar_height as f32) as u16).max(2);
+                Rectangle {
+                    x: meter_x + (i as u16 * (METER_BAR_WIDTH + METER_BAR_GAP)) as i16,
+                    y: center_y - (height / 2) as i16,
+                    width: METER_BAR_WIDTH,
+                    height,
+                }
+            })
+            .collect();
+        self.conn
+            .poly_fill_rectangle(self.window, self.text_gc, &bars)?;
+
+        Ok(())
+    }
+
+    fn draw(&self) -> Result<()> {
+        self.conn.clear_area(false, self.window, 0, 0, 0, 0)?;
+
+        // Mic indicator
+        let indicator_y = ((OVERLAY_HEIGHT - INDICATOR_SIZE) / 2) as i16;
+        self.conn.poly_fill_arc(
+            self.window,
+            self.indicator_gc,
+            &[XArc {
+                x: 12,
+                y: indicator_y,
+                width: INDICATOR_SIZE,
+                height: INDICATOR_SIZE,
+                angle1: 0,
+                angle2: 360 * 64,
+            }],
+        )?;
+
+        let text = if self.text.is_empty() {
+            "Listening..."
+        } else {
+            &self.text
+        };
+        let chars = to_char2b(visible_tail(text));
+        let baseline = (OVERLAY_HEIGHT / 2 + 6) as i16;
+        self.conn
+            .image_text16(self.window, self.text_gc, TEXT_X, baseline, &chars)?;
+
+        self.draw_meter()?;
+
+        Ok(())
+    }
+}
+
+/// Map an RMS level to a meter height on a logarithmic (dB) scale
+fn meter_height(rms: f32) -> f32 {
+    if rms <= 0.0 {
+        return 0.0;
+    }
+    let db = 20.0 * rms.log10();
+    ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0)
+}
+
+/// Return the last characters of `text` that fit into the overlay
+fn visible_tail(text: &str) -> &str {
+    let char_count = text.chars().count();
+    if char_count <= MAX_VISIBLE_CHARS {
+        return text;
+    }
+
+    let (offset, _) = text
+        .char_indices()
+        .nth(char_count - MAX_VISIBLE_CHARS)
+        .unwrap_or((0, ' '));
+    &text[offset..]
+}
+
+/// Encode text for ImageText16, replacing characters outside the BMP
+fn to_char2b(text: &str) -> Vec<Char2b> {
+    text.chars()
+        .map(|c| {
+            let code = u16::try_from(u32::from(c)).unwrap_or(u16::from(b'?'));
+            let [byte1, byte2] = code.to_be_bytes();
+            Char2b { byte1, byte2 }
+        })
+        .collect()
+}
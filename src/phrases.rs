@@ -0,0 +1,54 @@
+//! Whole-phrase replacement shared by the emoji and template filters.
+
+use std::collections::BTreeMap;
+
+/// Lowercase letters and digits of a word, ignoring punctuation
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Replace the phrases of `table` (matched as whole words, ignoring case and
+/// punctuation) with their replacement, longest phrase first. Punctuation
+/// after a phrase is kept.
+pub fn replace(text: &str, table: &BTreeMap<String, String>) -> String {
+    let phrases: Vec<(Vec<String>, &String)> = table
+        .iter()
+        .map(|(phrase, replacement)| {
+            let words = phrase.split_whitespace().map(normalize).collect::<Vec<_>>();
+            (words, replacement)
+        })
+        .filter(|(words, _)| !words.is_empty())
+        .collect();
+    let Some(longest) = phrases.iter().map(|(words, _)| words.len()).max() else {
+        return text.to_string();
+    };
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let normalized: Vec<String> = words.iter().map(|word| normalize(word)).collect();
+
+    let mut replaced = Vec::with_capacity(words.len());
+    let mut i = 0;
+    while i < words.len() {
+        let matched = (1..=longest.min(words.len() - i)).rev().find_map(|count| {
+            phrases
+                .iter()
+                .find(|(phrase, _)| phrase[..] == normalized[i..i + count])
+                .map(|(_, replacement)| (count, *replacement))
+        });
+        match matched {
+            Some((count, replacement)) => {
+                let last = words[i + count - 1];
+                let trailing = &last[last.trim_end_matches(|c: char| !c.is_alphanumeric()).len()..];
+                replaced.push(format!("{replacement}{trailing}"));
+                i += count;
+            }
+            None => {
+                replaced.push(words[i].to_string());
+                i += 1;
+            }
+        }
+    }
+    replaced.join(" ")
+}
@@ -0,0 +1,100 @@
+//! PII redaction: email addresses, phone and credit card numbers and
+//! configured patterns replaced by a placeholder such as "[email]" before a
+//! transcript is typed or stored.
+
+use crate::config::{PiiConfig, PiiKind};
+use eyre::{Result, WrapErr};
+use regex::Regex;
+
+const EMAIL: &str = r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b";
+
+/// 13 to 19 digits, optionally in groups; confirmed with the Luhn checksum
+const CARD_NUMBER: &str = r"\b\d(?:[ -]?\d){12,18}\b";
+
+/// International ("+49 30 1234567"), area code in parentheses or with a
+/// trunk prefix ("030 1234567"), or US style ("555-123-4567")
+const PHONE_NUMBER: &str = r"\+\d[\d ./()-]{5,}\d|\(\d{2,5}\) ?\d[\d .-]{4,}\d|\b0\d{2,5}[ /.-]?\d[\d .-]{4,}\d|\b\d{3}[-. ]\d{3}[-. ]\d{4}\b";
+
+/// Digits a phone number has, to tell it from other numbers
+const PHONE_DIGITS: std::ops::RangeInclusive<usize> = 7..=15;
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    regex: Regex,
+    placeholder: String,
+    kind: Option<PiiKind>,
+}
+
+/// The compiled patterns of a `[output.pii]` section
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    patterns: Vec<Pattern>,
+}
+
+fn luhn_valid(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+impl Redactor {
+    /// Compile the enabled kinds and the configured patterns; fails for an
+    /// invalid pattern
+    pub fn new(config: &PiiConfig) -> Result<Self> {
+        let mut patterns = Vec::new();
+        for kind in &config.kinds {
+            let (pattern, placeholder) = match kind {
+                PiiKind::Email => (EMAIL, "[email]"),
+                PiiKind::CreditCard => (CARD_NUMBER, "[card number]"),
+                PiiKind::Phone => (PHONE_NUMBER, "[phone number]"),
+            };
+            patterns.push(Pattern {
+                regex: Regex::new(pattern).expect("built-in PII pattern is valid"),
+                placeholder: placeholder.to_string(),
+                kind: Some(*kind),
+            });
+        }
+        for (name, pattern) in &config.patterns {
+            patterns.push(Pattern {
+                regex: Regex::new(pattern)
+                    .wrap_err_with(|| format!("Invalid PII pattern {name:?}"))?,
+                placeholder: format!("[{name}]"),
+                kind: None,
+            });
+        }
+        Ok(Self { patterns })
+    }
+
+    /// Replace every match in `text` with its placeholder
+    pub fn redact(&self, text: &str) -> String {
+        let mut redacted = text.to_string();
+        for pattern in &self.patterns {
+            redacted = pattern
+                .regex
+                .replace_all(&redacted, |captures: &regex::Captures| {
+                    let matched = &captures[0];
+                    let digits: Vec<u32> = matched.chars().filter_map(|c| c.to_digit(10)).collect();
+                    let confirmed = match pattern.kind {
+                        Some(PiiKind::CreditCard) => luhn_valid(&digits),
+                        Some(PiiKind::Phone) => PHONE_DIGITS.contains(&digits.len()),
+                        _ => true,
+                    };
+                    if confirmed {
+                        pattern.placeholder.clone()
+                    } else {
+                        matched.to_string()
+                    }
+                })
+                .into_owned();
+        }
+        redacted
+    }
+}
@@ -0,0 +1,168 @@
+//! The registry behind the `[output]` and `[post_processing]` sections:
+//! builds the filters, post-processors and handlers of a recording session,
+//! and the handler of a meeting session.
+
+use crate::config::{
+    expand_home, InterimDisplay, MeetingConfig, OutputConfig, OutputFilter, OutputHandler,
+    PostProcessingConfig, TranscriptionConfig,
+};
+use crate::emoji;
+use crate::filters::{
+    Capitalize, Emoji, Replacements, SentenceCase, Templates, TranscriptFilter, Vocabulary,
+};
+use crate::handlers::{
+    AtspiTranscriptionHandler, BufferTranscriptionHandler, ConsoleTranscriptionHandler,
+    FileTranscriptionHandler, FocusGuardHandler, KeyboardTranscriptionHandler, LlmCleanupHandler,
+    LogTranscriptionHandler, MeetingTranscriptionHandler, PrimarySelectionHandler, ReviewHandler,
+    SubtitleTranscriptionHandler, TranscriptionHandler, TypingQueueHandler,
+};
+use crate::llm::LlmClient;
+use crate::numbers::NumberFormat;
+use crate::pii::Redactor;
+use crate::profanity::Blocklist;
+use crate::spelling::SpellMode;
+use crate::templates;
+use eyre::{OptionExt, Result};
+use std::time::Duration;
+
+/// The configured filters, with the replacements of the session's
+/// transcription settings (which profiles and languages add to)
+pub fn filters(
+    output: &OutputConfig,
+    transcription: &TranscriptionConfig,
+) -> Vec<Box<dyn TranscriptFilter>> {
+    let transcription = transcription.for_current_language();
+    let mut filters: Vec<Box<dyn TranscriptFilter>> = Vec::new();
+    for filter in &output.filters {
+        match filter {
+            OutputFilter::Replacements if transcription.replacements.is_empty() => {}
+            OutputFilter::Replacements => {
+                filters.push(Box::new(Replacements(transcription.replacements.clone())))
+            }
+            OutputFilter::Capitalize => filters.push(Box::new(Capitalize)),
+            OutputFilter::SentenceCase => filters.push(Box::new(SentenceCase)),
+            OutputFilter::Emoji => filters.push(Box::new(Emoji(emoji::table(&output.emoji)))),
+            OutputFilter::Templates => {
+                filters.push(Box::new(Templates(templates::table(&output.templates))))
+            }
+            OutputFilter::Numbers => {
+                let locale = output
+                    .number_locale
+                    .as_deref()
+                    .unwrap_or(&transcription.language);
+                match NumberFormat::for_locale(locale) {
+                    Some(format) => filters.push(Box::new(format)),
+                    None => debug!("No number format for {}, not formatting numbers", locale),
+                }
+            }
+            OutputFilter::Profanity => filters.push(Box::new(Blocklist::new(
+                output.profanity.mode,
+                &output.profanity.words,
+            ))),
+            // The patterns were checked by `Config::load`
+            OutputFilter::Pii => match Redactor::new(&output.pii) {
+                Ok(redactor) => filters.push(Box::new(redactor)),
+                Err(e) => error!("Not redacting PII: {:#}", e),
+            },
+            OutputFilter::Spell => filters.push(Box::new(SpellMode::new(&output.spell))),
+            OutputFilter::Vocabulary if transcription.vocabulary.is_empty() => {}
+            OutputFilter::Vocabulary => {
+                filters.push(Box::new(Vocabulary(transcription.vocabulary.clone())))
+            }
+        }
+    }
+    filters
+}
+
+/// `handler` typing into the focused window behind the typing queue, stopped
+/// by a focus change and reviewed first if configured
+fn typing(
+    handler: impl TranscriptionHandler + 'static,
+    output: &OutputConfig,
+) -> Box<dyn TranscriptionHandler> {
+    let mut handler: Box<dyn TranscriptionHandler> = if output.abort_on_focus_change {
+        Box::new(FocusGuardHandler::new(handler))
+    } else {
+        Box::new(handler)
+    };
+    if output.review.enabled {
+        let auto_insert = output.review.auto_insert_secs.map(Duration::from_secs);
+        handler = Box::new(ReviewHandler::new(handler, auto_insert));
+    }
+    Box::new(TypingQueueHandler::new(handler, &output.typing))
+}
+
+/// The configured handlers as one, each receiving every transcript
+pub fn handlers(
+    output: &OutputConfig,
+    use_interim_results: bool,
+) -> Result<Box<dyn TranscriptionHandler>> {
+    // The overlay shows interim results either way
+    let type_interim_results =
+        use_interim_results && output.interim_display == InterimDisplay::Typed;
+    let mut handlers: Vec<Box<dyn TranscriptionHandler>> = Vec::new();
+    for handler in &output.handlers {
+        handlers.push(match handler {
+            OutputHandler::Keyboard => typing(
+                KeyboardTranscriptionHandler::new(type_interim_results)
+                    .with_chars_per_second(output.typing.chars_per_second)
+                    .with_layout(output.typing.layout.clone()),
+                output,
+            ),
+            OutputHandler::Atspi => typing(
+                AtspiTranscriptionHandler::new(type_interim_results)
+                    .with_chars_per_second(output.typing.chars_per_second)
+                    .with_layout(output.typing.layout.clone()),
+                output,
+            ),
+            OutputHandler::Buffer => Box::new(BufferTranscriptionHandler::new()),
+            OutputHandler::File => {
+                let path = output
+                    .file
+                    .as_deref()
+                    .ok_or_eyre("The `file` output handler needs a path in `[output] file`")?;
+                Box::new(FileTranscriptionHandler::open(&expand_home(path)?)?)
+            }
+            OutputHandler::Subtitles => {
+                let directory = output.subtitles.directory.as_deref().ok_or_eyre(
+                    "The `subtitles` output handler needs `[output.subtitles] directory`",
+                )?;
+                Box::new(SubtitleTranscriptionHandler::new(
+                    expand_home(directory)?,
+                    output.subtitles.format,
+                ))
+            }
+            OutputHandler::Primary => Box::new(PrimarySelectionHandler::new()),
+            OutputHandler::Log => Box::new(LogTranscriptionHandler::new()),
+            OutputHandler::Console => Box::new(ConsoleTranscriptionHandler::new()),
+        });
+    }
+    Ok(Box::new(handlers))
+}
+
+/// The handler of a meeting session, writing the transcript file
+pub fn meeting(meeting: &MeetingConfig) -> Result<Box<dyn TranscriptionHandler>> {
+    let directory = match &meeting.directory {
+        Some(directory) => expand_home(directory)?,
+        None => dirs::document_dir()
+            .or_else(dirs::home_dir)
+            .ok_or_eyre("Could not determine the documents directory")?
+            .join("Meetings"),
+    };
+    Ok(Box::new(MeetingTranscriptionHandler::create(
+        &directory,
+        meeting.format,
+    )?))
+}
+
+/// `inner` behind the configured post-processors, which see the transcripts
+/// after the filters
+pub fn post_processed(
+    inner: impl TranscriptionHandler + 'static,
+    post_processing: &PostProcessingConfig,
+) -> Box<dyn TranscriptionHandler> {
+    match &post_processing.llm {
+        Some(llm) => Box::new(LlmCleanupHandler::new(inner, LlmClient::new(llm.clone()))),
+        None => Box::new(inner),
+    }
+}
@@ -0,0 +1,141 @@
+//! XDG desktop portal requests over D-Bus, and the portal-only mode in which
+//! hotkeys and typing go through portals alone, as needed in a Flatpak
+//! sandbox without access to X11 or `/dev/uinput`.
+
+use crate::config::DesktopAccess;
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::{Connection, Proxy};
+use dbus::message::MatchRule;
+use eyre::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+pub const PATH: &str = "/org/freedesktop/portal/desktop";
+pub const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+pub const SESSION_INTERFACE: &str = "org.freedesktop.portal.Session";
+/// Requests may show a confirmation dialog, so give the user time to answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+static PORTAL_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Whether the app runs in a Flatpak sandbox
+pub fn is_sandboxed() -> bool {
+    std::path::Path::new("/.flatpak-info").exists() || std::env::var_os("FLATPAK_ID").is_some()
+}
+
+/// Choose portal or direct backends for hotkeys and typing, detecting a
+/// sandbox with `DesktopAccess::Auto`. Called once at startup.
+pub fn select_backends(access: DesktopAccess) {
+    let portal_only = match access {
+        DesktopAccess::Auto => is_sandboxed(),
+        DesktopAccess::Direct => false,
+        DesktopAccess::Portal => true,
+    };
+    if portal_only {
+        info!("Portal-only mode: hotkeys through GlobalShortcuts, typing through RemoteDesktop");
+    }
+    PORTAL_ONLY.store(portal_only, Ordering::Relaxed);
+}
+
+/// Whether hotkeys and typing go through portals only, see [`select_backends`]
+pub fn is_portal_only() -> bool {
+    PORTAL_ONLY.load(Ordering::Relaxed)
+}
+
+/// Whether a portal implementation is on the session bus
+pub fn is_available() -> Result<bool> {
+    let conn = Connection::new_session()?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(500),
+    );
+    let (has_owner,): (bool,) =
+        proxy.method_call("org.freedesktop.DBus", "NameHasOwner", (BUS_NAME,))?;
+    Ok(has_owner)
+}
+
+/// Run a portal method that answers through a Request object and wait for
+/// its Response signal. `call` receives the portal proxy and the options
+/// (already containing the handle token) and returns the request path.
+pub fn request(
+    conn: &Connection,
+    name: &str,
+    call: impl FnOnce(&Proxy<'_, &Connection>, PropMap) -> Result<dbus::Path<'static>>,
+) -> Result<PropMap> {
+    let token = handle_token(name);
+
+    // Subscribe before calling so a fast response can't be missed
+    let sender = conn.unique_name().trim_start_matches(':').replace('.', "_");
+    let expected_path = dbus::Path::from(format!("{PATH}/request/{sender}/{token}"));
+    let response = Arc::new(Mutex::new(None));
+    let response_slot = response.clone();
+    let match_token = conn.add_match(
+        MatchRule::new_signal(REQUEST_INTERFACE, "Response").with_path(expected_path.clone()),
+        move |(code, results): (u32, PropMap), _, _| {
+            *response_slot.lock().unwrap() = Some((code, results));
+            false
+        },
+    )?;
+
+    let mut options = PropMap::new();
+    options.insert(
+        "handle_token".to_string(),
+        Variant(Box::new(token) as Box<dyn RefArg>),
+    );
+    let proxy = conn.with_proxy(BUS_NAME, PATH, Duration::from_secs(5));
+    let request = call(&proxy, options)?;
+    if request != expected_path {
+        warn!("Portal returned unexpected request path {}", request);
+    }
+
+    let started = Instant::now();
+    let result = loop {
+        if let Some(result) = response.lock().unwrap().take() {
+            break result;
+        }
+        if started.elapsed() > REQUEST_TIMEOUT {
+            let _ = conn.remove_match(match_token);
+            bail!("Timed out waiting for the portal to respond");
+        }
+        conn.process(Duration::from_millis(100))?;
+    };
+
+    match result {
+        (0, results) => Ok(results),
+        (1, _) => bail!("Portal request '{name}' was cancelled by the user"),
+        (code, _) => bail!("Portal request '{name}' failed with response code {code}"),
+    }
+}
+
+/// Create a session with `interface`'s CreateSession method
+pub fn create_session(conn: &Connection, interface: &str) -> Result<dbus::Path<'static>> {
+    let results = request(conn, "session", |proxy, mut options| {
+        options.insert(
+            "session_handle_token".to_string(),
+            Variant(Box::new(handle_token("session")) as Box<dyn RefArg>),
+        );
+        let (request,): (dbus::Path<'static>,) = proxy
+            .method_call(interface, "CreateSession", (options,))
+            .map_err(|e| eyre!("Failed to create {interface} session: {e}"))?;
+        Ok(request)
+    })?;
+    results
+        .get("session_handle")
+        .and_then(|handle| handle.0.as_str())
+        .map(|handle| dbus::Path::from(handle.to_string()))
+        .ok_or_else(|| eyre!("Portal did not return a session handle"))
+}
+
+/// Close a session created with [`create_session`]
+pub fn close_session(conn: &Connection, session: &dbus::Path<'static>) {
+    let _: Result<(), _> = conn
+        .with_proxy(BUS_NAME, session, Duration::from_secs(5))
+        .method_call(SESSION_INTERFACE, "Close", ());
+}
+
+pub fn handle_token(name: &str) -> String {
+    format!("gnome_voice_input_{}_{}", name, std::process::id())
+}
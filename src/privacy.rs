@@ -0,0 +1,31 @@
+//! Privacy mode: nothing said is kept. The history records no transcripts,
+//! debug mode writes no recordings or response dumps, and log messages show
+//! how long a transcript was instead of its text.
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Switch privacy mode, see `AppState::set_privacy` for the app's switch
+pub fn set_enabled(enabled: bool) {
+    if ENABLED.swap(enabled, Ordering::Relaxed) != enabled {
+        info!(
+            "Privacy mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+    }
+}
+
+/// `text` for a log message, or only its length in privacy mode
+pub fn loggable(text: &str) -> Cow<'_, str> {
+    if is_enabled() {
+        Cow::Owned(format!("<{} characters>", text.chars().count()))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
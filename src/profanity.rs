@@ -0,0 +1,93 @@
+//! Profanity masking: words of a blocklist masked ("f***") or removed before
+//! anything is typed, whatever the backend's own filtering does.
+
+use crate::config::ProfanityMode;
+
+/// Blocked without configuration; `[output.profanity] words` adds to these.
+/// A trailing `*` also blocks words starting with the rest.
+const BUILTIN: [&str; 14] = [
+    "fuck*",
+    "motherfuck*",
+    "shit*",
+    "bullshit",
+    "asshole*",
+    "bitch*",
+    "bastard*",
+    "cunt*",
+    "dick",
+    "dickhead*",
+    "piss",
+    "pissed",
+    "wank*",
+    "twat*",
+];
+
+/// The words to block and what to do with them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blocklist {
+    mode: ProfanityMode,
+    /// Lowercase words, `*` suffix for prefixes
+    words: Vec<String>,
+}
+
+impl Blocklist {
+    /// The built-in words with `configured` added
+    pub fn new(mode: ProfanityMode, configured: &[String]) -> Self {
+        let words = BUILTIN
+            .iter()
+            .map(|word| word.to_string())
+            .chain(configured.iter().map(|word| word.trim().to_lowercase()))
+            .filter(|word| !word.is_empty())
+            .collect();
+        Self { mode, words }
+    }
+
+    fn is_blocked(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.words
+            .iter()
+            .any(|blocked| match blocked.strip_suffix('*') {
+                Some(prefix) => word.starts_with(prefix),
+                None => word == *blocked,
+            })
+    }
+
+    /// Mask or remove the blocked words of `text`, keeping the punctuation
+    /// around them
+    pub fn filter(&self, text: &str) -> String {
+        let mut filtered = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(char::is_alphanumeric) {
+            let (before, word_onwards) = rest.split_at(start);
+            let end = word_onwards
+                .find(|c: char| !c.is_alphanumeric() && c != '\'')
+                .unwrap_or(word_onwards.len());
+            let (word, after) = word_onwards.split_at(end);
+            rest = after;
+
+            if !self.is_blocked(word) {
+                filtered.push_str(before);
+                filtered.push_str(word);
+                continue;
+            }
+            match self.mode {
+                ProfanityMode::Mask => {
+                    filtered.push_str(before);
+                    let mut chars = word.chars();
+                    filtered.extend(chars.next());
+                    filtered.extend(chars.map(|_| '*'));
+                }
+                // Drop the space before the word, or after it at the start
+                ProfanityMode::Remove => match before.strip_suffix(' ') {
+                    Some(before) => filtered.push_str(before),
+                    None => {
+                        filtered.push_str(before);
+                        rest = rest.strip_prefix(' ').unwrap_or(rest);
+                    }
+                },
+            }
+        }
+        filtered.push_str(rest);
+        filtered
+    }
+}
@@ -0,0 +1,137 @@
+//! Masking of secrets such as the Deepgram API key in log output and error
+//! messages shown to the user.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::RwLock;
+
+/// Config keys whose values are masked wherever they appear in text, e.g.
+/// in the snippet of a parse error: the Deepgram key or keys and the LLM key
+/// of `[post_processing.llm]`
+const SECRET_KEYS: &[&str] = &["deepgram_api_key", "deepgram_api_keys", "api_key"];
+
+/// Values shorter than this are not secrets worth masking (e.g. "keyring")
+const MIN_SECRET_LEN: usize = 8;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Mask `secret` in everything redacted from now on
+pub fn register_secret(secret: &str) {
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// Masked form of a secret that still lets the user tell keys apart
+pub fn mask(secret: &str) -> String {
+    let count = secret.chars().count();
+    if count < MIN_SECRET_LEN {
+        return "****".to_string();
+    }
+    let visible: String = secret.chars().skip(count - 4).collect();
+    format!("****{visible}")
+}
+
+/// `text` with registered secrets and values of secret config keys masked
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let mut redacted = Cow::Borrowed(text);
+    for secret in SECRETS.read().unwrap().iter() {
+        if redacted.contains(secret.as_str()) {
+            redacted = Cow::Owned(redacted.replace(secret.as_str(), &mask(secret)));
+        }
+    }
+    for key in SECRET_KEYS {
+        if redacted.contains(key) {
+            redacted = Cow::Owned(mask_key_values(&redacted, key));
+        }
+    }
+    redacted
+}
+
+/// Mask the values in `key = "value"` and `key = ["value", ...]`
+/// assignments of `key` as a whole word
+fn mask_key_values(text: &str, key: &str) -> String {
+    let is_key_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(key) {
+        let (before, after) = rest.split_at(index + key.len());
+        result.push_str(before);
+        if before[..index].ends_with(is_key_char) || after.starts_with(is_key_char) {
+            rest = after;
+            continue;
+        }
+
+        let assignment = after.trim_start_matches([' ', '\t']);
+        let Some(value) = assignment.strip_prefix('=') else {
+            rest = after;
+            continue;
+        };
+        let value = value.trim_start_matches([' ', '\t']);
+        result.push_str(&after[..after.len() - value.len()]);
+        rest = match value.strip_prefix('[') {
+            Some(items) => {
+                result.push('[');
+                mask_items(items, &mut result)
+            }
+            None => mask_value(value, &mut result),
+        };
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Append the masked form of the value `value` starts with, returning the
+/// text after it (from its closing quote on)
+fn mask_value<'a>(value: &'a str, result: &mut String) -> &'a str {
+    let quote_len = usize::from(value.starts_with(['"', '\'']));
+    let (quote, value) = value.split_at(quote_len);
+    let end = value
+        .find(|c: char| c == '"' || c == '\'' || c.is_whitespace())
+        .unwrap_or(value.len());
+    result.push_str(quote);
+    if end > 0 {
+        result.push_str(&mask(&value[..end]));
+    }
+    &value[end..]
+}
+
+/// Append the array items `items` starts with, each quoted string masked,
+/// returning the text from the closing bracket on. The array may span lines
+/// and be cut off, as in an error snippet.
+fn mask_items<'a>(mut items: &'a str, result: &mut String) -> &'a str {
+    loop {
+        let item = items.trim_start_matches([' ', '\t', '\r', '\n', ',']);
+        result.push_str(&items[..items.len() - item.len()]);
+        let Some(quote) = item.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            return item;
+        };
+        let Some(end) = item[1..].find(quote) else {
+            return mask_value(item, result);
+        };
+        result.push(quote);
+        result.push_str(&mask(&item[1..1 + end]));
+        result.push(quote);
+        items = &item[end + 2..];
+    }
+}
+
+/// Output of the log formatter (stdout or stderr), with secrets masked
+pub struct Redacted<W>(pub W);
+
+impl<W: Write> Write for Redacted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The formatter writes each event in one call, so secrets are not split
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
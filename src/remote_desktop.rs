@@ -0,0 +1,173 @@
+//! Keyboard input through the XDG RemoteDesktop portal, used for typing in
+//! portal-only mode. The portal asks once for permission; the restore token
+//! it hands out is kept in the state directory so later sessions start
+//! without asking again.
+
+use crate::{portal, storage};
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::Connection;
+use enigo::Key;
+use eyre::{Result, WrapErr};
+use std::time::Duration;
+
+const REMOTE_DESKTOP_INTERFACE: &str = "org.freedesktop.portal.RemoteDesktop";
+const RESTORE_TOKEN_FILE: &str = "remote-desktop-token";
+/// Device type bit of the keyboard in SelectDevices
+const KEYBOARD_DEVICE: u32 = 1;
+/// Keep the permission until it is revoked (persist_mode)
+const PERSIST_UNTIL_REVOKED: u32 = 2;
+
+const KEYSYM_BACKSPACE: i32 = 0xff08;
+const KEYSYM_TAB: i32 = 0xff09;
+const KEYSYM_RETURN: i32 = 0xff0d;
+const KEYSYM_ESCAPE: i32 = 0xff1b;
+const KEYSYM_SHIFT: i32 = 0xffe1;
+const KEYSYM_CONTROL: i32 = 0xffe3;
+const KEYSYM_ALT: i32 = 0xffe9;
+const KEYSYM_SUPER: i32 = 0xffeb;
+
+/// A started RemoteDesktop session with keyboard access
+pub struct RemoteDesktop {
+    conn: Connection,
+    session: dbus::Path<'static>,
+}
+
+impl RemoteDesktop {
+    /// Create a session and start it, which may ask the user for permission
+    pub fn start() -> Result<Self> {
+        let conn = Connection::new_session().wrap_err("Failed to connect to session bus")?;
+        let session = portal::create_session(&conn, REMOTE_DESKTOP_INTERFACE)?;
+
+        let select_session = session.clone();
+        let restore_token = load_restore_token();
+        portal::request(&conn, "devices", move |proxy, mut options| {
+            options.insert("types".to_string(), variant(KEYBOARD_DEVICE));
+            options.insert("persist_mode".to_string(), variant(PERSIST_UNTIL_REVOKED));
+            if let Some(token) = restore_token {
+                options.insert("restore_token".to_string(), variant(token));
+            }
+            let (request,): (dbus::Path<'static>,) = proxy
+                .method_call(
+                    REMOTE_DESKTOP_INTERFACE,
+                    "SelectDevices",
+                    (select_session, options),
+                )
+                .wrap_err("Failed to select the keyboard device")?;
+            Ok(request)
+        })?;
+
+        let start_session = session.clone();
+        let results = portal::request(&conn, "start", move |proxy, options| {
+            let (request,): (dbus::Path<'static>,) = proxy
+                .method_call(
+                    REMOTE_DESKTOP_INTERFACE,
+                    "Start",
+                    (start_session, "", options),
+                )
+                .wrap_err("Failed to start the remote desktop session")?;
+            Ok(request)
+        })?;
+        let devices = results
+            .get("devices")
+            .and_then(|devices| devices.0.as_u64())
+            .unwrap_or_default();
+        if devices & u64::from(KEYBOARD_DEVICE) == 0 {
+            portal::close_session(&conn, &session);
+            bail!("The RemoteDesktop portal did not grant keyboard access");
+        }
+        if let Some(token) = results
+            .get("restore_token")
+            .and_then(|token| token.0.as_str())
+        {
+            save_restore_token(token);
+        }
+
+        info!("Typing through the RemoteDesktop portal");
+        Ok(Self { conn, session })
+    }
+
+    /// Press and release the key of `ch`
+    pub fn type_char(&self, ch: char) -> Result<()> {
+        self.click(keysym(ch))
+    }
+
+    /// Press or release `key`
+    pub fn key(&self, key: Key, pressed: bool) -> Result<()> {
+        self.notify(key_keysym(key)?, pressed)
+    }
+
+    /// Press and release `key`
+    pub fn click_key(&self, key: Key) -> Result<()> {
+        self.click(key_keysym(key)?)
+    }
+
+    fn click(&self, keysym: i32) -> Result<()> {
+        self.notify(keysym, true)?;
+        self.notify(keysym, false)
+    }
+
+    fn notify(&self, keysym: i32, pressed: bool) -> Result<()> {
+        self.conn
+            .with_proxy(portal::BUS_NAME, portal::PATH, Duration::from_secs(5))
+            .method_call::<(), _, _, _>(
+                REMOTE_DESKTOP_INTERFACE,
+                "NotifyKeyboardKeysym",
+                (&self.session, PropMap::new(), keysym, u32::from(pressed)),
+            )
+            .wrap_err("Failed to send a key through the RemoteDesktop portal")
+    }
+}
+
+impl Drop for RemoteDesktop {
+    fn drop(&mut self) {
+        portal::close_session(&self.conn, &self.session);
+    }
+}
+
+/// X11 keysym of a character: Latin-1 characters are their own keysym, other
+/// Unicode characters are offset by 0x1000000
+pub fn keysym(ch: char) -> i32 {
+    match ch {
+        '\n' => KEYSYM_RETURN,
+        '\t' => KEYSYM_TAB,
+        ' '..='~' | '\u{a0}'..='\u{ff}' => ch as i32,
+        _ => 0x0100_0000 + ch as i32,
+    }
+}
+
+fn key_keysym(key: Key) -> Result<i32> {
+    Ok(match key {
+        Key::Backspace => KEYSYM_BACKSPACE,
+        Key::Tab => KEYSYM_TAB,
+        Key::Return => KEYSYM_RETURN,
+        Key::Escape => KEYSYM_ESCAPE,
+        Key::Shift => KEYSYM_SHIFT,
+        Key::Control => KEYSYM_CONTROL,
+        Key::Alt => KEYSYM_ALT,
+        Key::Meta => KEYSYM_SUPER,
+        Key::Unicode(ch) => keysym(ch),
+        other => bail!("{other:?} can't be sent through the RemoteDesktop portal"),
+    })
+}
+
+fn variant(value: impl RefArg + 'static) -> Variant<Box<dyn RefArg>> {
+    Variant(Box::new(value))
+}
+
+fn load_restore_token() -> Option<String> {
+    let path = storage::state_dir().ok()?.join(RESTORE_TOKEN_FILE);
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+fn save_restore_token(token: &str) {
+    let result = storage::state_dir().and_then(|dir| {
+        std::fs::create_dir_all(&dir)?;
+        Ok(std::fs::write(dir.join(RESTORE_TOKEN_FILE), token)?)
+    });
+    if let Err(e) = result {
+        warn!("Failed to save the RemoteDesktop restore token: {:#}", e);
+    }
+}
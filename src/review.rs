@@ -0,0 +1,75 @@
+//! Review before insert: a final transcript is shown in a small zenity
+//! dialog to be inserted as is, edited first or discarded (`[output.review]`).
+
+use eyre::{Result, WrapErr};
+use std::process::{Command, Output};
+use std::time::Duration;
+
+const TITLE: &str = "Insert transcript?";
+const EDIT_BUTTON: &str = "Edit";
+/// zenity's exit status when `--timeout` expired
+const TIMEOUT_STATUS: i32 = 5;
+
+/// What to do with a reviewed transcript
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Review {
+    Insert(String),
+    Discard,
+}
+
+/// Ask whether to insert `text`, inserting it unchanged if not answered
+/// within `auto_insert`. Blocks until the dialog is closed.
+pub fn review(text: &str, auto_insert: Option<Duration>) -> Result<Review> {
+    let mut text = text.to_string();
+    loop {
+        let mut args = vec![
+            "--question".to_string(),
+            "--title".to_string(),
+            TITLE.to_string(),
+            "--no-markup".to_string(),
+            "--width=500".to_string(),
+            format!("--text={text}"),
+            "--ok-label=Insert".to_string(),
+            "--cancel-label=Discard".to_string(),
+            format!("--extra-button={EDIT_BUTTON}"),
+        ];
+        if let Some(auto_insert) = auto_insert {
+            args.push(format!("--timeout={}", auto_insert.as_secs().max(1)));
+        }
+        let output = run_zenity(&args)?;
+
+        if output.status.success() || output.status.code() == Some(TIMEOUT_STATUS) {
+            return Ok(Review::Insert(text));
+        }
+        if String::from_utf8_lossy(&output.stdout).trim() != EDIT_BUTTON {
+            return Ok(Review::Discard);
+        }
+        // Cancelling the edit shows the transcript again
+        if let Some(edited) = edit(&text)? {
+            text = edited;
+        }
+    }
+}
+
+fn edit(text: &str) -> Result<Option<String>> {
+    let output = run_zenity(&[
+        "--entry".to_string(),
+        "--title".to_string(),
+        TITLE.to_string(),
+        "--width=500".to_string(),
+        "--text=Edit the transcript:".to_string(),
+        format!("--entry-text={text}"),
+    ])?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let edited = String::from_utf8_lossy(&output.stdout);
+    Ok(Some(edited.trim_end_matches('\n').to_string()))
+}
+
+fn run_zenity(args: &[String]) -> Result<Output> {
+    Command::new("zenity")
+        .args(args)
+        .output()
+        .wrap_err("Failed to run zenity, is it installed?")
+}
@@ -0,0 +1,416 @@
+//! One recording session, from the start of capture until the last
+//! transcript is handled. Each session has an ID and publishes its lifecycle
+//! (started, first transcript, finalizing, stopped, errors) on the event bus.
+
+use crate::{
+    audio,
+    config::TranscriptionConfig,
+    focused_window::{self, FocusedWindow},
+    handlers::{
+        process_transcription_until, EventsHandler, FilterHandler, HistoryHandler,
+        OverlayTranscriptionHandler, RecentTranscriptsHandler, TranscriptionHandler,
+        UsageStatsHandler,
+    },
+    history::HistoryContext,
+    latency::{Stage, LATENCY},
+    pipeline,
+    state::{AppEvent, AppState},
+    stats,
+    transcription_utils::ConnectionStatus,
+};
+use eyre::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::AbortOnDropHandle;
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a recording session for the lifetime of the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionId(u64);
+
+impl SessionId {
+    fn next() -> Self {
+        Self(NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Owns capture, transcription stream and handlers of one session
+pub struct RecordingSession {
+    id: SessionId,
+    app_state: AppState,
+    /// Cancelled to discard the session's results (`cancel_recording`)
+    discard: CancellationToken,
+    /// Receives the transcripts last instead of the `[output]` handlers
+    output: Option<Box<dyn TranscriptionHandler>>,
+    /// Transcribe a meeting into a file (`[meeting]`), without time limits
+    meeting: bool,
+}
+
+impl RecordingSession {
+    /// Prepare a session, making it the current one. The recording state must
+    /// already be `Connecting` (`AppState::begin_recording`).
+    pub fn new(app_state: AppState) -> Self {
+        let id = SessionId::next();
+        let discard = CancellationToken::new();
+        *app_state.session_discard.write().unwrap() = discard.clone();
+        *app_state.current_session.write().unwrap() = Some(id);
+        app_state.muted.store(false, Ordering::Relaxed);
+        app_state.set_last_error(None);
+        Self {
+            id,
+            app_state,
+            discard,
+            output: None,
+            meeting: false,
+        }
+    }
+
+    /// Make this a meeting session: transcripts go to a meeting transcript
+    /// file instead of the `[output]` handlers, with speaker labels if
+    /// configured, until recording is stopped
+    pub fn meeting(mut self) -> Self {
+        self.meeting = true;
+        self
+    }
+
+    /// Hand the transcripts to `output` instead of the `[output]` handlers;
+    /// the `[output]` filters still apply
+    pub fn with_output(mut self, output: Box<dyn TranscriptionHandler>) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Run the session until recording is stopped and its results are
+    /// handled. Errors are published as `AppEvent::BackendError` and shown to
+    /// the user rather than returned.
+    pub async fn run(mut self) {
+        info!(
+            session = self.id.get(),
+            "Recording session {} started", self.id
+        );
+        self.app_state
+            .meeting
+            .store(self.meeting, Ordering::Relaxed);
+        self.app_state.publish(AppEvent::RecordingStarted(self.id));
+
+        let result = self.record().await;
+        if let Err(e) = &result {
+            error!("Recording error: {}", e);
+            self.app_state.set_last_error(Some(e.to_string()));
+            self.app_state.publish(AppEvent::BackendError {
+                session: Some(self.id),
+                message: e.to_string(),
+            });
+        }
+
+        {
+            let mut current = self.app_state.current_session.write().unwrap();
+            if *current == Some(self.id) {
+                *current = None;
+            }
+        }
+        self.app_state.meeting.store(false, Ordering::Relaxed);
+        self.app_state.end_recording(result.is_err());
+        if let Some(latency) = LATENCY.finish() {
+            debug!(
+                session = self.id.get(),
+                "Session {} latency: {}", self.id, latency
+            );
+        }
+        info!(
+            session = self.id.get(),
+            "Recording session {} ended", self.id
+        );
+        self.app_state.publish(AppEvent::RecordingStopped(self.id));
+    }
+
+    async fn record(&mut self) -> Result<()> {
+        let output = self.output.take();
+        let app_state = &self.app_state;
+        let Some((mut transcription_config, mut overridden, window)) =
+            session_transcription_config(app_state)
+        else {
+            return Ok(());
+        };
+        if !app_state.record_only {
+            overridden |= apply_usage_cap(app_state, &mut transcription_config)?;
+        }
+        let cloud = transcription_config.base_url.is_none();
+        let history_context = HistoryContext {
+            language: Some(transcription_config.language.clone()),
+            app: window.and_then(|window| window.classes.last().cloned()),
+            api_keys: Some(app_state.api_keys())
+                .filter(|api_keys| cloud && api_keys.keys().len() > 1),
+        };
+
+        let (audio_rx, format) = audio::start_capture(app_state).await?;
+        LATENCY.mark(Stage::CaptureStarted);
+
+        if app_state.record_only {
+            app_state.mark_recording();
+            let path = audio::save_recording(app_state, audio_rx, format).await?;
+            if let Some(path) = path {
+                info!("Saved recording to {}", path.display());
+            }
+            return Ok(());
+        }
+
+        let use_interim_results = transcription_config.use_interim_results;
+        let config = app_state.config();
+        let output = match output {
+            Some(output) => output,
+            None if self.meeting => pipeline::meeting(&config.meeting)?,
+            None => pipeline::handlers(&config.output, use_interim_results)?,
+        };
+        let filters = pipeline::filters(&config.output, &transcription_config);
+
+        debug!(
+            "Creating transcription stream with {} Hz sample rate",
+            format.sample_rate
+        );
+        let speaker_labels = self.meeting && config.meeting.speaker_labels;
+        let transcriber = if overridden || speaker_labels {
+            Arc::new(app_state.session_transcriber(transcription_config.clone(), speaker_labels))
+        } else {
+            app_state.transcriber.read().unwrap().clone()
+        };
+        let _connection_watch = self.watch_connection();
+        let transcription_rx = transcriber
+            .transcribe_session_stream(self.id, audio_rx, format)
+            .await?;
+        debug!("Transcription stream created, waiting for transcriptions");
+
+        let overlay = app_state.overlay.read().unwrap().clone();
+        if let Some(overlay) = &overlay {
+            overlay.show();
+        }
+
+        let handler = FilterHandler::new(
+            pipeline::post_processed(
+                EventsHandler::new(
+                    OverlayTranscriptionHandler::new(
+                        UsageStatsHandler::new(
+                            HistoryHandler::new(
+                                RecentTranscriptsHandler::new(output, app_state.clone()),
+                                app_state.history.clone(),
+                                history_context,
+                            ),
+                            app_state.stats.clone(),
+                        ),
+                        overlay.clone(),
+                    ),
+                    self.id,
+                    app_state.clone(),
+                ),
+                &config.post_processing,
+            ),
+            filters,
+        );
+        let dictation_started = Instant::now();
+        // Meetings run until they are stopped
+        let max_duration = app_state.max_recording_duration().filter(|_| !self.meeting);
+        let inactivity_timeout = config
+            .audio
+            .inactivity_timeout_secs
+            .map(Duration::from_secs)
+            .filter(|_| !self.meeting);
+        let mut last_final = Instant::now();
+        let mut events = app_state.events.subscribe();
+        let finalize_timeout = Duration::from_secs(app_state.config().audio.finalize_timeout_secs);
+        let mut interrupted = false;
+        let finish = CancellationToken::new();
+        let processing = process_transcription_until(
+            transcription_rx,
+            handler,
+            self.discard.clone(),
+            finish.clone(),
+        );
+        tokio::pin!(processing);
+
+        // Handle results while recording; the stream may also end on its own
+        let result = tokio::select! {
+            result = &mut processing => {
+                // Reconnecting failed, or the stream ended without an error
+                if app_state.is_recording() && !self.discard.is_cancelled() {
+                    interrupted = true;
+                }
+                Some(result)
+            }
+            _ = async {
+                while app_state.is_recording() {
+                    if max_duration.is_some_and(|max_duration| dictation_started.elapsed() >= max_duration) {
+                        info!("Maximum recording duration reached, stopping");
+                        app_state.stop_recording();
+                        break;
+                    }
+                    while let Ok(event) = events.try_recv() {
+                        if matches!(event, AppEvent::TranscriptFinal { session, .. } if session == self.id) {
+                            last_final = Instant::now();
+                        }
+                    }
+                    if let Some(timeout) = inactivity_timeout.filter(|timeout| last_final.elapsed() >= *timeout) {
+                        info!("No final transcript for {:?}, stopping", timeout);
+                        app_state.publish(AppEvent::InactivityTimeout(self.id));
+                        app_state.stop_recording();
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            } => None,
+        };
+
+        // Finalizing: capture has stopped and the end of the audio is sent
+        // upstream, so the stream closes once the last finals are handled
+        let result = match result {
+            Some(result) => result,
+            None => {
+                app_state.publish(AppEvent::Finalizing(self.id));
+                match tokio::time::timeout(finalize_timeout, &mut processing).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        warn!(
+                            "No end of transcription {:?} after stopping, dropping the rest",
+                            finalize_timeout
+                        );
+                        // The handlers still end the session, unless one of them hangs
+                        finish.cancel();
+                        tokio::time::timeout(finalize_timeout, &mut processing)
+                            .await
+                            .unwrap_or_else(|_| {
+                                warn!("Output handlers did not end the session in time");
+                                Ok(())
+                            })
+                    }
+                }
+            }
+        };
+        if let Err(e) = result {
+            error!("Transcription processing error: {}", e);
+        }
+
+        if let Some(overlay) = &overlay {
+            overlay.hide();
+        }
+
+        app_state
+            .stats
+            .record_dictation(dictation_started.elapsed(), cloud);
+        if let Err(e) = app_state.stats.save() {
+            warn!("Failed to save usage statistics: {:#}", e);
+        }
+
+        debug!("Transcription loop ended");
+        if interrupted {
+            let status = *app_state.connection_status.borrow();
+            bail!(
+                "Transcription ended unexpectedly ({}), recording stopped",
+                status
+            );
+        }
+        Ok(())
+    }
+
+    /// Follow the transcription connection while the returned handle lives:
+    /// move to `Recording` once connected, and publish
+    /// `AppEvent::Reconnecting` while a dropped connection is re-established
+    fn watch_connection(&self) -> AbortOnDropHandle<()> {
+        let mut connection_rx = self.app_state.connection_status.subscribe();
+        let app_state = self.app_state.clone();
+        let id = self.id;
+        AbortOnDropHandle::new(tokio::spawn(async move {
+            while connection_rx.changed().await.is_ok() {
+                let status = *connection_rx.borrow_and_update();
+                match status {
+                    ConnectionStatus::Connected => {
+                        app_state.mark_recording();
+                    }
+                    ConnectionStatus::Reconnecting => {
+                        app_state.publish(AppEvent::Reconnecting(id));
+                    }
+                    _ => {}
+                }
+            }
+        }))
+    }
+}
+
+/// Switch `transcription` to the fallback server once the monthly Deepgram
+/// usage reached the `[usage]` cap; fails without a fallback. Returns whether
+/// `transcription` changed.
+pub fn apply_usage_cap(
+    app_state: &AppState,
+    transcription: &mut TranscriptionConfig,
+) -> Result<bool> {
+    if transcription.base_url.is_some() {
+        return Ok(false);
+    }
+    let usage = app_state.config().usage.clone();
+    let Some(reason) = stats::usage_cap_reached(&usage, &app_state.stats.this_month()) else {
+        return Ok(false);
+    };
+    match usage.fallback_base_url {
+        Some(fallback) => {
+            info!(
+                "Monthly usage cap reached ({}), transcribing with {}",
+                reason, fallback
+            );
+            transcription.base_url = Some(fallback);
+            Ok(true)
+        }
+        None => bail!(
+            "Monthly usage cap reached ({reason}), raise [usage] in the config to keep dictating"
+        ),
+    }
+}
+
+/// Transcription settings for a new session: the configured ones, adjusted by
+/// the app rule matching the focused window, which is returned for the
+/// history. `None` if a rule disables recording for that window.
+fn session_transcription_config(
+    app_state: &AppState,
+) -> Option<(TranscriptionConfig, bool, Option<FocusedWindow>)> {
+    let config = app_state.config();
+    let mut transcription = config.transcription.clone();
+    if config.app_rules.is_empty() && !app_state.history.is_enabled() {
+        return Some((transcription, false, None));
+    }
+
+    let window = match focused_window::focused_window() {
+        Ok(Some(window)) => window,
+        Ok(None) => return Some((transcription, false, None)),
+        Err(e) => {
+            debug!("Could not determine the focused window: {:#}", e);
+            return Some((transcription, false, None));
+        }
+    };
+
+    let Some(rule) = config.app_rule_for(&window.classes, &window.title) else {
+        return Some((transcription, false, Some(window)));
+    };
+    if rule.disabled {
+        info!(
+            "Voice input disabled for focused window {:?} ({})",
+            window.classes, window.title
+        );
+        return None;
+    }
+    info!(
+        "Applying app rule for focused window {:?} ({})",
+        window.classes, window.title
+    );
+    rule.apply_to(&mut transcription);
+    Some((transcription, true, Some(window)))
+}
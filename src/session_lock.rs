@@ -0,0 +1,114 @@
+//! Stops recording when the screen locks, the screensaver starts or the
+//! system goes to sleep, so that the microphone never streams from an
+//! unattended machine.
+
+use crate::{config::Config, state::AppState};
+use dbus::blocking::{Connection, LocalConnection};
+use dbus::message::MatchRule;
+use eyre::{Result, WrapErr};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const SCREENSAVER_INTERFACES: [&str; 2] = ["org.gnome.ScreenSaver", "org.freedesktop.ScreenSaver"];
+const LOGIN_BUS_NAME: &str = "org.freedesktop.login1";
+const LOGIN_PATH: &str = "/org/freedesktop/login1";
+const LOGIN_MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const LOGIN_SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+
+/// Stop recording on lock, screensaver and sleep, if enabled in the
+/// configuration. Returns the thread listening for them.
+pub fn setup_session_lock(
+    config: &Config,
+    app_state: AppState,
+    shutdown_token: &CancellationToken,
+) -> Option<std::thread::JoinHandle<()>> {
+    if !config.ui.stop_on_lock {
+        info!("Recording continues while the screen is locked");
+        return None;
+    }
+
+    let lock_shutdown_token = shutdown_token.child_token();
+    Some(std::thread::spawn(move || {
+        info!("Starting session lock thread");
+        if let Err(e) = run_session_lock(app_state, lock_shutdown_token) {
+            warn!("Screen lock can't be watched: {:#}", e);
+        }
+        info!("Session lock thread exiting");
+    }))
+}
+
+fn stop(app_state: &AppState, reason: &str) {
+    if app_state.is_recording() {
+        info!("Stopping recording: {}", reason);
+        crate::stop_recording(app_state);
+    }
+}
+
+/// Listen for the `Lock` signal of this login session and `PrepareForSleep`
+/// on the system bus; None when logind isn't available
+fn watch_logind(app_state: &AppState) -> Option<Connection> {
+    let watch = || -> Result<Connection> {
+        let conn = Connection::new_system().wrap_err("Failed to connect to system bus")?;
+        let (session,): (dbus::Path<'static>,) = conn
+            .with_proxy(LOGIN_BUS_NAME, LOGIN_PATH, Duration::from_secs(5))
+            .method_call(
+                LOGIN_MANAGER_INTERFACE,
+                "GetSessionByPID",
+                (std::process::id(),),
+            )
+            .wrap_err("Failed to look up the login session")?;
+
+        let lock_state = app_state.clone();
+        conn.add_match(
+            MatchRule::new_signal(LOGIN_SESSION_INTERFACE, "Lock").with_path(session),
+            move |(): (), _, _| {
+                stop(&lock_state, "session locked");
+                true
+            },
+        )
+        .wrap_err("Failed to subscribe to session locks")?;
+
+        let sleep_state = app_state.clone();
+        conn.add_match(
+            MatchRule::new_signal(LOGIN_MANAGER_INTERFACE, "PrepareForSleep"),
+            move |(sleeping,): (bool,), _, _| {
+                if sleeping {
+                    stop(&sleep_state, "going to sleep");
+                }
+                true
+            },
+        )
+        .wrap_err("Failed to subscribe to sleep")?;
+        Ok(conn)
+    };
+    watch()
+        .inspect_err(|e| debug!("Not watching logind: {:#}", e))
+        .ok()
+}
+
+fn run_session_lock(app_state: AppState, shutdown_token: CancellationToken) -> Result<()> {
+    let session = LocalConnection::new_session().wrap_err("Failed to connect to session bus")?;
+    for interface in SCREENSAVER_INTERFACES {
+        let screensaver_state = app_state.clone();
+        session
+            .add_match(
+                MatchRule::new_signal(interface, "ActiveChanged"),
+                move |(active,): (bool,), _, _| {
+                    if active {
+                        stop(&screensaver_state, "screen locked or idle");
+                    }
+                    true
+                },
+            )
+            .wrap_err("Failed to subscribe to the screensaver")?;
+    }
+    let system = watch_logind(&app_state);
+
+    while !shutdown_token.is_cancelled() {
+        session.process(Duration::from_millis(50))?;
+        if let Some(system) = &system {
+            system.process(Duration::from_millis(50))?;
+        }
+    }
+    Ok(())
+}
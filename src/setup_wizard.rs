@@ -0,0 +1,175 @@
+use crate::{audio_source, config::Config, hotkey, keyring, redact, transcription};
+use cpal::traits::{DeviceTrait, HostTrait};
+use eyre::{Result, WrapErr};
+use std::io::{BufRead, Write};
+
+/// Interactive first-run setup on the terminal. Asks for the API key, input
+/// device, and hotkey, then writes the config file and returns the new config.
+pub async fn run_setup_wizard() -> Result<Config> {
+    let mut config = Config::default();
+
+    println!("Welcome to GNOME Voice Input!");
+    println!("No configuration found, let's create one.\n");
+
+    let api_key = ask_api_key().await?;
+    redact::register_secret(&api_key);
+    config.deepgram_api_key = if ask_store_in_keyring(&api_key)? {
+        keyring::KEYRING_PLACEHOLDER.to_string()
+    } else {
+        api_key.clone()
+    };
+    config.audio.device = ask_input_device(&config)?;
+    ask_hotkey(&mut config)?;
+
+    config.save()?;
+    config.deepgram_api_key = api_key;
+    println!(
+        "\nConfiguration written to {}",
+        Config::get_config_path(None)?.display()
+    );
+    println!("You can change these settings at any time by editing that file.\n");
+
+    Ok(config)
+}
+
+async fn ask_api_key() -> Result<String> {
+    println!("Get a Deepgram API key at https://console.deepgram.com/");
+
+    loop {
+        let api_key = prompt("Deepgram API key: ")?;
+        if api_key.is_empty() {
+            println!("The API key is required.");
+            continue;
+        }
+
+        print!("Checking API key... ");
+        std::io::stdout().flush()?;
+        match transcription::verify_api_key(&api_key).await {
+            Ok(()) => {
+                println!("ok");
+                return Ok(api_key);
+            }
+            Err(e) => {
+                println!("failed: {e}");
+                if confirm("Use this key anyway?")? {
+                    return Ok(api_key);
+                }
+            }
+        }
+    }
+}
+
+/// Offer to keep the key out of the config file. Returns true if it was stored.
+fn ask_store_in_keyring(api_key: &str) -> Result<bool> {
+    if !confirm("Store the API key in GNOME Keyring instead of the config file?")? {
+        return Ok(false);
+    }
+    match keyring::store_api_key(api_key) {
+        Ok(()) => Ok(true),
+        Err(e) => {
+            println!("Could not use the keyring ({e:#}), writing the key to the config file.");
+            Ok(false)
+        }
+    }
+}
+
+fn ask_input_device(config: &Config) -> Result<Option<String>> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+    let devices: Vec<String> = host
+        .input_devices()
+        .wrap_err("Failed to enumerate input devices")?
+        .filter_map(|device| device.name().ok())
+        .collect();
+
+    if devices.is_empty() {
+        println!("\nNo input devices found, the system default will be used.");
+        return Ok(None);
+    }
+
+    println!("\nAvailable microphones:");
+    println!("  0) System default");
+    for (index, name) in devices.iter().enumerate() {
+        let marker = if Some(name) == default_name.as_ref() {
+            " (current default)"
+        } else {
+            ""
+        };
+        println!("  {}) {}{}", index + 1, name, marker);
+    }
+
+    loop {
+        let answer = prompt("Microphone [0]: ")?;
+        let selection = if answer.is_empty() {
+            0
+        } else {
+            match answer.parse::<usize>() {
+                Ok(selection) if selection <= devices.len() => selection,
+                _ => {
+                    println!("Please enter a number between 0 and {}.", devices.len());
+                    continue;
+                }
+            }
+        };
+
+        if selection == 0 {
+            return Ok(None);
+        }
+
+        let device = devices[selection - 1].clone();
+        let mut audio_config = config.audio.clone();
+        audio_config.device = Some(device.clone());
+        if let Err(e) = audio_source::select_input_device(&audio_config) {
+            println!("Could not open {device}: {e}");
+            continue;
+        }
+        return Ok(Some(device));
+    }
+}
+
+fn ask_hotkey(config: &mut Config) -> Result<()> {
+    println!("\nChoose the hotkey that toggles recording.");
+    println!("Modifiers: super, ctrl, alt, shift (separate several with '+').");
+
+    loop {
+        let modifiers = prompt("Modifiers [super]: ")?;
+        let key = prompt("Key [v]: ")?;
+
+        let mut candidate = config.clone();
+        if !modifiers.is_empty() {
+            candidate.hotkey.modifiers = modifiers
+                .split('+')
+                .map(|modifier| modifier.trim().to_lowercase())
+                .filter(|modifier| !modifier.is_empty())
+                .collect();
+        }
+        if !key.is_empty() {
+            candidate.hotkey.key = key.to_lowercase();
+        }
+
+        match hotkey::parse_hotkey(&candidate) {
+            Ok(_) => {
+                config.hotkey = candidate.hotkey;
+                return Ok(());
+            }
+            Err(e) => println!("Invalid hotkey: {e}"),
+        }
+    }
+}
+
+pub fn prompt(question: &str) -> Result<String> {
+    print!("{question}");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    let read = std::io::stdin().lock().read_line(&mut answer)?;
+    if read == 0 {
+        bail!("Setup aborted");
+    }
+    Ok(answer.trim().to_string())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    let answer = prompt(&format!("{question} [y/N]: "))?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
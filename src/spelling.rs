@@ -0,0 +1,149 @@
+//! Spell mode: between two spoken commands ("start spelling", "stop
+//! spelling") letters, NATO alphabet words, digits and symbol names are
+//! written as characters, e.g. "alpha bravo seven dash x" as "ab7-x".
+
+use crate::config::SpellConfig;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Words upper-casing the following character
+const CAPITAL: [&str; 3] = ["capital", "uppercase", "cap"];
+
+fn character(word: &str) -> Option<char> {
+    let c = match word {
+        "alpha" | "alfa" => 'a',
+        "bravo" => 'b',
+        "charlie" => 'c',
+        "delta" => 'd',
+        "echo" => 'e',
+        "foxtrot" => 'f',
+        "golf" => 'g',
+        "hotel" => 'h',
+        "india" => 'i',
+        "juliet" | "juliett" => 'j',
+        "kilo" => 'k',
+        "lima" => 'l',
+        "mike" => 'm',
+        "november" => 'n',
+        "oscar" => 'o',
+        "papa" => 'p',
+        "quebec" => 'q',
+        "romeo" => 'r',
+        "sierra" => 's',
+        "tango" => 't',
+        "uniform" => 'u',
+        "victor" => 'v',
+        "whiskey" | "whisky" => 'w',
+        "xray" => 'x',
+        "yankee" => 'y',
+        "zulu" => 'z',
+        "zero" | "oh" => '0',
+        "one" => '1',
+        "two" => '2',
+        "three" => '3',
+        "four" => '4',
+        "five" => '5',
+        "six" => '6',
+        "seven" => '7',
+        "eight" => '8',
+        "nine" | "niner" => '9',
+        "dash" | "hyphen" | "minus" => '-',
+        "underscore" => '_',
+        "dot" | "period" | "point" => '.',
+        "at" => '@',
+        "slash" => '/',
+        "plus" => '+',
+        "hash" | "pound" => '#',
+        "space" => ' ',
+        _ => {
+            let mut chars = word.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(c),
+                _ => None,
+            };
+        }
+    };
+    Some(c)
+}
+
+/// Lowercase letters and digits of a word; "X-ray" is "xray"
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The `spell` filter; remembers across final transcripts whether spell mode
+/// is on
+pub struct SpellMode {
+    start: Vec<String>,
+    stop: Vec<String>,
+    active: AtomicBool,
+}
+
+impl SpellMode {
+    pub fn new(config: &SpellConfig) -> Self {
+        let words = |phrase: &str| phrase.split_whitespace().map(normalize).collect();
+        Self {
+            start: words(&config.start),
+            stop: words(&config.stop),
+            active: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// `text` with the spelled parts written as characters and the commands
+    /// removed; None when nothing is left. Only final transcripts switch the
+    /// mode, interim ones are shown as they would be spelled.
+    pub fn process(&self, text: &str, is_final: bool) -> Option<String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let normalized: Vec<String> = words.iter().map(|word| normalize(word)).collect();
+        let is_phrase =
+            |i: usize, phrase: &[String]| !phrase.is_empty() && normalized[i..].starts_with(phrase);
+
+        let mut active = self.is_active();
+        let mut output: Vec<String> = Vec::new();
+        let mut spelled = String::new();
+        let mut capital = false;
+        let mut i = 0;
+        while i < words.len() {
+            if !active && is_phrase(i, &self.start) {
+                active = true;
+                i += self.start.len();
+                continue;
+            }
+            if active && is_phrase(i, &self.stop) {
+                active = false;
+                output.extend((!spelled.is_empty()).then(|| std::mem::take(&mut spelled)));
+                i += self.stop.len();
+                continue;
+            }
+
+            if !active {
+                output.push(words[i].to_string());
+            } else if CAPITAL.contains(&normalized[i].as_str()) {
+                capital = true;
+            } else {
+                match character(&normalized[i]) {
+                    Some(c) if capital => spelled.extend(c.to_uppercase()),
+                    Some(c) => spelled.push(c),
+                    // Numbers and words that aren't spelled are kept as said
+                    None => spelled.push_str(&normalized[i]),
+                }
+                capital = false;
+            }
+            i += 1;
+        }
+        output.extend((!spelled.is_empty()).then_some(spelled));
+
+        if is_final && active != self.is_active() {
+            debug!("Spell mode {}", if active { "on" } else { "off" });
+            self.active.store(active, Ordering::Relaxed);
+        }
+        let output = output.join(" ");
+        (!output.is_empty()).then_some(output)
+    }
+}
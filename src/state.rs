@@ -1,16 +1,206 @@
-use crate::{config::Config, transcription};
-use std::sync::atomic::AtomicBool;
-use std::sync::{Arc, RwLock};
+use crate::{
+    api_keys::ApiKeys,
+    config::{Config, DebugConfig, TranscriptionConfig},
+    history::History,
+    latency::LATENCY,
+    overlay::Overlay,
+    portal, privacy,
+    session::SessionId,
+    stats::UsageStats,
+    transcription,
+    transcription_utils::ConnectionStatus,
+};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
 use tokio_util::sync::CancellationToken;
 
+/// Something that happened in the application, published on
+/// `AppState::events` for the tray, notifications, D-Bus and other observers
+#[derive(Clone)]
+pub enum AppEvent {
+    /// A recording session started capturing audio
+    RecordingStarted(SessionId),
+    /// The first (interim or final) transcript of a session arrived
+    FirstTranscript(SessionId),
+    /// The transcription connection dropped during the session and is being
+    /// re-established; capture continues
+    Reconnecting(SessionId),
+    /// Recording was stopped; the session's remaining results are handled
+    Finalizing(SessionId),
+    /// Recording was stopped because no final transcript arrived for
+    /// `audio.inactivity_timeout_secs`
+    InactivityTimeout(SessionId),
+    /// The session is over, also after an error
+    RecordingStopped(SessionId),
+    TranscriptFinal {
+        session: SessionId,
+        text: String,
+    },
+    /// Capture or transcription failed, in a session or outside of one
+    BackendError {
+        session: Option<SessionId>,
+        message: String,
+    },
+    /// Audio capture failed, e.g. the input device stopped delivering
+    /// audio; the session's audio stream ends with it
+    CaptureFailed(String),
+    /// The configuration file was reloaded and applied
+    ConfigReloaded,
+}
+
+/// Events are logged when published, so in privacy mode a final transcript
+/// shows only its length
+impl std::fmt::Debug for AppEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RecordingStarted(session) => {
+                f.debug_tuple("RecordingStarted").field(session).finish()
+            }
+            Self::FirstTranscript(session) => {
+                f.debug_tuple("FirstTranscript").field(session).finish()
+            }
+            Self::Reconnecting(session) => f.debug_tuple("Reconnecting").field(session).finish(),
+            Self::Finalizing(session) => f.debug_tuple("Finalizing").field(session).finish(),
+            Self::InactivityTimeout(session) => {
+                f.debug_tuple("InactivityTimeout").field(session).finish()
+            }
+            Self::RecordingStopped(session) => {
+                f.debug_tuple("RecordingStopped").field(session).finish()
+            }
+            Self::TranscriptFinal { session, text } => f
+                .debug_struct("TranscriptFinal")
+                .field("session", session)
+                .field("text", &privacy::loggable(text))
+                .finish(),
+            Self::BackendError { session, message } => f
+                .debug_struct("BackendError")
+                .field("session", session)
+                .field("message", message)
+                .finish(),
+            Self::CaptureFailed(message) => f.debug_tuple("CaptureFailed").field(message).finish(),
+            Self::ConfigReloaded => f.write_str("ConfigReloaded"),
+        }
+    }
+}
+
+/// The parts of the config `AppState::replace_transcriber` builds the
+/// transcriber from; it has to be replaced when they change
+#[derive(Clone, PartialEq)]
+pub struct TranscriberSettings {
+    pub api_key: String,
+    pub api_keys: Vec<String>,
+    pub transcription: TranscriptionConfig,
+    pub debug: DebugConfig,
+    pub channel_labels: Vec<String>,
+}
+
+impl TranscriberSettings {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            api_key: config.deepgram_api_key.clone(),
+            api_keys: config.api_keys(),
+            transcription: config.transcription.clone(),
+            debug: config.debug.clone(),
+            channel_labels: config.audio.channel_labels.clone(),
+        }
+    }
+}
+
+/// Phase of recording, observed by the tray, D-Bus and stdin control. Only
+/// one session can be past `Idle`/`Error` at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordingState {
+    #[default]
+    Idle,
+    /// A session started capturing while its transcription connection is set up
+    Connecting,
+    Recording,
+    /// Capture stopped; the remaining results are being handled
+    Finalizing,
+    /// The last session failed; a new one may be started
+    Error,
+}
+
+impl RecordingState {
+    /// Whether audio is being captured
+    pub fn is_capturing(self) -> bool {
+        matches!(self, Self::Connecting | Self::Recording)
+    }
+
+    /// Whether a session is running, so no other may start
+    pub fn is_busy(self) -> bool {
+        matches!(self, Self::Connecting | Self::Recording | Self::Finalizing)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Connecting => "connecting",
+            Self::Recording => "recording",
+            Self::Finalizing => "finalizing",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl std::fmt::Display for RecordingState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
-    pub config: Arc<RwLock<Config>>,
-    pub recording: Arc<AtomicBool>,
+    /// Current configuration; see `config`, `subscribe_config` and `update_config`
+    config: Arc<watch::Sender<Arc<Config>>>,
+    /// See `recording_state` and the transition methods below it
+    recording: Arc<watch::Sender<RecordingState>>,
+    /// Cancelled to discard the current recording session's results
+    pub session_discard: Arc<RwLock<CancellationToken>>,
+    /// Recording session in progress, if any
+    pub current_session: Arc<RwLock<Option<SessionId>>>,
+    /// Event bus; see `AppEvent`
+    pub events: broadcast::Sender<AppEvent>,
+    /// False while voice input is paused; hotkey and recording are ignored
+    pub enabled: Arc<AtomicBool>,
+    /// True while the microphone is muted; silence is streamed so the
+    /// transcription session stays open
+    pub muted: Arc<AtomicBool>,
+    /// True while the current session is a meeting transcript
+    pub meeting: Arc<AtomicBool>,
+    /// RMS level of the most recent audio chunk (f32 bits, 0.0 when idle)
+    pub input_level: Arc<AtomicU32>,
     pub transcriber: Arc<RwLock<Arc<transcription::Transcriber>>>,
+    /// Health of the transcription backend connection, updated by the transcriber
+    pub connection_status: Arc<watch::Sender<ConnectionStatus>>,
+    pub overlay: Arc<RwLock<Option<Overlay>>>,
+    /// Most recent final transcripts, newest first
+    pub recent_transcripts: Arc<RwLock<VecDeque<String>>>,
+    /// Error of the most recent recording attempt, cleared when a new one starts
+    pub last_error: Arc<RwLock<Option<String>>>,
+    pub stats: Arc<UsageStats>,
+    pub history: Arc<History>,
+    /// Signalled whenever state shown in the UI (tray) changed
+    pub ui_refresh: Arc<watch::Sender<()>>,
     pub shutdown_token: CancellationToken,
     pub debug: bool,
     pub custom_config_path: Option<std::path::PathBuf>,
+    /// Maximum recording duration from the command line (`--duration`),
+    /// taking precedence over `audio.max_duration_secs`
+    pub duration_override: Option<Duration>,
+    /// Log transcripts instead of typing them (`--dry-run`)
+    pub dry_run: bool,
+    /// Save recordings without transcribing them (`--record-only`)
+    pub record_only: bool,
+    /// Audio recorded in place of the microphone (`--input-file`, `--input-signal`)
+    pub input: Option<crate::audio::AudioInput>,
+    /// Config files with their contents as last written by this instance, so
+    /// the config watcher can ignore its own writes
+    written_config: Arc<Mutex<HashMap<PathBuf, String>>>,
 }
 
 impl AppState {
@@ -20,19 +210,364 @@ impl AppState {
         custom_config_path: Option<std::path::PathBuf>,
         shutdown_token: CancellationToken,
     ) -> Self {
-        let transcriber = Arc::new(transcription::Transcriber::new(
-            config.deepgram_api_key.clone(),
-            config.transcription.clone(),
-            debug,
-        ));
+        let connection_status = Arc::new(watch::channel(ConnectionStatus::Disconnected).0);
+        let stats = Arc::new(UsageStats::load());
+        let transcriber = Arc::new(
+            transcription::Transcriber::new(
+                config.deepgram_api_key.clone(),
+                config.transcription.clone(),
+                connection_status.clone(),
+                stats.clone(),
+                debug,
+            )
+            .with_api_keys(Arc::new(ApiKeys::new(config.api_keys())))
+            .with_debug_artifacts(config.debug.clone())
+            .with_channel_labels(config.audio.channel_labels.clone()),
+        );
 
+        let enabled = !config.paused;
+        privacy::set_enabled(config.privacy);
+        portal::select_backends(config.desktop_access);
+        let history = Arc::new(History::load(&config.history));
         Self {
-            config: Arc::new(RwLock::new(config)),
-            recording: Arc::new(AtomicBool::new(false)),
+            config: Arc::new(watch::channel(Arc::new(config)).0),
+            recording: Arc::new(watch::channel(RecordingState::Idle).0),
+            session_discard: Arc::new(RwLock::new(CancellationToken::new())),
+            current_session: Arc::new(RwLock::new(None)),
+            events: broadcast::channel(64).0,
+            enabled: Arc::new(AtomicBool::new(enabled)),
+            muted: Arc::new(AtomicBool::new(false)),
+            meeting: Arc::new(AtomicBool::new(false)),
+            input_level: Arc::new(AtomicU32::new(0)),
             transcriber: Arc::new(RwLock::new(transcriber)),
+            connection_status,
+            overlay: Arc::new(RwLock::new(None)),
+            recent_transcripts: Arc::new(RwLock::new(VecDeque::new())),
+            last_error: Arc::new(RwLock::new(None)),
+            stats,
+            history,
+            ui_refresh: Arc::new(watch::channel(()).0),
             shutdown_token,
             debug,
             custom_config_path,
+            duration_override: None,
+            dry_run: false,
+            record_only: false,
+            input: None,
+            written_config: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Snapshot of the current configuration
+    pub fn config(&self) -> Arc<Config> {
+        self.config.borrow().clone()
+    }
+
+    /// Receiver notified whenever the configuration changes
+    pub fn subscribe_config(&self) -> watch::Receiver<Arc<Config>> {
+        self.config.subscribe()
+    }
+
+    /// Change the configuration in place and notify subscribers
+    pub fn update_config<R>(&self, update: impl FnOnce(&mut Config) -> R) -> R {
+        let mut result = None;
+        self.config
+            .send_modify(|config| result = Some(update(Arc::make_mut(config))));
+        result.expect("send_modify runs the closure")
+    }
+
+    /// Replace the configuration (e.g. after the file was reloaded), returning
+    /// the previous one
+    pub fn replace_config(&self, config: Config) -> Arc<Config> {
+        self.config.send_replace(Arc::new(config))
+    }
+
+    pub fn recording_state(&self) -> RecordingState {
+        *self.recording.borrow()
+    }
+
+    /// Receiver notified on every recording state change
+    pub fn subscribe_recording_state(&self) -> watch::Receiver<RecordingState> {
+        self.recording.subscribe()
+    }
+
+    /// Whether audio is being captured (connecting or recording)
+    pub fn is_recording(&self) -> bool {
+        self.recording_state().is_capturing()
+    }
+
+    /// Move to `Connecting` unless a session is already running. Returns
+    /// whether the caller may start a session.
+    pub fn begin_recording(&self) -> bool {
+        let begun =
+            self.transition(|state| (!state.is_busy()).then_some(RecordingState::Connecting));
+        if begun {
+            LATENCY.begin();
+        }
+        begun
+    }
+
+    /// The session's transcription connection is up
+    pub fn mark_recording(&self) -> bool {
+        self.transition(|state| {
+            (state == RecordingState::Connecting).then_some(RecordingState::Recording)
+        })
+    }
+
+    /// Stop capturing and let the session finish. Returns whether anything
+    /// was being captured.
+    pub fn stop_recording(&self) -> bool {
+        self.transition(|state| state.is_capturing().then_some(RecordingState::Finalizing))
+    }
+
+    /// The session is over
+    pub fn end_recording(&self, failed: bool) {
+        let state = if failed {
+            RecordingState::Error
+        } else {
+            RecordingState::Idle
+        };
+        self.transition(|_| Some(state));
+    }
+
+    /// Apply the state `next` returns for the current one, if any
+    fn transition(&self, next: impl FnOnce(RecordingState) -> Option<RecordingState>) -> bool {
+        let mut previous = None;
+        let changed = self.recording.send_if_modified(|state| match next(*state) {
+            Some(next) if next != *state => {
+                previous = Some(std::mem::replace(state, next));
+                true
+            }
+            _ => false,
+        });
+        if let Some(previous) = previous {
+            debug!(
+                "Recording state: {} -> {}",
+                previous,
+                self.recording_state()
+            );
+            self.request_ui_refresh();
+        }
+        changed
+    }
+
+    /// Apply a runtime change to the transcription settings and rebuild the
+    /// transcriber. Takes effect with the next recording session.
+    pub fn update_transcription_config(&self, update: impl FnOnce(&mut TranscriptionConfig)) {
+        self.update_config(|config| update(&mut config.transcription));
+
+        self.replace_transcriber(&self.config());
+        self.persist_config();
+    }
+
+    /// Build a new transcriber for the given config and use it for future
+    /// sessions. The key rotation carries over unless the keys changed.
+    pub fn replace_transcriber(&self, config: &Config) {
+        let settings = TranscriberSettings::new(config);
+        let mut api_keys = self.api_keys();
+        if api_keys.keys() != settings.api_keys {
+            api_keys = Arc::new(ApiKeys::new(settings.api_keys));
+        }
+        let transcriber = Arc::new(
+            transcription::Transcriber::new(
+                settings.api_key,
+                settings.transcription,
+                self.connection_status.clone(),
+                self.stats.clone(),
+                self.debug,
+            )
+            .with_api_keys(api_keys)
+            .with_debug_artifacts(settings.debug)
+            .with_channel_labels(settings.channel_labels),
+        );
+        *self.transcriber.write().unwrap() = transcriber;
+    }
+
+    /// The Deepgram API keys of the transcribers, see [`ApiKeys`]
+    pub fn api_keys(&self) -> Arc<ApiKeys> {
+        self.transcriber.read().unwrap().api_keys().clone()
+    }
+
+    /// Activate a profile (or none), rebuilding the transcriber. Takes effect
+    /// with the next recording session.
+    pub fn switch_profile(&self, name: Option<&str>) -> eyre::Result<()> {
+        // Validate first so subscribers aren't notified of a failed switch
+        let mut config = Config::clone(&self.config());
+        config.apply_profile(name)?;
+        self.replace_config(config);
+
+        self.replace_transcriber(&self.config());
+        self.persist_config();
+        self.request_ui_refresh();
+        Ok(())
+    }
+
+    /// Transcriber for a single session with settings that differ from the
+    /// configured ones (e.g. overridden by an app rule, or labeling speakers
+    /// in a meeting)
+    pub fn session_transcriber(
+        &self,
+        transcription: TranscriptionConfig,
+        speaker_labels: bool,
+    ) -> transcription::Transcriber {
+        let config = self.config();
+        let mut transcriber = transcription::Transcriber::new(
+            config.deepgram_api_key.clone(),
+            transcription,
+            self.connection_status.clone(),
+            self.stats.clone(),
+            self.debug,
+        )
+        .with_api_keys(self.api_keys())
+        .with_debug_artifacts(config.debug.clone())
+        .with_channel_labels(config.audio.channel_labels.clone());
+        if speaker_labels {
+            transcriber = transcriber.with_speaker_labels();
+        }
+        transcriber
+    }
+
+    /// Switch to the language following the current one in
+    /// `transcription.languages`. Returns the new language, or `None` if no
+    /// languages are configured.
+    pub fn switch_to_next_language(&self) -> Option<String> {
+        let languages = self.config().transcription.languages.clone();
+        if languages.is_empty() {
+            return None;
+        }
+
+        let mut next = None;
+        self.update_transcription_config(|transcription| {
+            let index = languages
+                .iter()
+                .position(|language| *language == transcription.language)
+                .map_or(0, |index| (index + 1) % languages.len());
+            transcription.language = languages[index].clone();
+            next = Some(transcription.language.clone());
+        });
+        self.request_ui_refresh();
+        next
+    }
+
+    /// Pause or resume voice input. Pausing stops any active recording.
+    pub fn set_enabled(&self, enabled: bool) {
+        let changed = self.config.send_if_modified(|config| {
+            if config.paused == enabled {
+                Arc::make_mut(config).paused = !enabled;
+                true
+            } else {
+                false
+            }
+        });
+        if changed {
+            self.persist_config();
+        }
+
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.stop_recording();
+        }
+        self.request_ui_refresh();
+    }
+
+    /// Switch privacy mode, see [`privacy`]. The choice is saved to the config.
+    pub fn set_privacy(&self, enabled: bool) {
+        let changed = self.config.send_if_modified(|config| {
+            if config.privacy != enabled {
+                Arc::make_mut(config).privacy = enabled;
+                true
+            } else {
+                false
+            }
+        });
+        if changed {
+            self.persist_config();
+        }
+
+        privacy::set_enabled(enabled);
+        self.request_ui_refresh();
+    }
+
+    /// How long a recording session may run before it is stopped automatically
+    pub fn max_recording_duration(&self) -> Option<Duration> {
+        self.duration_override.or_else(|| {
+            self.config()
+                .audio
+                .max_duration_secs
+                .map(Duration::from_secs)
+        })
+    }
+
+    /// Write settings changed at runtime back to the config files
+    fn persist_config(&self) {
+        // Held while writing so the watcher can't compare against stale contents
+        let mut written_config = self.written_config.lock().unwrap();
+        let config = self.config();
+        match config.save_runtime_settings(self.custom_config_path.clone()) {
+            Ok(written) => {
+                // Only the record of what was saved changes, so subscribers aren't notified
+                self.config.send_if_modified(|current| {
+                    Arc::make_mut(current).runtime_settings_saved(&config);
+                    false
+                });
+                written_config.extend(written);
+            }
+            Err(e) => warn!("Failed to save settings to the config file: {:#}", e),
+        }
+    }
+
+    /// Whether the file at `path` is unchanged since this instance last wrote it
+    pub fn is_own_config_write(&self, path: &Path) -> bool {
+        let written_config = self.written_config.lock().unwrap();
+        path.canonicalize().is_ok_and(|path| {
+            written_config.get(&path).is_some_and(|written| {
+                std::fs::read_to_string(&path).is_ok_and(|contents| contents == *written)
+            })
+        })
+    }
+
+    /// Mute or unmute the microphone of the running session. Returns whether
+    /// it is muted now, or `None` if nothing is being recorded.
+    pub fn toggle_muted(&self) -> Option<bool> {
+        if !self.is_recording() {
+            return None;
+        }
+        let muted = !self.muted.fetch_xor(true, Ordering::Relaxed);
+        self.request_ui_refresh();
+        Some(muted)
+    }
+
+    /// Current microphone input level in the range 0.0..=1.0
+    pub fn input_level(&self) -> f32 {
+        f32::from_bits(self.input_level.load(Ordering::Relaxed))
+    }
+
+    /// Record (or clear) the error of the current recording attempt
+    pub fn set_last_error(&self, error: Option<String>) {
+        *self.last_error.write().unwrap() =
+            error.map(|error| crate::redact::redact(&error).into_owned());
+        self.request_ui_refresh();
+    }
+
+    /// Publish an event on the event bus; nobody listening is fine
+    pub fn publish(&self, event: AppEvent) {
+        debug!("Event: {:?}", event);
+        let _ = self.events.send(event);
+    }
+
+    /// Notify UI components that they should re-render
+    pub fn request_ui_refresh(&self) {
+        self.ui_refresh.send_replace(());
+    }
+
+    /// Remember a final transcript, keeping only the configured number of entries
+    pub fn push_recent_transcript(&self, text: String) {
+        let limit = self.config().ui.recent_transcripts;
+        {
+            let mut recent = self.recent_transcripts.write().unwrap();
+            recent.push_front(text);
+            recent.truncate(limit);
         }
+        self.request_ui_refresh();
     }
 }
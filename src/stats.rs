@@ -0,0 +1,195 @@
+//! Per-day usage statistics (time dictated, words typed, transcription
+//! latency), persisted as JSON in the state directory.
+
+use crate::config::UsageConfig;
+use chrono::{Datelike, Days, Local, NaiveDate};
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const STATS_FILE: &str = "stats.json";
+/// Days of statistics kept in the stats file
+const RETENTION_DAYS: u64 = 90;
+
+/// Totals for a single day (or a range of days when summed up)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DayStats {
+    pub seconds_dictated: f64,
+    pub words_typed: u64,
+    pub latency_ms_total: u64,
+    pub latency_samples: u64,
+    /// The part of `seconds_dictated` transcribed by Deepgram's service
+    #[serde(default)]
+    pub cloud_seconds: f64,
+}
+
+impl DayStats {
+    pub fn minutes_dictated(&self) -> f64 {
+        self.seconds_dictated / 60.0
+    }
+
+    /// Average time from the end of an utterance to its final transcript
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.latency_samples > 0)
+            .then(|| Duration::from_millis(self.latency_ms_total / self.latency_samples))
+    }
+
+    fn add(&mut self, other: &DayStats) {
+        self.seconds_dictated += other.seconds_dictated;
+        self.words_typed += other.words_typed;
+        self.latency_ms_total += other.latency_ms_total;
+        self.latency_samples += other.latency_samples;
+        self.cloud_seconds += other.cloud_seconds;
+    }
+}
+
+/// Why the Deepgram usage of `month` reached the cap of `usage`, if it did
+pub fn usage_cap_reached(usage: &UsageConfig, month: &DayStats) -> Option<String> {
+    let minutes = month.cloud_seconds / 60.0;
+    if let Some(cap) = usage.monthly_minutes.filter(|cap| minutes >= *cap) {
+        return Some(format!("{minutes:.0} of {cap} minutes used this month"));
+    }
+    let dollars = minutes * usage.price_per_minute;
+    usage
+        .monthly_dollars
+        .filter(|cap| dollars >= *cap)
+        .map(|cap| format!("${dollars:.2} of ${cap:.2} spent this month"))
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct StatsFile {
+    days: BTreeMap<NaiveDate, DayStats>,
+}
+
+/// Usage statistics shared between the recording pipeline and the tray
+pub struct UsageStats {
+    path: Option<PathBuf>,
+    file: Mutex<StatsFile>,
+}
+
+impl UsageStats {
+    /// Load the statistics from the state directory, starting empty if there
+    /// are none yet or they can't be read
+    pub fn load() -> Self {
+        let path = stats_path()
+            .inspect_err(|e| warn!("Usage statistics will not be persisted: {:#}", e))
+            .ok();
+
+        let file = path
+            .as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                std::fs::read_to_string(path)
+                    .wrap_err("Failed to read stats file")
+                    .and_then(|content| {
+                        serde_json::from_str(&content).wrap_err("Failed to parse stats file")
+                    })
+                    .inspect_err(|e| warn!("Ignoring stats file {}: {:#}", path.display(), e))
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        Self {
+            path,
+            file: Mutex::new(file),
+        }
+    }
+
+    /// Statistics that start empty and are never written to disk
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            file: Mutex::default(),
+        }
+    }
+
+    /// Record a session of `duration`, transcribed by Deepgram's service if
+    /// `cloud`
+    pub fn record_dictation(&self, duration: Duration, cloud: bool) {
+        self.update_today(|day| {
+            day.seconds_dictated += duration.as_secs_f64();
+            if cloud {
+                day.cloud_seconds += duration.as_secs_f64();
+            }
+        });
+    }
+
+    pub fn record_words(&self, words: usize) {
+        self.update_today(|day| day.words_typed += words as u64);
+    }
+
+    pub fn record_latency(&self, latency: Duration) {
+        self.update_today(|day| {
+            day.latency_ms_total += latency.as_millis() as u64;
+            day.latency_samples += 1;
+        });
+    }
+
+    pub fn today(&self) -> DayStats {
+        self.last_days(1)
+    }
+
+    /// Totals for the last `days` days including today
+    pub fn last_days(&self, days: u64) -> DayStats {
+        let today = Local::now().date_naive();
+        let first = today
+            .checked_sub_days(Days::new(days.saturating_sub(1)))
+            .unwrap_or(NaiveDate::MIN);
+        self.since(first)
+    }
+
+    /// Totals for the current calendar month
+    pub fn this_month(&self) -> DayStats {
+        let today = Local::now().date_naive();
+        self.since(today.with_day(1).unwrap_or(today))
+    }
+
+    /// Totals from `first` up to today
+    fn since(&self, first: NaiveDate) -> DayStats {
+        let today = Local::now().date_naive();
+        let file = self.file.lock().unwrap();
+        let mut total = DayStats::default();
+        for day in file.days.range(first..=today).map(|(_, day)| day) {
+            total.add(day);
+        }
+        total
+    }
+
+    /// Write the statistics to disk, dropping days past the retention period
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let content = {
+            let mut file = self.file.lock().unwrap();
+            if let Some(cutoff) = Local::now()
+                .date_naive()
+                .checked_sub_days(Days::new(RETENTION_DAYS))
+            {
+                file.days.retain(|date, _| *date > cutoff);
+            }
+            serde_json::to_string_pretty(&*file)?
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).wrap_err("Failed to create state directory")?;
+        }
+        std::fs::write(path, content)
+            .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    fn update_today(&self, update: impl FnOnce(&mut DayStats)) {
+        let today = Local::now().date_naive();
+        let mut file = self.file.lock().unwrap();
+        update(file.days.entry(today).or_default());
+    }
+}
+
+fn stats_path() -> Result<PathBuf> {
+    Ok(crate::storage::state_dir()?.join(STATS_FILE))
+}
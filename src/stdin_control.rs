@@ -0,0 +1,75 @@
+//! Line commands on stdin for `--headless`, where there is no tray or hotkey
+//! to control the app: `toggle`, `start`, `stop`, `cancel`, `pause`,
+//! `resume`, `language <code>`, `status` and `quit`.
+
+use crate::state::AppState;
+use std::sync::atomic::Ordering;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Read commands from stdin until it is closed or `shutdown_token` is
+/// cancelled. `quit` cancels `shutdown_token`.
+pub fn spawn_stdin_control(
+    app_state: AppState,
+    shutdown_token: &CancellationToken,
+) -> JoinHandle<()> {
+    let shutdown_token = shutdown_token.clone();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        loop {
+            let line = tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                line = lines.next_line() => line,
+            };
+            let line = match line {
+                Ok(Some(line)) => line,
+                Ok(None) => {
+                    debug!("stdin closed, no more commands");
+                    break;
+                }
+                Err(e) => {
+                    warn!("Failed to read command from stdin: {}", e);
+                    break;
+                }
+            };
+            run_command(line.trim(), &app_state, &shutdown_token).await;
+        }
+    })
+}
+
+async fn run_command(line: &str, app_state: &AppState, shutdown_token: &CancellationToken) {
+    let (command, argument) = match line.split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, argument.trim()),
+        None => (line, ""),
+    };
+    match command {
+        "" => {}
+        "toggle" => crate::toggle_recording(app_state.clone()).await,
+        "start" => crate::start_recording(app_state.clone()),
+        "stop" => crate::stop_recording(app_state),
+        "cancel" => crate::cancel_recording(app_state),
+        "pause" => app_state.set_enabled(false),
+        "resume" => app_state.set_enabled(true),
+        "language" if !argument.is_empty() => {
+            info!("Switching language to {} via stdin", argument);
+            app_state.update_transcription_config(|transcription| {
+                transcription.language = argument.to_string()
+            });
+            app_state.request_ui_refresh();
+        }
+        "status" => {
+            let state = if !app_state.enabled.load(Ordering::Relaxed) {
+                "paused"
+            } else {
+                app_state.recording_state().as_str()
+            };
+            println!("{state}");
+        }
+        "quit" => shutdown_token.cancel(),
+        _ => warn!(
+            "Unknown command {:?}, expected toggle, start, stop, cancel, pause, resume, language <code>, status or quit",
+            line
+        ),
+    }
+}
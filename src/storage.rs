@@ -0,0 +1,121 @@
+//! Locations of files the application writes at runtime.
+
+use crate::config::{expand_home, DebugConfig};
+use eyre::{OptionExt, Result, WrapErr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Directory for persistent runtime data such as statistics and history
+/// (`$XDG_STATE_HOME/gnome-voice-input`)
+pub fn state_dir() -> Result<PathBuf> {
+    let state_dir = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .ok_or_eyre("Could not determine state directory")?;
+    Ok(state_dir.join("gnome-voice-input"))
+}
+
+/// Directory for the rotating log files (`$XDG_STATE_HOME/gnome-voice-input/logs`)
+pub fn log_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("logs"))
+}
+
+/// Directory for recordings saved by `--record-only`, which are never
+/// deleted automatically (`$XDG_DATA_HOME/gnome-voice-input/recordings`)
+pub fn recordings_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().ok_or_eyre("Could not determine data directory")?;
+    Ok(data_dir.join("gnome-voice-input").join("recordings"))
+}
+
+/// Path for a new `--record-only` recording named after the current time
+pub fn new_recording_path() -> Result<PathBuf> {
+    let dir = recordings_dir()?;
+    std::fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir.join(recording_file_name()))
+}
+
+/// Extensions of the files written in debug mode, the only ones pruned
+const DEBUG_EXTENSIONS: [&str; 2] = ["wav", "jsonl"];
+
+/// Files of one session in debug mode
+pub struct DebugArtifacts {
+    /// WAV file of the audio sent to Deepgram
+    pub audio: PathBuf,
+    /// Deepgram's responses as JSON lines
+    pub responses: PathBuf,
+}
+
+/// Directory for the files written in debug mode (`[debug] directory`,
+/// `$XDG_STATE_HOME/gnome-voice-input/recordings` by default)
+pub fn debug_artifacts_dir(debug: &DebugConfig) -> Result<PathBuf> {
+    match &debug.directory {
+        Some(directory) => expand_home(directory),
+        None => Ok(state_dir()?.join("recordings")),
+    }
+}
+
+/// Paths for the debug files of `session` (0 outside sessions), named after
+/// `[debug] file_name`. Creates the directory and first deletes the files
+/// beyond `max_age_days` and `max_total_mb`.
+pub fn new_debug_artifacts(debug: &DebugConfig, session: Option<u64>) -> Result<DebugArtifacts> {
+    let dir = debug_artifacts_dir(debug)?;
+    std::fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+    prune_debug_artifacts(&dir, debug)?;
+
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+    let name = debug
+        .file_name
+        .replace("{timestamp}", &timestamp)
+        .replace("{session}", &session.unwrap_or(0).to_string());
+    Ok(DebugArtifacts {
+        audio: dir.join(format!("{name}.wav")),
+        responses: dir.join(format!("{name}.jsonl")),
+    })
+}
+
+fn recording_file_name() -> String {
+    chrono::Local::now()
+        .format("%Y%m%d-%H%M%S%.3f.wav")
+        .to_string()
+}
+
+/// Delete the debug files in `dir` older than `max_age_days`, then the oldest
+/// ones until the rest fits into `max_total_mb`
+fn prune_debug_artifacts(dir: &Path, debug: &DebugConfig) -> Result<()> {
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter(|entry| {
+            let path = entry.path();
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            extension.is_some_and(|extension| DEBUG_EXTENSIONS.contains(&extension))
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+        })
+        .collect();
+    files.sort_by_key(|(modified, _, _)| std::cmp::Reverse(*modified));
+
+    let max_age = debug
+        .max_age_days
+        .map(|days| Duration::from_secs(days * 24 * 60 * 60));
+    let max_total = debug.max_total_mb * 1024 * 1024;
+    // Once the newest files fill `max_total`, all older ones go
+    let mut kept = 0;
+    for (modified, size, path) in files {
+        let expired = max_age
+            .is_some_and(|max_age| modified.elapsed().is_ok_and(|elapsed| elapsed > max_age));
+        if !expired {
+            kept += size;
+            if kept <= max_total {
+                continue;
+            }
+        }
+        if let Err(e) = std::fs::remove_file(&path) {
+            warn!("Failed to remove old debug file {}: {}", path.display(), e);
+        }
+    }
+    Ok(())
+}
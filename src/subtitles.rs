@@ -0,0 +1,57 @@
+//! SubRip (`.srt`) and WebVTT (`.vtt`) cues for the `subtitles` output
+//! handler, which captions a session with its final transcripts.
+
+use crate::config::SubtitleFormat;
+use std::time::Duration;
+
+pub fn extension(format: SubtitleFormat) -> &'static str {
+    match format {
+        SubtitleFormat::Srt => "srt",
+        SubtitleFormat::Vtt => "vtt",
+    }
+}
+
+/// Written once at the start of a file
+pub fn header(format: SubtitleFormat) -> &'static str {
+    match format {
+        SubtitleFormat::Srt => "",
+        SubtitleFormat::Vtt => "WEBVTT\n\n",
+    }
+}
+
+/// The `index`th cue (counting from 1) showing `text` from `start` to `end`,
+/// followed by the blank line separating cues
+pub fn cue(
+    format: SubtitleFormat,
+    index: usize,
+    start: Duration,
+    end: Duration,
+    text: &str,
+) -> String {
+    let range = format!(
+        "{} --> {}",
+        timestamp(format, start),
+        timestamp(format, end)
+    );
+    match format {
+        SubtitleFormat::Srt => format!("{index}\n{range}\n{text}\n\n"),
+        SubtitleFormat::Vtt => format!("{range}\n{text}\n\n"),
+    }
+}
+
+/// `HH:MM:SS,mmm` for SubRip, `HH:MM:SS.mmm` for WebVTT
+fn timestamp(format: SubtitleFormat, time: Duration) -> String {
+    let millis = time.as_millis();
+    let separator = match format {
+        SubtitleFormat::Srt => ',',
+        SubtitleFormat::Vtt => '.',
+    };
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
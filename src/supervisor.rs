@@ -0,0 +1,152 @@
+//! Watches the long-running components and audio capture: restarts the
+//! hotkey listener or tray when their thread or task died (e.g. panicked),
+//! and notifies the user when a component keeps failing or audio capture
+//! fails in consecutive sessions.
+
+use crate::{
+    app_manager::{SharedComponents, SupervisedComponent},
+    notifications,
+    state::{AppEvent, AppState},
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Restarts of one component within `RESTART_WINDOW` before giving up on it
+const MAX_RESTARTS: usize = 3;
+const RESTART_WINDOW: Duration = Duration::from_secs(600);
+
+/// Sessions in a row whose capture failed before the user is told
+const CAPTURE_FAILURE_LIMIT: usize = 2;
+
+#[derive(Default)]
+struct Supervisor {
+    /// Recent restarts per component
+    restarts: HashMap<SupervisedComponent, Vec<Instant>>,
+    /// Components that failed too often and are left stopped
+    given_up: Vec<SupervisedComponent>,
+    /// Capture failures since audio last reached the transcriber
+    capture_failures: usize,
+}
+
+impl Supervisor {
+    /// Record a restart; false if the component failed too often to try again
+    fn allow_restart(&mut self, component: SupervisedComponent) -> bool {
+        let now = Instant::now();
+        let restarts = self.restarts.entry(component).or_default();
+        restarts.retain(|restart| now.duration_since(*restart) < RESTART_WINDOW);
+        if restarts.len() >= MAX_RESTARTS {
+            return false;
+        }
+        restarts.push(now);
+        true
+    }
+
+    async fn check_components(
+        &mut self,
+        app_state: &AppState,
+        components: &SharedComponents,
+        shutdown_token: &CancellationToken,
+    ) {
+        let mut components = components.lock().await;
+        let Some(components) = components.as_mut() else {
+            return;
+        };
+
+        for component in components.dead_components() {
+            if self.given_up.contains(&component) {
+                continue;
+            }
+            if !self.allow_restart(component) {
+                error!(
+                    "{} died {} times within {:?}, not restarting it again",
+                    component, MAX_RESTARTS, RESTART_WINDOW
+                );
+                self.given_up.push(component);
+                report(
+                    app_state,
+                    &format!("{component} keeps failing"),
+                    &format!("{component} stopped working and was not restarted. Restart voice input or check the log."),
+                );
+                continue;
+            }
+
+            warn!("{} died unexpectedly, restarting it", component);
+            if let Err(e) = components
+                .restart(component, app_state, shutdown_token)
+                .await
+            {
+                error!("Failed to restart {}: {:#}", component, e);
+            }
+        }
+    }
+
+    fn handle_event(&mut self, app_state: &AppState, event: AppEvent) {
+        match event {
+            AppEvent::CaptureFailed(message) => {
+                self.capture_failures += 1;
+                if self.capture_failures == CAPTURE_FAILURE_LIMIT {
+                    report(
+                        app_state,
+                        "Audio capture keeps failing",
+                        &format!(
+                            "{message}. Check the microphone with `gnome-voice-input doctor`."
+                        ),
+                    );
+                }
+            }
+            AppEvent::FirstTranscript(_) => self.capture_failures = 0,
+            _ => {}
+        }
+    }
+}
+
+/// Publish a persistent failure and show it as a notification
+fn report(app_state: &AppState, summary: &str, body: &str) {
+    app_state.publish(AppEvent::BackendError {
+        session: None,
+        message: format!("{summary}: {body}"),
+    });
+    let (summary, body) = (summary.to_string(), body.to_string());
+    // The notification is a blocking D-Bus call
+    std::thread::spawn(move || {
+        if let Err(e) = notifications::notify(&summary, &body) {
+            warn!("Failed to show failure notification: {:#}", e);
+        }
+    });
+}
+
+/// Check the components every few seconds and follow capture failures until
+/// shutdown
+pub fn spawn_supervisor(
+    app_state: AppState,
+    components: SharedComponents,
+    shutdown_token: &CancellationToken,
+) -> JoinHandle<()> {
+    let shutdown_token = shutdown_token.clone();
+    tokio::spawn(async move {
+        let mut supervisor = Supervisor::default();
+        let mut events = app_state.events.subscribe();
+        let mut checks = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                _ = checks.tick() => {
+                    supervisor.check_components(&app_state, &components, &shutdown_token).await;
+                }
+                event = events.recv() => match event {
+                    Ok(event) => supervisor.handle_event(&app_state, event),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("Supervisor skipped {} events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+        debug!("Supervisor stopped");
+    })
+}
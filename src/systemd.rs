@@ -0,0 +1,154 @@
+//! Running as a systemd user service (`--service`): readiness and watchdog
+//! notifications over `$NOTIFY_SOCKET`, and the unit installed by
+//! `gnome-voice-input install-service`.
+
+use crate::dbus_service;
+use eyre::{OptionExt, Result, WrapErr};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const UNIT_NAME: &str = "gnome-voice-input.service";
+
+/// Exit status for configuration errors (EX_CONFIG). The unit doesn't restart
+/// on it, since restarting won't fix the config.
+pub const EXIT_CONFIG: i32 = 78;
+
+/// Send a state such as `READY=1` to the service manager. Does nothing when
+/// not started by systemd.
+pub fn notify(state: &str) {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(e) = send_notification(Path::new(&socket_path), state) {
+        warn!("Failed to notify systemd ({}): {:#}", state, e);
+    }
+}
+
+fn send_notification(socket_path: &Path, state: &str) -> Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    // A leading '@' denotes a socket in the abstract namespace
+    match socket_path.to_str().and_then(|path| path.strip_prefix('@')) {
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &address)?;
+        }
+        None => {
+            socket.send_to(state.as_bytes(), socket_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Watchdog timeout configured in the unit (`WatchdogSec=`), if it applies to
+/// this process
+fn watchdog_timeout() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse() != Ok(std::process::id()) {
+            return None;
+        }
+    }
+    Some(Duration::from_micros(usec))
+}
+
+/// Ping the watchdog, if one is configured, at half its timeout for as long
+/// as the runtime is responsive
+pub fn spawn_watchdog(shutdown_token: &CancellationToken) {
+    let Some(timeout) = watchdog_timeout() else {
+        return;
+    };
+    let interval = timeout / 2;
+    info!("Pinging the systemd watchdog every {:?}", interval);
+
+    let shutdown_token = shutdown_token.clone();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = shutdown_token.cancelled() => break,
+                _ = ticks.tick() => notify("WATCHDOG=1"),
+            }
+        }
+    });
+}
+
+/// Write the user unit running this executable with `--service` and the D-Bus
+/// service file that lets the bus start it on demand, reload systemd and
+/// optionally enable and start the unit. Returns the unit path.
+pub fn install_service(config_path: Option<PathBuf>, enable: bool) -> Result<PathBuf> {
+    let executable = std::env::current_exe().wrap_err("Failed to locate the executable")?;
+    let mut exec_start = format!("\"{}\" --service", executable.display());
+    if let Some(config_path) = config_path {
+        let config_path = config_path
+            .canonicalize()
+            .wrap_err_with(|| format!("Config file not found: {}", config_path.display()))?;
+        exec_start.push_str(&format!(" --config \"{}\"", config_path.display()));
+    }
+
+    let unit = format!(
+        "[Unit]
+Description=Voice input for GNOME using Deepgram
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+Type=notify
+ExecStart={exec_start}
+Restart=on-failure
+RestartSec=5
+WatchdogSec=30
+RestartPreventExitStatus={EXIT_CONFIG}
+
+[Install]
+WantedBy=graphical-session.target
+"
+    );
+
+    let unit_dir = dirs::config_dir()
+        .ok_or_eyre("Failed to get config directory")?
+        .join("systemd/user");
+    std::fs::create_dir_all(&unit_dir)
+        .wrap_err_with(|| format!("Failed to create {}", unit_dir.display()))?;
+    let unit_path = unit_dir.join(UNIT_NAME);
+    std::fs::write(&unit_path, unit)
+        .wrap_err_with(|| format!("Failed to write {}", unit_path.display()))?;
+
+    let activation_dir = dirs::data_dir()
+        .ok_or_eyre("Failed to get data directory")?
+        .join("dbus-1/services");
+    std::fs::create_dir_all(&activation_dir)
+        .wrap_err_with(|| format!("Failed to create {}", activation_dir.display()))?;
+    let activation_path = activation_dir.join(format!("{}.service", dbus_service::BUS_NAME));
+    let activation = format!(
+        "[D-BUS Service]
+Name={}
+Exec={exec_start}
+SystemdService={UNIT_NAME}
+",
+        dbus_service::BUS_NAME
+    );
+    std::fs::write(&activation_path, activation)
+        .wrap_err_with(|| format!("Failed to write {}", activation_path.display()))?;
+
+    systemctl(&["daemon-reload"])?;
+    if enable {
+        systemctl(&["enable", "--now", UNIT_NAME])?;
+    }
+    Ok(unit_path)
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .wrap_err("Failed to run systemctl")?;
+    if !status.success() {
+        bail!("systemctl --user {} failed: {}", args.join(" "), status);
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Template variables: "insert today's date", "insert time" or "insert" and
+//! the name of a user-defined variable, expanded to a formatted value.
+
+use crate::phrases;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, TimeZone};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// Spoken before a variable name to expand it
+const TRIGGER: &str = "insert";
+
+/// Variables available without configuration; `[output.templates]` adds to
+/// and overrides these
+const BUILTIN: [(&str, &str); 5] = [
+    ("date", "%Y-%m-%d"),
+    ("today's date", "%Y-%m-%d"),
+    ("time", "%H:%M"),
+    ("weekday", "%A"),
+    ("timestamp", "%Y-%m-%d %H:%M:%S"),
+];
+
+/// The built-in variables with `configured` added, keyed by lowercase name
+pub fn table(configured: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut table: BTreeMap<String, String> = BUILTIN
+        .iter()
+        .map(|(name, format)| (name.to_string(), format.to_string()))
+        .collect();
+    table.extend(
+        configured
+            .iter()
+            .map(|(name, format)| (name.to_lowercase(), format.clone())),
+    );
+    table
+}
+
+/// Whether `format` is a valid strftime format; values without `%` are
+/// inserted as they are
+pub fn is_valid_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+/// Replace "insert" followed by a variable name with the variable formatted
+/// at `now`
+pub fn expand<Tz: TimeZone>(
+    text: &str,
+    variables: &BTreeMap<String, String>,
+    now: &DateTime<Tz>,
+) -> String
+where
+    Tz::Offset: Display,
+{
+    // Invalid formats are rejected by `Config::load`; skipped here as
+    // formatting them panics
+    let values = variables
+        .iter()
+        .filter(|(_, format)| is_valid_format(format))
+        .map(|(name, format)| (format!("{TRIGGER} {name}"), now.format(format).to_string()))
+        .collect();
+    phrases::replace(text, &values)
+}
@@ -0,0 +1,111 @@
+//! TLS setup of the transcription connection (`[transcription.tls]`): CA
+//! certificates trusted besides the built-in roots, and optionally no
+//! certificate verification at all.
+
+use crate::config::TlsConfig;
+use eyre::{Result, WrapErr};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Accepts any server certificate, for `verify = false`
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// The certificates of a PEM file
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .wrap_err_with(|| format!("Failed to read CA certificates from {}", path.display()))?;
+    if certificates.is_empty() {
+        bail!("No certificates in {}", path.display());
+    }
+    Ok(certificates)
+}
+
+/// The client config for `config`; None when the defaults of the WebSocket
+/// client (built-in roots, verification on) apply
+pub fn client_config(config: &TlsConfig) -> Result<Option<Arc<ClientConfig>>> {
+    if config.ca_file.is_none() && config.verify {
+        return Ok(None);
+    }
+
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    if let Some(path) = &config.ca_file {
+        let (added, ignored) = roots.add_parsable_certificates(read_certificates(path)?);
+        if ignored > 0 {
+            warn!(
+                "Ignored {} invalid certificates in {}",
+                ignored,
+                path.display()
+            );
+        }
+        debug!("Trusting {} certificates from {}", added, path.display());
+    }
+
+    let provider = Arc::new(crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .wrap_err("Failed to set up TLS")?;
+    let client_config = if config.verify {
+        builder.with_root_certificates(roots).with_no_client_auth()
+    } else {
+        warn!("TLS certificate verification of the transcription connection is off");
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerification(provider)))
+            .with_no_client_auth()
+    };
+    Ok(Some(Arc::new(client_config)))
+}
@@ -1,34 +1,256 @@
+use deepgram::common::stream_response::StreamResponse;
 use deepgram::{
-    common::options::{Encoding, Language, Model, Options},
+    common::options::{Encoding, Endpointing, Language, Model, Options},
     Deepgram,
 };
-use eyre::Result;
-use futures::stream::StreamExt;
-use tokio::sync::mpsc;
+use eyre::{Result, WrapErr};
+use futures::stream::{Stream, StreamExt};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
 
-use crate::transcription_utils::{handle_full_response, TranscriptionResult};
-use crate::{audio_utils::create_audio_stream, config::TranscriptionConfig};
+use crate::api_keys::ApiKeys;
+use crate::audio::AudioFormat;
+use crate::config::{DebugConfig, TranscriptionConfig};
+use crate::latency::{Stage, LATENCY};
+use crate::metrics::METRICS;
+use crate::session::SessionId;
+use crate::stats::UsageStats;
+use crate::transcription_utils::{
+    channel_label, handle_full_response, label_speakers, ConnectionStatus, TranscriptionResult,
+};
+use crate::{listen, privacy, storage, tls};
+
+const DEEPGRAM_PROJECTS_URL: &str = "https://api.deepgram.com/v1/projects";
+/// Reconnects within one recording before the session is given up
+const MAX_RECONNECTS: u32 = 3;
+/// Wait before a reconnect, multiplied by the attempt number
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// Check that an API key is accepted by Deepgram with a lightweight authenticated request
+pub async fn verify_api_key(api_key: &str) -> Result<()> {
+    let response = reqwest::Client::new()
+        .get(DEEPGRAM_PROJECTS_URL)
+        .header("Authorization", format!("Token {api_key}"))
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .wrap_err("Failed to reach Deepgram")?;
+
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        reqwest::StatusCode::UNAUTHORIZED => bail!("Deepgram rejected the API key"),
+        reqwest::StatusCode::FORBIDDEN => bail!("API key is valid but lacks permissions"),
+        status => bail!("Unexpected response from Deepgram: HTTP {}", status),
+    }
+}
+
+/// Map a failed connection attempt to the status shown to the user
+fn classify_connection_error(error: &eyre::Report) -> ConnectionStatus {
+    if listen::is_auth_error(error) {
+        ConnectionStatus::AuthError
+    } else {
+        ConnectionStatus::Offline
+    }
+}
+
+/// Forward the audio unchanged while writing it to the WAV file `path`
+fn record_debug_wav(
+    mut audio_rx: mpsc::Receiver<Vec<u8>>,
+    path: &Path,
+    format: AudioFormat,
+) -> mpsc::Receiver<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: format.channels,
+        sample_rate: format.sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let writer = hound::WavWriter::create(path, spec)
+        .wrap_err_with(|| format!("Failed to create {}", path.display()));
+    let mut writer = match writer {
+        Ok(writer) => writer,
+        Err(e) => {
+            warn!("Debug recording disabled: {:#}", e);
+            return audio_rx;
+        }
+    };
+    info!("Saving audio sent to Deepgram to {}", path.display());
+
+    let (tx, rx) = mpsc::channel(audio_rx.max_capacity());
+    tokio::spawn(async move {
+        while let Some(chunk) = audio_rx.recv().await {
+            for sample in chunk.chunks_exact(2) {
+                if let Err(e) = writer.write_sample(i16::from_le_bytes([sample[0], sample[1]])) {
+                    warn!("Failed to write debug recording: {}", e);
+                    break;
+                }
+            }
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+        if let Err(e) = writer.finalize() {
+            warn!("Failed to finalize debug recording: {}", e);
+        }
+    });
+    rx
+}
+
+/// File receiving every response of a session as a JSON line, next to the
+/// WAV file of its audio in debug mode
+struct ResponseDump(File);
+
+impl ResponseDump {
+    fn create(path: &Path) -> Option<Self> {
+        match File::create(path) {
+            Ok(file) => {
+                info!("Saving Deepgram responses to {}", path.display());
+                Some(Self(file))
+            }
+            Err(e) => {
+                warn!("Failed to create {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn write(&mut self, response: &StreamResponse) -> Result<()> {
+        let line = serde_json::to_string(response)?;
+        writeln!(self.0, "{line}")?;
+        Ok(())
+    }
+}
+
+/// Convert mpsc::Receiver to a Stream of audio chunks.
+/// The receiver stays locked while the stream exists, so a reconnected
+/// stream continues with the next chunk.
+fn create_audio_stream(
+    mut audio_rx: tokio::sync::OwnedMutexGuard<mpsc::Receiver<Vec<u8>>>,
+) -> impl Stream<Item = Vec<u8>> {
+    futures::stream::poll_fn(move |cx| match audio_rx.poll_recv(cx) {
+        std::task::Poll::Ready(Some(data)) => {
+            trace!("Audio stream produced {} bytes", data.len());
+            std::task::Poll::Ready(Some(data))
+        }
+        std::task::Poll::Ready(None) => {
+            debug!("Audio stream ended");
+            std::task::Poll::Ready(None)
+        }
+        std::task::Poll::Pending => std::task::Poll::Pending,
+    })
+}
 
 pub struct Transcriber {
     client: Deepgram,
+    api_keys: Arc<ApiKeys>,
     config: TranscriptionConfig,
-    _debug: bool,
+    connection_status: Arc<watch::Sender<ConnectionStatus>>,
+    stats: Arc<UsageStats>,
+    debug: bool,
+    /// Where the debug files go, see [`storage::new_debug_artifacts`]
+    debug_artifacts: DebugConfig,
+    /// Silence after which an utterance ends, if utterance ends are reported
+    utterance_end: Option<Duration>,
+    /// Request diarization and label final transcripts by speaker
+    speaker_labels: bool,
+    /// Labels of the channels of multichannel audio, by channel index
+    channel_labels: Vec<String>,
 }
 
 impl Transcriber {
-    pub fn new(api_key: String, config: TranscriptionConfig, debug: bool) -> Self {
-        let client = Deepgram::new(&api_key).expect("Failed to create Deepgram client");
+    pub fn new(
+        api_key: String,
+        config: TranscriptionConfig,
+        connection_status: Arc<watch::Sender<ConnectionStatus>>,
+        stats: Arc<UsageStats>,
+        debug: bool,
+    ) -> Self {
+        let client = match &config.base_url {
+            Some(base_url) => Deepgram::with_base_url_and_api_key(base_url.as_str(), &api_key),
+            None => Deepgram::new(&api_key),
+        }
+        .expect("Failed to create Deepgram client");
         Self {
             client,
-            config,
-            _debug: debug,
+            api_keys: Arc::new(ApiKeys::new(vec![api_key])),
+            config: config.for_current_language(),
+            connection_status,
+            stats,
+            debug,
+            debug_artifacts: DebugConfig::default(),
+            utterance_end: None,
+            speaker_labels: false,
+            channel_labels: Vec::new(),
         }
     }
 
+    /// Connect with these keys instead of the one given to `new`, switching
+    /// to the next when Deepgram rejects the current one
+    pub fn with_api_keys(mut self, api_keys: Arc<ApiKeys>) -> Self {
+        self.api_keys = api_keys;
+        self
+    }
+
+    pub fn api_keys(&self) -> &Arc<ApiKeys> {
+        &self.api_keys
+    }
+
+    /// Report `TranscriptionResult::UtteranceEnd` after a final result that
+    /// is followed by `silence`
+    pub fn with_utterance_end(mut self, silence: Duration) -> Self {
+        self.utterance_end = Some(silence);
+        self
+    }
+
+    /// Have Deepgram tell speakers apart and put each speaker turn of a final
+    /// transcript on its own line, starting with "Speaker N:"
+    pub fn with_speaker_labels(mut self) -> Self {
+        self.speaker_labels = true;
+        self
+    }
+
+    /// Start each line of a final transcript of multichannel audio with the
+    /// label of its channel, e.g. "Me:" and "Others:" (`[audio]
+    /// channel_labels`)
+    pub fn with_channel_labels(mut self, channel_labels: Vec<String>) -> Self {
+        self.channel_labels = channel_labels;
+        self
+    }
+
+    /// Name and retention of the files saved in debug mode (`[debug]`)
+    pub fn with_debug_artifacts(mut self, debug_artifacts: DebugConfig) -> Self {
+        self.debug_artifacts = debug_artifacts;
+        self
+    }
+
     pub async fn transcribe_stream(
-        self: std::sync::Arc<Self>,
+        self: Arc<Self>,
+        audio_rx: mpsc::Receiver<Vec<u8>>,
+        format: AudioFormat,
+    ) -> Result<mpsc::Receiver<TranscriptionResult>> {
+        self.transcribe(None, audio_rx, format).await
+    }
+
+    /// `transcribe_stream` for a recording session, whose ID names the files
+    /// saved in debug mode
+    pub async fn transcribe_session_stream(
+        self: Arc<Self>,
+        session: SessionId,
+        audio_rx: mpsc::Receiver<Vec<u8>>,
+        format: AudioFormat,
+    ) -> Result<mpsc::Receiver<TranscriptionResult>> {
+        self.transcribe(Some(session), audio_rx, format).await
+    }
+
+    async fn transcribe(
+        self: Arc<Self>,
+        session: Option<SessionId>,
         audio_rx: mpsc::Receiver<Vec<u8>>,
-        sample_rate: u32,
+        format: AudioFormat,
     ) -> Result<mpsc::Receiver<TranscriptionResult>> {
         debug!("Creating transcription stream");
         let (text_tx, text_rx) = mpsc::channel(10);
@@ -73,46 +295,185 @@ impl Transcriber {
             }
         };
 
+        // Nova-3 (also used for unknown models) takes keyterms, older models keywords
+        if !self.config.vocabulary.is_empty() {
+            let terms = self.config.vocabulary.iter().map(String::as_str);
+            options_builder = match self.config.model.as_str() {
+                "nova-2" | "nova" | "enhanced" | "base" => options_builder.keywords(terms),
+                _ => options_builder.keyterms(terms),
+            };
+        }
+
+        if self.speaker_labels {
+            options_builder = options_builder.diarize(true);
+        }
+        // Each channel is transcribed on its own
+        if format.channels > 1 {
+            options_builder = options_builder.multichannel(true);
+        }
+
         let options = options_builder.build();
 
         debug!("Starting WebSocket task with options: {:?}", options);
         tokio::spawn(async move {
-            match self
-                .start_websocket_stream(options, audio_rx, text_tx, sample_rate)
+            self.run_stream(options, session, audio_rx, text_tx, format)
                 .await
-            {
-                Ok(_) => info!("WebSocket stream completed"),
-                Err(e) => error!("WebSocket stream error: {}", e),
-            }
         });
 
         Ok(text_rx)
     }
 
-    async fn start_websocket_stream(
+    /// Stream the audio until it ends. If the connection drops while audio is
+    /// still being captured, reconnect and continue with the same capture.
+    async fn run_stream(
         &self,
         options: Options,
+        session: Option<SessionId>,
         audio_rx: mpsc::Receiver<Vec<u8>>,
         text_tx: mpsc::Sender<TranscriptionResult>,
-        sample_rate: u32,
+        format: AudioFormat,
+    ) {
+        let (audio_rx, mut dump) = if self.debug && !privacy::is_enabled() {
+            match storage::new_debug_artifacts(&self.debug_artifacts, session.map(SessionId::get)) {
+                Ok(paths) => (
+                    record_debug_wav(audio_rx, &paths.audio, format),
+                    ResponseDump::create(&paths.responses),
+                ),
+                Err(e) => {
+                    warn!("Debug recording disabled: {:#}", e);
+                    (audio_rx, None)
+                }
+            }
+        } else {
+            (audio_rx, None)
+        };
+        let audio_rx = Arc::new(tokio::sync::Mutex::new(audio_rx));
+
+        let mut reconnects = 0;
+        loop {
+            let result = self
+                .start_websocket_stream(
+                    options.clone(),
+                    audio_rx.clone(),
+                    &text_tx,
+                    format,
+                    &mut dump,
+                )
+                .await;
+            match &result {
+                Ok(()) => info!("WebSocket stream completed"),
+                Err(e) => error!("WebSocket stream error: {}", e),
+            }
+
+            let audio_ended = {
+                let audio_rx = audio_rx.lock().await;
+                audio_rx.is_closed() && audio_rx.is_empty()
+            };
+            // A first connection that fails is reported to the session as is
+            if audio_ended || text_tx.is_closed() || (result.is_err() && reconnects == 0) {
+                return;
+            }
+            if reconnects == MAX_RECONNECTS {
+                error!(
+                    "Transcription stream lost, giving up after {} reconnects",
+                    reconnects
+                );
+                self.connection_status
+                    .send_replace(ConnectionStatus::Offline);
+                return;
+            }
+
+            reconnects += 1;
+            METRICS.reconnects.inc();
+            warn!(
+                "Transcription stream ended while recording, reconnecting ({}/{})",
+                reconnects, MAX_RECONNECTS
+            );
+            self.connection_status
+                .send_replace(ConnectionStatus::Reconnecting);
+            tokio::time::sleep(RECONNECT_DELAY * reconnects).await;
+        }
+    }
+
+    async fn start_websocket_stream(
+        &self,
+        options: Options,
+        audio_rx: Arc<tokio::sync::Mutex<mpsc::Receiver<Vec<u8>>>>,
+        text_tx: &mpsc::Sender<TranscriptionResult>,
+        format: AudioFormat,
+        dump: &mut Option<ResponseDump>,
     ) -> Result<()> {
         info!("Starting WebSocket connection to Deepgram");
 
-        // Convert the audio receiver into a stream that produces Result<Bytes, _>
-        let audio_stream = create_audio_stream(audio_rx);
+        self.connection_status
+            .send_replace(ConnectionStatus::Connecting);
 
-        // Create WebSocket stream with specific audio settings
-        let mut stream = self
-            .client
-            .transcription()
+        // Request options with specific audio settings
+        let transcription = self.client.transcription();
+        let mut request = transcription
             .stream_request_with_options(options)
             .encoding(Encoding::Linear16)
-            .sample_rate(sample_rate)
-            .channels(1)
+            .sample_rate(format.sample_rate)
+            .channels(format.channels)
             .interim_results(self.config.use_interim_results)
-            .keep_alive() // Enable keep-alive
-            .stream(audio_stream)
-            .await?;
+            .keep_alive(); // Enable keep-alive
+        if let Some(silence) = self.utterance_end {
+            request = request.endpointing(Endpointing::CustomDurationMs(
+                silence.as_millis().try_into().unwrap_or(u32::MAX),
+            ));
+        }
+        let query = request
+            .urlencoded()
+            .wrap_err("Failed to encode the transcription options")?;
+        let url = listen::listen_url(self.config.base_url.as_deref(), &query)?;
+        let tls = tls::client_config(&self.config.tls)?;
+
+        // Each key is tried at most once per connection
+        let mut attempts = 0;
+        let (mut stream, key_index) = loop {
+            let audio_stream =
+                create_audio_stream(audio_rx.clone().lock_owned().await).inspect(move |chunk| {
+                    METRICS.record_audio_streamed(
+                        chunk.len() / usize::from(format.channels),
+                        format.sample_rate,
+                    );
+                    LATENCY.mark(Stage::FirstAudioSent);
+                });
+            let (key_index, api_key) = self.api_keys.current();
+            let stream_result =
+                listen::connect(url.clone(), api_key, tls.clone(), Box::pin(audio_stream)).await;
+            attempts += 1;
+            match stream_result {
+                Ok(stream) => {
+                    if self.api_keys.keys().len() > 1 {
+                        info!("Connected with API key {}", self.api_keys.label(key_index));
+                    }
+                    break (stream, key_index);
+                }
+                Err(e) => {
+                    METRICS.connection_failures.inc();
+                    let rejected = listen::is_auth_error(&e) || listen::is_quota_error(&e);
+                    if rejected && attempts < self.api_keys.keys().len() {
+                        let next = self.api_keys.rotate(key_index);
+                        warn!(
+                            "Deepgram rejected API key {} ({:#}), switching to {}",
+                            self.api_keys.label(key_index),
+                            e,
+                            self.api_keys.label(next)
+                        );
+                        continue;
+                    }
+                    self.connection_status
+                        .send_replace(classify_connection_error(&e));
+                    return Err(e);
+                }
+            }
+        };
+        METRICS.connections.inc();
+        self.connection_status
+            .send_replace(ConnectionStatus::Connected);
+        // Audio offsets reported by Deepgram are relative to the stream start
+        let stream_started = Instant::now();
 
         info!(
             "WebSocket stream created, request_id: {}",
@@ -123,30 +484,131 @@ impl Transcriber {
         let mut result_count = 0;
         while let Some(result) = stream.next().await {
             result_count += 1;
-            debug!("Received result #{}: {:?}", result_count, result);
+            debug!(
+                "Received result #{}: {}",
+                result_count,
+                privacy::loggable(&format!("{result:?}"))
+            );
 
             match result {
                 Ok(response) => {
-                    if let Err(e) = self.handle_stream_response(response, &text_tx).await {
+                    if let Some(file) = dump {
+                        if let Err(e) = file.write(&response) {
+                            warn!(
+                                "Failed to save Deepgram response, no longer saving: {:#}",
+                                e
+                            );
+                            *dump = None;
+                        }
+                    }
+                    self.record_latency(&response, stream_started);
+                    if let Err(e) = self
+                        .handle_stream_response(response, key_index, text_tx)
+                        .await
+                    {
                         error!("Error handling response: {}", e);
                     }
                 }
                 Err(e) => {
-                    error!("Stream error: {:?}", e);
+                    error!("Stream error: {:#}", e);
+                    METRICS.transcription_errors.inc();
+                    self.connection_status
+                        .send_replace(ConnectionStatus::Offline);
                 }
             }
         }
 
+        // Keep error states visible until the next connection attempt
+        self.connection_status.send_if_modified(|status| {
+            let was_connected = *status == ConnectionStatus::Connected;
+            if was_connected {
+                *status = ConnectionStatus::Disconnected;
+            }
+            was_connected
+        });
+
         info!("Transcription stream ended after {} results", result_count);
         Ok(())
     }
 
+    /// Track how long after the end of the transcribed audio a final result arrived
+    fn record_latency(&self, response: &StreamResponse, stream_started: Instant) {
+        if let StreamResponse::TranscriptResponse {
+            start,
+            duration,
+            is_final: true,
+            ..
+        } = response
+        {
+            let audio_end = Duration::from_secs_f64((start + duration).max(0.0));
+            let latency = stream_started.elapsed().saturating_sub(audio_end);
+            debug!("Final transcript latency: {:?}", latency);
+            self.stats.record_latency(latency);
+            METRICS.transcription_latency.observe(latency);
+        }
+    }
+
+    /// Pass on the results of a response received on a connection made with
+    /// the API key at `key_index`
     async fn handle_stream_response(
         &self,
-        response: deepgram::common::stream_response::StreamResponse,
+        response: StreamResponse,
+        key_index: usize,
         text_tx: &mpsc::Sender<TranscriptionResult>,
     ) -> Result<()> {
-        if let Some(result) = handle_full_response(response, self.config.use_interim_results) {
+        let speech_final = matches!(
+            response,
+            StreamResponse::TranscriptResponse {
+                speech_final: true,
+                ..
+            }
+        );
+
+        let labeled = if self.speaker_labels {
+            label_speakers(&response)
+        } else {
+            None
+        };
+        let channel = channel_label(&response, &self.channel_labels);
+
+        let mut results = Vec::new();
+        if let Some(mut result) = handle_full_response(response, self.config.use_interim_results) {
+            if let TranscriptionResult::Interim(transcript)
+            | TranscriptionResult::Final(transcript) = &mut result
+            {
+                transcript.api_key = Some(key_index);
+            }
+            if let TranscriptionResult::Final(transcript) = &mut result {
+                if let Some(labeled) = labeled {
+                    transcript.text = labeled;
+                }
+                if let Some(channel) = channel {
+                    transcript.text = transcript
+                        .text
+                        .lines()
+                        .map(|line| format!("{channel}: {line}"))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                }
+            }
+            match result {
+                TranscriptionResult::Interim(_) => {
+                    METRICS.interim_transcripts.inc();
+                    LATENCY.mark(Stage::FirstInterim);
+                }
+                TranscriptionResult::Final(_) => {
+                    METRICS.final_transcripts.inc();
+                    LATENCY.mark(Stage::FirstFinal);
+                }
+                TranscriptionResult::UtteranceEnd => {}
+            }
+            results.push(result);
+        }
+        if speech_final && self.utterance_end.is_some() {
+            results.push(TranscriptionResult::UtteranceEnd);
+        }
+
+        for result in results {
             if text_tx.send(result).await.is_err() {
                 error!("Failed to send transcript - receiver dropped");
                 return Err(eyre!("Text receiver dropped"));
@@ -1,9 +1,220 @@
-use deepgram::common::stream_response::StreamResponse;
+use crate::privacy;
+use deepgram::common::stream_response::{self, StreamResponse};
+use std::time::Duration;
+
+/// Health of the connection to the transcription backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Disconnected,
+    Connecting,
+    Connected,
+    AuthError,
+    Offline,
+    /// The connection dropped during a recording and is being re-established
+    Reconnecting,
+}
+
+impl std::fmt::Display for ConnectionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            ConnectionStatus::Disconnected => "Not connected",
+            ConnectionStatus::Connecting => "Connecting",
+            ConnectionStatus::Connected => "Connected",
+            ConnectionStatus::AuthError => "Authentication failed",
+            ConnectionStatus::Offline => "Offline",
+            ConnectionStatus::Reconnecting => "Reconnecting",
+        };
+        f.write_str(label)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum TranscriptionResult {
-    Interim(String),
-    Final(String),
+    Interim(Transcript),
+    Final(Transcript),
+    /// The speaker paused after the preceding final result; only reported by
+    /// transcribers created `with_utterance_end`
+    UtteranceEnd,
+}
+
+impl TranscriptionResult {
+    /// The transcript of an interim or final result
+    pub fn transcript(&self) -> Option<&Transcript> {
+        match self {
+            TranscriptionResult::Interim(transcript) | TranscriptionResult::Final(transcript) => {
+                Some(transcript)
+            }
+            TranscriptionResult::UtteranceEnd => None,
+        }
+    }
+
+    /// The text of an interim or final result
+    pub fn text(&self) -> Option<&str> {
+        self.transcript().map(|transcript| transcript.text.as_str())
+    }
+}
+
+/// The text of a result with what Deepgram reported about it. Results built
+/// from text alone (tests, replays) have a confidence of 1.0 and no words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcript {
+    pub text: String,
+    /// 0.0..=1.0
+    pub confidence: f64,
+    /// Language Deepgram detected, e.g. with `language = "multi"`
+    pub language: Option<String>,
+    /// Speaker of the first word, numbered from 0; only with speaker labels
+    pub speaker: Option<u32>,
+    /// Channel of multichannel audio, 0 for the microphone
+    pub channel: usize,
+    pub words: Vec<TranscriptWord>,
+    /// Where the transcribed audio starts, from the start of the stream
+    pub start: Duration,
+    pub duration: Duration,
+    /// Index of the Deepgram API key the transcriber was connected with, see
+    /// `ApiKeys`
+    pub api_key: Option<usize>,
+}
+
+/// A word of a `Transcript` with its timing from the start of the stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptWord {
+    /// With punctuation and capitalization if requested
+    pub word: String,
+    pub start: Duration,
+    pub end: Duration,
+    pub confidence: f64,
+    pub speaker: Option<u32>,
+}
+
+impl Transcript {
+    /// A transcript of `text` without any metadata
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            confidence: 1.0,
+            language: None,
+            speaker: None,
+            channel: 0,
+            words: Vec::new(),
+            start: Duration::ZERO,
+            duration: Duration::ZERO,
+            api_key: None,
+        }
+    }
+
+    /// Where the transcribed audio ends, from the start of the stream
+    pub fn end(&self) -> Duration {
+        self.start + self.duration
+    }
+
+    /// This transcript with `text` in place of its text, e.g. after a filter
+    /// changed it; the words are left as recognized
+    pub fn with_text(self, text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..self
+        }
+    }
+
+    /// Join the transcript that followed this one, e.g. to type a backlog at
+    /// once: the texts separated by `separator`, words and timing combined
+    pub fn append(&mut self, next: Transcript, separator: &str) {
+        self.text.push_str(separator);
+        self.text.push_str(&next.text);
+        self.confidence = self.confidence.min(next.confidence);
+        self.duration = next.end().saturating_sub(self.start);
+        self.words.extend(next.words);
+    }
+}
+
+impl From<String> for Transcript {
+    fn from(text: String) -> Self {
+        Transcript::new(text)
+    }
+}
+
+impl From<&str> for Transcript {
+    fn from(text: &str) -> Self {
+        Transcript::new(text)
+    }
+}
+
+/// Seconds reported by Deepgram as a duration, negative ones as zero
+fn seconds(seconds: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds).unwrap_or_default()
+}
+
+fn speaker(speaker: Option<i32>) -> Option<u32> {
+    speaker.and_then(|speaker| u32::try_from(speaker).ok())
+}
+
+impl From<&stream_response::Word> for TranscriptWord {
+    fn from(word: &stream_response::Word) -> Self {
+        Self {
+            word: word
+                .punctuated_word
+                .clone()
+                .unwrap_or_else(|| word.word.clone()),
+            start: seconds(word.start),
+            end: seconds(word.end),
+            confidence: word.confidence,
+            speaker: speaker(word.speaker),
+        }
+    }
+}
+
+/// Transcript of a final response with a line per speaker turn, "Speaker 1:
+/// ..." (numbered from 1); `None` without speaker information, which Deepgram
+/// only sends for diarized requests
+pub fn label_speakers(response: &StreamResponse) -> Option<String> {
+    let StreamResponse::TranscriptResponse {
+        is_final: true,
+        channel,
+        ..
+    } = response
+    else {
+        return None;
+    };
+    let words = &channel.alternatives.first()?.words;
+
+    let mut turns: Vec<(i32, Vec<&str>)> = Vec::new();
+    for word in words {
+        let speaker = word.speaker?;
+        let text = word.punctuated_word.as_deref().unwrap_or(&word.word);
+        match turns.last_mut() {
+            Some((current, words)) if *current == speaker => words.push(text),
+            _ => turns.push((speaker, vec![text])),
+        }
+    }
+    if turns.is_empty() {
+        return None;
+    }
+    let lines: Vec<String> = turns
+        .into_iter()
+        .map(|(speaker, words)| format!("Speaker {}: {}", speaker + 1, words.join(" ")))
+        .collect();
+    Some(lines.join("\n"))
+}
+
+/// Label of the channel a response of a multichannel stream transcribes:
+/// `labels[i]` for channel i, "Channel N" (numbered from 1) beyond them;
+/// `None` for a single channel
+pub fn channel_label(response: &StreamResponse, labels: &[String]) -> Option<String> {
+    let StreamResponse::TranscriptResponse { channel_index, .. } = response else {
+        return None;
+    };
+    let (&index, &channels) = (channel_index.first()?, channel_index.get(1)?);
+    if channels < 2 {
+        return None;
+    }
+    let index = usize::try_from(index).ok()?;
+    Some(
+        labels
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| format!("Channel {}", index + 1)),
+    )
 }
 
 /// Handle a full transcription response (for main application)
@@ -13,7 +224,12 @@ pub fn handle_full_response(
 ) -> Option<TranscriptionResult> {
     match response {
         StreamResponse::TranscriptResponse {
-            is_final, channel, ..
+            is_final,
+            channel,
+            channel_index,
+            start,
+            duration,
+            ..
         } => {
             debug!("TranscriptResponse - is_final: {}", is_final);
             debug!(
@@ -26,19 +242,46 @@ pub fn handle_full_response(
                 let transcript = alternative.transcript.trim();
                 debug!(
                     "Transcript text: '{}', confidence: {:.2}, is_final: {}",
-                    transcript, alternative.confidence, is_final
+                    privacy::loggable(transcript),
+                    alternative.confidence,
+                    is_final
                 );
 
                 if !transcript.is_empty() {
+                    let words: Vec<TranscriptWord> =
+                        alternative.words.iter().map(TranscriptWord::from).collect();
+                    let transcript = Transcript {
+                        text: transcript.to_string(),
+                        confidence: alternative.confidence,
+                        language: alternative.languages.first().cloned().or_else(|| {
+                            alternative
+                                .words
+                                .iter()
+                                .find_map(|word| word.language.clone())
+                        }),
+                        speaker: words.first().and_then(|word| word.speaker),
+                        channel: channel_index
+                            .first()
+                            .and_then(|&index| usize::try_from(index).ok())
+                            .unwrap_or_default(),
+                        words,
+                        start: seconds(start),
+                        duration: seconds(duration),
+                        api_key: None,
+                    };
                     return Some(if is_final {
                         info!(
                             "Final transcript: {} (confidence: {:.2})",
-                            transcript, alternative.confidence
+                            privacy::loggable(&transcript.text),
+                            transcript.confidence
                         );
-                        TranscriptionResult::Final(transcript.to_string())
+                        TranscriptionResult::Final(transcript)
                     } else if use_interim_results {
-                        debug!("Interim transcript: {}", transcript);
-                        TranscriptionResult::Interim(transcript.to_string())
+                        debug!(
+                            "Interim transcript: {}",
+                            privacy::loggable(&transcript.text)
+                        );
+                        TranscriptionResult::Interim(transcript)
                     } else {
                         // Skip interim results if disabled
                         return None;
@@ -68,7 +311,10 @@ pub fn handle_full_response(
             );
         }
         _ => {
-            debug!("Received unknown response type: {:?}", response);
+            debug!(
+                "Received unknown response type: {}",
+                privacy::loggable(&format!("{response:?}"))
+            );
         }
     }
 
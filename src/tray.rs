@@ -1,80 +1,160 @@
-use crate::{config::Config, state::AppState};
+use crate::{
+    config::Config,
+    desktop, history_window,
+    icons::{self, IconState},
+    privacy,
+    state::{AppState, RecordingState},
+    transcription_utils::ConnectionStatus,
+};
 use dbus::blocking::Connection;
-use ksni::{self, menu::StandardItem, MenuItem, Tray, TrayService};
-use std::path::Path;
-use std::time::Duration;
+use eyre::OptionExt;
+use ksni::{
+    self,
+    menu::{CheckmarkItem, RadioGroup, RadioItem, StandardItem, SubMenu},
+    Icon, MenuItem, Status, ToolTip, Tray, TrayService,
+};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tokio::runtime::Handle;
+use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
 
 struct VoiceInputTray {
     app_state: AppState,
     handle: Handle,
     config: Config,
+    /// When the current recording started, tracked for the elapsed time display
+    recording_since: Option<Instant>,
 }
 
-/// Check if an icon exists in common icon theme directories
-fn icon_exists(icon_name: &str) -> bool {
-    let icon_dirs = vec![
-        "/usr/share/icons/hicolor",
-        "/usr/share/icons/Adwaita",
-        "/usr/share/icons/gnome",
-        "/usr/share/pixmaps",
-    ];
-
-    for dir in icon_dirs {
-        // Check various common sizes and formats
-        let patterns = vec![
-            format!("{}/16x16/status/{}.png", dir, icon_name),
-            format!("{}/22x22/status/{}.png", dir, icon_name),
-            format!("{}/24x24/status/{}.png", dir, icon_name),
-            format!("{}/48x48/status/{}.png", dir, icon_name),
-            format!("{}/scalable/status/{}.svg", dir, icon_name),
-            format!("{}/16x16/devices/{}.png", dir, icon_name),
-            format!("{}/22x22/devices/{}.png", dir, icon_name),
-            format!("{}/24x24/devices/{}.png", dir, icon_name),
-            format!("{}/48x48/devices/{}.png", dir, icon_name),
-            format!("{}/scalable/devices/{}.svg", dir, icon_name),
-            format!("{}/{}.png", dir, icon_name),
-            format!("{}/{}.svg", dir, icon_name),
-        ];
-
-        for pattern in patterns {
-            if Path::new(&pattern).exists() {
-                return true;
+impl VoiceInputTray {
+    /// Path of the config file this instance was started with
+    fn config_path(&self) -> eyre::Result<PathBuf> {
+        Config::get_config_path(self.app_state.custom_config_path.clone())
+    }
+
+    fn icon_state(&self) -> IconState {
+        if self.is_paused() {
+            IconState::Paused
+        } else if self.app_state.recording_state().is_busy() {
+            // Alternate between two shades every half second when animated
+            let pulse = self.config.ui.animate_tray_icon
+                && self
+                    .recording_since
+                    .is_some_and(|since| since.elapsed().as_millis() / 500 % 2 == 1);
+            if pulse {
+                IconState::RecordingPulse
+            } else {
+                IconState::Recording
             }
+        } else if self.app_state.last_error.read().unwrap().is_some()
+            || matches!(
+                self.connection_status(),
+                ConnectionStatus::AuthError | ConnectionStatus::Offline
+            )
+        {
+            IconState::Error
+        } else {
+            IconState::Idle
         }
     }
-    false
+
+    /// Keep the recording start time in sync with the recording state
+    fn tick(&mut self) {
+        let recording = self.app_state.recording_state().is_busy();
+        match (recording, self.recording_since) {
+            (true, None) => self.recording_since = Some(Instant::now()),
+            (false, Some(_)) => self.recording_since = None,
+            _ => {}
+        }
+    }
+
+    fn connection_status(&self) -> ConnectionStatus {
+        *self.app_state.connection_status.borrow()
+    }
+
+    fn is_paused(&self) -> bool {
+        !self
+            .app_state
+            .enabled
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
 }
 
 impl Tray for VoiceInputTray {
     fn title(&self) -> String {
-        "Voice Input".to_string()
+        if self.is_paused() {
+            "Voice Input (paused)".to_string()
+        } else if let Some(since) = self.recording_since {
+            let elapsed = since.elapsed().as_secs();
+            let state = if self
+                .app_state
+                .muted
+                .load(std::sync::atomic::Ordering::Relaxed)
+            {
+                "muted"
+            } else {
+                "recording"
+            };
+            format!(
+                "Voice Input ({} {}:{:02})",
+                state,
+                elapsed / 60,
+                elapsed % 60
+            )
+        } else {
+            "Voice Input".to_string()
+        }
     }
 
-    fn icon_name(&self) -> String {
-        // Try multiple common icon names for better compatibility
-        // First try specific microphone icons, then fallback to generic audio
-        let icon_candidates = vec![
-            "audio-input-microphone",
-            "microphone",
-            "audio-card",
-            "media-record",
-            "audio-x-generic",
-            "application-x-executable",
-        ];
-
-        for icon in &icon_candidates {
-            if icon_exists(icon) {
-                info!("Using icon: {}", icon);
-                return icon.to_string();
-            }
+    fn icon_pixmap(&self) -> Vec<Icon> {
+        icons::tray_icon(self.icon_state())
+    }
+
+    /// Hosts such as Plasma emphasize the item and keep it out of the
+    /// overflow area while recording, or after an error
+    fn status(&self) -> Status {
+        if self.app_state.recording_state().is_busy() || self.icon_state() == IconState::Error {
+            Status::NeedsAttention
+        } else {
+            Status::Active
         }
+    }
 
-        // If no icon found, use a name that should exist
-        warn!("No suitable icon found in system theme, using fallback");
-        info!("Tried icons: {:?}", icon_candidates);
-        "application-x-executable".to_string()
+    /// Shown in place of the icon in the NeedsAttention status, so the same
+    /// (possibly pulsing) icon
+    fn attention_icon_pixmap(&self) -> Vec<Icon> {
+        icons::tray_icon(self.icon_state())
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let config = self.app_state.config();
+        let mut description = format!(
+            "Deepgram: {}\nLanguage: {}\nProfile: {}\nPrivacy mode: {}",
+            self.connection_status(),
+            config.transcription.language,
+            config.active_profile.as_deref().unwrap_or("none"),
+            if privacy::is_enabled() {
+                "on, nothing is kept"
+            } else {
+                "off"
+            }
+        );
+        if let Some(transcript) = self.app_state.recent_transcripts.read().unwrap().front() {
+            description.push_str(&format!(
+                "\nLast transcript: {}",
+                escape_markup(&shorten(transcript))
+            ));
+        }
+        if let Some(error) = self.app_state.last_error.read().unwrap().as_deref() {
+            description.push_str(&format!("\nError: {}", escape_markup(error)));
+        }
+        ToolTip {
+            title: self.title(),
+            description,
+            icon_pixmap: self.icon_pixmap(),
+            ..Default::default()
+        }
     }
 
     fn id(&self) -> String {
@@ -82,14 +162,23 @@ impl Tray for VoiceInputTray {
     }
 
     fn menu(&self) -> Vec<MenuItem<Self>> {
-        use std::sync::atomic::Ordering;
-
         // Get current recording status
-        let is_recording = self.app_state.recording.load(Ordering::Relaxed);
-        let status_label = if is_recording {
-            "🔴 Recording Active"
+        let recording_state = self.app_state.recording_state();
+        let is_recording = recording_state.is_capturing();
+        let is_paused = self.is_paused();
+        let is_meeting = self
+            .app_state
+            .meeting
+            .load(std::sync::atomic::Ordering::Relaxed);
+        let status_label = if is_paused {
+            "⏸ Voice Input Paused"
         } else {
-            "⚪ Recording Inactive"
+            match recording_state {
+                RecordingState::Connecting => "🔴 Connecting…",
+                RecordingState::Recording => "🔴 Recording Active",
+                RecordingState::Finalizing => "⏳ Finishing Transcription…",
+                RecordingState::Idle | RecordingState::Error => "⚪ Recording Inactive",
+            }
         };
 
         // Format the hotkey display string from config
@@ -112,6 +201,115 @@ impl Tray for VoiceInputTray {
             self.config.hotkey.key.to_uppercase()
         );
 
+        // Offer the configured models, including the active one if it isn't listed
+        let (active_model, mut models) = {
+            let config = self.app_state.config();
+            (
+                config.transcription.model.clone(),
+                config.transcription.models.clone(),
+            )
+        };
+        if !models.contains(&active_model) {
+            models.insert(0, active_model.clone());
+        }
+        let selected_model = models
+            .iter()
+            .position(|model| model == &active_model)
+            .unwrap_or_default();
+        let models_options = models
+            .iter()
+            .map(|model| RadioItem {
+                label: model.clone(),
+                ..Default::default()
+            })
+            .collect();
+
+        // "None" followed by the configured profiles
+        let (active_profile, profiles): (Option<String>, Vec<String>) = {
+            let config = self.app_state.config();
+            (
+                config.active_profile.clone(),
+                config
+                    .profiles
+                    .iter()
+                    .map(|profile| profile.name.clone())
+                    .collect(),
+            )
+        };
+        let selected_profile = active_profile
+            .as_ref()
+            .and_then(|active| profiles.iter().position(|name| name == active))
+            .map_or(0, |index| index + 1);
+        let profiles_options = std::iter::once("None".to_string())
+            .chain(profiles.iter().cloned())
+            .map(|label| RadioItem {
+                label: menu_label(&label),
+                ..Default::default()
+            })
+            .collect();
+        let has_profiles = !profiles.is_empty();
+
+        let recent_transcripts: Vec<MenuItem<Self>> = {
+            let recent = self.app_state.recent_transcripts.read().unwrap();
+            if recent.is_empty() {
+                vec![StandardItem {
+                    label: "No transcripts yet".to_string(),
+                    enabled: false,
+                    ..Default::default()
+                }
+                .into()]
+            } else {
+                recent
+                    .iter()
+                    .map(|text| {
+                        let text = text.clone();
+                        StandardItem {
+                            label: menu_label(&text),
+                            icon_name: "edit-copy".to_string(),
+                            activate: Box::new(move |_tray: &mut Self| {
+                                info!("Copying recent transcript to clipboard");
+                                if let Err(e) = desktop::copy_to_clipboard(&text) {
+                                    error!("Failed to copy transcript: {}", e);
+                                }
+                            }),
+                            ..Default::default()
+                        }
+                        .into()
+                    })
+                    .collect()
+            }
+        };
+
+        let statistics: Vec<MenuItem<Self>> = [
+            ("Today", self.app_state.stats.today()),
+            ("Last 7 days", self.app_state.stats.last_days(7)),
+            ("This month", self.app_state.stats.this_month()),
+        ]
+        .iter()
+        .flat_map(|(period, stats)| {
+            let latency = stats
+                .average_latency()
+                .map(|latency| format!("{} ms", latency.as_millis()))
+                .unwrap_or_else(|| "n/a".to_string());
+            [
+                format!("{period}: {:.1} min dictated", stats.minutes_dictated()),
+                format!("{period}: {} words typed", stats.words_typed),
+                format!("{period}: {latency} average latency"),
+            ]
+        })
+        .map(|label| {
+            StandardItem {
+                label,
+                enabled: false,
+                ..Default::default()
+            }
+            .into()
+        })
+        .collect();
+
+        let last_error = self.app_state.last_error.read().unwrap().clone();
+        let is_private = privacy::is_enabled();
+
         vec![
             // Status indicator (non-interactive)
             StandardItem {
@@ -128,6 +326,22 @@ impl Tray for VoiceInputTray {
                 ..Default::default()
             }
             .into(),
+            StandardItem {
+                label: menu_label(&format!("⚠ {}", last_error.as_deref().unwrap_or_default())),
+                icon_name: "dialog-warning".to_string(),
+                enabled: false,
+                visible: last_error.is_some(),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "🔒 Privacy Mode: nothing is kept".to_string(),
+                icon_name: "security-high".to_string(),
+                enabled: false,
+                visible: is_private,
+                ..Default::default()
+            }
+            .into(),
             MenuItem::Separator,
             StandardItem {
                 label: format!("Toggle Recording ({hotkey_str})"),
@@ -139,7 +353,156 @@ impl Tray for VoiceInputTray {
                         crate::toggle_recording(app_state).await;
                     });
                 }),
-                enabled: true,
+                enabled: !is_paused,
+                ..Default::default()
+            }
+            .into(),
+            CheckmarkItem {
+                label: "Meeting Transcript".to_string(),
+                checked: is_meeting,
+                activate: Box::new(|tray: &mut Self| {
+                    let app_state = tray.app_state.clone();
+                    if app_state.meeting.load(std::sync::atomic::Ordering::Relaxed) {
+                        info!("Stopping meeting transcript from tray menu");
+                        crate::stop_recording(&app_state);
+                    } else {
+                        info!("Meeting transcript requested from tray menu");
+                        tray.handle.spawn(async move {
+                            crate::start_meeting(app_state);
+                        });
+                    }
+                }),
+                enabled: !is_paused && (is_meeting || !is_recording),
+                ..Default::default()
+            }
+            .into(),
+            CheckmarkItem {
+                label: "Pause Voice Input".to_string(),
+                checked: is_paused,
+                activate: Box::new(|tray: &mut Self| {
+                    let paused = tray.is_paused();
+                    info!(
+                        "{} voice input from tray menu",
+                        if paused { "Resuming" } else { "Pausing" }
+                    );
+                    tray.app_state.set_enabled(paused);
+                }),
+                ..Default::default()
+            }
+            .into(),
+            CheckmarkItem {
+                label: "Privacy Mode".to_string(),
+                checked: is_private,
+                activate: Box::new(|tray: &mut Self| {
+                    let private = privacy::is_enabled();
+                    info!(
+                        "{} privacy mode from tray menu",
+                        if private { "Disabling" } else { "Enabling" }
+                    );
+                    tray.app_state.set_privacy(!private);
+                }),
+                ..Default::default()
+            }
+            .into(),
+            SubMenu {
+                label: format!("Model ({active_model})"),
+                icon_name: "preferences-system".to_string(),
+                submenu: vec![RadioGroup {
+                    selected: selected_model,
+                    select: Box::new(move |tray: &mut Self, index| {
+                        let Some(model) = models.get(index).cloned() else {
+                            return;
+                        };
+                        info!("Switching transcription model to {} from tray menu", model);
+                        tray.app_state.update_transcription_config(|transcription| {
+                            transcription.model = model
+                        });
+                    }),
+                    options: models_options,
+                }
+                .into()],
+                ..Default::default()
+            }
+            .into(),
+            SubMenu {
+                label: format!("Profile ({})", active_profile.as_deref().unwrap_or("none")),
+                icon_name: "avatar-default".to_string(),
+                submenu: vec![RadioGroup {
+                    selected: selected_profile,
+                    select: Box::new(move |tray: &mut Self, index| {
+                        let profile = index.checked_sub(1).and_then(|i| profiles.get(i));
+                        info!("Switching to profile {:?} from tray menu", profile);
+                        if let Err(e) = tray.app_state.switch_profile(profile.map(String::as_str)) {
+                            error!("Failed to switch profile: {}", e);
+                        }
+                    }),
+                    options: profiles_options,
+                }
+                .into()],
+                visible: has_profiles,
+                ..Default::default()
+            }
+            .into(),
+            SubMenu {
+                label: "Recent Transcripts".to_string(),
+                icon_name: "document-open-recent".to_string(),
+                submenu: recent_transcripts,
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Transcription History...".to_string(),
+                icon_name: "document-open-recent".to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    info!("Opening transcription history from tray menu");
+                    let history = tray.app_state.history.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = history_window::show_history_window(&history) {
+                            error!("History window error: {:#}", e);
+                        }
+                    });
+                }),
+                ..Default::default()
+            }
+            .into(),
+            SubMenu {
+                label: "Statistics".to_string(),
+                icon_name: "x-office-spreadsheet".to_string(),
+                submenu: statistics,
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Open Config File".to_string(),
+                icon_name: "document-open".to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    info!("Open config file requested from tray menu");
+                    if let Err(e) = tray
+                        .config_path()
+                        .and_then(|path| desktop::open_path(&path))
+                    {
+                        error!("Failed to open config file: {}", e);
+                    }
+                }),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Open Config Directory".to_string(),
+                icon_name: "folder-open".to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    info!("Open config directory requested from tray menu");
+                    let result = tray.config_path().and_then(|path| {
+                        let dir = path
+                            .parent()
+                            .ok_or_eyre("Config file has no parent directory")?;
+                        desktop::open_path(dir)
+                    });
+                    if let Err(e) = result {
+                        error!("Failed to open config directory: {}", e);
+                    }
+                }),
                 ..Default::default()
             }
             .into(),
@@ -159,50 +522,46 @@ impl Tray for VoiceInputTray {
     }
 }
 
-/// Check if StatusNotifierWatcher is available on D-Bus
-fn check_status_notifier_support() -> bool {
-    match Connection::new_session() {
-        Ok(conn) => {
-            let proxy = conn.with_proxy(
-                "org.freedesktop.DBus",
-                "/org/freedesktop/DBus",
-                Duration::from_millis(500),
-            );
-
-            // Check if StatusNotifierWatcher service is available
-            let result: Result<(Vec<String>,), _> =
-                proxy.method_call("org.freedesktop.DBus", "ListNames", ());
-
-            match result {
-                Ok((names,)) => {
-                    let has_watcher = names.iter().any(|name| {
-                        name == "org.kde.StatusNotifierWatcher"
-                            || name.contains("StatusNotifierWatcher")
-                    });
+/// Shorten text for use as a menu label; underscores would otherwise mark access keys
+fn menu_label(text: &str) -> String {
+    shorten(text).replace('_', "__")
+}
 
-                    if !has_watcher {
-                        warn!("StatusNotifierWatcher not found on D-Bus");
-                        warn!("App indicators won't appear in GNOME without the AppIndicator extension");
-                        warn!("Install it from: https://extensions.gnome.org/extension/615/appindicator-support/");
-                    }
+/// `text` cut to a length that fits a menu item or tooltip line
+fn shorten(text: &str) -> String {
+    const MAX_LABEL_CHARS: usize = 50;
 
-                    has_watcher
-                }
-                Err(e) => {
-                    error!("Failed to list D-Bus names: {}", e);
-                    false
-                }
-            }
-        }
-        Err(e) => {
-            error!("Failed to connect to D-Bus session bus: {}", e);
-            false
-        }
+    let mut label: String = text.chars().take(MAX_LABEL_CHARS).collect();
+    if text.chars().count() > MAX_LABEL_CHARS {
+        label.push('…');
     }
+    label
+}
+
+/// `text` for a tooltip description, which hosts may render as HTML
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Whether a StatusNotifierWatcher, which displays tray icons, is on the
+/// session bus. GNOME only has one with the AppIndicator extension.
+pub fn status_notifier_available() -> eyre::Result<bool> {
+    let conn = Connection::new_session()?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_millis(500),
+    );
+    let (names,): (Vec<String>,) = proxy.method_call("org.freedesktop.DBus", "ListNames", ())?;
+    Ok(names
+        .iter()
+        .any(|name| name.contains("StatusNotifierWatcher")))
 }
 
 /// Get the current desktop environment
-fn detect_desktop_environment() -> &'static str {
+pub fn detect_desktop_environment() -> &'static str {
     if let Ok(desktop) = std::env::var("XDG_CURRENT_DESKTOP") {
         if desktop.to_lowercase().contains("gnome") {
             return "GNOME";
@@ -236,10 +595,68 @@ pub fn setup_tray(
         return None;
     }
 
-    match create_tray(app_state, config.clone()) {
+    match create_tray(app_state.clone(), config.clone()) {
         Ok(Some(tray)) => {
             info!("System tray service started successfully");
 
+            // Re-render the tray whenever the app state signals a change
+            let refresh_handle = tray.handle();
+            let mut refresh_rx = app_state.ui_refresh.subscribe();
+            let mut connection_rx = app_state.connection_status.subscribe();
+            let mut config_rx = app_state.subscribe_config();
+            let mut recording_rx = app_state.subscribe_recording_state();
+            let mut events = app_state.events.subscribe();
+            let refresh_shutdown_token = shutdown_token.child_token();
+            tokio::spawn(async move {
+                // Drives the elapsed time (and icon animation) while recording
+                let mut timer = tokio::time::interval(Duration::from_millis(500));
+                let mut was_recording = false;
+                loop {
+                    tokio::select! {
+                        changed = refresh_rx.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                            refresh_handle.update(|tray| tray.tick());
+                        }
+                        changed = connection_rx.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                            refresh_handle.update(|tray| tray.tick());
+                        }
+                        changed = config_rx.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                            refresh_handle.update(|tray| tray.tick());
+                        }
+                        changed = recording_rx.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                            refresh_handle.update(|tray| tray.tick());
+                        }
+                        event = events.recv() => match event {
+                            Ok(_) => refresh_handle.update(|tray| tray.tick()),
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                debug!("Tray skipped {} events", skipped);
+                                refresh_handle.update(|tray| tray.tick());
+                            }
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        },
+                        _ = timer.tick() => {
+                            let is_recording = recording_rx.borrow().is_busy();
+                            if is_recording || was_recording {
+                                refresh_handle.update(|tray| tray.tick());
+                            }
+                            was_recording = is_recording;
+                        }
+                        _ = refresh_shutdown_token.cancelled() => break,
+                    }
+                }
+            });
+
             // Create a channel for shutdown signaling
             let (shutdown_tx, shutdown_rx) = std::sync::mpsc::channel::<()>();
             let tray_shutdown_token = shutdown_token.child_token();
@@ -315,30 +732,20 @@ fn create_tray(
     info!("Detected desktop environment: {}", desktop);
 
     // Check for StatusNotifierWatcher support
-    let has_support = check_status_notifier_support();
-
-    if desktop == "GNOME" && !has_support {
-        let separator = "=".repeat(70);
-        warn!("{}", separator);
-        warn!("GNOME SYSTEM TRAY SETUP REQUIRED");
-        warn!("{}", separator);
-        warn!("GNOME doesn't support app indicators natively.");
-        warn!("To see the system tray icon, you need to:");
-        warn!("");
-        warn!("1. Install the AppIndicator extension:");
-        warn!("   https://extensions.gnome.org/extension/615/appindicator-support/");
-        warn!("");
-        warn!("2. Or install via package manager:");
-        warn!("   Ubuntu/Debian: sudo apt install gnome-shell-extension-appindicator");
-        warn!("   Fedora: sudo dnf install gnome-shell-extension-appindicator");
-        warn!("   Arch: sudo pacman -S gnome-shell-extension-appindicator");
-        warn!("");
-        warn!("3. Log out and log back in after installation");
-        warn!("");
-        warn!("The app will continue to work via hotkey (Super+V)");
-        warn!("{}", separator);
-
-        // Still try to create the tray - it will be ready if user installs extension
+    let has_support = match status_notifier_available() {
+        Ok(has_support) => has_support,
+        Err(e) => {
+            error!("Failed to look up StatusNotifierWatcher: {}", e);
+            false
+        }
+    };
+
+    if !has_support {
+        // Still create the tray, it becomes visible once a watcher appears
+        warn!(
+            "No StatusNotifierWatcher on the session bus, so the tray icon won't be visible. \
+             Run `gnome-voice-input doctor` for how to fix it."
+        );
     }
 
     let handle = Handle::current();
@@ -346,14 +753,10 @@ fn create_tray(
         app_state: app_state.clone(),
         handle,
         config,
+        recording_since: None,
     };
 
     let service = TrayService::new(tray);
     info!("System tray service created successfully");
-    if desktop == "GNOME" && !has_support {
-        info!(
-            "Tray icon registered but won't be visible until AppIndicator extension is installed"
-        );
-    }
     Ok(Some(service))
 }
@@ -0,0 +1,82 @@
+//! Custom vocabulary: names and jargon sent to Deepgram as keyterms and
+//! corrected in transcripts when recognized slightly differently, e.g.
+//! "cube CTL" as "kubectl".
+
+use crate::config::edit_distance;
+use eyre::{Result, WrapErr};
+use std::path::Path;
+
+/// Words of a transcript joined when comparing them to a term
+const MAX_WORDS_PER_TERM: usize = 3;
+
+/// Terms of a dictionary file: one per line, `#` starts a comment
+pub fn read_file(path: &Path) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read vocabulary file {}", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Lowercase letters and digits only, so that spacing, case and punctuation
+/// don't matter when comparing
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Edits tolerated between a term and the recognized words: none for short
+/// terms, where near matches are mostly other words
+fn tolerance(term: &str) -> usize {
+    match term.chars().count() {
+        0..=4 => 0,
+        5..=8 => 1,
+        _ => 2,
+    }
+}
+
+/// Replace up to three consecutive words that sound like a vocabulary term
+/// (same letters ignoring spacing, case and punctuation, or within a small
+/// edit distance) with the term. Punctuation after the words is kept.
+pub fn correct(text: &str, terms: &[String]) -> String {
+    let terms: Vec<(String, &String)> = terms
+        .iter()
+        .map(|term| (normalize(term), term))
+        .filter(|(normalized, _)| !normalized.is_empty())
+        .collect();
+    let words: Vec<&str> = text.split_whitespace().collect();
+
+    let mut corrected = Vec::with_capacity(words.len());
+    let mut i = 0;
+    while i < words.len() {
+        let matched = (1..=MAX_WORDS_PER_TERM.min(words.len() - i))
+            .rev()
+            .find_map(|count| {
+                let candidate = normalize(&words[i..i + count].concat());
+                terms
+                    .iter()
+                    .find(|(normalized, _)| {
+                        edit_distance(&candidate, normalized) <= tolerance(normalized)
+                    })
+                    .map(|(_, term)| (count, *term))
+            });
+        match matched {
+            Some((count, term)) => {
+                let last = words[i + count - 1];
+                let trailing = &last[last.trim_end_matches(|c: char| !c.is_alphanumeric()).len()..];
+                corrected.push(format!("{term}{trailing}"));
+                i += count;
+            }
+            None => {
+                corrected.push(words[i].to_string());
+                i += 1;
+            }
+        }
+    }
+    corrected.join(" ")
+}
@@ -0,0 +1,309 @@
+//! Tests of the capture loop with file and synthetic audio sources in place
+//! of the microphone, and of the files recordings are saved to.
+
+use gnome_voice_input::audio::{capture_from, AudioFormat};
+use gnome_voice_input::audio_source::{
+    AudioSource, DualSource, FileSource, Signal, SyntheticSource,
+};
+use gnome_voice_input::config::DebugConfig;
+use gnome_voice_input::microphone;
+use gnome_voice_input::state::AppEvent;
+use gnome_voice_input::{storage, AppState, Config};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+const SAMPLE_RATE: u32 = 16000;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// App state that is recording, with chunks of `audio.audio_chunk_ms`
+fn recording_state() -> AppState {
+    let app_state = AppState::new(Config::default(), false, None, CancellationToken::new());
+    assert!(app_state.begin_recording());
+    app_state
+}
+
+fn samples_per_chunk(app_state: &AppState) -> usize {
+    (SAMPLE_RATE * app_state.config().audio.audio_chunk_ms / 1000) as usize
+}
+
+fn decode(chunk: &[u8]) -> Vec<i16> {
+    chunk
+        .chunks_exact(2)
+        .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+        .collect()
+}
+
+async fn next_chunk(audio_rx: &mut mpsc::Receiver<Vec<u8>>) -> Option<Vec<u8>> {
+    tokio::time::timeout(TIMEOUT, audio_rx.recv())
+        .await
+        .expect("no audio chunk in time")
+}
+
+async fn drain(mut audio_rx: mpsc::Receiver<Vec<u8>>) -> Vec<Vec<u8>> {
+    let mut chunks = Vec::new();
+    while let Some(chunk) = next_chunk(&mut audio_rx).await {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+fn sine(frequency: f32, amplitude: f32) -> SyntheticSource {
+    SyntheticSource::new(
+        Signal::Sine {
+            frequency,
+            amplitude,
+        },
+        SAMPLE_RATE,
+    )
+}
+
+#[tokio::test]
+async fn sine_is_streamed_as_linear16_chunks() {
+    let app_state = recording_state();
+    let (mut audio_rx, format) = capture_from(&app_state, || Ok(Box::new(sine(440.0, 0.5))))
+        .await
+        .unwrap();
+    assert_eq!(format, AudioFormat::mono(SAMPLE_RATE));
+
+    let chunk = decode(&next_chunk(&mut audio_rx).await.unwrap());
+
+    assert_eq!(chunk.len(), samples_per_chunk(&app_state));
+    let peak = chunk
+        .iter()
+        .map(|sample| sample.unsigned_abs())
+        .max()
+        .unwrap();
+    assert!((16000..=16400).contains(&peak), "peak {peak}");
+    let level = f32::from_bits(app_state.input_level.load(Ordering::Relaxed));
+    assert!((0.3..0.4).contains(&level), "level {level}");
+}
+
+#[tokio::test]
+async fn muted_capture_streams_silence() {
+    let app_state = recording_state();
+    app_state.muted.store(true, Ordering::Relaxed);
+    let (mut audio_rx, _) = capture_from(&app_state, || Ok(Box::new(sine(440.0, 0.5))))
+        .await
+        .unwrap();
+
+    for _ in 0..5 {
+        let chunk = decode(&next_chunk(&mut audio_rx).await.unwrap());
+        assert!(chunk.iter().all(|&sample| sample == 0));
+    }
+}
+
+#[tokio::test]
+async fn stopping_the_recording_ends_the_capture() {
+    let app_state = recording_state();
+    let (mut audio_rx, _) = capture_from(&app_state, || {
+        Ok(Box::new(SyntheticSource::new(Signal::Silence, SAMPLE_RATE)))
+    })
+    .await
+    .unwrap();
+    next_chunk(&mut audio_rx).await.unwrap();
+
+    assert!(app_state.stop_recording());
+    drain(audio_rx).await;
+
+    assert_eq!(app_state.input_level.load(Ordering::Relaxed), 0);
+}
+
+#[tokio::test]
+async fn file_is_mixed_down_and_ends_with_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("stereo.wav");
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    // Half a second of 0.5 on the left and silence on the right
+    for _ in 0..SAMPLE_RATE / 2 {
+        writer.write_sample(i16::MAX / 2 + 1).unwrap();
+        writer.write_sample(0i16).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let app_state = recording_state();
+    let (audio_rx, format) = capture_from(&app_state, move || {
+        Ok(Box::new(FileSource::open(&path, None)?))
+    })
+    .await
+    .unwrap();
+    let samples: Vec<i16> = drain(audio_rx)
+        .await
+        .iter()
+        .flat_map(|chunk| decode(chunk))
+        .collect();
+
+    assert_eq!(format.sample_rate, SAMPLE_RATE);
+    assert_eq!(samples.len(), (SAMPLE_RATE / 2) as usize);
+    assert!(samples
+        .iter()
+        .all(|&sample| (8190..=8194).contains(&sample)));
+}
+
+#[tokio::test]
+async fn dual_source_interleaves_microphone_and_system_audio() {
+    let app_state = recording_state();
+    let (mut audio_rx, format) = capture_from(&app_state, || {
+        let system = SyntheticSource::new(Signal::Silence, SAMPLE_RATE);
+        Ok(Box::new(DualSource::new(
+            Box::new(sine(440.0, 0.5)),
+            Box::new(system),
+        )?))
+    })
+    .await
+    .unwrap();
+    assert_eq!(format.channels, 2);
+
+    let chunk = decode(&next_chunk(&mut audio_rx).await.unwrap());
+
+    assert_eq!(chunk.len(), 2 * samples_per_chunk(&app_state));
+    let (microphone, system): (Vec<_>, Vec<_>) = chunk
+        .chunks_exact(2)
+        .map(|frame| (frame[0], frame[1]))
+        .unzip();
+    assert!(microphone
+        .iter()
+        .any(|&sample| sample.unsigned_abs() > 16000));
+    assert!(system.iter().all(|&sample| sample == 0));
+}
+
+#[test]
+fn dual_source_needs_matching_sample_rates() {
+    let result = DualSource::new(
+        Box::new(SyntheticSource::new(Signal::Silence, SAMPLE_RATE)),
+        Box::new(SyntheticSource::new(Signal::Silence, 48000)),
+    );
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn source_errors_are_returned_when_opening() {
+    let app_state = recording_state();
+
+    let result = capture_from(&app_state, || {
+        FileSource::open("/nonexistent.wav".as_ref(), None)
+            .map(|source| Box::new(source) as Box<dyn AudioSource>)
+    })
+    .await;
+
+    assert!(result.is_err());
+}
+
+/// A device that stopped delivering samples without reporting an error
+struct StalledSource;
+
+impl AudioSource for StalledSource {
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn read(&mut self, timeout: Duration) -> eyre::Result<Option<Vec<f32>>> {
+        std::thread::sleep(timeout);
+        Ok(Some(Vec::new()))
+    }
+}
+
+#[tokio::test]
+async fn stalled_source_ends_the_capture() {
+    let app_state = recording_state();
+    let mut events = app_state.events.subscribe();
+    let (audio_rx, _) = capture_from(&app_state, || Ok(Box::new(StalledSource)))
+        .await
+        .unwrap();
+
+    assert!(drain(audio_rx).await.is_empty());
+    let event = tokio::time::timeout(TIMEOUT, events.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(event, AppEvent::CaptureFailed(_)), "{event:?}");
+    assert!(app_state.is_recording());
+}
+
+#[test]
+fn signals_parse_from_the_command_line() {
+    assert_eq!("silence".parse(), Ok(Signal::Silence));
+    assert_eq!(
+        "sine:1000".parse(),
+        Ok(Signal::Sine {
+            frequency: 1000.0,
+            amplitude: 0.5
+        })
+    );
+    assert!("sine:0".parse::<Signal>().is_err());
+    assert!("noise".parse::<Signal>().is_err());
+}
+
+#[test]
+fn realtime_source_is_paced() {
+    let mut source = SyntheticSource::new(Signal::Silence, SAMPLE_RATE).realtime();
+    let started = std::time::Instant::now();
+    let mut samples = 0;
+    while samples < SAMPLE_RATE as usize / 10 {
+        samples += source
+            .read(Duration::from_millis(10))
+            .unwrap()
+            .unwrap()
+            .len();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(90));
+}
+
+#[test]
+fn pactl_output_is_parsed() {
+    assert_eq!(microphone::parse_mute("Mute: yes\n"), Some(true));
+    assert_eq!(microphone::parse_mute("Mute: no\n"), Some(false));
+    assert_eq!(microphone::parse_mute(""), None);
+    assert_eq!(
+        microphone::parse_volume(
+            "Volume: front-left: 32768 /  50% / -18.06 dB,   front-right: 32768 /  50% / -18.06 dB\n        balance 0.00\n"
+        ),
+        Some(50)
+    );
+    assert_eq!(
+        microphone::parse_volume("Volume: mono: 65536 / 100% / 0.00 dB\n"),
+        Some(100)
+    );
+    assert_eq!(microphone::parse_volume("Volume:"), None);
+}
+
+#[test]
+fn debug_artifacts_are_named_and_pruned() {
+    let dir = tempfile::tempdir().unwrap();
+    let debug = DebugConfig {
+        directory: Some(dir.path().to_path_buf()),
+        file_name: "session{session}".to_string(),
+        max_total_mb: 1,
+        max_age_days: None,
+    };
+    let now = SystemTime::now();
+    let write = |name: &str, size: usize, age_secs: u64| {
+        let path = dir.path().join(name);
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(now - Duration::from_secs(age_secs))
+            .unwrap();
+    };
+    write("old.wav", 600 * 1024, 20);
+    write("newer.wav", 300 * 1024, 10);
+    write("newest.jsonl", 300 * 1024, 0);
+    write("notes.txt", 600 * 1024, 30);
+
+    let artifacts = storage::new_debug_artifacts(&debug, Some(7)).unwrap();
+
+    assert_eq!(artifacts.audio, dir.path().join("session7.wav"));
+    assert_eq!(artifacts.responses, dir.path().join("session7.jsonl"));
+    // Only the newest debug files fitting into 1 MB are kept, other files stay
+    assert!(!dir.path().join("old.wav").exists());
+    assert!(dir.path().join("newer.wav").exists());
+    assert!(dir.path().join("newest.jsonl").exists());
+    assert!(dir.path().join("notes.txt").exists());
+}
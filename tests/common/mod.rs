@@ -0,0 +1,235 @@
+//! A local WebSocket server speaking enough of the Deepgram streaming
+//! protocol to drive `Transcriber` without network access or an API key.
+
+use futures::{SinkExt, StreamExt};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Reported as `dg-request-id` on every upgrade, which the client requires
+const REQUEST_ID: &str = "6c4a1e55-9d3e-4a3b-8f0c-2f1d7b8e9a10";
+
+/// What the server does on one connection
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    /// Sent in order once the first audio chunk arrived
+    pub responses: Vec<String>,
+    /// Drop the connection after the responses, as a network failure would,
+    /// instead of waiting for the client to close the stream
+    pub drop_after_responses: bool,
+    /// Refuse the WebSocket upgrade with this HTTP status
+    pub reject: Option<u16>,
+}
+
+impl Script {
+    pub fn respond(responses: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            responses: responses.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn then_drop(mut self) -> Self {
+        self.drop_after_responses = true;
+        self
+    }
+
+    pub fn reject(status: u16) -> Self {
+        Self {
+            reject: Some(status),
+            ..Self::default()
+        }
+    }
+}
+
+/// A connection as seen by the server
+#[derive(Debug, Clone, Default)]
+pub struct Connection {
+    /// Path and query of the upgrade request, carrying the transcription options
+    pub uri: String,
+    pub authorization: Option<String>,
+    pub audio_bytes: usize,
+    /// Control messages received, e.g. `Finalize` and `CloseStream`
+    pub control_messages: Vec<String>,
+}
+
+impl Connection {
+    /// Value of a query parameter of the upgrade request
+    pub fn query(&self, name: &str) -> Option<String> {
+        let (_, query) = self.uri.split_once('?')?;
+        query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            (key == name).then(|| value.to_string())
+        })
+    }
+}
+
+pub struct MockDeepgram {
+    address: SocketAddr,
+    connections: Arc<Mutex<Vec<Connection>>>,
+}
+
+impl MockDeepgram {
+    /// Listen on a free local port. Each connection takes the next script;
+    /// connections beyond the scripts get no responses.
+    pub async fn start(scripts: impl IntoIterator<Item = Script>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let mut scripts: VecDeque<Script> = scripts.into_iter().collect();
+
+        let recorded = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let script = scripts.pop_front().unwrap_or_default();
+                let index = {
+                    let mut connections = recorded.lock().unwrap();
+                    connections.push(Connection::default());
+                    connections.len() - 1
+                };
+                tokio::spawn(serve(stream, script, recorded.clone(), index));
+            }
+        });
+
+        Self {
+            address,
+            connections,
+        }
+    }
+
+    /// Base URL for `[transcription] base_url`
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    pub fn connections(&self) -> Vec<Connection> {
+        self.connections.lock().unwrap().clone()
+    }
+}
+
+// The handshake callback's error type is fixed by tungstenite
+#[allow(clippy::result_large_err)]
+async fn serve(
+    stream: TcpStream,
+    script: Script,
+    connections: Arc<Mutex<Vec<Connection>>>,
+    index: usize,
+) {
+    let record =
+        |update: &mut dyn FnMut(&mut Connection)| update(&mut connections.lock().unwrap()[index]);
+
+    let callback = |request: &Request, mut response: Response| {
+        record(&mut |connection| {
+            connection.uri = request.uri().to_string();
+            connection.authorization = request
+                .headers()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+        });
+        if let Some(status) = script.reject {
+            let mut error = ErrorResponse::new(None);
+            *error.status_mut() = StatusCode::from_u16(status).unwrap();
+            return Err(error);
+        }
+        response
+            .headers_mut()
+            .insert("dg-request-id", HeaderValue::from_static(REQUEST_ID));
+        Ok(response)
+    };
+    let Ok(mut socket) = tokio_tungstenite::accept_hdr_async(stream, callback).await else {
+        return;
+    };
+
+    let mut responded = false;
+    while let Some(Ok(message)) = socket.next().await {
+        match message {
+            Message::Binary(audio) => {
+                record(&mut |connection| connection.audio_bytes += audio.len())
+            }
+            Message::Text(text) => {
+                record(&mut |connection| connection.control_messages.push(text.to_string()));
+                if text.contains("CloseStream") {
+                    break;
+                }
+            }
+            Message::Close(_) => return,
+            _ => {}
+        }
+        if !responded {
+            responded = true;
+            for response in &script.responses {
+                if socket.send(Message::text(response.as_str())).await.is_err() {
+                    return;
+                }
+            }
+            if script.drop_after_responses {
+                // Give the client time to read the responses before the reset
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                return;
+            }
+        }
+    }
+
+    let _ = socket.send(Message::text(metadata())).await;
+    let _ = socket.close(None).await;
+}
+
+/// A `Results` message with one alternative
+pub fn transcript(text: &str, is_final: bool, speech_final: bool) -> String {
+    json!({
+        "type": "Results",
+        "channel_index": [0, 1],
+        "duration": 1.0,
+        "start": 0.0,
+        "is_final": is_final,
+        "speech_final": speech_final,
+        "from_finalize": false,
+        "channel": {
+            "alternatives": [{
+                "transcript": text,
+                "confidence": 0.98,
+                "words": [],
+            }],
+        },
+        "metadata": {
+            "request_id": REQUEST_ID,
+            "model_info": { "name": "mock", "version": "0", "arch": "mock" },
+            "model_uuid": "00000000-0000-0000-0000-000000000000",
+        },
+    })
+    .to_string()
+}
+
+pub fn interim(text: &str) -> String {
+    transcript(text, false, false)
+}
+
+pub fn final_result(text: &str) -> String {
+    transcript(text, true, false)
+}
+
+/// A final transcript of channel `index` of a stream with `channels`
+pub fn channel_final(text: &str, index: usize, channels: usize) -> String {
+    let mut response: serde_json::Value = serde_json::from_str(&final_result(text)).unwrap();
+    response["channel_index"] = json!([index, channels]);
+    response.to_string()
+}
+
+/// The summary Deepgram sends before closing a stream
+fn metadata() -> String {
+    json!({
+        "type": "Metadata",
+        "request_id": REQUEST_ID,
+        "created": "2024-01-01T00:00:00.000Z",
+        "duration": 1.0,
+        "channels": 1,
+    })
+    .to_string()
+}
@@ -0,0 +1,114 @@
+//! Tests of loading the config from its files and saving runtime settings
+//! back into them.
+
+use gnome_voice_input::config::{unknown_keys, CONFIG_TEMPLATE};
+use gnome_voice_input::Config;
+
+#[test]
+fn runtime_settings_are_saved_where_they_take_effect() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    let main = CONFIG_TEMPLATE.replace(
+        "deepgram_api_key = \"\"",
+        "deepgram_api_key = \"test-key-123456\"",
+    );
+    std::fs::write(&path, &main).unwrap();
+    let overlay = dir.path().join("config.local.toml");
+    std::fs::write(&overlay, "[transcription]\nlanguage = \"de\"\n").unwrap();
+    let mut config = Config::load(Some(path.clone())).unwrap();
+    assert_eq!(config.transcription.language, "de");
+
+    // Only the changed setting is written, the overlay's language stays there
+    config.paused = true;
+    let written = config.save_runtime_settings(Some(path.clone())).unwrap();
+    let main_path = path.canonicalize().unwrap();
+    assert_eq!(written.len(), 1);
+    assert_eq!(written[0].0, main_path);
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        main.replacen("123456\"\n", "123456\"\npaused = true\n", 1)
+    );
+
+    // The overlay sets the language, so a new one has to go there
+    config.transcription.language = "fr".to_string();
+    let written = config.save_runtime_settings(Some(path.clone())).unwrap();
+    assert_eq!(written.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(&overlay).unwrap(),
+        "[transcription]\nlanguage = \"fr\"\n"
+    );
+    assert_eq!(
+        Config::load(Some(path)).unwrap().transcription.language,
+        "fr"
+    );
+}
+
+#[test]
+fn unknown_keys_are_reported_with_a_suggestion() {
+    let warnings = unknown_keys(
+        "[transcription]\nlangauge = \"en\"\n\n[transcription.replacements]\nanything = \"goes\"\n\n[[profiles]]\nname = \"work\"\ncolour = \"red\"\n",
+    );
+    assert_eq!(
+        warnings,
+        [
+            "unknown config key `profiles.colour`",
+            "unknown config key `transcription.langauge`, did you mean `language`?",
+        ]
+    );
+    assert!(unknown_keys(CONFIG_TEMPLATE).is_empty());
+}
+
+#[test]
+fn parse_errors_name_the_location_without_the_api_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "deepgram_api_key = \"secret-key-123456\n[transcription]\n",
+    )
+    .unwrap();
+    let error = format!("{:#}", Config::load(Some(path)).unwrap_err());
+    assert!(error.contains("config.toml:\nTOML parse error at line 1, column 38"));
+    assert!(error.contains("deepgram_api_key = \"****3456"));
+    assert!(!error.contains("secret-key"));
+}
+
+#[test]
+fn includes_and_the_local_overlay_are_merged_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    let main = CONFIG_TEMPLATE.replacen(
+        "deepgram_api_key = \"\"",
+        "include = [\"keys.toml\"]\ndeepgram_api_key = \"\"",
+        1,
+    );
+    std::fs::write(&path, main).unwrap();
+    std::fs::write(
+        dir.path().join("keys.toml"),
+        "deepgram_api_key = \"included-key-123456\"\n\n[transcription]\nlanguage = \"fr\"\nmodel = \"nova-2\"\n",
+    )
+    .unwrap();
+    let overlay = dir.path().join("config.local.toml");
+    std::fs::write(&overlay, "[transcription]\nlanguage = \"de\"\n").unwrap();
+
+    let path = path.canonicalize().unwrap();
+    let sources = Config::source_paths(&path).unwrap();
+    assert_eq!(
+        sources,
+        [
+            path.clone(),
+            dir.path().join("keys.toml").canonicalize().unwrap(),
+            overlay.canonicalize().unwrap(),
+        ]
+    );
+
+    // Tables are merged key by key, later files win
+    let config = Config::load(Some(path.clone())).unwrap();
+    assert_eq!(config.deepgram_api_key, "included-key-123456");
+    assert_eq!(config.transcription.language, "de");
+    assert_eq!(config.transcription.model, "nova-2");
+
+    std::fs::remove_file(dir.path().join("keys.toml")).unwrap();
+    let error = format!("{:#}", Config::load(Some(path)).unwrap_err());
+    assert!(error.contains("Included config file not found"));
+}
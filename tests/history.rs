@@ -0,0 +1,127 @@
+//! Tests of the transcript history: the SQLite store, search and the
+//! `history export` formats.
+
+use chrono::TimeZone;
+use gnome_voice_input::config::HistoryConfig;
+use gnome_voice_input::history::{History, HistoryContext, HistoryEntry};
+use gnome_voice_input::history_export::{self, ExportFormat};
+use gnome_voice_input::Transcript;
+use std::time::Duration;
+
+#[test]
+fn history_store_records_finals_with_their_context() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("history.jsonl"),
+        r#"{"id":1,"timestamp":"2026-03-02T08:30:00+01:00","text":"from the old file"}"#,
+    )
+    .unwrap();
+    let settings = HistoryConfig {
+        // Too long to compute a cutoff for, keeps everything
+        retention_days: Some(u64::MAX),
+        max_entries: Some(2),
+        ..HistoryConfig::default()
+    };
+    let history = History::open(dir.path(), &settings).unwrap();
+    let context = HistoryContext {
+        language: Some("en".to_string()),
+        app: Some("firefox".to_string()),
+        api_keys: None,
+    };
+
+    let mut transcript = Transcript::new("Hello there");
+    transcript.confidence = 0.875;
+    transcript.duration = Duration::from_millis(1500);
+    history.add(&transcript, &context).unwrap();
+
+    let entries = history.entries();
+    let texts: Vec<&str> = entries.iter().map(|entry| entry.text.as_str()).collect();
+    assert_eq!(texts, ["Hello there", "from the old file"]);
+    assert_eq!(entries[0].duration_secs, Some(1.5));
+    assert_eq!(entries[0].confidence, Some(0.875));
+    assert_eq!(entries[0].app.as_deref(), Some("firefox"));
+    assert!(!dir.path().join("history.jsonl").exists());
+
+    let day = chrono::NaiveDate::from_ymd_opt(2026, 3, 2);
+    assert_eq!(history.search("OLD", day, day).len(), 1);
+
+    // Beyond max_entries the oldest go
+    history.add(&Transcript::new("third"), &context).unwrap();
+    let reopened = History::open(dir.path(), &settings).unwrap();
+    let texts: Vec<String> = reopened
+        .entries()
+        .into_iter()
+        .map(|entry| entry.text)
+        .collect();
+    assert_eq!(texts, ["third", "Hello there"]);
+}
+
+fn history_entry(id: u64, day: u32, hour: u32, text: &str) -> HistoryEntry {
+    HistoryEntry {
+        id,
+        timestamp: chrono::Local
+            .with_ymd_and_hms(2026, 3, day, hour, 30, 0)
+            .unwrap(),
+        text: text.to_string(),
+        language: Some("en".to_string()),
+        app: Some("firefox".to_string()),
+        duration_secs: None,
+        confidence: Some(0.9),
+        api_key: None,
+    }
+}
+
+#[test]
+fn history_export_selects_entries_since_a_day_oldest_first() {
+    let entries = vec![
+        history_entry(3, 5, 9, "newest"),
+        history_entry(2, 4, 18, "middle"),
+        history_entry(1, 2, 8, "oldest"),
+    ];
+    let since = chrono::NaiveDate::from_ymd_opt(2026, 3, 4);
+
+    let selected = history_export::select(entries, since);
+    let ids: Vec<u64> = selected.iter().map(|entry| entry.id).collect();
+    assert_eq!(ids, [2, 3]);
+
+    let csv = history_export::export(&selected, ExportFormat::Csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "id,timestamp,text,language,app,duration_secs,confidence"
+    );
+    assert!(lines[1].starts_with("2,2026-03-04T18:30:00"));
+    assert!(lines[1].ends_with(",middle,en,firefox,,0.9"));
+
+    let markdown = history_export::export(&selected, ExportFormat::Markdown).unwrap();
+    assert!(markdown.contains("## 2026-03-04\n\n- **18:30** middle _(firefox)_\n"));
+    assert!(markdown.contains("## 2026-03-05\n\n- **09:30** newest _(firefox)_\n"));
+
+    let json = history_export::export(&selected, ExportFormat::Json).unwrap();
+    let parsed: Vec<HistoryEntry> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed[1].text, "newest");
+}
+
+#[test]
+fn history_export_quotes_csv_fields_with_separators() {
+    let entries = [history_entry(1, 2, 8, "Yes, he said \"no\"\nthen left")];
+
+    let csv = history_export::export(&entries, ExportFormat::Csv).unwrap();
+    assert!(csv.contains(",\"Yes, he said \"\"no\"\"\nthen left\","));
+    assert_eq!("md".parse::<ExportFormat>(), Ok(ExportFormat::Markdown));
+    assert!("xml".parse::<ExportFormat>().is_err());
+}
+
+#[test]
+fn history_search_matches_text_ignoring_case_within_days() {
+    let entry = history_entry(1, 4, 18, "Call the Dentist tomorrow");
+    let day = |day| chrono::NaiveDate::from_ymd_opt(2026, 3, day);
+
+    assert!(entry.matches("dentist"));
+    assert!(entry.matches(""));
+    assert!(!entry.matches("doctor"));
+    assert!(entry.recorded_between(day(4), day(4)));
+    assert!(entry.recorded_between(None, day(5)));
+    assert!(!entry.recorded_between(day(5), None));
+    assert!(!entry.recorded_between(None, day(3)));
+}
@@ -0,0 +1,646 @@
+//! Tests of the `[output]` pipeline: filters and the handlers it builds.
+
+use async_trait::async_trait;
+use chrono::TimeZone;
+use gnome_voice_input::config::{
+    LanguageSettings, MeetingConfig, MeetingFormat, OutputConfig, OutputFilter, OutputHandler,
+    PiiConfig, ProfanityMode, SpellConfig, SubtitleFormat, SubtitlesConfig, TranscriptionConfig,
+    TypingBacklog, TypingConfig,
+};
+use gnome_voice_input::filters::{SentenceCase, TranscriptFilter};
+use gnome_voice_input::handlers::{FilterHandler, FocusGuardHandler, TypingQueueHandler};
+use gnome_voice_input::keyboard::FocusLost;
+use gnome_voice_input::numbers::NumberFormat;
+use gnome_voice_input::pii::Redactor;
+use gnome_voice_input::pipeline;
+use gnome_voice_input::privacy;
+use gnome_voice_input::profanity::Blocklist;
+use gnome_voice_input::spelling::SpellMode;
+use gnome_voice_input::{
+    keyboard_layout, redact, remote_desktop, subtitles, templates, vocabulary,
+};
+use gnome_voice_input::{
+    process_transcription_with_handler, Transcript, TranscriptionHandler, TranscriptionResult,
+};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+async fn run(
+    handler: impl TranscriptionHandler,
+    results: impl IntoIterator<Item = TranscriptionResult>,
+) {
+    let (results_tx, results_rx) = mpsc::channel(10);
+    for result in results {
+        results_tx.send(result).await.unwrap();
+    }
+    drop(results_tx);
+    process_transcription_with_handler(results_rx, handler, CancellationToken::new())
+        .await
+        .unwrap();
+}
+
+/// The text of a final transcript after `filter`
+fn apply(filter: &dyn TranscriptFilter, text: &str) -> String {
+    filter.apply(text.to_string(), true).unwrap()
+}
+
+#[test]
+fn filters_follow_the_configured_order() {
+    let transcription = TranscriptionConfig {
+        replacements: BTreeMap::from([("dot com".to_string(), ".com".to_string())]),
+        ..TranscriptionConfig::default()
+    };
+    let output = OutputConfig {
+        filters: vec![OutputFilter::Capitalize, OutputFilter::Replacements],
+        ..OutputConfig::default()
+    };
+
+    let filters = pipeline::filters(&output, &transcription);
+
+    assert_eq!(filters.len(), 2);
+    // Capitalized first, so the replacement no longer matches at the start
+    assert_eq!(apply(&filters, "dot com is taken"), "Dot com is taken");
+}
+
+#[test]
+fn per_language_replacements_are_included() {
+    let transcription = TranscriptionConfig {
+        language: "de".to_string(),
+        per_language: BTreeMap::from([(
+            "de".to_string(),
+            LanguageSettings {
+                replacements: BTreeMap::from([("neue Zeile".to_string(), "\n".to_string())]),
+                ..LanguageSettings::default()
+            },
+        )]),
+        ..TranscriptionConfig::default()
+    };
+
+    let filters = pipeline::filters(&OutputConfig::default(), &transcription);
+
+    assert_eq!(filters.len(), 1);
+    assert_eq!(apply(&filters, "Hallo neue Zeile"), "Hallo \n");
+}
+
+#[tokio::test]
+async fn file_handler_appends_filtered_finals() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dictation.txt");
+    std::fs::write(&path, "earlier\n").unwrap();
+    let output = OutputConfig {
+        filters: vec![OutputFilter::Capitalize],
+        handlers: vec![OutputHandler::File, OutputHandler::Log],
+        file: Some(path.clone()),
+        ..OutputConfig::default()
+    };
+    let handler = FilterHandler::new(
+        pipeline::handlers(&output, true).unwrap(),
+        pipeline::filters(&output, &TranscriptionConfig::default()),
+    );
+
+    run(
+        handler,
+        [
+            TranscriptionResult::Interim("hel".into()),
+            TranscriptionResult::Final("hello there".into()),
+            TranscriptionResult::Final("second line".into()),
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "earlier\nHello there\nSecond line\n"
+    );
+}
+
+#[tokio::test]
+async fn subtitles_handler_writes_a_cue_per_final() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = OutputConfig {
+        handlers: vec![OutputHandler::Subtitles],
+        subtitles: SubtitlesConfig {
+            directory: Some(dir.path().to_path_buf()),
+            format: SubtitleFormat::Vtt,
+        },
+        ..OutputConfig::default()
+    };
+
+    run(
+        pipeline::handlers(&output, true).unwrap(),
+        [
+            TranscriptionResult::Interim("hel".into()),
+            TranscriptionResult::Final("hello there".into()),
+            TranscriptionResult::Final("second line".into()),
+        ],
+    )
+    .await;
+
+    let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    let path = files[0].as_ref().unwrap().path();
+    assert_eq!(path.extension().unwrap(), "vtt");
+    let subtitles = std::fs::read_to_string(path).unwrap();
+    let lines: Vec<&str> = subtitles.lines().collect();
+    assert_eq!(lines[0], "WEBVTT");
+    assert!(lines[2].starts_with("00:00:00.") && lines[2].contains(" --> 00:00:00."));
+    assert_eq!(lines[3], "hello there");
+    assert_eq!(lines[6], "second line");
+}
+
+#[test]
+fn subtitle_cues_follow_the_format() {
+    let start = Duration::from_millis(3_723_045);
+    let end = Duration::from_millis(3_725_500);
+
+    assert_eq!(
+        subtitles::cue(SubtitleFormat::Srt, 2, start, end, "Hello"),
+        "2\n01:02:03,045 --> 01:02:05,500\nHello\n\n"
+    );
+    assert_eq!(
+        subtitles::cue(SubtitleFormat::Vtt, 2, start, end, "Hello"),
+        "01:02:03.045 --> 01:02:05.500\nHello\n\n"
+    );
+}
+
+#[tokio::test]
+async fn meeting_transcript_has_a_timestamped_line_per_speaker_turn() {
+    let dir = tempfile::tempdir().unwrap();
+    let meeting = MeetingConfig {
+        directory: Some(dir.path().to_path_buf()),
+        format: MeetingFormat::Markdown,
+        speaker_labels: true,
+    };
+
+    run(
+        pipeline::meeting(&meeting).unwrap(),
+        [
+            TranscriptionResult::Interim("Let's".into()),
+            TranscriptionResult::Final("Speaker 1: Let's start.\nSpeaker 2: Sure.".into()),
+        ],
+    )
+    .await;
+
+    let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    let path = files[0].as_ref().unwrap().path();
+    assert!(path.to_string_lossy().ends_with(" Meeting.md"));
+    let transcript = std::fs::read_to_string(path).unwrap();
+    let lines: Vec<&str> = transcript.lines().collect();
+    assert!(lines[0].starts_with("# Meeting "));
+    assert!(lines[2].starts_with("- **") && lines[2].ends_with("** Speaker 1: Let's start."));
+    assert!(lines[3].ends_with("** Speaker 2: Sure."));
+    assert_eq!(lines.len(), 4);
+}
+
+#[test]
+fn privacy_mode_logs_only_the_length_of_transcripts() {
+    assert_eq!(privacy::loggable("Meet me at noon"), "Meet me at noon");
+
+    privacy::set_enabled(true);
+    let logged = privacy::loggable("Meet me at noon").into_owned();
+    privacy::set_enabled(false);
+
+    assert_eq!(logged, "<15 characters>");
+}
+
+#[test]
+fn redaction_masks_every_deepgram_key_of_an_array() {
+    let snippet = "deepgram_api_keys = [\"first0secret1\", 'second0secret2',\n  \"third0secret3\"]";
+
+    assert_eq!(
+        redact::redact(snippet),
+        "deepgram_api_keys = [\"****ret1\", '****ret2',\n  \"****ret3\"]"
+    );
+    assert_eq!(
+        redact::redact("deepgram_api_keys = [\"cut0off0secret"),
+        "deepgram_api_keys = [\"****cret"
+    );
+}
+
+#[test]
+fn redaction_masks_the_llm_api_key() {
+    assert_eq!(
+        redact::redact("[post_processing.llm]\napi_key = \"sk-llm0secret9\"\n"),
+        "[post_processing.llm]\napi_key = \"****ret9\"\n"
+    );
+    assert_eq!(
+        redact::redact("openai_api_key_file = \"/path/to/key\""),
+        "openai_api_key_file = \"/path/to/key\""
+    );
+}
+
+#[test]
+fn keyboard_layout_is_read_from_gnome_sources_and_setxkbmap() {
+    assert_eq!(
+        keyboard_layout::parse_gnome_sources("[('xkb', 'fr+azerty'), ('xkb', 'us')]").as_deref(),
+        Some("fr")
+    );
+    assert_eq!(
+        keyboard_layout::parse_gnome_sources("[('ibus', 'anthy'), ('xkb', 'de(nodeadkeys)')]")
+            .as_deref(),
+        Some("de")
+    );
+    assert_eq!(keyboard_layout::parse_gnome_sources("@a(ss) []"), None);
+    assert_eq!(
+        keyboard_layout::parse_setxkbmap(
+            "rules:      evdev\nmodel:      pc105\nlayout:     be,us\n"
+        )
+        .as_deref(),
+        Some("be")
+    );
+}
+
+#[test]
+fn only_letters_and_whitespace_are_typed_on_non_us_layouts() {
+    for ch in ['a', 'é', ' ', '1', ',', '!'] {
+        assert!(keyboard_layout::types_reliably(Some("us"), ch));
+        assert!(keyboard_layout::types_reliably(None, ch));
+    }
+    assert!(keyboard_layout::types_reliably(Some("fr"), 'q'));
+    assert!(keyboard_layout::types_reliably(Some("fr"), 'é'));
+    assert!(!keyboard_layout::types_reliably(Some("fr"), '1'));
+    assert!(!keyboard_layout::types_reliably(Some("fr"), '.'));
+    assert!(!keyboard_layout::types_reliably(Some("de"), '-'));
+}
+
+#[test]
+fn remote_desktop_keysyms_cover_latin1_and_unicode() {
+    assert_eq!(remote_desktop::keysym('a'), 0x61);
+    assert_eq!(remote_desktop::keysym('é'), 0xe9);
+    assert_eq!(remote_desktop::keysym('\n'), 0xff0d);
+    assert_eq!(remote_desktop::keysym('€'), 0x0100_20ac);
+}
+
+#[test]
+fn file_handler_needs_a_path() {
+    let output = OutputConfig {
+        handlers: vec![OutputHandler::File],
+        ..OutputConfig::default()
+    };
+
+    assert!(pipeline::handlers(&output, false).is_err());
+}
+
+#[test]
+fn vocabulary_corrects_words_that_sound_like_a_term() {
+    let terms = [
+        "kubectl".to_string(),
+        "GitHub".to_string(),
+        "Qt".to_string(),
+    ];
+
+    assert_eq!(
+        vocabulary::correct("run cube CTL apply, then push to get hub.", &terms),
+        "run kubectl apply, then push to GitHub."
+    );
+    assert_eq!(
+        vocabulary::correct("cute and quiet", &terms),
+        "cute and quiet"
+    );
+    assert_eq!(
+        vocabulary::correct("the QT widgets", &terms),
+        "the Qt widgets"
+    );
+}
+
+#[test]
+fn vocabulary_file_ignores_comments_and_blank_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vocabulary.txt");
+    std::fs::write(&path, "# Work\nkubectl\n\nGitHub  # hosting\n").unwrap();
+
+    assert_eq!(vocabulary::read_file(&path).unwrap(), ["kubectl", "GitHub"]);
+}
+
+#[test]
+fn sentence_case_capitalizes_sentences_and_the_pronoun_i() {
+    assert_eq!(
+        apply(&SentenceCase, "i think so.  what do i know ?i'm not sure"),
+        "I think so. What do I know? I'm not sure"
+    );
+    assert_eq!(
+        apply(&SentenceCase, "see example.com , e.g. the docs"),
+        "See example.com, e.g. the docs"
+    );
+    assert_eq!(
+        apply(&SentenceCase, "first line\nsecond,third"),
+        "First line\nSecond, third"
+    );
+}
+
+#[test]
+fn emoji_filter_replaces_spoken_emoji() {
+    let output = OutputConfig {
+        filters: vec![OutputFilter::Emoji],
+        emoji: BTreeMap::from([("Tada".to_string(), "🎉".to_string())]),
+        ..OutputConfig::default()
+    };
+
+    let filters = pipeline::filters(&output, &TranscriptionConfig::default());
+
+    assert_eq!(
+        apply(&filters, "Sounds good thumbs up emoji. Tada! smiley face"),
+        "Sounds good 👍. 🎉! 🙂"
+    );
+}
+
+#[test]
+fn templates_expand_builtin_and_configured_variables() {
+    let variables = templates::table(&BTreeMap::from([
+        ("My Email".to_string(), "me@example.com".to_string()),
+        ("date".to_string(), "%d.%m.%Y".to_string()),
+    ]));
+    let now = chrono::Utc.with_ymd_and_hms(2024, 3, 9, 14, 5, 0).unwrap();
+
+    assert_eq!(
+        templates::expand(
+            "Sent on insert date at insert time, reply to insert my email.",
+            &variables,
+            &now
+        ),
+        "Sent on 09.03.2024 at 14:05, reply to me@example.com."
+    );
+    assert_eq!(
+        templates::expand("insert today's date", &variables, &now),
+        "2024-03-09"
+    );
+    assert!(!templates::is_valid_format("%Q"));
+}
+
+#[test]
+fn numbers_follow_the_configured_locale() {
+    let german = NumberFormat::for_locale("de-DE").unwrap();
+
+    assert_eq!(
+        apply(
+            &german,
+            "It costs $1,234.50, about 12.5% more than 20€ for 3.5kg in 2024."
+        ),
+        "It costs 1.234,50 $, about 12,5 % more than 20 € for 3,5 kg in 2024."
+    );
+    assert_eq!(
+        apply(&german, "Version 1.2.3 at 10:30, the 1990s"),
+        "Version 1.2.3 at 10:30, the 1990s"
+    );
+    assert_eq!(
+        apply(&NumberFormat::for_locale("nl").unwrap(), "€12.50"),
+        "€ 12,50"
+    );
+}
+
+#[test]
+fn numbers_default_to_the_transcription_language() {
+    let output = OutputConfig {
+        filters: vec![OutputFilter::Numbers],
+        ..OutputConfig::default()
+    };
+    let transcription = |language: &str| TranscriptionConfig {
+        language: language.to_string(),
+        ..TranscriptionConfig::default()
+    };
+
+    assert_eq!(
+        apply(&pipeline::filters(&output, &transcription("fr")), "1,500.5"),
+        "1 500,5"
+    );
+    assert!(pipeline::filters(&output, &transcription("multi")).is_empty());
+}
+
+#[test]
+fn profanity_is_masked_or_removed() {
+    let words = ["Darn".to_string(), "frick*".to_string()];
+    let masking = Blocklist::new(ProfanityMode::Mask, &words);
+    let removing = Blocklist::new(ProfanityMode::Remove, &words);
+
+    assert_eq!(
+        masking.filter("Shit, the darn build is fricking broken."),
+        "S***, the d*** build is f******* broken."
+    );
+    assert_eq!(
+        removing.filter("Shit, the darn build is fricking broken."),
+        ", the build is broken."
+    );
+    assert_eq!(masking.filter("Dickens wrote it"), "Dickens wrote it");
+}
+
+#[test]
+fn pii_is_redacted() {
+    let redactor = Redactor::new(&PiiConfig {
+        patterns: BTreeMap::from([("employee id".to_string(), r"\bEMP-\d{6}\b".to_string())]),
+        ..PiiConfig::default()
+    })
+    .unwrap();
+
+    assert_eq!(
+        redactor.redact(
+            "Mail jane.doe@example.co.uk or call +49 30 1234567, card 4111 1111 1111 1111, id EMP-123456."
+        ),
+        "Mail [email] or call [phone number], card [card number], id [employee id]."
+    );
+    assert_eq!(
+        redactor.redact("Order 1234 5678 9012 3456 costs 1,234.50 in 2024"),
+        "Order 1234 5678 9012 3456 costs 1,234.50 in 2024"
+    );
+    assert!(Redactor::new(&PiiConfig {
+        patterns: BTreeMap::from([("broken".to_string(), "(".to_string())]),
+        ..PiiConfig::default()
+    })
+    .is_err());
+}
+
+/// Drops transcripts that are a spoken command
+struct DropCommands;
+
+impl TranscriptFilter for DropCommands {
+    fn apply(&self, text: String, _is_final: bool) -> Option<String> {
+        (!text.starts_with("command")).then_some(text)
+    }
+}
+
+#[tokio::test]
+async fn dropped_transcripts_skip_the_remaining_filters_and_handlers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dictation.txt");
+    let output = OutputConfig {
+        handlers: vec![OutputHandler::File],
+        file: Some(path.clone()),
+        ..OutputConfig::default()
+    };
+    let filters: Vec<Box<dyn TranscriptFilter>> =
+        vec![Box::new(DropCommands), Box::new(SentenceCase)];
+
+    run(
+        FilterHandler::new(pipeline::handlers(&output, false).unwrap(), filters),
+        [
+            TranscriptionResult::Final("first".into()),
+            TranscriptionResult::Final("command undo".into()),
+            TranscriptionResult::Final("second".into()),
+        ],
+    )
+    .await;
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "First\nSecond\n");
+}
+
+#[test]
+fn spell_mode_writes_spoken_characters() {
+    let spell = SpellMode::new(&SpellConfig::default());
+
+    assert_eq!(
+        spell.process("My plate is start spelling", true).as_deref(),
+        Some("My plate is")
+    );
+    assert!(spell.is_active());
+    // Interim transcripts show the spelling without ending the mode
+    assert_eq!(
+        spell
+            .process("capital bravo, X-ray seven", false)
+            .as_deref(),
+        Some("Bx7")
+    );
+    assert_eq!(
+        spell
+            .process(
+                "Capital Bravo, X-ray seven dash 42. Stop spelling. Thanks.",
+                true
+            )
+            .as_deref(),
+        Some("Bx7-42 Thanks.")
+    );
+    assert!(!spell.is_active());
+    assert_eq!(spell.process("Start spelling.", true), None);
+}
+
+/// Records what it is asked to type, slowly starting each session
+#[derive(Clone, Default)]
+struct SlowTarget(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl TranscriptionHandler for SlowTarget {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> eyre::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("interim:{}", transcript.text));
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> eyre::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("final:{}", transcript.text));
+        Ok(())
+    }
+
+    async fn on_transcription_start(&mut self) -> eyre::Result<()> {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn typing_queue_coalesces_a_backlog_unless_configured_to_keep_it() {
+    for (backlog, typed) in [
+        (TypingBacklog::Coalesce, vec!["final:one three four"]),
+        (
+            TypingBacklog::Keep,
+            vec!["final:one", "interim:two", "final:three", "final:four"],
+        ),
+    ] {
+        let target = SlowTarget::default();
+        let config = TypingConfig {
+            backlog,
+            ..TypingConfig::default()
+        };
+
+        run(
+            TypingQueueHandler::new(target.clone(), &config),
+            [
+                TranscriptionResult::Final("one".into()),
+                TranscriptionResult::Interim("two".into()),
+                TranscriptionResult::Final("three".into()),
+                TranscriptionResult::Final("four".into()),
+            ],
+        )
+        .await;
+
+        // The session ends once everything queued is typed
+        assert_eq!(*target.0.lock().unwrap(), typed, "{backlog:?}");
+    }
+}
+
+/// Focused window seen by the focus guard under test
+static FOCUSED_WINDOW: AtomicU32 = AtomicU32::new(1);
+
+fn focused_window() -> Option<u32> {
+    Some(FOCUSED_WINDOW.load(Ordering::Relaxed))
+}
+
+/// Typing target whose user clicks another window after "one" and while
+/// "cut off" is typed
+#[derive(Clone, Default)]
+struct SwitchingTarget(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl TranscriptionHandler for SwitchingTarget {
+    async fn on_interim_result(&mut self, _transcript: Transcript) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> eyre::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("final:{}", transcript.text));
+        match transcript.text.as_str() {
+            "one" => FOCUSED_WINDOW.store(2, Ordering::Relaxed),
+            "cut off" => {
+                FOCUSED_WINDOW.store(3, Ordering::Relaxed);
+                return Err(FocusLost.into());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn on_focus_lost(&mut self) -> eyre::Result<()> {
+        self.0.lock().unwrap().push("focus lost".to_string());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn focus_guard_drops_finals_after_the_focus_moved() {
+    let target = SwitchingTarget::default();
+
+    run(
+        FocusGuardHandler::new(target.clone()).with_active_window(focused_window),
+        [
+            TranscriptionResult::Final("one".into()),
+            TranscriptionResult::Final("two".into()),
+            TranscriptionResult::Final("three".into()),
+            TranscriptionResult::Final("cut off".into()),
+            TranscriptionResult::Final("four".into()),
+        ],
+    )
+    .await;
+
+    // After a dropped utterance the newly focused window is typed into
+    assert_eq!(
+        *target.0.lock().unwrap(),
+        [
+            "final:one",
+            "focus lost",
+            "final:three",
+            "final:cut off",
+            "focus lost",
+            "final:four"
+        ]
+    );
+}
@@ -0,0 +1,200 @@
+//! Tests of the `[post_processing.llm]` cleanup against a local server
+//! answering like an OpenAI-compatible chat completions endpoint.
+
+use async_trait::async_trait;
+use eyre::Result;
+use gnome_voice_input::config::LlmConfig;
+use gnome_voice_input::handlers::LlmCleanupHandler;
+use gnome_voice_input::llm::LlmClient;
+use gnome_voice_input::{
+    process_transcription_with_handler, Transcript, TranscriptionHandler, TranscriptionResult,
+};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// A request as received by `MockLlm`
+#[derive(Debug, Clone)]
+struct Request {
+    head: String,
+    body: serde_json::Value,
+}
+
+/// Answers every request with `status` and `content` after `delay`
+struct MockLlm {
+    endpoint: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockLlm {
+    async fn start(status: u16, content: &str, delay: Duration) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let body = json!({
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": content } }],
+        })
+        .to_string();
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let request = read_request(&mut stream).await;
+                recorded.lock().unwrap().push(request);
+                tokio::time::sleep(delay).await;
+                let response = format!(
+                    "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        Self { endpoint, requests }
+    }
+
+    fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(stream: &mut tokio::net::TcpStream) -> Request {
+    let mut data = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let read = stream.read(&mut buffer).await.unwrap();
+        data.extend_from_slice(&buffer[..read]);
+        let text = String::from_utf8_lossy(&data);
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let length = head
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().ok())?
+                })
+                .unwrap_or(0);
+            if body.len() >= length || read == 0 {
+                return Request {
+                    head: head.to_string(),
+                    body: serde_json::from_str(body).unwrap_or_default(),
+                };
+            }
+        }
+    }
+}
+
+fn config(server: &MockLlm) -> LlmConfig {
+    LlmConfig {
+        endpoint: server.endpoint.clone(),
+        model: "mock-model".to_string(),
+        prompt: "Fix it".to_string(),
+        api_key: Some("llm-test-key".to_string()),
+        timeout_ms: 1000,
+    }
+}
+
+/// Records the transcripts it receives
+#[derive(Clone, Default)]
+struct RecordingHandler {
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl TranscriptionHandler for RecordingHandler {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("interim:{}", transcript.text));
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("final:{}", transcript.text));
+        Ok(())
+    }
+}
+
+/// Run `results` through the cleanup and return what reached the handler
+async fn clean_up(config: LlmConfig, results: Vec<TranscriptionResult>) -> Vec<String> {
+    let handler = RecordingHandler::default();
+    let (results_tx, results_rx) = mpsc::channel(10);
+    for result in results {
+        results_tx.send(result).await.unwrap();
+    }
+    drop(results_tx);
+    process_transcription_with_handler(
+        results_rx,
+        LlmCleanupHandler::new(handler.clone(), LlmClient::new(config)),
+        CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+    let calls = handler.calls.lock().unwrap().clone();
+    calls
+}
+
+#[tokio::test]
+async fn finals_are_replaced_by_the_cleaned_text() {
+    let server = MockLlm::start(200, " I think so. \n", Duration::ZERO).await;
+
+    let calls = clean_up(
+        config(&server),
+        vec![
+            TranscriptionResult::Interim("i think".into()),
+            TranscriptionResult::Final("i think so".into()),
+        ],
+    )
+    .await;
+
+    assert_eq!(calls, ["interim:i think", "final:I think so."]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert!(
+        request.head.starts_with("POST /v1/chat/completions "),
+        "{}",
+        request.head
+    );
+    assert!(request
+        .head
+        .to_ascii_lowercase()
+        .contains("authorization: bearer llm-test-key"));
+    assert_eq!(request.body["model"], "mock-model");
+    assert_eq!(request.body["messages"][0]["content"], "Fix it");
+    assert_eq!(request.body["messages"][1]["content"], "i think so");
+}
+
+#[tokio::test]
+async fn slow_model_falls_back_to_the_raw_transcript() {
+    let server = MockLlm::start(200, "Too late.", Duration::from_secs(5)).await;
+    let config = LlmConfig {
+        timeout_ms: 200,
+        ..config(&server)
+    };
+
+    let calls = clean_up(config, vec![TranscriptionResult::Final("raw text".into())]).await;
+
+    assert_eq!(calls, ["final:raw text"]);
+}
+
+#[tokio::test]
+async fn failed_request_falls_back_to_the_raw_transcript() {
+    let server = MockLlm::start(500, "ignored", Duration::ZERO).await;
+
+    let calls = clean_up(
+        config(&server),
+        vec![TranscriptionResult::Final("raw text".into())],
+    )
+    .await;
+
+    assert_eq!(calls, ["final:raw text"]);
+}
@@ -0,0 +1,813 @@
+//! End-to-end tests of `Transcriber`, the handler pipeline and
+//! `VoiceInputEngine` against the mock Deepgram server in `common`.
+
+mod common;
+
+use async_trait::async_trait;
+use common::{channel_final, final_result, interim, transcript, MockDeepgram, Script};
+use eyre::Result;
+use gnome_voice_input::api_keys::ApiKeys;
+use gnome_voice_input::audio::{AudioFormat, AudioInput};
+use gnome_voice_input::audio_source::Signal;
+use gnome_voice_input::config::{OutputFilter, TlsConfig, TranscriptionConfig};
+use gnome_voice_input::state::{AppEvent, RecordingState};
+use gnome_voice_input::stats::UsageStats;
+use gnome_voice_input::transcription_utils::ConnectionStatus;
+use gnome_voice_input::{listen, privacy, tls};
+use gnome_voice_input::{
+    process_transcription_until, process_transcription_with_handler, Config, Transcriber,
+    Transcript, TranscriptionHandler, TranscriptionResult, VoiceInputEngine,
+};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_util::sync::CancellationToken;
+
+const API_KEY: &str = "test-key";
+const SAMPLE_RATE: u32 = 16000;
+/// 20 ms of silence as 16 bit mono samples
+const CHUNK: [u8; 640] = [0; 640];
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Harness {
+    server: MockDeepgram,
+    status: watch::Receiver<ConnectionStatus>,
+    transcriber: Arc<Transcriber>,
+}
+
+impl Harness {
+    async fn new(
+        scripts: impl IntoIterator<Item = Script>,
+        configure: impl FnOnce(&mut TranscriptionConfig),
+    ) -> Self {
+        let server = MockDeepgram::start(scripts).await;
+        let mut config = TranscriptionConfig {
+            base_url: Some(server.base_url()),
+            ..TranscriptionConfig::default()
+        };
+        configure(&mut config);
+        let (status_tx, status) = watch::channel(ConnectionStatus::Disconnected);
+        let transcriber = Arc::new(Transcriber::new(
+            API_KEY.to_string(),
+            config,
+            Arc::new(status_tx),
+            Arc::new(UsageStats::in_memory()),
+            false,
+        ));
+        Self {
+            server,
+            status,
+            transcriber,
+        }
+    }
+
+    /// Stream `chunks` of audio, then end it, and collect all results
+    async fn transcribe(&self, chunks: usize) -> Vec<TranscriptionResult> {
+        let (audio_tx, audio_rx) = mpsc::channel(100);
+        for _ in 0..chunks {
+            audio_tx.send(CHUNK.to_vec()).await.unwrap();
+        }
+        drop(audio_tx);
+        let results = self
+            .transcriber
+            .clone()
+            .transcribe_stream(audio_rx, AudioFormat::mono(SAMPLE_RATE))
+            .await
+            .unwrap();
+        collect(results).await
+    }
+}
+
+async fn collect(mut results: mpsc::Receiver<TranscriptionResult>) -> Vec<TranscriptionResult> {
+    let mut collected = Vec::new();
+    tokio::time::timeout(TIMEOUT, async {
+        while let Some(result) = results.recv().await {
+            collected.push(result);
+        }
+    })
+    .await
+    .expect("transcription stream did not end");
+    collected
+}
+
+/// Results as "interim:text", "final:text" and "utterance-end" for comparing
+fn describe(results: &[TranscriptionResult]) -> Vec<String> {
+    results
+        .iter()
+        .map(|result| match result {
+            TranscriptionResult::Interim(transcript) => format!("interim:{}", transcript.text),
+            TranscriptionResult::Final(transcript) => format!("final:{}", transcript.text),
+            TranscriptionResult::UtteranceEnd => "utterance-end".to_string(),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn final_transcripts_are_delivered() {
+    let harness = Harness::new(
+        [Script::respond([
+            interim("hello"),
+            final_result("hello world"),
+        ])],
+        |_| {},
+    )
+    .await;
+
+    let results = harness.transcribe(5).await;
+
+    assert_eq!(describe(&results), ["final:hello world"]);
+    assert_eq!(*harness.status.borrow(), ConnectionStatus::Disconnected);
+}
+
+#[tokio::test]
+async fn finals_carry_confidence_language_words_and_timing() {
+    let mut response: serde_json::Value =
+        serde_json::from_str(&final_result("Hello there.")).unwrap();
+    response["start"] = serde_json::json!(2.0);
+    response["channel"]["alternatives"][0]["languages"] = serde_json::json!(["en"]);
+    response["channel"]["alternatives"][0]["words"] = serde_json::json!([
+        {"word": "hello", "punctuated_word": "Hello", "start": 2.1, "end": 2.5,
+         "confidence": 0.99, "speaker": 1},
+        {"word": "there", "punctuated_word": "there.", "start": 2.5, "end": 2.9,
+         "confidence": 0.97, "speaker": 1},
+    ]);
+    let harness = Harness::new([Script::respond([response.to_string()])], |_| {}).await;
+
+    let results = harness.transcribe(5).await;
+
+    let transcript = results[0].transcript().unwrap();
+    assert_eq!(transcript.text, "Hello there.");
+    assert_eq!(transcript.confidence, 0.98);
+    assert_eq!(transcript.language.as_deref(), Some("en"));
+    assert_eq!(transcript.speaker, Some(1));
+    assert_eq!(transcript.start, Duration::from_secs(2));
+    assert_eq!(transcript.end(), Duration::from_secs(3));
+    let words: Vec<_> = transcript.words.iter().map(|word| &word.word).collect();
+    assert_eq!(words, ["Hello", "there."]);
+    assert_eq!(transcript.words[1].end, Duration::from_millis(2900));
+}
+
+#[tokio::test]
+async fn request_carries_key_options_and_audio() {
+    let harness = Harness::new([Script::default()], |config| {
+        config.language = "de".to_string();
+        config.model = "nova-2".to_string();
+    })
+    .await;
+
+    harness.transcribe(5).await;
+
+    let connections = harness.server.connections();
+    assert_eq!(connections.len(), 1);
+    let connection = &connections[0];
+    assert!(
+        connection.uri.starts_with("/v1/listen?"),
+        "{}",
+        connection.uri
+    );
+    assert_eq!(
+        connection.authorization.as_deref(),
+        Some(format!("Token {API_KEY}").as_str())
+    );
+    assert_eq!(connection.query("language").as_deref(), Some("de"));
+    assert_eq!(connection.query("model").as_deref(), Some("nova-2"));
+    assert_eq!(connection.query("encoding").as_deref(), Some("linear16"));
+    assert_eq!(connection.query("sample_rate").as_deref(), Some("16000"));
+    assert_eq!(connection.query("channels").as_deref(), Some("1"));
+    assert_eq!(connection.audio_bytes, 5 * CHUNK.len());
+    assert!(connection
+        .control_messages
+        .iter()
+        .any(|message| message.contains("CloseStream")));
+}
+
+#[tokio::test]
+async fn vocabulary_is_sent_as_keyterms_or_keywords() {
+    for (model, parameter) in [("nova-3", "keyterm"), ("nova-2", "keywords")] {
+        let harness = Harness::new([Script::default()], |config| {
+            config.model = model.to_string();
+            config.vocabulary = vec!["kubectl".to_string()];
+        })
+        .await;
+
+        harness.transcribe(1).await;
+
+        let connection = &harness.server.connections()[0];
+        assert_eq!(
+            connection.query(parameter).as_deref(),
+            Some("kubectl"),
+            "{}",
+            connection.uri
+        );
+    }
+}
+
+#[tokio::test]
+async fn interim_results_follow_the_config() {
+    let harness = Harness::new(
+        [Script::respond([interim("hel"), final_result("hello")])],
+        |config| config.use_interim_results = true,
+    )
+    .await;
+
+    let results = harness.transcribe(3).await;
+
+    assert_eq!(describe(&results), ["interim:hel", "final:hello"]);
+}
+
+#[tokio::test]
+async fn utterance_end_follows_speech_final() {
+    let server = MockDeepgram::start([Script::respond([
+        transcript("one", true, false),
+        transcript("two", true, true),
+    ])])
+    .await;
+    let config = TranscriptionConfig {
+        base_url: Some(server.base_url()),
+        ..TranscriptionConfig::default()
+    };
+    let transcriber = Arc::new(
+        Transcriber::new(
+            API_KEY.to_string(),
+            config,
+            Arc::new(watch::channel(ConnectionStatus::Disconnected).0),
+            Arc::new(UsageStats::in_memory()),
+            false,
+        )
+        .with_utterance_end(Duration::from_millis(800)),
+    );
+    let (audio_tx, audio_rx) = mpsc::channel(10);
+    audio_tx.send(CHUNK.to_vec()).await.unwrap();
+    drop(audio_tx);
+
+    let results = transcriber
+        .transcribe_stream(audio_rx, AudioFormat::mono(SAMPLE_RATE))
+        .await
+        .unwrap();
+    let results = collect(results).await;
+
+    assert_eq!(
+        describe(&results),
+        ["final:one", "final:two", "utterance-end"]
+    );
+    assert_eq!(
+        server.connections()[0].query("endpointing").as_deref(),
+        Some("800")
+    );
+}
+
+#[tokio::test]
+async fn finals_of_two_channel_audio_are_labeled_by_channel() {
+    let server = MockDeepgram::start([Script::respond([
+        channel_final("Can you hear me?", 0, 2),
+        channel_final("Yes, loud and clear.", 1, 2),
+    ])])
+    .await;
+    let config = TranscriptionConfig {
+        base_url: Some(server.base_url()),
+        ..TranscriptionConfig::default()
+    };
+    let transcriber = Arc::new(
+        Transcriber::new(
+            API_KEY.to_string(),
+            config,
+            Arc::new(watch::channel(ConnectionStatus::Disconnected).0),
+            Arc::new(UsageStats::in_memory()),
+            false,
+        )
+        .with_channel_labels(vec!["Me".to_string(), "Others".to_string()]),
+    );
+    let (audio_tx, audio_rx) = mpsc::channel(10);
+    audio_tx.send(CHUNK.to_vec()).await.unwrap();
+    drop(audio_tx);
+
+    let format = AudioFormat {
+        sample_rate: SAMPLE_RATE,
+        channels: 2,
+    };
+    let results = transcriber
+        .transcribe_stream(audio_rx, format)
+        .await
+        .unwrap();
+    let results = collect(results).await;
+
+    assert_eq!(
+        describe(&results),
+        [
+            "final:Me: Can you hear me?",
+            "final:Others: Yes, loud and clear."
+        ]
+    );
+    let connection = &server.connections()[0];
+    assert_eq!(connection.query("channels").as_deref(), Some("2"));
+    assert_eq!(connection.query("multichannel").as_deref(), Some("true"));
+}
+
+#[tokio::test]
+async fn rejected_key_reports_auth_error() {
+    let harness = Harness::new([Script::reject(401)], |_| {}).await;
+
+    let results = harness.transcribe(3).await;
+
+    assert!(results.is_empty());
+    assert_eq!(*harness.status.borrow(), ConnectionStatus::AuthError);
+    assert_eq!(harness.server.connections().len(), 1);
+}
+
+#[tokio::test]
+async fn forbidden_key_reports_auth_error() {
+    let harness = Harness::new([Script::reject(403)], |_| {}).await;
+
+    harness.transcribe(3).await;
+
+    assert_eq!(*harness.status.borrow(), ConnectionStatus::AuthError);
+}
+
+#[tokio::test]
+async fn other_rejected_handshakes_report_offline() {
+    let harness = Harness::new([Script::reject(500)], |_| {}).await;
+
+    harness.transcribe(3).await;
+
+    assert_eq!(*harness.status.borrow(), ConnectionStatus::Offline);
+}
+
+#[tokio::test]
+async fn rejected_key_is_replaced_by_the_next_one() {
+    let server = MockDeepgram::start([
+        Script::reject(402),
+        Script::respond([final_result("hello")]),
+    ])
+    .await;
+    let config = TranscriptionConfig {
+        base_url: Some(server.base_url()),
+        ..TranscriptionConfig::default()
+    };
+    let api_keys = Arc::new(ApiKeys::new(vec![
+        "spent-key".to_string(),
+        "spare-key".to_string(),
+    ]));
+    let transcriber = Arc::new(
+        Transcriber::new(
+            API_KEY.to_string(),
+            config,
+            Arc::new(watch::channel(ConnectionStatus::Disconnected).0),
+            Arc::new(UsageStats::in_memory()),
+            false,
+        )
+        .with_api_keys(api_keys.clone()),
+    );
+    let (audio_tx, audio_rx) = mpsc::channel(10);
+    audio_tx.send(CHUNK.to_vec()).await.unwrap();
+    drop(audio_tx);
+
+    let results = transcriber
+        .transcribe_stream(audio_rx, AudioFormat::mono(SAMPLE_RATE))
+        .await
+        .unwrap();
+    let results = collect(results).await;
+
+    assert_eq!(describe(&results), ["final:hello"]);
+    // Transcripts tell which key they were received with, for the history
+    assert_eq!(results[0].transcript().unwrap().api_key, Some(1));
+    let authorizations: Vec<_> = server
+        .connections()
+        .into_iter()
+        .map(|connection| connection.authorization)
+        .collect();
+    assert_eq!(
+        authorizations,
+        [
+            Some("Token spent-key".to_string()),
+            Some("Token spare-key".to_string())
+        ]
+    );
+    // Later sessions start with the key that worked
+    assert_eq!(api_keys.current(), (1, "spare-key"));
+}
+
+#[tokio::test]
+async fn dropped_connection_is_reestablished_while_recording() {
+    let harness = Harness::new(
+        [
+            Script::respond([final_result("before")]).then_drop(),
+            Script::respond([final_result("after")]),
+        ],
+        |_| {},
+    )
+    .await;
+
+    // Keep capturing until both transcripts arrived
+    let (audio_tx, audio_rx) = mpsc::channel(100);
+    let stop_capture = CancellationToken::new();
+    let capture = tokio::spawn({
+        let stop_capture = stop_capture.clone();
+        async move {
+            while !stop_capture.is_cancelled() {
+                if audio_tx.send(CHUNK.to_vec()).await.is_err() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+    });
+
+    let mut results = harness
+        .transcriber
+        .clone()
+        .transcribe_stream(audio_rx, AudioFormat::mono(SAMPLE_RATE))
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    tokio::time::timeout(TIMEOUT, async {
+        while received.len() < 2 {
+            received.push(results.recv().await.expect("results ended early"));
+        }
+    })
+    .await
+    .expect("no transcript after reconnecting");
+    stop_capture.cancel();
+    capture.await.unwrap();
+    received.extend(collect(results).await);
+
+    assert_eq!(describe(&received), ["final:before", "final:after"]);
+    let connections = harness.server.connections();
+    assert_eq!(connections.len(), 2);
+    assert!(connections
+        .iter()
+        .all(|connection| connection.audio_bytes > 0));
+    assert_eq!(*harness.status.borrow(), ConnectionStatus::Disconnected);
+}
+
+/// Records the calls a handler receives
+#[derive(Clone, Default)]
+struct RecordingHandler {
+    calls: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl TranscriptionHandler for RecordingHandler {
+    async fn on_interim_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("interim:{}", transcript.text));
+        Ok(())
+    }
+
+    async fn on_final_result(&mut self, transcript: Transcript) -> Result<()> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("final:{}", transcript.text));
+        Ok(())
+    }
+
+    async fn on_transcription_start(&mut self) -> Result<()> {
+        self.calls.lock().unwrap().push("start".to_string());
+        Ok(())
+    }
+
+    async fn on_transcription_end(&mut self) -> Result<()> {
+        self.calls.lock().unwrap().push("end".to_string());
+        Ok(())
+    }
+
+    async fn on_transcription_discarded(&mut self) -> Result<()> {
+        self.calls.lock().unwrap().push("discarded".to_string());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn handler_receives_the_session() {
+    let harness = Harness::new(
+        [Script::respond([
+            interim("good"),
+            final_result("good morning"),
+            final_result("how are you"),
+        ])],
+        |config| config.use_interim_results = true,
+    )
+    .await;
+    let (audio_tx, audio_rx) = mpsc::channel(10);
+    audio_tx.send(CHUNK.to_vec()).await.unwrap();
+    drop(audio_tx);
+    let results = harness
+        .transcriber
+        .clone()
+        .transcribe_stream(audio_rx, AudioFormat::mono(SAMPLE_RATE))
+        .await
+        .unwrap();
+    let handler = RecordingHandler::default();
+
+    tokio::time::timeout(
+        TIMEOUT,
+        process_transcription_with_handler(results, handler.clone(), CancellationToken::new()),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(
+        *handler.calls.lock().unwrap(),
+        [
+            "start",
+            "interim:good",
+            "final:good morning",
+            "final:how are you",
+            "end"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn discarded_session_skips_the_end_hook() {
+    // The connection stays open as long as audio is captured
+    let harness = Harness::new([Script::respond([final_result("never typed")])], |_| {}).await;
+    let (audio_tx, audio_rx) = mpsc::channel(10);
+    audio_tx.send(CHUNK.to_vec()).await.unwrap();
+    let results = harness
+        .transcriber
+        .clone()
+        .transcribe_stream(audio_rx, AudioFormat::mono(SAMPLE_RATE))
+        .await
+        .unwrap();
+    let handler = RecordingHandler::default();
+    let discard = CancellationToken::new();
+    discard.cancel();
+
+    process_transcription_with_handler(results, handler.clone(), discard)
+        .await
+        .unwrap();
+    drop(audio_tx);
+
+    assert_eq!(*handler.calls.lock().unwrap(), ["start", "discarded"]);
+}
+
+#[tokio::test]
+async fn finished_session_drops_the_remaining_results_but_ends() {
+    // The results keep coming, as when the stream does not close after stopping
+    let (results_tx, results) = mpsc::channel(10);
+    results_tx
+        .send(TranscriptionResult::Final(Transcript::new("too late")))
+        .await
+        .unwrap();
+    let handler = RecordingHandler::default();
+    let finish = CancellationToken::new();
+    finish.cancel();
+
+    process_transcription_until(results, handler.clone(), CancellationToken::new(), finish)
+        .await
+        .unwrap();
+
+    assert_eq!(*handler.calls.lock().unwrap(), ["start", "end"]);
+}
+
+fn engine_config(server: &MockDeepgram) -> Config {
+    let mut config = Config::default();
+    config.deepgram_api_key = API_KEY.to_string();
+    config.transcription.base_url = Some(server.base_url());
+    config.history.enabled = false;
+    config
+}
+
+async fn next_transcript(
+    transcripts: &mut broadcast::Receiver<TranscriptionResult>,
+) -> TranscriptionResult {
+    tokio::time::timeout(TIMEOUT, transcripts.recv())
+        .await
+        .expect("no transcript in time")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn engine_delivers_transcripts_to_subscribers() {
+    let server = MockDeepgram::start([Script::respond([final_result("hello engine")])]).await;
+    let engine = VoiceInputEngine::new(engine_config(&server))
+        .with_input(AudioInput::Signal(Signal::Silence));
+    let mut transcripts = engine.subscribe_transcripts();
+
+    engine.start().unwrap();
+    assert!(engine.start().is_err());
+    let result = next_transcript(&mut transcripts).await;
+    engine.stop().await.unwrap();
+
+    assert_eq!(describe(&[result]), ["final:hello engine"]);
+    assert_eq!(engine.recording_state(), RecordingState::Idle);
+}
+
+#[tokio::test]
+async fn engine_applies_the_output_filters() {
+    let server =
+        MockDeepgram::start([Script::respond([final_result("first new line second")])]).await;
+    let mut config = engine_config(&server);
+    config.transcription.replacements =
+        BTreeMap::from([(" new line ".to_string(), "\n".to_string())]);
+    config.output.filters = vec![OutputFilter::Replacements, OutputFilter::Capitalize];
+    let engine = VoiceInputEngine::new(config).with_input(AudioInput::Signal(Signal::Silence));
+    let mut transcripts = engine.subscribe_transcripts();
+
+    engine.start().unwrap();
+    let result = next_transcript(&mut transcripts).await;
+    engine.stop().await.unwrap();
+
+    assert_eq!(describe(&[result]), ["final:First\nsecond"]);
+}
+
+#[tokio::test]
+async fn engine_uses_the_new_config_for_the_next_session() {
+    let server = MockDeepgram::start([
+        Script::respond([final_result("one")]),
+        Script::respond([final_result("zwei")]),
+    ])
+    .await;
+    let engine = VoiceInputEngine::new(engine_config(&server))
+        .with_input(AudioInput::Signal(Signal::Silence));
+    let mut transcripts = engine.subscribe_transcripts();
+
+    engine.start().unwrap();
+    next_transcript(&mut transcripts).await;
+    engine.stop().await.unwrap();
+    let mut config = engine_config(&server);
+    config.transcription.language = "de".to_string();
+    engine.set_config(config).unwrap();
+    engine.start().unwrap();
+    let result = next_transcript(&mut transcripts).await;
+    engine.stop().await.unwrap();
+
+    assert_eq!(describe(&[result]), ["final:zwei"]);
+    let languages: Vec<_> = server
+        .connections()
+        .iter()
+        .map(|connection| connection.query("language"))
+        .collect();
+    assert_eq!(languages, [Some("en".to_string()), Some("de".to_string())]);
+}
+
+#[tokio::test]
+async fn engine_reports_a_failed_session() {
+    let server = MockDeepgram::start([Script::reject(401)]).await;
+    let engine = VoiceInputEngine::new(engine_config(&server))
+        .with_input(AudioInput::Signal(Signal::Silence));
+    let mut events = engine.subscribe_events();
+
+    engine.start().unwrap();
+    let error = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            if let AppEvent::BackendError { message, .. } = events.recv().await.unwrap() {
+                break message;
+            }
+        }
+    })
+    .await
+    .expect("no error event");
+
+    assert!(!error.is_empty());
+    assert!(engine.stop().await.is_err());
+    assert_eq!(engine.recording_state(), RecordingState::Error);
+}
+
+#[tokio::test]
+async fn engine_stops_a_session_without_transcripts() {
+    let server = MockDeepgram::start([Script::respond([])]).await;
+    let mut config = engine_config(&server);
+    config.audio.inactivity_timeout_secs = Some(1);
+    let engine = VoiceInputEngine::new(config).with_input(AudioInput::Signal(Signal::Silence));
+    let mut events = engine.subscribe_events();
+
+    engine.start().unwrap();
+    tokio::time::timeout(TIMEOUT, async {
+        while !matches!(events.recv().await.unwrap(), AppEvent::InactivityTimeout(_)) {}
+    })
+    .await
+    .expect("no inactivity timeout");
+    tokio::time::timeout(TIMEOUT, async {
+        while !matches!(events.recv().await.unwrap(), AppEvent::RecordingStopped(_)) {}
+    })
+    .await
+    .expect("session did not end");
+
+    assert_eq!(engine.recording_state(), RecordingState::Idle);
+}
+
+/// Log output of a test's tracing subscriber
+#[derive(Clone, Default)]
+struct CapturedLog(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn published_finals_are_not_logged_in_privacy_mode() {
+    let server = MockDeepgram::start([Script::respond([final_result("meet me at noon")])]).await;
+    let engine = VoiceInputEngine::new(engine_config(&server))
+        .with_input(AudioInput::Signal(Signal::Silence));
+    let mut events = engine.subscribe_events();
+    let log = CapturedLog::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer({
+            let log = log.clone();
+            move || log.clone()
+        })
+        .finish();
+    let _subscriber = tracing::subscriber::set_default(subscriber);
+
+    privacy::set_enabled(true);
+    engine.start().unwrap();
+    tokio::time::timeout(TIMEOUT, async {
+        while !matches!(
+            events.recv().await.unwrap(),
+            AppEvent::TranscriptFinal { .. }
+        ) {}
+    })
+    .await
+    .expect("no final transcript");
+    engine.stop().await.unwrap();
+    privacy::set_enabled(false);
+
+    let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+    assert!(log.contains(r#"text: "<15 characters>" }"#));
+    assert!(!log.contains("meet me"));
+}
+
+#[test]
+fn listen_url_uses_websocket_schemes() {
+    let url = listen::listen_url(Some("http://localhost:8080"), "model=nova-3").unwrap();
+    assert_eq!(url.as_str(), "ws://localhost:8080/v1/listen?model=nova-3");
+    let url = listen::listen_url(None, "language=en").unwrap();
+    assert_eq!(url.as_str(), "wss://api.deepgram.com/v1/listen?language=en");
+    assert!(listen::listen_url(Some("ftp://example.com"), "").is_err());
+}
+
+#[test]
+fn tls_settings_build_a_client_config() {
+    assert!(tls::client_config(&TlsConfig::default()).unwrap().is_none());
+    let unverified = TlsConfig {
+        verify: false,
+        ..TlsConfig::default()
+    };
+    assert!(tls::client_config(&unverified).unwrap().is_some());
+
+    let dir = tempfile::tempdir().unwrap();
+    let ca_file = dir.path().join("ca.pem");
+    std::fs::write(&ca_file, "not a certificate\n").unwrap();
+    let without_certificates = TlsConfig {
+        ca_file: Some(ca_file),
+        ..TlsConfig::default()
+    };
+    assert!(tls::client_config(&without_certificates).is_err());
+}
+
+#[tokio::test]
+async fn engine_refuses_sessions_over_the_usage_cap() {
+    let server = MockDeepgram::start([]).await;
+    let mut config = engine_config(&server);
+    // Only sessions on Deepgram's service count
+    config.transcription.base_url = None;
+    config.usage.monthly_minutes = Some(0.0);
+    let engine = VoiceInputEngine::new(config).with_input(AudioInput::Signal(Signal::Silence));
+    let mut events = engine.subscribe_events();
+
+    engine.start().unwrap();
+    let error = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            if let AppEvent::BackendError { message, .. } = events.recv().await.unwrap() {
+                break message;
+            }
+        }
+    })
+    .await
+    .expect("no error event");
+
+    assert!(error.contains("usage cap"), "{error}");
+    assert!(server.connections().is_empty());
+}
+
+#[tokio::test]
+async fn engine_uses_the_fallback_server_over_the_usage_cap() {
+    let server = MockDeepgram::start([Script::respond([final_result("local")])]).await;
+    let mut config = engine_config(&server);
+    config.transcription.base_url = None;
+    config.usage.monthly_dollars = Some(0.0);
+    config.usage.fallback_base_url = Some(server.base_url());
+    let engine = VoiceInputEngine::new(config).with_input(AudioInput::Signal(Signal::Silence));
+    let mut transcripts = engine.subscribe_transcripts();
+
+    engine.start().unwrap();
+    let result = next_transcript(&mut transcripts).await;
+    engine.stop().await.unwrap();
+
+    assert_eq!(describe(&[result]), ["final:local"]);
+}